@@ -1,19 +1,27 @@
-use crate::compressor::{
-    compress_cimage, preview_cimage, CompressionResult, CompressionStatus, CompressionSummary,
-    OptionsPayload,
-};
+use crate::commands::jobs::backup_store;
+use crate::commands::session::save_session;
 use crate::errors::CommandError;
-use crate::{AppData, CImage, ImageStatus};
+use crate::events::TauriEventSink;
+use caesium_core::app_data::AppData;
+use caesium_core::batch::{compress_images, BatchSettings};
+use caesium_core::cache::CompressionCache;
+use caesium_core::compressor::{planned_output_path, preview_cimage, CompressionContext};
+use caesium_core::events::EventSink;
+use caesium_core::memory::ResourceLimits;
+use caesium_core::options::OptionsPayload;
+use caesium_core::plan::CompressionPlan;
+use caesium_core::report::{ReportFormat, ReportRecorder};
+use caesium_core::CImage;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::cmp::max;
-use std::collections::HashSet;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::thread;
-use std::time::{Duration, Instant};
 use tauri::{Emitter, Manager};
 
+const CACHE_FILE_NAME: &str = "compression-cache.json";
+
 #[tauri::command]
 pub fn pause_compression(app: tauri::AppHandle) -> Result<(), CommandError> {
     let state = app.state::<Mutex<AppData>>();
@@ -58,22 +66,14 @@ pub async fn compress(
     options: OptionsPayload,
     threads: usize,
     base_folder: String,
+    max_file_size: Option<u64>,
+    memory_budget: Option<u64>,
+    quality_metrics: Option<bool>,
+    keep_backups: Option<bool>,
+    use_cache: Option<bool>,
 ) -> Result<(), CommandError> {
-    let start_time = Instant::now();
-    let total_images = Arc::new(AtomicUsize::new(0));
-    let total_success = Arc::new(AtomicUsize::new(0));
-    let total_errors = Arc::new(AtomicUsize::new(0));
-    let total_warnings = Arc::new(AtomicUsize::new(0));
-    let original_size = Arc::new(AtomicUsize::new(0));
-    let compressed_size = Arc::new(AtomicUsize::new(0));
-    let files_on_pause = Arc::new(Mutex::new(HashSet::<String>::new()));
-    let max_threads = max(threads, 1);
-
-    let thread_pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(max_threads)
-        .build()?;
-
-    app.emit("fileList:compressionProgress", 0)?;
+    // Checked before the journal, the cache and the session are touched
+    options.validate()?;
 
     //TODO avoid cloning everything if performance will suffer
     let state = app.state::<Mutex<AppData>>();
@@ -102,134 +102,191 @@ pub async fn compress(
         .store(true, Ordering::Relaxed);
     // SNAPSHOT what's needed to work on
     let images: Vec<CImage> = state.file_list.iter().cloned().collect();
+    let compression_status = Arc::clone(&state.compression_status);
     //
     drop(state); // Unlock immediately
 
-    total_images.store(images.len(), Ordering::Relaxed);
-
-    let progress = AtomicUsize::new(0);
-
-    thread_pool.install(|| {
-        let _ = images.par_iter().try_for_each(|cimage| {
-            loop {
-                let state = app.state::<Mutex<AppData>>();
-                let (is_cancelled, is_paused) = {
-                    let state = state.lock().unwrap();
-                    (
-                        state
-                            .compression_status
-                            .is_compression_cancelled
-                            .load(Ordering::Relaxed),
-                        state
-                            .compression_status
-                            .is_compression_paused
-                            .load(Ordering::Relaxed),
-                    )
-                };
-
-                if is_cancelled {
-                    return Err(()); // Stop iteration
-                }
-
-                if is_paused {
-                    if files_on_pause.lock().unwrap().len() < max_threads {
-                        files_on_pause.lock().unwrap().insert(cimage.id.clone());
-                    }
-
-                    if files_on_pause.lock().unwrap().len() == max_threads {
-                        app.emit("fileList:compressionPaused", ()).unwrap(); //TODO
-                        files_on_pause.lock().unwrap().insert(String::from(""));
-                        // This will cause the event not to trigger at every loop
-                    }
-
-                    thread::sleep(Duration::from_millis(500)); // Sleep while paused
-                    continue;
-                } else {
-                    files_on_pause.lock().unwrap().clear();
-                    break; // Continue processing
-                }
-            }
-            original_size.fetch_add(cimage.size as usize, Ordering::Relaxed);
-            let r = CompressionResult {
-                status: CompressionStatus::Warning,
-                cimage: CImage {
-                    status: ImageStatus::Compressing,
-                    ..cimage.clone()
-                },
-            };
-            app.emit("fileList:updateCImage", r).unwrap(); //TODO
-            let result = compress_cimage(&app, cimage, &options, &base_folder);
-
-            // Count results
-            match result.status {
-                CompressionStatus::Success => total_success.fetch_add(1, Ordering::Relaxed),
-                CompressionStatus::Warning => total_warnings.fetch_add(1, Ordering::Relaxed),
-                CompressionStatus::Error => total_errors.fetch_add(1, Ordering::Relaxed),
-            };
-
-            compressed_size.fetch_add(result.cimage.compressed_size as usize, Ordering::Relaxed);
-
-            let state = app.state::<Mutex<AppData>>();
-            let mut state = state.lock().unwrap(); //TODO
-            state.file_list.replace(result.clone().cimage);
-            app.emit("fileList:updateCImage", result).unwrap(); //TODO
-            progress.fetch_add(1, Ordering::Relaxed);
-            app.emit(
-                "fileList:compressionProgress",
-                progress.load(Ordering::Relaxed),
-            )
-            .unwrap(); //TODO
-
-            Ok(())
-        });
-    });
-
-    let elapsed_time = start_time.elapsed();
-    let summary = CompressionSummary {
-        total_images: total_images.load(Ordering::Relaxed),
-        total_success: total_success.load(Ordering::Relaxed),
-        total_skipped: total_warnings.load(Ordering::Relaxed),
-        total_errors: total_errors.load(Ordering::Relaxed),
-        original_size: original_size.load(Ordering::Relaxed),
-        compressed_size: compressed_size.load(Ordering::Relaxed),
-        total_time: elapsed_time.as_millis() as u64,
+    let defaults = ResourceLimits::default();
+    let limits = ResourceLimits {
+        max_file_size: max_file_size.unwrap_or(defaults.max_file_size),
+        memory_budget: memory_budget.unwrap_or(defaults.memory_budget),
     };
 
-    app.emit("fileList:compressionFinished", summary)?;
+    // A broken cache only costs speed, the batch runs without it
+    let cache = use_cache
+        .unwrap_or(true)
+        .then(|| {
+            app.path()
+                .app_cache_dir()
+                .map_err(CommandError::from)
+                .and_then(|dir| Ok(CompressionCache::open(dir.join(CACHE_FILE_NAME))?))
+                .inspect_err(|e| log::error!("Cannot open the compression cache: {e}"))
+                .ok()
+        })
+        .flatten();
+    // Without a journal the job still runs, it just cannot be undone
+    let backups = keep_backups
+        .unwrap_or(true)
+        .then(|| {
+            backup_store(&app)
+                .and_then(|store| Ok((store.start_job()?, store)))
+                .inspect_err(|e| log::error!("Cannot start the job journal: {e}"))
+                .ok()
+        })
+        .flatten();
 
-    let state = app.state::<Mutex<AppData>>();
-    let state = state.lock()?;
+    let report = ReportRecorder::new();
+    let result = compress_images(
+        &images,
+        &options,
+        &base_folder,
+        &BatchSettings {
+            threads,
+            limits,
+            cache: cache.as_ref(),
+            journal: backups.as_ref().map(|(journal, _)| journal),
+            report: Some(&report),
+            quality_metrics: quality_metrics.unwrap_or(false),
+        },
+        &compression_status,
+        &TauriEventSink::new(&app),
+    );
 
-    state
-        .compression_status
+    if let Some(Err(e)) = cache.as_ref().map(|c| c.save()) {
+        log::error!("Cannot save the compression cache: {e}");
+    }
+    if let Some(Err(e)) = backups.map(|(journal, store)| store.finish_job(journal)) {
+        log::error!("Cannot finish the job journal: {e}");
+    }
+    if let Ok(summary) = &result {
+        app.state::<Mutex<AppData>>().lock()?.last_report = Some(report.finish(summary));
+    }
+
+    compression_status
         .is_compressing
         .store(false, Ordering::Relaxed);
+
+    // Keep the results around in case the app does not exit cleanly
+    if let Err(e) = save_session(&app) {
+        log::error!("Cannot save the session: {e}");
+    }
+
+    result?;
     Ok(())
 }
 
+/// Saves the report of the last batch, the format is guessed from the extension if not given
+#[tauri::command]
+pub fn export_report(
+    app: tauri::AppHandle,
+    path: String,
+    format: Option<ReportFormat>,
+) -> Result<(), CommandError> {
+    let path = PathBuf::from(path);
+    let format = format
+        .or_else(|| ReportFormat::from_path(&path))
+        .unwrap_or(ReportFormat::Json);
+    let state = app.state::<Mutex<AppData>>();
+    let state = state.lock()?;
+    let report = state
+        .last_report
+        .as_ref()
+        .ok_or_else(|| CommandError::Generic("There is no compression to report".into()))?;
+    Ok(report.save(&path, format)?)
+}
+
 #[tauri::command]
 pub async fn preview(
     app: tauri::AppHandle,
     ids: Vec<String>,
     options: OptionsPayload,
     threads: usize,
-) {
+) -> Result<(), CommandError> {
+    options.validate()?;
     rayon::ThreadPoolBuilder::new().num_threads(max(threads, 1));
+    let preview_directory = app.path().temp_dir()?;
     let state = app.state::<Mutex<AppData>>();
-    let state = state.lock().unwrap(); //TODO
+    let state = state.lock()?;
 
     let images: Vec<CImage> = ids
         .iter()
-        .map(|id| state.file_list.get(id.as_str()).cloned().unwrap())
+        .filter_map(|id| state.file_list.get(id.as_str()).cloned())
         .collect();
 
     drop(state);
 
+    let sink = TauriEventSink::new(&app);
     images.par_iter().for_each(|cimage| {
-        let result = preview_cimage(&app, cimage, &options);
-        let state = app.state::<Mutex<AppData>>();
-        let mut state = state.lock().unwrap(); //TODO
-        state.file_list.replace(result.clone().cimage);
-        app.emit("fileList:updateCImage", result).unwrap(); //TODO
+        let result = preview_cimage(cimage, &options, &preview_directory);
+        sink.image_updated(&result);
     });
+
+    Ok(())
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct OutputPathPreview {
+    pub id: String,
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Resolves the output path of the given images without compressing them
+#[tauri::command]
+pub fn preview_output_paths(
+    app: tauri::AppHandle,
+    ids: Vec<String>,
+    options: OptionsPayload,
+    base_folder: String,
+) -> Result<Vec<OutputPathPreview>, CommandError> {
+    options.validate()?;
+    let state = app.state::<Mutex<AppData>>();
+    let state = state.lock()?;
+
+    let context = CompressionContext::new(&base_folder, u64::MAX);
+    // Counters follow the order the images are compressed in
+    let previews = state
+        .file_list
+        .iter()
+        .enumerate()
+        .filter(|(_, cimage)| ids.contains(&cimage.id))
+        .map(|(index, cimage)| {
+            let path = planned_output_path(
+                cimage,
+                &options,
+                &CompressionContext {
+                    counter: index + 1,
+                    ..context
+                },
+            );
+            OutputPathPreview {
+                id: cimage.id.clone(),
+                path: path.as_ref().ok().map(|p| p.display().to_string()),
+                error: path.err().map(|e| e.to_string()),
+            }
+        })
+        .collect();
+    Ok(previews)
+}
+
+/// Reports what `compress` would do with the current list, without writing anything
+#[tauri::command]
+pub async fn plan_compression(
+    app: tauri::AppHandle,
+    options: OptionsPayload,
+    base_folder: String,
+    max_file_size: Option<u64>,
+) -> Result<CompressionPlan, CommandError> {
+    let state = app.state::<Mutex<AppData>>();
+    let state = state.lock()?;
+    let images: Vec<CImage> = state.file_list.iter().cloned().collect();
+    drop(state);
+
+    let max_file_size = max_file_size.unwrap_or(ResourceLimits::default().max_file_size);
+    Ok(caesium_core::plan::plan_compression(
+        &images,
+        &options,
+        &base_folder,
+        max_file_size,
+    )?)
 }
@@ -0,0 +1,58 @@
+use crate::errors::CommandError;
+use caesium_core::app_data::AppData;
+use caesium_core::journal::{BackupStore, JobInfo, UndoResult, DEFAULT_RETAINED_JOBS};
+use caesium_core::scan_files::{map_file, FileList};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use tauri::Manager;
+
+const BACKUPS_FOLDER_NAME: &str = "backups";
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct UndoJobResult {
+    pub undo: UndoResult,
+    pub file_list: FileList,
+}
+
+pub(crate) fn backup_store(app: &tauri::AppHandle) -> Result<BackupStore, CommandError> {
+    Ok(BackupStore::new(
+        app.path().app_data_dir()?.join(BACKUPS_FOLDER_NAME),
+        DEFAULT_RETAINED_JOBS,
+    ))
+}
+
+#[tauri::command]
+pub fn list_jobs(app: tauri::AppHandle) -> Result<Vec<JobInfo>, CommandError> {
+    Ok(backup_store(&app)?.jobs()?)
+}
+
+/// Reverts a compression job and resets the restored images in the list
+#[tauri::command]
+pub fn undo_job(app: tauri::AppHandle, job_id: String) -> Result<UndoJobResult, CommandError> {
+    let state = app.state::<Mutex<AppData>>();
+    if state
+        .lock()?
+        .compression_status
+        .is_compressing
+        .load(Ordering::Relaxed)
+    {
+        return Err(CommandError::Generic(
+            "Cannot undo a job while compressing".into(),
+        ));
+    }
+
+    let undo = backup_store(&app)?.undo(&job_id)?;
+
+    let mut state = state.lock()?;
+    for path in &undo.restored {
+        if let Some(cimage) = map_file(path) {
+            if state.file_list.get(&cimage.id).is_some() {
+                state.file_list.replace(cimage);
+            }
+        }
+    }
+    Ok(UndoJobResult {
+        undo,
+        file_list: state.file_list_payload(),
+    })
+}
@@ -1,13 +1,13 @@
-use crate::app_data::AppData;
+use crate::commands::process_files;
 use crate::errors::CommandError;
-use crate::scan_files::{get_file_mime_type, process_files, FileList};
+use caesium_core::app_data::{AppData, FileListColumn, SortOrder};
+use caesium_core::scan_files::{get_media_type, FileList};
 use regex::Regex;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::{absolute, Path, PathBuf};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tauri::{Emitter, Manager};
-use tauri_plugin_dialog::FilePath;
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
 pub struct AdvancedImportDialogSizeFilter {
@@ -32,10 +32,7 @@ pub fn clear_list(app: tauri::AppHandle) -> Result<FileList, CommandError> {
 
 #[tauri::command]
 pub fn add_from_drop(app: tauri::AppHandle, files_or_folders: Vec<String>, recursive: bool) {
-    let entries = files_or_folders
-        .iter()
-        .map(|f| FilePath::from(PathBuf::from(f)))
-        .collect();
+    let entries = files_or_folders.iter().map(PathBuf::from).collect();
     process_files(&app, entries, recursive)
 }
 
@@ -54,14 +51,7 @@ pub fn remove_items_from_list(
     state.file_list.remove_ids(&keys);
     state.compute_base_path()?;
 
-    Ok(FileList {
-        files: state.file_list.paged_list.clone(),
-        total_files: state.file_list.len(),
-        base_folder: absolute(state.base_path.clone().unwrap_or(PathBuf::new())) //TODO
-            .unwrap_or_default()
-            .display()
-            .to_string(),
-    })
+    Ok(state.file_list_payload())
 }
 
 fn remove_all_items_from_list(state: &mut AppData) -> Result<FileList, CommandError> {
@@ -82,14 +72,7 @@ pub fn change_page(app: tauri::AppHandle, page: usize) -> Result<FileList, Comma
 
     state.file_list.change_page(page);
 
-    Ok(FileList {
-        files: state.file_list.paged_list.clone(),
-        total_files: state.file_list.len(),
-        base_folder: absolute(state.base_path.clone().unwrap_or(PathBuf::new())) //TODO
-            .unwrap_or_default()
-            .display()
-            .to_string(),
-    })
+    Ok(state.file_list_payload())
 }
 
 #[tauri::command]
@@ -101,21 +84,16 @@ pub fn sort_list(
     let state = app.state::<Mutex<AppData>>();
     let mut state = state.lock()?;
 
-    let file_list_column = crate::app_data::FileListColumn::from_str(&column)
-        .ok_or_else(|| CommandError::Generic(Box::from(format!("Unknown column: {column}"))))?;
-    let order = crate::app_data::SortOrder::from_str(order.as_str())
-        .ok_or_else(|| CommandError::Generic(Box::from(format!("Unknown ordering: {order}"))))?;
+    let file_list_column = column
+        .parse::<FileListColumn>()
+        .map_err(|_| CommandError::Generic(Box::from(format!("Unknown column: {column}"))))?;
+    let order = order
+        .parse::<SortOrder>()
+        .map_err(|_| CommandError::Generic(Box::from(format!("Unknown ordering: {order}"))))?;
 
     state.file_list.sort_list_by(file_list_column, order);
 
-    Ok(FileList {
-        files: state.file_list.paged_list.clone(),
-        total_files: state.file_list.len(),
-        base_folder: absolute(state.base_path.clone().unwrap_or(PathBuf::new())) //TODO
-            .unwrap_or_default()
-            .display()
-            .to_string(),
-    })
+    Ok(state.file_list_payload())
 }
 
 #[tauri::command]
@@ -125,14 +103,7 @@ pub fn filter_list(app: tauri::AppHandle, query: String) -> Result<FileList, Com
 
     state.file_list.filter_list(&query);
 
-    Ok(FileList {
-        files: state.file_list.paged_list.clone(),
-        total_files: state.file_list.len(),
-        base_folder: absolute(state.base_path.clone().unwrap_or(PathBuf::new())) //TODO
-            .unwrap_or_default()
-            .display()
-            .to_string(),
-    })
+    Ok(state.file_list_payload())
 }
 
 #[tauri::command]
@@ -149,10 +120,11 @@ pub fn add_from_advanced_import(
             return;
         }
         if path.is_file() {
-            let file_format = get_file_mime_type(&path);
-            if file_format.media_type() == "text/plain"
-                || file_format.media_type() == "application/octet-stream"
-            {
+            let media_type = get_media_type(&path);
+            if matches!(
+                media_type.as_deref(),
+                Some("text/plain" | "application/octet-stream")
+            ) {
                 if let Ok(file) = File::open(&path) {
                     let reader = BufReader::new(file);
                     for line in reader.lines().map_while(Result::ok) {
@@ -160,7 +132,7 @@ pub fn add_from_advanced_import(
                             .canonicalize()
                             .unwrap_or_default();
                         if line_path.exists() && is_valid_for_advanced_import(&line_path, &filter) {
-                            validated_file_list.push(FilePath::from(line_path));
+                            validated_file_list.push(line_path);
                         }
                     }
                 }
@@ -169,7 +141,7 @@ pub fn add_from_advanced_import(
         }
 
         if is_valid_for_advanced_import(&path, &filter) {
-            validated_file_list.push(FilePath::from(path));
+            validated_file_list.push(path);
         }
     });
 
@@ -1,10 +1,19 @@
 pub(crate) mod compression;
+pub(crate) mod jobs;
 pub(crate) mod list;
 pub(crate) mod post_compression_actions;
+pub(crate) mod presets;
+pub(crate) mod session;
+pub(crate) mod watch;
 
-use crate::scan_files::process_files;
+use crate::events::TauriEventSink;
+use caesium_core::app_data::AppData;
+use caesium_core::scan_files::import_files;
 use std::env;
-use tauri_plugin_dialog::DialogExt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+use tauri_plugin_dialog::{DialogExt, FilePath};
 
 #[tauri::command]
 pub fn open_import_folder_dialog(app: tauri::AppHandle, recursive: bool) {
@@ -14,7 +23,7 @@ pub fn open_import_folder_dialog(app: tauri::AppHandle, recursive: bool) {
             None => return,
         };
 
-        process_files(&app, folders, recursive);
+        process_files(&app, to_paths(folders), recursive);
     })
 }
 #[tauri::command]
@@ -23,17 +32,21 @@ pub fn open_import_files_dialog(app: tauri::AppHandle) {
 
     #[cfg(any(target_os = "windows", target_os = "macos"))]
     {
-        dialog = dialog.add_filter(
-            "Supported Images",
-            &["jpg", "jpeg", "png", "gif", "webp", "tif", "tiff"],
-        );
+        let extensions: Vec<&str> = [
+            "jpg", "jpeg", "png", "gif", "webp", "tif", "tiff", "avif", "jxl", "heic", "heif",
+            "bmp",
+        ]
+        .into_iter()
+        .chain(caesium_core::codecs::raw_extensions())
+        .collect();
+        dialog = dialog.add_filter("Supported Images", &extensions);
     }
     dialog.pick_files(move |f| {
         let files = match f {
             Some(a) => a,
             None => return,
         };
-        process_files(&app, files, false);
+        process_files(&app, to_paths(files), false);
     });
 }
 
@@ -51,3 +64,15 @@ pub fn get_max_threads() -> usize {
         Some(v) => v.into(),
     }
 }
+
+pub(crate) fn process_files(app: &tauri::AppHandle, file_paths: Vec<PathBuf>, recursive: bool) {
+    let state = app.state::<Mutex<AppData>>();
+    import_files(&state, &file_paths, recursive, &TauriEventSink::new(app));
+}
+
+fn to_paths(file_paths: Vec<FilePath>) -> Vec<PathBuf> {
+    file_paths
+        .into_iter()
+        .filter_map(|f| f.as_path().map(PathBuf::from))
+        .collect()
+}
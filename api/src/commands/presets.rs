@@ -0,0 +1,67 @@
+use crate::errors::CommandError;
+use caesium_core::options::OptionsPayload;
+use caesium_core::presets::{Preset, PresetStore};
+use std::path::PathBuf;
+use tauri::Manager;
+
+pub(crate) fn preset_store(app: &tauri::AppHandle) -> Result<PresetStore, CommandError> {
+    Ok(PresetStore::new(
+        app.path().app_config_dir()?.join("presets"),
+    ))
+}
+
+#[tauri::command]
+pub fn list_presets(app: tauri::AppHandle) -> Result<Vec<Preset>, CommandError> {
+    Ok(preset_store(&app)?.list()?)
+}
+
+#[tauri::command]
+pub fn create_preset(
+    app: tauri::AppHandle,
+    name: String,
+    description: String,
+    options: OptionsPayload,
+) -> Result<Preset, CommandError> {
+    Ok(preset_store(&app)?.create(Preset {
+        name,
+        description,
+        options,
+    })?)
+}
+
+#[tauri::command]
+pub fn update_preset(
+    app: tauri::AppHandle,
+    name: String,
+    preset: Preset,
+) -> Result<Preset, CommandError> {
+    Ok(preset_store(&app)?.update(&name, preset)?)
+}
+
+#[tauri::command]
+pub fn delete_preset(app: tauri::AppHandle, name: String) -> Result<(), CommandError> {
+    Ok(preset_store(&app)?.delete(&name)?)
+}
+
+#[tauri::command]
+pub fn duplicate_preset(
+    app: tauri::AppHandle,
+    name: String,
+    new_name: String,
+) -> Result<Preset, CommandError> {
+    Ok(preset_store(&app)?.duplicate(&name, &new_name)?)
+}
+
+#[tauri::command]
+pub fn import_preset(app: tauri::AppHandle, path: String) -> Result<Preset, CommandError> {
+    Ok(preset_store(&app)?.import(&PathBuf::from(path))?)
+}
+
+#[tauri::command]
+pub fn export_preset(
+    app: tauri::AppHandle,
+    name: String,
+    path: String,
+) -> Result<(), CommandError> {
+    Ok(preset_store(&app)?.export(&name, &PathBuf::from(path))?)
+}
@@ -0,0 +1,77 @@
+use crate::errors::CommandError;
+use caesium_core::app_data::{AppData, AppDataFileListSorting};
+use caesium_core::scan_files::FileList;
+use caesium_core::session::{RevalidationResult, Session};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::Manager;
+
+const SESSION_FILE_NAME: &str = "session.json";
+
+// Set once the webview asked for the previous session, so that reloading the
+// webview does not restore it again and an empty list never overwrites it
+static SESSION_RESTORED: AtomicBool = AtomicBool::new(false);
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct RestoredList {
+    pub file_list: FileList,
+    pub sorting: AppDataFileListSorting,
+    pub revalidation: RevalidationResult,
+}
+
+fn session_path(app: &tauri::AppHandle) -> Result<PathBuf, CommandError> {
+    Ok(app.path().app_data_dir()?.join(SESSION_FILE_NAME))
+}
+
+/// Loads the list saved when the app was last closed, if any
+#[tauri::command]
+pub fn restore_session(app: tauri::AppHandle) -> Result<Option<RestoredList>, CommandError> {
+    if SESSION_RESTORED.swap(true, Ordering::Relaxed) {
+        return Ok(None);
+    }
+    let path = session_path(&app)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(restore_list(&app, &path)?))
+}
+
+#[tauri::command]
+pub fn open_project(app: tauri::AppHandle, path: String) -> Result<RestoredList, CommandError> {
+    restore_list(&app, Path::new(&path))
+}
+
+#[tauri::command]
+pub fn save_project(app: tauri::AppHandle, path: String) -> Result<(), CommandError> {
+    save_list(&app, Path::new(&path))
+}
+
+pub(crate) fn save_session(app: &tauri::AppHandle) -> Result<(), CommandError> {
+    if !SESSION_RESTORED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    save_list(app, &session_path(app)?)
+}
+
+fn save_list(app: &tauri::AppHandle, path: &Path) -> Result<(), CommandError> {
+    let state = app.state::<Mutex<AppData>>();
+    let session = Session::from_app_data(&*state.lock()?);
+    session.save(path)?;
+    Ok(())
+}
+
+fn restore_list(app: &tauri::AppHandle, path: &Path) -> Result<RestoredList, CommandError> {
+    let mut session = Session::load(path)?;
+    let revalidation = session.revalidate();
+
+    let state = app.state::<Mutex<AppData>>();
+    let mut state = state.lock()?;
+    state.restore_session(session);
+
+    Ok(RestoredList {
+        file_list: state.file_list_payload(),
+        sorting: state.file_list.sorting.clone(),
+        revalidation,
+    })
+}
@@ -0,0 +1,100 @@
+use crate::commands::presets::preset_store;
+use crate::errors::CommandError;
+use crate::events::TauriEventSink;
+use caesium_core::cache::CompressionCache;
+use caesium_core::watch::{FolderWatcher, WatchError, WatchFolder, WatchSettings};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+
+const WATCH_FOLDERS_FILE_NAME: &str = "watch-folders.json";
+// Kept apart from the cache of the main list, both are saved independently
+const WATCH_CACHE_FILE_NAME: &str = "watch-cache.json";
+
+fn watch_folders_path(app: &tauri::AppHandle) -> Result<PathBuf, CommandError> {
+    Ok(app.path().app_config_dir()?.join(WATCH_FOLDERS_FILE_NAME))
+}
+
+/// Creates the watcher and resumes the folders registered in previous runs
+pub(crate) fn start_watcher(app: &tauri::AppHandle) -> Result<FolderWatcher, CommandError> {
+    let cache = app
+        .path()
+        .app_cache_dir()
+        .map_err(CommandError::from)
+        .and_then(|dir| Ok(CompressionCache::open(dir.join(WATCH_CACHE_FILE_NAME))?))
+        .inspect_err(|e| log::error!("Cannot open the watch folder cache: {e}"))
+        .ok();
+    let mut watcher = FolderWatcher::new(
+        WatchSettings::default(),
+        cache.map(Arc::new),
+        Arc::new(TauriEventSink::new(app)),
+    )?;
+
+    let store = preset_store(app)?;
+    for folder in WatchFolder::load_all(&watch_folders_path(app)?)? {
+        let result = store
+            .get(&folder.preset)
+            .map_err(CommandError::from)
+            .and_then(|preset| {
+                Ok(watcher.watch(&folder.path, folder.recursive, preset.options)?)
+            });
+        if let Err(e) = result {
+            log::error!("Cannot watch {}: {e}", folder.path.display());
+        }
+    }
+    Ok(watcher)
+}
+
+#[tauri::command]
+pub fn list_watch_folders(app: tauri::AppHandle) -> Result<Vec<WatchFolder>, CommandError> {
+    Ok(WatchFolder::load_all(&watch_folders_path(&app)?)?)
+}
+
+#[tauri::command]
+pub fn add_watch_folder(
+    app: tauri::AppHandle,
+    path: String,
+    preset: String,
+    recursive: bool,
+) -> Result<WatchFolder, CommandError> {
+    let options = preset_store(&app)?.get(&preset)?.options;
+    let state = app.state::<Mutex<Option<FolderWatcher>>>();
+    let mut state = state.lock()?;
+    let watcher = state
+        .as_mut()
+        .ok_or_else(|| CommandError::Generic("Watch folders are not available".into()))?;
+
+    let config_path = watch_folders_path(&app)?;
+    let mut folders = WatchFolder::load_all(&config_path)?;
+    let folder = WatchFolder {
+        path: watcher.watch(Path::new(&path), recursive, options)?,
+        preset,
+        recursive,
+    };
+    folders.retain(|f| f.path != folder.path);
+    folders.push(folder.clone());
+    if let Err(e) = WatchFolder::save_all(&config_path, &folders) {
+        watcher.unwatch(&folder.path)?;
+        return Err(e.into());
+    }
+    Ok(folder)
+}
+
+#[tauri::command]
+pub fn remove_watch_folder(app: tauri::AppHandle, path: String) -> Result<(), CommandError> {
+    let path = PathBuf::from(path);
+    let state = app.state::<Mutex<Option<FolderWatcher>>>();
+    if let Some(watcher) = state.lock()?.as_mut() {
+        // Folders whose preset was deleted are registered but never started
+        match watcher.unwatch(&path) {
+            Ok(()) | Err(WatchError::NotWatched(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let config_path = watch_folders_path(&app)?;
+    let mut folders = WatchFolder::load_all(&config_path)?;
+    folders.retain(|f| f.path != path);
+    WatchFolder::save_all(&config_path, &folders)?;
+    Ok(())
+}
@@ -1,3 +1,6 @@
+use caesium_core::errors::BatchError;
+use serde::Serialize;
+
 #[derive(Debug, thiserror::Error)]
 pub enum CommandError {
     #[error(transparent)]
@@ -8,16 +11,44 @@ pub enum CommandError {
     Poisoned,
     #[error(transparent)]
     ThreadPool(#[from] rayon::ThreadPoolBuildError),
+    #[error(transparent)]
+    InvalidOptions(#[from] caesium_core::options::OptionsError),
+    #[error(transparent)]
+    Preset(#[from] caesium_core::presets::PresetError),
+    #[error(transparent)]
+    Session(#[from] caesium_core::session::SessionError),
+    #[error(transparent)]
+    Cache(#[from] caesium_core::cache::CacheError),
+    #[error(transparent)]
+    Watch(#[from] caesium_core::watch::WatchError),
+    #[error(transparent)]
+    Journal(#[from] caesium_core::journal::JournalError),
+    #[error(transparent)]
+    Report(#[from] caesium_core::report::ReportError),
     #[error("{0}")]
     Generic(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
 
-impl serde::Serialize for CommandError {
+impl Serialize for CommandError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::ser::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        match self {
+            // Sent field by field, so the frontend can point at the invalid options
+            CommandError::InvalidOptions(e) => e.errors.serialize(serializer),
+            _ => serializer.serialize_str(self.to_string().as_ref()),
+        }
+    }
+}
+
+// Invalid options are reported the same way whether the batch or the command found them
+impl From<BatchError> for CommandError {
+    fn from(e: BatchError) -> Self {
+        match e {
+            BatchError::InvalidOptions(e) => CommandError::InvalidOptions(e),
+            BatchError::ThreadPool(e) => CommandError::ThreadPool(e),
+        }
     }
 }
 
@@ -0,0 +1,80 @@
+use caesium_core::app_data::AppData;
+use caesium_core::compressor::{CompressionResult, CompressionSummary};
+use caesium_core::events::EventSink;
+use caesium_core::scan_files::{FileImportProgress, FileList, ImportFinishedResult};
+use caesium_core::ImageStatus;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{Emitter, Manager};
+
+/// Forwards core events to the webview and keeps the managed [`AppData`] in sync
+pub struct TauriEventSink {
+    app: tauri::AppHandle,
+}
+
+#[derive(Serialize, Clone)]
+struct WatchedFileProcessed<'a> {
+    folder: PathBuf,
+    result: &'a CompressionResult,
+}
+
+impl TauriEventSink {
+    pub fn new(app: &tauri::AppHandle) -> Self {
+        Self { app: app.clone() }
+    }
+
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Err(e) = self.app.emit(event, payload) {
+            log::error!("Cannot emit event {event}: {e}");
+        }
+    }
+}
+
+impl EventSink for TauriEventSink {
+    fn import_started(&self) {
+        self.emit("fileImporter:importStarted", ());
+    }
+
+    fn import_progress(&self, progress: &FileImportProgress) {
+        self.emit("fileImporter:importProgress", progress);
+    }
+
+    fn import_finished(&self, result: &ImportFinishedResult, file_list: &FileList) {
+        self.emit("fileImporter:importFinished", result);
+        self.emit("fileList:getList", file_list);
+    }
+
+    fn image_updated(&self, result: &CompressionResult) {
+        if !matches!(result.cimage.status, ImageStatus::Compressing) {
+            let state = self.app.state::<Mutex<AppData>>();
+            match state.lock() {
+                Ok(mut state) => state.file_list.replace(result.cimage.clone()),
+                Err(_) => log::error!("Cannot update {}, state is poisoned", result.cimage.id),
+            }
+        }
+        self.emit("fileList:updateCImage", result);
+    }
+
+    fn compression_progress(&self, progress: usize) {
+        self.emit("fileList:compressionProgress", progress);
+    }
+
+    fn compression_paused(&self) {
+        self.emit("fileList:compressionPaused", ());
+    }
+
+    fn compression_finished(&self, summary: &CompressionSummary) {
+        self.emit("fileList:compressionFinished", summary);
+    }
+
+    fn watched_file_processed(&self, folder: &Path, result: &CompressionResult) {
+        self.emit(
+            "watchFolder:fileProcessed",
+            WatchedFileProcessed {
+                folder: folder.to_path_buf(),
+                result,
+            },
+        );
+    }
+}
@@ -1,78 +1,32 @@
-use crate::app_data::AppData;
 use crate::commands::compression::{
-    cancel_compression, compress, pause_compression, preview, resume_compression,
+    cancel_compression, compress, export_report, pause_compression, plan_compression, preview,
+    preview_output_paths, resume_compression,
 };
+use crate::commands::jobs::{list_jobs, undo_job};
 use crate::commands::list::{
     add_from_advanced_import, add_from_drop, change_page, clear_list, filter_list,
     remove_items_from_list, sort_list,
 };
 use crate::commands::post_compression_actions::exec_post_compression_action;
+use crate::commands::presets::{
+    create_preset, delete_preset, duplicate_preset, export_preset, import_preset, list_presets,
+    update_preset,
+};
+use crate::commands::session::{open_project, restore_session, save_project, save_session};
+use crate::commands::watch::{
+    add_watch_folder, list_watch_folders, remove_watch_folder, start_watcher,
+};
 use crate::commands::{
     get_executable_dir, get_max_threads, open_import_files_dialog, open_import_folder_dialog,
 };
-use serde_repr::*;
-use std::borrow::Borrow;
-use std::env;
-use std::hash::{Hash, Hasher};
+use caesium_core::app_data::AppData;
 use std::sync::Mutex;
-use tauri::Manager;
+use tauri::{Manager, RunEvent};
 use tauri_plugin_log::{Target, TargetKind};
 
-mod app_data;
 mod commands;
-mod compressor;
 mod errors;
-mod scan_files;
-
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
-pub struct CImage {
-    pub id: String,
-    pub name: String,
-    pub path: String,
-    pub directory: String,
-    pub mime_type: String,
-    pub size: u64,
-    pub width: usize,
-    pub height: usize,
-
-    pub compressed_width: usize,
-    pub compressed_height: usize,
-    pub compressed_size: u64,
-    pub compressed_file_path: String,
-    pub info: String,
-    pub status: ImageStatus,
-}
-
-#[derive(Serialize_repr, Deserialize_repr, Clone, Debug, Default)]
-#[repr(i8)]
-pub enum ImageStatus {
-    #[default]
-    New = 0,
-    Success = 1,
-    Warning = 2,
-    Error = 3,
-    Compressing = -1,
-}
-
-// Equality and hashing only based on `id`
-impl PartialEq for CImage {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
-    }
-}
-impl Eq for CImage {}
-
-impl Hash for CImage {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
-    }
-}
-
-impl Borrow<str> for CImage {
-    fn borrow(&self) -> &str {
-        &self.id
-    }
-}
+mod events;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -106,6 +60,10 @@ pub fn run() {
                 window.close_devtools();
             }
             app.manage(Mutex::new(AppData::new()));
+            let watcher = start_watcher(app.handle())
+                .inspect_err(|e| log::error!("Cannot start the watch folders: {e}"))
+                .ok();
+            app.manage(Mutex::new(watcher));
             Ok(())
         })
         .plugin(tauri_plugin_opener::init())
@@ -119,6 +77,9 @@ pub fn run() {
             remove_items_from_list,
             compress,
             preview,
+            preview_output_paths,
+            plan_compression,
+            export_report,
             get_executable_dir,
             get_max_threads,
             exec_post_compression_action,
@@ -128,8 +89,30 @@ pub fn run() {
             pause_compression,
             resume_compression,
             cancel_compression,
-            add_from_advanced_import
+            add_from_advanced_import,
+            list_presets,
+            create_preset,
+            update_preset,
+            delete_preset,
+            duplicate_preset,
+            import_preset,
+            export_preset,
+            restore_session,
+            open_project,
+            save_project,
+            list_watch_folders,
+            add_watch_folder,
+            remove_watch_folder,
+            list_jobs,
+            undo_job
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                if let Err(e) = save_session(app) {
+                    log::error!("Cannot save the session: {e}");
+                }
+            }
+        });
 }
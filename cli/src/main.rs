@@ -0,0 +1,871 @@
+use caesium_core::app_data::CompressionStatus as BatchStatus;
+use caesium_core::batch::{compress_images, BatchSettings};
+use caesium_core::cache::CompressionCache;
+use caesium_core::compressor::{CompressionResult, CompressionStatus, CompressionSummary};
+use caesium_core::events::{EventSink, NoopEventSink};
+use caesium_core::journal::{BackupStore, OriginalAction, DEFAULT_RETAINED_JOBS};
+use caesium_core::memory::{ResourceLimits, DEFAULT_MAX_FILE_SIZE, DEFAULT_MEMORY_BUDGET};
+use caesium_core::options::{
+    CollisionPolicy, CompressionMode, CustomMetadataField, MetadataFieldKind, MetadataOptions,
+    MetadataPreset, OptionsPayload, OutputFormat, ResizeFit, ResizeMode, WatermarkKind,
+    WatermarkPosition,
+};
+use caesium_core::plan::{plan_compression, ImagePlan, PlannedAction};
+use caesium_core::presets::load_preset_file;
+use caesium_core::report::{ReportFormat, ReportRecorder};
+use caesium_core::scan_files::{map_file, scan_files};
+use caesium_core::watch::{FolderWatcher, WatchSettings};
+use caesium_core::{CImage, ImageStatus};
+use clap::Parser;
+use std::fs;
+use std::path::{absolute, Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+const EXIT_SUCCESS: u8 = 0;
+const EXIT_COMPRESSION_ERRORS: u8 = 1;
+const EXIT_INVALID_ARGUMENTS: u8 = 2;
+const EXIT_NO_IMAGES: u8 = 3;
+
+const MB: u64 = 1024 * 1024;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "caesium",
+    version,
+    about = "Compress images from the command line using the Caesium pipeline"
+)]
+struct Args {
+    /// Files or folders to compress
+    #[arg(required_unless_present = "undo")]
+    inputs: Vec<PathBuf>,
+
+    /// JSON file with the same compression, resize and output options sent by the app
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Preset file (JSON or TOML) exported from the app
+    #[arg(short, long, conflicts_with = "config")]
+    preset: Option<PathBuf>,
+
+    /// Folder where compressed images are written
+    #[arg(short, long, conflicts_with = "same_folder_as_input")]
+    output: Option<PathBuf>,
+
+    /// Write compressed images next to the originals
+    #[arg(long)]
+    same_folder_as_input: bool,
+
+    /// Scan folders recursively
+    #[arg(short = 'R', long)]
+    recursive: bool,
+
+    /// Recreate the input folder structure inside the output folder
+    #[arg(long)]
+    keep_structure: bool,
+
+    /// Quality applied to JPEG, PNG, GIF, WebP, AVIF and JPEG XL
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(0..=100))]
+    quality: Option<u32>,
+
+    /// Use lossless compression for PNG, WebP and JPEG XL
+    #[arg(long)]
+    lossless: bool,
+
+    /// AVIF encoder speed, from 1 (slowest, smallest output) to 10
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=10))]
+    avif_speed: Option<u32>,
+
+    /// JPEG XL encoder effort, from 1 (fastest) to 9 (slowest, smallest output)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=9))]
+    jxl_effort: Option<u32>,
+
+    /// Compress to a maximum output size, in bytes
+    #[arg(long)]
+    max_size: Option<usize>,
+
+    /// Find the lowest quality whose output keeps at least this SSIM (0-1)
+    #[arg(long, value_name = "SSIM", conflicts_with_all = ["max_size", "quality"])]
+    target_ssim: Option<f64>,
+
+    /// Maximum number of encodes tried to reach --target-ssim
+    #[arg(long, requires = "target_ssim")]
+    target_iterations: Option<u32>,
+
+    /// Output format
+    #[arg(short, long, value_parser = ["original", "jpg", "png", "webp", "tiff", "avif", "jxl"])]
+    format: Option<String>,
+
+    /// Format of HEIC, BMP and camera RAW inputs when the output format is "original"
+    #[arg(long, value_parser = ["jpg", "png", "webp", "tiff", "avif", "jxl"])]
+    decode_only_format: Option<String>,
+
+    /// Suffix appended to the output filename
+    #[arg(long)]
+    suffix: Option<String>,
+
+    /// Output name template, such as "{exif:DateTimeOriginal:%Y/%m}/{name}_{width}w.{ext}"
+    #[arg(long)]
+    template: Option<String>,
+
+    /// What to do when the output file already exists or another input has the same output
+    #[arg(long, value_parser = ["overwrite", "skip", "rename", "overwrite_if_older"])]
+    on_collision: Option<String>,
+
+    /// Resize so that the long edge matches this value, in pixels
+    #[arg(long)]
+    long_edge: Option<u32>,
+
+    /// Resize to this width, in pixels. With --height the image is fit in that box, see --fit
+    #[arg(long, conflicts_with_all = ["long_edge", "crop_aspect_ratio", "max_megapixels"])]
+    width: Option<u32>,
+
+    /// Resize to this height, in pixels
+    #[arg(long, conflicts_with_all = ["long_edge", "crop_aspect_ratio", "max_megapixels"])]
+    height: Option<u32>,
+
+    /// How the image fits in the --width and --height box: "contain" fits inside it,
+    /// "cover" fills it and crops around the focal point, "pad" fills the rest with
+    /// --background and "stretch" ignores the aspect ratio
+    #[arg(long, requires_all = ["width", "height"], value_parser = ["contain", "cover", "pad", "stretch"])]
+    fit: Option<String>,
+
+    /// Center of the crops, in percent of the width and height, such as "50,30"
+    #[arg(long, value_name = "X,Y")]
+    focal_point: Option<String>,
+
+    /// Padding color of --fit pad, "#RRGGBB" or "#RRGGBBAA"
+    #[arg(long)]
+    background: Option<String>,
+
+    /// Crop to an aspect ratio without resizing, such as "1:1" or "16:9"
+    #[arg(long, value_name = "W:H", conflicts_with_all = ["long_edge", "max_megapixels"])]
+    crop_aspect_ratio: Option<String>,
+
+    /// Scale down images bigger than this many megapixels
+    #[arg(long, conflicts_with = "long_edge")]
+    max_megapixels: Option<f64>,
+
+    /// Rotate the pixels as the EXIF orientation says before resizing, and reset it
+    #[arg(long)]
+    auto_orient: bool,
+
+    /// Draw this logo over the images, after resizing
+    #[arg(long, value_name = "PATH", conflicts_with = "watermark_text")]
+    watermark_image: Option<String>,
+
+    /// Draw this text over the images, with --watermark-font
+    #[arg(long, requires = "watermark_font")]
+    watermark_text: Option<String>,
+
+    /// TrueType or OpenType font of --watermark-text
+    #[arg(long, value_name = "PATH")]
+    watermark_font: Option<String>,
+
+    /// Color of --watermark-text, "#RRGGBB" or "#RRGGBBAA"
+    #[arg(long)]
+    watermark_color: Option<String>,
+
+    /// Where the watermark is drawn
+    #[arg(long, value_parser = [
+        "top_left", "top", "top_right", "left", "center", "right", "bottom_left", "bottom",
+        "bottom_right",
+    ])]
+    watermark_position: Option<String>,
+
+    /// Distance of the watermark from the edges, in percent of the short edge of the image
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=50))]
+    watermark_margin: Option<u32>,
+
+    /// Opacity of the watermark, from 0 to 100
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=100))]
+    watermark_opacity: Option<u32>,
+
+    /// Width of the watermark, in percent of the short edge of the image
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=100))]
+    watermark_scale: Option<u32>,
+
+    /// Repeat the watermark over the whole image instead of placing it once
+    #[arg(long)]
+    watermark_tiled: bool,
+
+    /// Remove metadata from the output
+    #[arg(long)]
+    strip_metadata: bool,
+
+    /// Metadata kept in JPEG, PNG and WebP outputs: "all", "privacy" (drops the location,
+    /// device identifiers, thumbnails, comments, XMP and IPTC) or a comma-separated list of
+    /// exif, gps, device-ids, xmp, iptc, icc, orientation, thumbnail and comments
+    #[arg(
+        long,
+        value_name = "PRESET|CATEGORIES",
+        conflicts_with = "strip_metadata"
+    )]
+    keep_metadata: Option<String>,
+
+    /// Artist written to the EXIF, XMP and IPTC metadata of JPEG, PNG, WebP and TIFF outputs.
+    /// Like the values below, it accepts the placeholders of --template, such as "{exif:Artist}"
+    #[arg(long)]
+    artist: Option<String>,
+
+    /// Copyright notice written to the metadata, such as "© {date:%Y} Jane Doe"
+    #[arg(long)]
+    copyright: Option<String>,
+
+    /// Description written to the metadata
+    #[arg(long)]
+    description: Option<String>,
+
+    /// XMP property written to the metadata, such as "photoshop:Credit=Jane Doe". Can be repeated
+    #[arg(long, value_name = "NAME=VALUE")]
+    xmp: Vec<String>,
+
+    /// IPTC dataset written to the metadata, such as "City=Rome". Can be repeated
+    #[arg(long, value_name = "NAME=VALUE")]
+    iptc: Vec<String>,
+
+    /// Number of threads, defaults to the available parallelism
+    #[arg(short, long)]
+    threads: Option<usize>,
+
+    /// Report files bigger than this as errors instead of compressing them, in MB
+    #[arg(long, default_value_t = DEFAULT_MAX_FILE_SIZE / MB)]
+    max_file_size: u64,
+
+    /// Estimated memory all threads can use at once, in MB (0 for unlimited)
+    #[arg(long, default_value_t = DEFAULT_MEMORY_BUDGET / MB)]
+    memory_budget: u64,
+
+    /// Cache file used to skip inputs already compressed with the same options
+    #[arg(long)]
+    cache: Option<PathBuf>,
+
+    /// Folder where a journal and backups of the replaced files are kept, so the run can be undone
+    #[arg(long)]
+    backup_dir: Option<PathBuf>,
+
+    /// Undo a run recorded in --backup-dir, then exit
+    #[arg(long, value_name = "JOB_ID", requires = "backup_dir")]
+    undo: Option<String>,
+
+    /// Keep running and compress the images added to the input folders
+    #[arg(short, long, conflicts_with = "json")]
+    watch: bool,
+
+    /// Print what would be done to each image without writing anything
+    #[arg(long, conflicts_with = "watch")]
+    dry_run: bool,
+
+    /// Write a report of every image to this file, as CSV, JSON or HTML depending on the extension
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["watch", "dry_run"])]
+    report: Option<PathBuf>,
+
+    /// Measure SSIM and PSNR of every output against its original, which takes extra time
+    #[arg(long)]
+    quality_metrics: bool,
+
+    /// Print the summary as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Only print the summary
+    #[arg(long)]
+    quiet: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let backups = args
+        .backup_dir
+        .as_ref()
+        .map(|d| BackupStore::new(d, DEFAULT_RETAINED_JOBS));
+    if let (Some(store), Some(job_id)) = (&backups, &args.undo) {
+        return undo_job(store, job_id);
+    }
+
+    let options = match build_options(&args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    let cache = match args.cache.as_ref().map(CompressionCache::open).transpose() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Cannot open the cache: {e}");
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    if args.watch {
+        return watch_inputs(&args, options, cache);
+    }
+
+    let report_format = match &args.report {
+        Some(path) => match ReportFormat::from_path(path) {
+            Some(format) => Some(format),
+            None => {
+                eprintln!("The report must be a .csv, .json or .html file");
+                return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+            }
+        },
+        None => None,
+    };
+
+    let (base_path, files) = scan_files(&args.inputs, None, args.recursive);
+    let images: Vec<CImage> = files.iter().filter_map(|f| map_file(f)).collect();
+
+    if images.is_empty() {
+        eprintln!("No supported images found");
+        return ExitCode::from(EXIT_NO_IMAGES);
+    }
+
+    let base_folder = base_path
+        .and_then(|p| absolute(p).ok())
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+
+    if args.dry_run {
+        return print_plan(&args, &images, &options, &base_folder);
+    }
+
+    let threads = args.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|v| v.get())
+            .unwrap_or(1)
+    });
+    let sink: &dyn EventSink = if args.quiet || args.json {
+        &NoopEventSink
+    } else {
+        &ConsoleEventSink
+    };
+
+    let journal = match backups.as_ref().map(BackupStore::start_job).transpose() {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("Cannot start the job journal: {e}");
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    let report = report_format.map(|_| ReportRecorder::new());
+    let summary = match compress_images(
+        &images,
+        &options,
+        &base_folder,
+        &BatchSettings {
+            threads,
+            limits: ResourceLimits {
+                max_file_size: args.max_file_size.saturating_mul(MB),
+                memory_budget: args.memory_budget.saturating_mul(MB),
+            },
+            cache: cache.as_ref(),
+            journal: journal.as_ref(),
+            report: report.as_ref(),
+            quality_metrics: args.quality_metrics,
+        },
+        &BatchStatus::default(),
+        sink,
+    ) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    if let Some(Err(e)) = cache.as_ref().map(|c| c.save()) {
+        eprintln!("Cannot save the cache: {e}");
+    }
+    if let (Some(store), Some(journal)) = (&backups, journal) {
+        if let Err(e) = store.finish_job(journal) {
+            eprintln!("Cannot finish the job journal: {e}");
+        }
+    }
+    if let (Some(path), Some(report), Some(format)) = (&args.report, report, report_format) {
+        if let Err(e) = report.finish(&summary).save(path, format) {
+            eprintln!("Cannot write the report: {e}");
+        }
+    }
+
+    if args.json {
+        match serde_json::to_string_pretty(&summary) {
+            Ok(s) => println!("{s}"),
+            Err(e) => eprintln!("{e}"),
+        }
+    } else {
+        print_summary(&summary);
+    }
+
+    if summary.total_errors > 0 {
+        ExitCode::from(EXIT_COMPRESSION_ERRORS)
+    } else {
+        ExitCode::from(EXIT_SUCCESS)
+    }
+}
+
+fn print_plan(
+    args: &Args,
+    images: &[CImage],
+    options: &OptionsPayload,
+    base_folder: &str,
+) -> ExitCode {
+    let max_file_size = args.max_file_size.saturating_mul(MB);
+    let plan = match plan_compression(images, options, base_folder, max_file_size) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    if args.json {
+        match serde_json::to_string_pretty(&plan) {
+            Ok(s) => println!("{s}"),
+            Err(e) => eprintln!("{e}"),
+        }
+    } else {
+        if !args.quiet {
+            for image in &plan.images {
+                print_planned_image(image);
+            }
+        }
+        println!(
+            "Would compress {} images, skip {} and fail {}. {} originals would be replaced, trashed or deleted",
+            plan.total_compress, plan.total_skip, plan.total_fail, plan.total_originals_affected
+        );
+        if plan.output_collisions > 0 {
+            println!(
+                "{} images have the same output path as another image",
+                plan.output_collisions
+            );
+        }
+    }
+
+    if plan.total_fail > 0 {
+        ExitCode::from(EXIT_COMPRESSION_ERRORS)
+    } else {
+        ExitCode::from(EXIT_SUCCESS)
+    }
+}
+
+fn print_planned_image(image: &ImagePlan) {
+    let collision = match image.collision_policy {
+        Some(CollisionPolicy::Rename) => " [renamed]",
+        Some(CollisionPolicy::Overwrite | CollisionPolicy::OverwriteIfOlder) => " [overwrites]",
+        _ => "",
+    };
+    let original = match image.original {
+        OriginalAction::Kept => "",
+        OriginalAction::Replaced => ", replaces the original",
+        OriginalAction::Trashed => ", original moved to the trash",
+        OriginalAction::Deleted => ", original deleted",
+    };
+    match image.action {
+        PlannedAction::Compress => println!(
+            "[COMPRESS] {} -> {} ({}x{}){original}{collision}",
+            image.input_path, image.output_path, image.output_width, image.output_height
+        ),
+        PlannedAction::Skip => println!("[SKIP] {}: {}", image.input_path, image.reason),
+        PlannedAction::Fail => eprintln!("[FAIL] {}: {}", image.input_path, image.reason),
+    }
+}
+
+fn undo_job(store: &BackupStore, job_id: &str) -> ExitCode {
+    let result = match store.undo(job_id) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+    for path in &result.restored {
+        println!("[RESTORED] {}", path.display());
+    }
+    for conflict in &result.conflicts {
+        eprintln!(
+            "[CONFLICT] {}: {}",
+            conflict.path.display(),
+            conflict.reason
+        );
+    }
+    println!(
+        "Restored {} files, {} conflicts",
+        result.restored.len(),
+        result.conflicts.len()
+    );
+
+    if result.conflicts.is_empty() {
+        ExitCode::from(EXIT_SUCCESS)
+    } else {
+        ExitCode::from(EXIT_COMPRESSION_ERRORS)
+    }
+}
+
+fn watch_inputs(args: &Args, options: OptionsPayload, cache: Option<CompressionCache>) -> ExitCode {
+    let settings = WatchSettings {
+        max_file_size: args.max_file_size.saturating_mul(MB),
+        ..WatchSettings::default()
+    };
+    let sink: Arc<dyn EventSink + Send> = if args.quiet {
+        Arc::new(NoopEventSink)
+    } else {
+        Arc::new(ConsoleEventSink)
+    };
+    let mut watcher = match FolderWatcher::new(settings, cache.map(Arc::new), sink) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("Cannot start watching: {e}");
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+    for input in &args.inputs {
+        if let Err(e) = watcher.watch(input, args.recursive, options.clone()) {
+            eprintln!("Cannot watch {}: {e}", input.display());
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    }
+
+    println!("Watching for new images, press Ctrl+C to stop");
+    loop {
+        std::thread::park();
+    }
+}
+
+fn build_options(args: &Args) -> Result<OptionsPayload, String> {
+    let mut options = match (&args.config, &args.preset) {
+        (Some(path), _) => {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Cannot read config file {}: {e}", path.display()))?;
+            serde_json::from_str::<OptionsPayload>(&content)
+                .map_err(|e| format!("Invalid config file {}: {e}", path.display()))?
+        }
+        (None, Some(path)) => {
+            load_preset_file(path)
+                .map_err(|e| format!("Invalid preset {}: {e}", path.display()))?
+                .options
+        }
+        (None, None) => OptionsPayload::default(),
+    };
+
+    if let Some(output) = &args.output {
+        options.output_options.output_folder = output.display().to_string();
+        options.output_options.same_folder_as_input = false;
+    }
+    if args.same_folder_as_input {
+        options.output_options.same_folder_as_input = true;
+    }
+    if args.keep_structure {
+        options.output_options.keep_folder_structure = true;
+    }
+    if let Some(format) = &args.format {
+        options.output_options.output_format = format
+            .parse::<OutputFormat>()
+            .map_err(|_| format!("Unknown output format: {format}"))?;
+    }
+    if let Some(format) = &args.decode_only_format {
+        options.output_options.decode_only_format = format
+            .parse::<OutputFormat>()
+            .map_err(|_| format!("Unknown output format: {format}"))?;
+    }
+    if let Some(suffix) = &args.suffix {
+        options.output_options.suffix = suffix.clone();
+    }
+    if let Some(template) = &args.template {
+        options.output_options.filename_template = template.clone();
+    }
+    if let Some(policy) = &args.on_collision {
+        options.output_options.collision_policy = policy
+            .parse::<CollisionPolicy>()
+            .map_err(|_| format!("Unknown collision policy: {policy}"))?;
+    }
+
+    if let Some(quality) = args.quality {
+        options.compression_options.jpeg.quality = quality;
+        options.compression_options.png.quality = quality;
+        options.compression_options.gif.quality = quality;
+        options.compression_options.webp.quality = quality;
+        options.compression_options.avif.quality = quality;
+        options.compression_options.jxl.quality = quality;
+    }
+    if args.lossless {
+        options.compression_options.webp.lossless = true;
+        options.compression_options.jxl.lossless = true;
+        options.compression_options.png.quality = 100;
+    }
+    if let Some(speed) = args.avif_speed {
+        options.compression_options.avif.speed = speed;
+    }
+    if let Some(effort) = args.jxl_effort {
+        options.compression_options.jxl.effort = effort;
+    }
+    if let Some(max_size) = args.max_size {
+        options.compression_options.compression_mode = CompressionMode::Size;
+        options.compression_options.max_size_value = max_size;
+        options.compression_options.max_size_unit = 1;
+    }
+    if let Some(ssim) = args.target_ssim {
+        options.compression_options.compression_mode = CompressionMode::TargetQuality;
+        options.compression_options.target_quality.ssim = ssim;
+    }
+    if let Some(iterations) = args.target_iterations {
+        options.compression_options.target_quality.max_iterations = iterations;
+    }
+    if args.strip_metadata {
+        options.compression_options.keep_metadata = false;
+    }
+    if let Some(metadata) = &args.keep_metadata {
+        options.compression_options.keep_metadata = true;
+        options.compression_options.metadata = parse_metadata_options(metadata)?;
+    }
+    let fields = &mut options.compression_options.metadata_fields;
+    if let Some(artist) = &args.artist {
+        fields.artist = artist.clone();
+    }
+    if let Some(copyright) = &args.copyright {
+        fields.copyright = copyright.clone();
+    }
+    if let Some(description) = &args.description {
+        fields.description = description.clone();
+    }
+    for (kind, values) in [
+        (MetadataFieldKind::Xmp, &args.xmp),
+        (MetadataFieldKind::Iptc, &args.iptc),
+    ] {
+        for value in values {
+            let (name, value) = value
+                .split_once('=')
+                .ok_or_else(|| format!("Expected NAME=VALUE, found {value}"))?;
+            fields.custom.push(CustomMetadataField {
+                kind,
+                name: name.trim().to_string(),
+                value: value.to_string(),
+            });
+        }
+    }
+
+    if let Some(long_edge) = args.long_edge {
+        options.resize_options.resize_enabled = true;
+        options.resize_options.resize_mode = ResizeMode::LongEdge;
+        options.resize_options.long_edge = long_edge;
+    }
+    let resize = &mut options.resize_options;
+    match (args.width, args.height) {
+        (Some(width), Some(height)) => {
+            resize.resize_enabled = true;
+            resize.resize_mode = ResizeMode::Dimensions;
+            resize.width = width;
+            resize.height = height;
+        }
+        (Some(width), None) => {
+            resize.resize_enabled = true;
+            resize.resize_mode = ResizeMode::Width;
+            resize.width = width;
+        }
+        (None, Some(height)) => {
+            resize.resize_enabled = true;
+            resize.resize_mode = ResizeMode::Height;
+            resize.height = height;
+        }
+        (None, None) => {}
+    }
+    if let Some(fit) = &args.fit {
+        resize.keep_aspect_ratio = fit != "stretch";
+        resize.fit = match fit.as_str() {
+            "cover" => ResizeFit::Cover,
+            "pad" => ResizeFit::Pad,
+            _ => ResizeFit::Contain,
+        };
+    }
+    if let Some(focal_point) = &args.focal_point {
+        (resize.focal_point_x, resize.focal_point_y) = parse_pair(focal_point, ',')
+            .ok_or_else(|| format!("Expected X,Y percentages, found {focal_point}"))?;
+    }
+    if let Some(background) = &args.background {
+        resize.background = background.clone();
+    }
+    if let Some(ratio) = &args.crop_aspect_ratio {
+        resize.resize_enabled = true;
+        resize.resize_mode = ResizeMode::AspectRatio;
+        (resize.aspect_ratio_width, resize.aspect_ratio_height) = parse_pair(ratio, ':')
+            .ok_or_else(|| format!("Expected an aspect ratio such as 16:9, found {ratio}"))?;
+    }
+    if let Some(megapixels) = args.max_megapixels {
+        resize.resize_enabled = true;
+        resize.resize_mode = ResizeMode::MaxMegapixels;
+        resize.max_megapixels = megapixels;
+    }
+    if args.auto_orient {
+        options.resize_options.auto_orient = true;
+    }
+
+    let watermark = &mut options.watermark_options;
+    if let Some(image) = &args.watermark_image {
+        watermark.enabled = true;
+        watermark.kind = WatermarkKind::Image;
+        watermark.image_path = image.clone();
+    }
+    if let Some(text) = &args.watermark_text {
+        watermark.enabled = true;
+        watermark.kind = WatermarkKind::Text;
+        watermark.text = text.clone();
+    }
+    if let Some(font) = &args.watermark_font {
+        watermark.font_path = font.clone();
+    }
+    if let Some(color) = &args.watermark_color {
+        watermark.color = color.clone();
+    }
+    if let Some(position) = &args.watermark_position {
+        watermark.position = position
+            .parse::<WatermarkPosition>()
+            .map_err(|_| format!("Unknown watermark position: {position}"))?;
+    }
+    if let Some(margin) = args.watermark_margin {
+        watermark.margin = margin;
+    }
+    if let Some(opacity) = args.watermark_opacity {
+        watermark.opacity = opacity;
+    }
+    if let Some(scale) = args.watermark_scale {
+        watermark.scale = scale;
+    }
+    if args.watermark_tiled {
+        watermark.tiled = true;
+    }
+
+    options.validate().map_err(|e| e.to_string())?;
+
+    Ok(options)
+}
+
+/// Two numbers separated by `separator`, such as "16:9"
+fn parse_pair(value: &str, separator: char) -> Option<(u32, u32)> {
+    let (first, second) = value.split_once(separator)?;
+    Some((first.trim().parse().ok()?, second.trim().parse().ok()?))
+}
+
+fn parse_metadata_options(value: &str) -> Result<MetadataOptions, String> {
+    let preset = match value {
+        "all" => MetadataPreset::All,
+        "privacy" => MetadataPreset::Privacy,
+        _ => MetadataPreset::Custom,
+    };
+    if preset != MetadataPreset::Custom {
+        return Ok(MetadataOptions {
+            preset,
+            ..MetadataOptions::default()
+        });
+    }
+
+    let mut metadata = MetadataOptions {
+        preset,
+        exif: false,
+        gps: false,
+        device_ids: false,
+        xmp: false,
+        iptc: false,
+        icc: false,
+        orientation: false,
+        thumbnail: false,
+        comments: false,
+    };
+    for category in value.split(',').map(str::trim) {
+        let kept = match category {
+            "exif" => &mut metadata.exif,
+            "gps" => &mut metadata.gps,
+            "device-ids" => &mut metadata.device_ids,
+            "xmp" => &mut metadata.xmp,
+            "iptc" => &mut metadata.iptc,
+            "icc" => &mut metadata.icc,
+            "orientation" => &mut metadata.orientation,
+            "thumbnail" => &mut metadata.thumbnail,
+            "comments" => &mut metadata.comments,
+            _ => return Err(format!("Unknown metadata category: {category}")),
+        };
+        *kept = true;
+    }
+    Ok(metadata)
+}
+
+struct ConsoleEventSink;
+
+impl EventSink for ConsoleEventSink {
+    fn image_updated(&self, result: &CompressionResult) {
+        let c = &result.cimage;
+        if matches!(c.status, ImageStatus::Compressing) {
+            return;
+        }
+        let rule = c
+            .applied_rule
+            .as_ref()
+            .map(|r| format!(" [rule: {r}]"))
+            .unwrap_or_default();
+        let quality = c
+            .quality_metrics
+            .map(|m| format!(" [SSIM {:.4}, PSNR {:.2} dB]", m.ssim, m.psnr))
+            .unwrap_or_default();
+        if result.cached {
+            println!("[UNCHANGED] {} -> {}", c.path, c.compressed_file_path);
+            return;
+        }
+        match result.status {
+            CompressionStatus::Success if c.collision_policy == Some(CollisionPolicy::Rename) => {
+                println!(
+                    "[RENAMED] {} -> {} ({} -> {} bytes){quality}{rule}",
+                    c.path, c.compressed_file_path, c.size, c.compressed_size
+                )
+            }
+            CompressionStatus::Success => println!(
+                "[OK] {} -> {} ({} -> {} bytes){quality}{rule}",
+                c.path, c.compressed_file_path, c.size, c.compressed_size
+            ),
+            CompressionStatus::Warning => println!("[SKIPPED] {}: {}", c.path, c.info),
+            CompressionStatus::Error => eprintln!("[ERROR] {}: {}", c.path, c.info),
+        }
+    }
+
+    fn watched_file_processed(&self, _folder: &Path, result: &CompressionResult) {
+        self.image_updated(result);
+    }
+}
+
+fn print_summary(summary: &CompressionSummary) {
+    let saved = summary
+        .original_size
+        .saturating_sub(summary.compressed_size);
+    let saved_percentage = if summary.original_size == 0 {
+        0.0
+    } else {
+        saved as f64 / summary.original_size as f64 * 100.0
+    };
+
+    println!(
+        "Compressed {} images in {}ms: {} succeeded, {} skipped, {} failed, {} unchanged",
+        summary.total_images,
+        summary.total_time,
+        summary.total_success,
+        summary.total_skipped,
+        summary.total_errors,
+        summary.total_cached
+    );
+    for (code, count) in &summary.errors_by_code {
+        println!("  {code:?}: {count}");
+    }
+    if summary.output_collisions > 0 {
+        println!(
+            "{} images had the same output path as another image",
+            summary.output_collisions
+        );
+    }
+    println!(
+        "Original size: {} bytes, compressed size: {} bytes, saved: {} bytes ({:.2}%)",
+        summary.original_size, summary.compressed_size, saved, saved_percentage
+    );
+    if let Some(job_id) = &summary.job_id {
+        println!("Undo this run with --undo {job_id}");
+    }
+}
@@ -1,53 +1,66 @@
+use crate::report::CompressionReport;
+use crate::scan_files::FileList;
 use crate::{scan_files, CImage};
 use indexmap::IndexSet;
 use std::cmp::{min, Ordering};
 use std::ops::Div;
-use std::path::PathBuf;
+use std::path::{absolute, PathBuf};
+use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
 pub enum FileListColumn {
     #[default]
     Filename,
     Size,
     Resolution,
     Saved,
+    Quality,
 }
 
-impl FileListColumn {
-    pub(crate) fn from_str(s: &str) -> Option<Self> {
+impl FromStr for FileListColumn {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "filename" => Some(FileListColumn::Filename),
-            "size" => Some(FileListColumn::Size),
-            "resolution" => Some(FileListColumn::Resolution),
-            "saved" => Some(FileListColumn::Saved),
-            _ => None,
+            "filename" => Ok(FileListColumn::Filename),
+            "size" => Ok(FileListColumn::Size),
+            "resolution" => Ok(FileListColumn::Resolution),
+            "saved" => Ok(FileListColumn::Saved),
+            "quality" => Ok(FileListColumn::Quality),
+            _ => Err(()),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
 pub enum SortOrder {
     #[default]
     Ascending,
     Descending,
 }
 
-impl SortOrder {
-    pub(crate) fn from_str(s: &str) -> Option<Self> {
+impl FromStr for SortOrder {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "ascending" => Some(SortOrder::Ascending),
-            "descending" => Some(SortOrder::Descending),
-            _ => None,
+            "ascending" => Ok(SortOrder::Ascending),
+            "descending" => Ok(SortOrder::Descending),
+            _ => Err(()),
         }
     }
 }
 
-#[derive(Default)]
 pub struct AppData {
-    pub(crate) file_list: AppDataFileList,
-    pub(crate) base_path: Option<PathBuf>,
-    pub(crate) compression_status: CompressionStatus,
+    pub file_list: AppDataFileList,
+    pub base_path: Option<PathBuf>,
+    pub compression_status: Arc<CompressionStatus>,
+    /// Report of the last finished batch
+    pub last_report: Option<CompressionReport>,
 }
 
 #[derive(Default)]
@@ -57,7 +70,6 @@ pub struct CompressionStatus {
     pub is_compressing: AtomicBool,
 }
 
-#[derive(Default)]
 pub struct AppDataFileList {
     pub list: IndexSet<CImage>,
     pub filtered_ids: IndexSet<String>,
@@ -68,25 +80,28 @@ pub struct AppDataFileList {
     pub sorting: AppDataFileListSorting,
 }
 
-#[derive(Default)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
 pub struct AppDataFileListSorting {
-    column: FileListColumn,
-    order: SortOrder,
+    pub column: FileListColumn,
+    pub order: SortOrder,
 }
 
-impl AppData {
-    pub fn default() -> Self {
+impl Default for AppData {
+    fn default() -> Self {
         Self {
             file_list: AppDataFileList::new(),
             base_path: None,
-            compression_status: CompressionStatus {
+            compression_status: Arc::new(CompressionStatus {
                 is_compression_cancelled: AtomicBool::new(false),
                 is_compression_paused: AtomicBool::new(false),
                 is_compressing: AtomicBool::new(false),
-            },
+            }),
+            last_report: None,
         }
     }
+}
 
+impl AppData {
     pub fn new() -> Self {
         Self::default()
     }
@@ -107,10 +122,21 @@ impl AppData {
         self.base_path = base_path;
         Ok(())
     }
+
+    pub fn file_list_payload(&self) -> FileList {
+        FileList {
+            files: self.file_list.paged_list.clone(),
+            total_files: self.file_list.len(),
+            base_folder: absolute(self.base_path.clone().unwrap_or_default())
+                .unwrap_or_default()
+                .display()
+                .to_string(),
+        }
+    }
 }
 
-impl AppDataFileList {
-    pub fn default() -> Self {
+impl Default for AppDataFileList {
+    fn default() -> Self {
         Self {
             list: IndexSet::new(),
             filtered_ids: IndexSet::new(),
@@ -121,7 +147,9 @@ impl AppDataFileList {
             sorting: AppDataFileListSorting::default(),
         }
     }
+}
 
+impl AppDataFileList {
     pub fn new() -> Self {
         Self::default()
     }
@@ -158,6 +186,10 @@ impl AppDataFileList {
         self.list.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn full_len(&self) -> usize {
         self.list.len()
     }
@@ -206,6 +238,10 @@ impl AppDataFileList {
                 FileListColumn::Saved => get_saved_size(a.size, a.compressed_size)
                     .partial_cmp(&(get_saved_size(b.size, b.compressed_size)))
                     .unwrap_or(Ordering::Equal), // TODO Don't know if this is the best way to handle this
+                // Images without metrics come first, as if they were the worst
+                FileListColumn::Quality => get_ssim(a)
+                    .partial_cmp(&get_ssim(b))
+                    .unwrap_or(Ordering::Equal),
             };
 
             match self.sorting.order {
@@ -260,6 +296,10 @@ impl AppDataFileList {
     }
 }
 
+fn get_ssim(cimage: &CImage) -> f64 {
+    cimage.quality_metrics.map_or(-1.0, |m| m.ssim)
+}
+
 fn get_saved_size(old_size: u64, new_size: u64) -> f64 {
     if old_size == 0 {
         return 0.0;
@@ -0,0 +1,180 @@
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A file written next to its destination under a temporary name and renamed into
+/// place by [`AtomicFile::commit`]. Until then `path` is left untouched, and the
+/// temporary file is removed if the value is dropped without committing, e.g. on
+/// error or panic. An existing destination keeps its permissions.
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: Option<File>,
+    permissions: Option<Permissions>,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let directory = path.parent().unwrap_or(Path::new("."));
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Missing file name"))?
+            .to_string_lossy();
+        let temp_path = directory.join(format!(
+            ".{file_name}.{}-{}.tmp",
+            process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let permissions = match fs::metadata(path) {
+            Ok(metadata) => Some(metadata.permissions()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            temp_path,
+            file: Some(file),
+            permissions,
+            committed: false,
+        })
+    }
+
+    pub fn file(&self) -> &File {
+        self.file.as_ref().expect("file is only taken on commit")
+    }
+
+    pub fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file
+            .as_mut()
+            .expect("file is only taken on commit")
+            .write_all(data)
+    }
+
+    /// Flushes the content to disk and replaces the destination with it
+    pub fn commit(mut self) -> io::Result<()> {
+        if let Some(file) = self.file.take() {
+            file.sync_all()?;
+            if let Some(permissions) = self.permissions.take() {
+                file.set_permissions(permissions)?;
+            }
+        }
+        fs::rename(&self.temp_path, &self.path)?;
+        self.committed = true;
+        sync_directory(&self.path);
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Close the handle first, Windows cannot remove open files
+        self.file.take();
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Copies `from` over `to` through an [`AtomicFile`]
+pub fn copy_atomically(from: &Path, to: &Path) -> io::Result<()> {
+    let mut output = AtomicFile::create(to)?;
+    let mut input = File::open(from)?;
+    io::copy(
+        &mut input,
+        output.file.as_mut().expect("file is only taken on commit"),
+    )?;
+    output.commit()
+}
+
+/// Makes the rename durable, failures are ignored as not every platform supports it
+fn sync_directory(path: &Path) {
+    #[cfg(unix)]
+    if let Some(directory) = path.parent() {
+        if let Ok(directory) = File::open(directory) {
+            let _ = directory.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names of the files in `directory`, sorted
+    fn file_names(directory: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn commit_replaces_the_destination() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("a.jpg");
+        fs::write(&path, b"old").unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(file_names(directory.path()).len(), 2);
+
+        file.commit().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(file_names(directory.path()), ["a.jpg"]);
+    }
+
+    #[test]
+    fn uncommitted_files_are_removed() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("a.jpg");
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+        assert!(file_names(directory.path()).is_empty());
+    }
+
+    #[test]
+    fn failed_copies_leave_no_temporary_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("a.jpg");
+        fs::write(&path, b"old").unwrap();
+
+        let missing = directory.path().join("missing.jpg");
+        assert!(copy_atomically(&missing, &path).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(file_names(directory.path()), ["a.jpg"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replaced_files_keep_their_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("a.jpg");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        file.commit().unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+}
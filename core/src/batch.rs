@@ -0,0 +1,205 @@
+use crate::app_data::CompressionStatus as BatchStatus;
+use crate::cache::CompressionCache;
+use crate::collision::OutputReservations;
+use crate::compressor::{
+    compress_cimage, reserve_outputs, CompressionContext, CompressionResult, CompressionStatus,
+    CompressionSummary,
+};
+use crate::errors::{BatchError, CompressionErrorCode};
+use crate::events::EventSink;
+use crate::journal::JobJournal;
+use crate::memory::{estimate_memory_usage, MemoryBudget, ResourceLimits};
+use crate::options::OptionsPayload;
+use crate::report::ReportRecorder;
+use crate::{CImage, ImageStatus};
+use chrono::Local;
+use std::cmp::max;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How a batch runs, independently of the compression options
+pub struct BatchSettings<'a> {
+    pub threads: usize,
+    pub limits: ResourceLimits,
+    /// Outputs of previous runs to reuse, updated with the new results
+    pub cache: Option<&'a CompressionCache>,
+    /// Records every write so the job can be undone
+    pub journal: Option<&'a JobJournal>,
+    /// Collects per-image results for a [`CompressionReport`](crate::report::CompressionReport)
+    pub report: Option<&'a ReportRecorder>,
+    /// Measure the similarity of each output to its original, see [`crate::metrics`]
+    pub quality_metrics: bool,
+}
+
+/// Compresses `images` on a dedicated pool of `settings.threads` workers.
+///
+/// Options are validated before any image is touched, and output paths are claimed
+/// in input order so images colliding with an earlier one follow the collision
+/// policy whatever the scheduling. Images are handed to the workers in input order
+/// once their estimated memory fits in the memory budget, so big files wait for
+/// others to finish instead of running all at once. Pause and cancel
+/// requests are read from `status` between images, and every update is reported
+/// through `sink`. The returned summary is also sent to [`EventSink::compression_finished`].
+pub fn compress_images(
+    images: &[CImage],
+    options: &OptionsPayload,
+    base_folder: &str,
+    settings: &BatchSettings,
+    status: &BatchStatus,
+    sink: &dyn EventSink,
+) -> Result<CompressionSummary, BatchError> {
+    options.validate()?;
+
+    let start_time = Instant::now();
+    let started = Local::now();
+    let total_success = AtomicUsize::new(0);
+    let total_errors = AtomicUsize::new(0);
+    let total_warnings = AtomicUsize::new(0);
+    let original_size = AtomicUsize::new(0);
+    let compressed_size = AtomicUsize::new(0);
+    let errors_by_code = Mutex::new(BTreeMap::<CompressionErrorCode, usize>::new());
+    let files_on_pause = Mutex::new(HashSet::<String>::new());
+    let total_cached = AtomicUsize::new(0);
+    let max_threads = max(settings.threads, 1);
+    let memory_budget = MemoryBudget::new(settings.limits.memory_budget);
+    let reservations = OutputReservations::new();
+    let context = |index: usize| CompressionContext {
+        base_folder,
+        max_file_size: settings.limits.max_file_size,
+        cache: settings.cache,
+        journal: settings.journal,
+        reservations: Some(&reservations),
+        counter: index + 1,
+        started,
+        quality_metrics: settings.quality_metrics,
+    };
+
+    let output_collisions = reserve_outputs(images, options, context);
+    if output_collisions > 0 {
+        log::warn!("{output_collisions} images have the same output path as another image");
+    }
+
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(max_threads)
+        .build()?;
+
+    sink.compression_progress(0);
+
+    let progress = AtomicUsize::new(0);
+
+    // Runs on a worker, with the memory of the image already reserved
+    let compress = |index: usize, cimage: &CImage| {
+        loop {
+            let is_cancelled = status.is_compression_cancelled.load(Ordering::Relaxed);
+            let is_paused = status.is_compression_paused.load(Ordering::Relaxed);
+
+            if is_cancelled {
+                return;
+            }
+
+            if is_paused {
+                let mut files_on_pause = files_on_pause.lock().unwrap_or_else(|e| e.into_inner());
+                if files_on_pause.len() < max_threads {
+                    files_on_pause.insert(cimage.id.clone());
+                }
+
+                if files_on_pause.len() == max_threads {
+                    sink.compression_paused();
+                    files_on_pause.insert(String::from(""));
+                    // This will cause the event not to trigger at every loop
+                }
+                drop(files_on_pause);
+
+                thread::sleep(Duration::from_millis(500)); // Sleep while paused
+                continue;
+            } else {
+                files_on_pause
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clear();
+                break; // Continue processing
+            }
+        }
+
+        original_size.fetch_add(cimage.size as usize, Ordering::Relaxed);
+        sink.image_updated(&CompressionResult {
+            status: CompressionStatus::Warning,
+            cimage: CImage {
+                status: ImageStatus::Compressing,
+                ..cimage.clone()
+            },
+            cached: false,
+        });
+        let image_start = Instant::now();
+        let result = compress_cimage(cimage, options, &context(index));
+        if let Some(report) = settings.report {
+            report.record(index, &result, image_start.elapsed());
+        }
+
+        // Count results
+        match result.status {
+            CompressionStatus::Success => total_success.fetch_add(1, Ordering::Relaxed),
+            CompressionStatus::Warning => total_warnings.fetch_add(1, Ordering::Relaxed),
+            CompressionStatus::Error => total_errors.fetch_add(1, Ordering::Relaxed),
+        };
+        if result.cached {
+            total_cached.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(code) = result.cimage.error_code {
+            *errors_by_code
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(code)
+                .or_default() += 1;
+        }
+
+        compressed_size.fetch_add(result.cimage.compressed_size as usize, Ordering::Relaxed);
+
+        sink.image_updated(&result);
+        let progress = progress.fetch_add(1, Ordering::Relaxed) + 1;
+        sink.compression_progress(progress);
+    };
+
+    // Memory is reserved here rather than on the workers: a worker waiting for memory
+    // could be the one that has to run the task releasing it
+    thread_pool.in_place_scope(|scope| {
+        let compress = &compress;
+        for (index, cimage) in images.iter().enumerate() {
+            if status.is_compression_cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            let reservation = memory_budget.acquire(estimate_memory_usage(cimage));
+            scope.spawn(move |_| {
+                compress(index, cimage);
+                drop(reservation);
+            });
+        }
+    });
+
+    let elapsed_time = start_time.elapsed();
+    let summary = CompressionSummary {
+        total_images: images.len(),
+        total_success: total_success.load(Ordering::Relaxed),
+        total_skipped: total_warnings.load(Ordering::Relaxed),
+        total_errors: total_errors.load(Ordering::Relaxed),
+        total_cached: total_cached.load(Ordering::Relaxed),
+        job_id: settings
+            .journal
+            .filter(|j| !j.is_empty())
+            .map(|j| j.id().to_string()),
+        output_collisions,
+        errors_by_code: errors_by_code
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner()),
+        original_size: original_size.load(Ordering::Relaxed),
+        compressed_size: compressed_size.load(Ordering::Relaxed),
+        total_time: elapsed_time.as_millis() as u64,
+    };
+
+    sink.compression_finished(&summary);
+
+    Ok(summary)
+}
@@ -0,0 +1,381 @@
+use crate::atomic_file::AtomicFile;
+use crate::compressor::{CompressionResult, CompressionStatus};
+use crate::metrics::QualityMetrics;
+use crate::options::{OptionsPayload, WatermarkKind, WatermarkOptions};
+use crate::template::TextTemplate;
+use crate::CImage;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Version written in the cache file, older or newer caches are discarded
+pub const CACHE_SCHEMA_VERSION: u32 = 2;
+
+/// Output produced for an input content and a set of effective options
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub status: CompressionStatus,
+    pub info: String,
+    pub output_path: PathBuf,
+    pub output_size: u64,
+    pub output_sha256: String,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub quality_metrics: Option<QualityMetrics>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Remembers the outputs of previous runs so unchanged inputs are not compressed again.
+///
+/// Entries are keyed by the SHA-256 of the input content together with the effective
+/// options, the watermark file, the output path and whether quality metrics were
+/// measured. An entry is only trusted if its output is still on disk with the same
+/// checksum.
+pub struct CompressionCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    is_dirty: AtomicBool,
+}
+
+impl CompressionCache {
+    /// Loads the cache stored in `path`, starting empty if it does not exist or is outdated
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, CacheError> {
+        let path = path.into();
+        let entries = match fs::read(&path) {
+            Ok(content) => match serde_json::from_slice::<CacheFile>(&content) {
+                Ok(file) if file.version == CACHE_SCHEMA_VERSION => file.entries,
+                Ok(_) | Err(_) => {
+                    log::warn!("Discarding unreadable cache {}", path.display());
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path,
+            entries: Mutex::new(entries),
+            is_dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), CacheError> {
+        if !self.is_dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let entries = self.lock().clone();
+        let content = serde_json::to_vec(&CacheFile {
+            version: CACHE_SCHEMA_VERSION,
+            entries,
+        })?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = AtomicFile::create(&self.path)?;
+        file.write_all(&content)?;
+        file.commit()?;
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+        self.is_dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the previous result for `cimage` if its output is still valid
+    pub(crate) fn lookup(&self, key: &str, cimage: &CImage) -> Option<CompressionResult> {
+        let entry = self.lock().get(key).cloned()?;
+        let is_valid = fs::metadata(&entry.output_path).is_ok_and(|m| m.len() == entry.output_size)
+            && file_sha256(&entry.output_path).is_ok_and(|hash| hash == entry.output_sha256);
+
+        if !is_valid {
+            self.lock().remove(key);
+            self.is_dirty.store(true, Ordering::Relaxed);
+            return None;
+        }
+
+        Some(CompressionResult {
+            status: entry.status,
+            cimage: CImage {
+                status: entry.status.into(),
+                info: format!("Unchanged since the last run. {}", entry.info)
+                    .trim()
+                    .to_string(),
+                compressed_width: entry.width,
+                compressed_height: entry.height,
+                compressed_size: entry.output_size,
+                compressed_file_path: entry.output_path.display().to_string(),
+                quality_metrics: entry.quality_metrics,
+                ..cimage.clone()
+            },
+            cached: true,
+        })
+    }
+
+    /// Stores a result, only completed outputs are worth remembering. Entries are keyed
+    /// by the planned output path, which differs from the actual one when it was renamed
+    /// to avoid a collision.
+    pub(crate) fn record(
+        &self,
+        input_sha256: &str,
+        options: &OptionsPayload,
+        planned_path: &Path,
+        quality_metrics: bool,
+        result: &CompressionResult,
+    ) {
+        let cimage = &result.cimage;
+        if matches!(result.status, CompressionStatus::Error)
+            || cimage.compressed_file_path.is_empty()
+        {
+            return;
+        }
+
+        let output_path = Path::new(&cimage.compressed_file_path);
+        let output_sha256 = match file_sha256(output_path) {
+            Ok(hash) => hash,
+            Err(e) => {
+                log::warn!("Cannot hash {}: {e}", output_path.display());
+                return;
+            }
+        };
+        let entry = CacheEntry {
+            status: result.status,
+            info: cimage.info.clone(),
+            output_path: output_path.to_path_buf(),
+            output_size: cimage.compressed_size,
+            output_sha256: output_sha256.clone(),
+            width: cimage.compressed_width,
+            height: cimage.compressed_height,
+            quality_metrics: cimage.quality_metrics,
+        };
+
+        let mut entries = self.lock();
+        // When the original was replaced, the next run will read the output as input
+        if output_path == Path::new(&cimage.path) {
+            entries.insert(
+                cache_key(&output_sha256, options, planned_path, quality_metrics),
+                entry.clone(),
+            );
+        }
+        let key = cache_key(input_sha256, options, planned_path, quality_metrics);
+        entries.insert(key, entry);
+        self.is_dirty.store(true, Ordering::Relaxed);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Outputs named or tagged after the time or the position of the image in the batch are
+/// not the same from one run to the next
+pub(crate) fn can_cache(options: &OptionsPayload) -> bool {
+    let fields = &options.compression_options.metadata_fields;
+    let templates = [
+        &options.output_options.filename_template,
+        &fields.artist,
+        &fields.copyright,
+        &fields.description,
+    ];
+    templates
+        .into_iter()
+        .chain(fields.custom.iter().map(|custom| &custom.value))
+        .all(|template| TextTemplate::parse(template).is_ok_and(|t| !t.varies_between_runs()))
+}
+
+pub(crate) fn cache_key(
+    input_sha256: &str,
+    options: &OptionsPayload,
+    output_path: &Path,
+    quality_metrics: bool,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(input_sha256);
+    hasher.update("|");
+    hasher.update(serde_json::to_string(options).unwrap_or_default());
+    hasher.update("|");
+    // The logo or the font can be replaced while their path stays the same
+    hasher.update(watermark_stamp(&options.watermark_options).unwrap_or_default());
+    hasher.update("|");
+    hasher.update(output_path.as_os_str().as_encoded_bytes());
+    // Results stored without metrics would otherwise hide them once they are turned on
+    hasher.update(if quality_metrics { "|metrics" } else { "|" });
+    format!("{:x}", hasher.finalize())
+}
+
+/// Size and modification time of the file drawn by an enabled watermark
+fn watermark_stamp(watermark: &WatermarkOptions) -> Option<String> {
+    if !watermark.enabled {
+        return None;
+    }
+    let path = match watermark.kind {
+        WatermarkKind::Image => &watermark.image_path,
+        WatermarkKind::Text => &watermark.font_path,
+    };
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{}:{}", metadata.len(), modified.as_nanos()))
+}
+
+pub fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT_SHA256: &str = "input";
+
+    /// A cache in a temporary folder and a compressed output recorded in it
+    struct Fixture {
+        directory: tempfile::TempDir,
+        cache: CompressionCache,
+        options: OptionsPayload,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let directory = tempfile::tempdir().unwrap();
+            let cache = CompressionCache::open(directory.path().join("cache.json")).unwrap();
+            let fixture = Self {
+                directory,
+                cache,
+                options: OptionsPayload::default(),
+            };
+            let output = fixture.output();
+            fs::write(&output, b"compressed").unwrap();
+            let result = CompressionResult {
+                status: CompressionStatus::Success,
+                cimage: CImage {
+                    path: fixture.directory.path().join("a.jpg").display().to_string(),
+                    compressed_size: 10,
+                    compressed_file_path: output.display().to_string(),
+                    ..CImage::default()
+                },
+                cached: false,
+            };
+            fixture.cache.record(
+                INPUT_SHA256,
+                &fixture.options,
+                &fixture.output(),
+                false,
+                &result,
+            );
+            fixture
+        }
+
+        fn output(&self) -> PathBuf {
+            self.directory.path().join("a.min.jpg")
+        }
+
+        fn lookup(&self, options: &OptionsPayload) -> Option<CompressionResult> {
+            let key = cache_key(INPUT_SHA256, options, &self.output(), false);
+            self.cache.lookup(&key, &CImage::default())
+        }
+    }
+
+    #[test]
+    fn unchanged_inputs_reuse_the_recorded_output() {
+        let fixture = Fixture::new();
+
+        let result = fixture.lookup(&fixture.options).unwrap();
+        assert!(result.cached);
+        assert_eq!(result.status, CompressionStatus::Success);
+        assert_eq!(result.cimage.compressed_size, 10);
+        assert_eq!(
+            result.cimage.compressed_file_path,
+            fixture.output().display().to_string()
+        );
+    }
+
+    #[test]
+    fn changed_options_miss() {
+        let fixture = Fixture::new();
+        let mut options = fixture.options.clone();
+        options.compression_options.jpeg.quality -= 1;
+
+        assert!(fixture.lookup(&options).is_none());
+        assert_eq!(fixture.cache.len(), 1);
+    }
+
+    #[test]
+    fn replaced_watermarks_miss() {
+        let fixture = Fixture::new();
+        let mark = fixture.directory.path().join("mark.png");
+        fs::write(&mark, b"logo").unwrap();
+        let mut options = fixture.options.clone();
+        options.watermark_options.enabled = true;
+        options.watermark_options.image_path = mark.display().to_string();
+        let key = || cache_key(INPUT_SHA256, &options, &fixture.output(), false);
+        let before = key();
+
+        fs::write(&mark, b"new logo").unwrap();
+
+        assert_ne!(key(), before);
+    }
+
+    #[test]
+    fn templates_that_change_between_runs_are_not_cached() {
+        let mut options = OptionsPayload::default();
+        options.output_options.filename_template = "{parent}/{name}".to_string();
+        options.compression_options.metadata_fields.artist = "{exif:Artist}".to_string();
+        assert!(can_cache(&options));
+
+        options.output_options.filename_template = "{name}-{counter:03}".to_string();
+        assert!(!can_cache(&options));
+
+        options.output_options.filename_template.clear();
+        options.compression_options.metadata_fields.copyright = "© {date:%Y}".to_string();
+        assert!(!can_cache(&options));
+    }
+
+    #[test]
+    fn modified_outputs_are_forgotten() {
+        let fixture = Fixture::new();
+        fs::write(fixture.output(), b"edited out").unwrap();
+
+        assert!(fixture.lookup(&fixture.options).is_none());
+        assert!(fixture.cache.is_empty());
+    }
+
+    #[test]
+    fn saved_entries_are_loaded_back() {
+        let fixture = Fixture::new();
+        fixture.cache.save().unwrap();
+
+        let cache = CompressionCache::open(&fixture.cache.path).unwrap();
+        assert_eq!(cache.len(), 1);
+    }
+}
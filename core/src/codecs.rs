@@ -0,0 +1,449 @@
+use crate::options::{CompressionOptions, OutputFormat};
+use exif::{In, Tag};
+use image::codecs::avif::{AvifEncoder, ColorSpace};
+#[cfg(feature = "jxl")]
+use image::error::EncodingError;
+use image::error::{DecodingError, ImageFormatHint};
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+#[cfg(any(feature = "jxl", feature = "heif"))]
+use image::RgbaImage;
+use image::{
+    DynamicImage, ExtendedColorType, ImageEncoder, ImageError, ImageFormat, ImageResult, RgbImage,
+};
+#[cfg(feature = "jxl")]
+use jpegxl_rs::decode::PixelFormat;
+#[cfg(feature = "jxl")]
+use jpegxl_rs::encode::{EncoderResult, EncoderSpeed};
+#[cfg(feature = "jxl")]
+use jpegxl_rs::{decoder_builder, encoder_builder};
+#[cfg(feature = "heif")]
+use libheif_rs::{ColorSpace as HeifColorSpace, HeifContext, LibHeif, RgbChroma};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Seek};
+use std::path::Path;
+
+const JXL_CODESTREAM_SIGNATURE: &[u8] = &[0xFF, 0x0A];
+const JXL_CONTAINER_SIGNATURE: &[u8] = &[
+    0x00, 0x00, 0x00, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+];
+
+/// Brands of the HEIF files holding HEVC images, AVIF uses the same container
+const HEIC_BRANDS: &[&[u8]] = &[b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx"];
+
+/// Camera RAW formats by extension, as most of them are TIFF files to a content sniffer
+const RAW_FORMATS: &[(&str, &str)] = &[
+    ("dng", "image/x-adobe-dng"),
+    ("cr2", "image/x-canon-cr2"),
+    ("crw", "image/x-canon-crw"),
+    ("nef", "image/x-nikon-nef"),
+    ("nrw", "image/x-nikon-nrw"),
+    ("arw", "image/x-sony-arw"),
+    ("srf", "image/x-sony-srf"),
+    ("sr2", "image/x-sony-sr2"),
+    ("orf", "image/x-olympus-orf"),
+    ("rw2", "image/x-panasonic-rw2"),
+    ("raf", "image/x-fuji-raf"),
+    ("pef", "image/x-pentax-pef"),
+    ("srw", "image/x-samsung-srw"),
+    ("erf", "image/x-epson-erf"),
+    ("kdc", "image/x-kodak-kdc"),
+    ("dcr", "image/x-kodak-dcr"),
+    ("mrw", "image/x-minolta-mrw"),
+    ("3fr", "image/x-hasselblad-3fr"),
+    ("mos", "image/x-leaf-mos"),
+    ("iiq", "image/x-phaseone-iiq"),
+];
+
+/// File extensions of the supported camera RAW formats
+pub fn raw_extensions() -> impl Iterator<Item = &'static str> {
+    RAW_FORMATS.iter().map(|(extension, _)| *extension)
+}
+
+/// Inputs that are only decoded, their outputs are always converted to
+/// [`OutputOptions::decode_only_format`](crate::options::OutputOptions::decode_only_format)
+/// unless another output format is selected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SourceFormat {
+    Heic,
+    Bmp,
+    Raw,
+}
+
+impl SourceFormat {
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type {
+            "image/heic" | "image/heif" => Some(SourceFormat::Heic),
+            "image/bmp" => Some(SourceFormat::Bmp),
+            _ if RAW_FORMATS.iter().any(|(_, m)| *m == mime_type) => Some(SourceFormat::Raw),
+            _ => None,
+        }
+    }
+}
+
+/// libcaesium cannot read inputs of this type, they are decoded here instead
+pub fn needs_decoding(mime_type: &str) -> bool {
+    Codec::from_mime_type(mime_type).is_some() || SourceFormat::from_mime_type(mime_type).is_some()
+}
+
+/// Mime type of a camera RAW file, guessed from its extension
+pub fn raw_mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    RAW_FORMATS
+        .iter()
+        .find(|(e, _)| *e == extension)
+        .map(|(_, mime_type)| *mime_type)
+}
+
+/// Formats libcaesium cannot read or write, handled with the `image` and `jpegxl-rs` crates.
+/// Decoding AVIF needs the `avif-native` feature, JPEG XL the `jxl` feature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Avif,
+    JpegXl,
+}
+
+impl Codec {
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type {
+            "image/avif" => Some(Codec::Avif),
+            "image/jxl" => Some(Codec::JpegXl),
+            _ => None,
+        }
+    }
+
+    /// Codec that encodes the output, `None` when libcaesium does
+    pub fn for_output(output_format: OutputFormat, mime_type: &str) -> Option<Self> {
+        match output_format {
+            OutputFormat::Avif => Some(Codec::Avif),
+            OutputFormat::JpegXl => Some(Codec::JpegXl),
+            OutputFormat::Original => Self::from_mime_type(mime_type),
+            _ => None,
+        }
+    }
+
+    pub fn quality(self, options: &CompressionOptions) -> u32 {
+        match self {
+            Codec::Avif => options.avif.quality,
+            Codec::JpegXl => options.jxl.quality,
+        }
+    }
+
+    pub fn is_lossless(self, options: &CompressionOptions) -> bool {
+        match self {
+            Codec::Avif => false,
+            Codec::JpegXl => options.jxl.lossless,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Codec::Avif => "AVIF",
+            Codec::JpegXl => "JPEG XL",
+        }
+    }
+
+    /// Encodes `image` with the options of the codec, except for the quality which is
+    /// given separately so it can be searched. Metadata is not carried over.
+    pub fn encode(
+        self,
+        image: &DynamicImage,
+        options: &CompressionOptions,
+        quality: u32,
+    ) -> ImageResult<Vec<u8>> {
+        let (width, height) = (image.width(), image.height());
+        let has_alpha = image.color().has_alpha();
+        let pixels = if has_alpha {
+            image.to_rgba8().into_raw()
+        } else {
+            image.to_rgb8().into_raw()
+        };
+
+        match self {
+            Codec::Avif => {
+                let avif = &options.avif;
+                let mut data = Vec::new();
+                let color_type = if has_alpha {
+                    ExtendedColorType::Rgba8
+                } else {
+                    ExtendedColorType::Rgb8
+                };
+                AvifEncoder::new_with_speed_quality(
+                    &mut data,
+                    avif.speed as u8,
+                    quality.clamp(1, 100) as u8,
+                )
+                .with_colorspace(ColorSpace::Bt709)
+                .write_image(&pixels, width, height, color_type)?;
+                Ok(data)
+            }
+            Codec::JpegXl => encode_jxl(&pixels, width, height, has_alpha, options, quality),
+        }
+    }
+
+    #[cfg(feature = "jxl")]
+    fn format_hint(self) -> ImageFormatHint {
+        ImageFormatHint::Name(self.name().to_string())
+    }
+}
+
+/// Error of the codecs left out of the build, see the features of this crate
+#[cfg(not(all(feature = "jxl", feature = "heif")))]
+fn not_built(format: &str) -> ImageError {
+    ImageError::Unsupported(image::error::UnsupportedError::from(ImageFormatHint::Name(
+        format.to_string(),
+    )))
+}
+
+#[cfg(feature = "jxl")]
+fn encode_jxl(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    has_alpha: bool,
+    options: &CompressionOptions,
+    quality: u32,
+) -> ImageResult<Vec<u8>> {
+    let jxl = &options.jxl;
+    let encoding_error = |e: jpegxl_rs::EncodeError| {
+        ImageError::Encoding(EncodingError::new(Codec::JpegXl.format_hint(), e))
+    };
+    let mut encoder = encoder_builder()
+        .has_alpha(has_alpha)
+        .lossless(jxl.lossless)
+        .uses_original_profile(jxl.lossless)
+        .quality(if jxl.lossless {
+            0.0
+        } else {
+            jxl_distance(quality)
+        })
+        .speed(jxl_speed(jxl.effort))
+        .build()
+        .map_err(encoding_error)?;
+    let result: EncoderResult<u8> = encoder
+        .encode::<u8, u8>(pixels, width, height)
+        .map_err(encoding_error)?;
+    Ok(result.data)
+}
+
+#[cfg(not(feature = "jxl"))]
+fn encode_jxl(
+    _pixels: &[u8],
+    _width: u32,
+    _height: u32,
+    _has_alpha: bool,
+    _options: &CompressionOptions,
+    _quality: u32,
+) -> ImageResult<Vec<u8>> {
+    Err(not_built(Codec::JpegXl.name()))
+}
+
+/// Decodes an input file, camera RAW files cannot be told apart by their content
+pub fn decode_input(data: &[u8], mime_type: &str) -> ImageResult<DynamicImage> {
+    match SourceFormat::from_mime_type(mime_type) {
+        Some(SourceFormat::Raw) => decode_raw(data),
+        _ => decode(data),
+    }
+}
+
+/// Decodes any supported image but camera RAW files, guessing the format from the content
+pub fn decode(data: &[u8]) -> ImageResult<DynamicImage> {
+    if data.starts_with(JXL_CODESTREAM_SIGNATURE) || data.starts_with(JXL_CONTAINER_SIGNATURE) {
+        return decode_jxl(data);
+    }
+    if data.get(4..8) == Some(b"ftyp") && data.get(8..12).is_some_and(|b| HEIC_BRANDS.contains(&b))
+    {
+        return decode_heic(data);
+    }
+    image::load_from_memory(data)
+}
+
+#[cfg(feature = "jxl")]
+fn decode_jxl(data: &[u8]) -> ImageResult<DynamicImage> {
+    let decoding_error = |e: jpegxl_rs::DecodeError| {
+        ImageError::Decoding(DecodingError::new(Codec::JpegXl.format_hint(), e))
+    };
+    let decoder = decoder_builder()
+        .pixel_format(PixelFormat {
+            num_channels: 4,
+            ..PixelFormat::default()
+        })
+        .build()
+        .map_err(decoding_error)?;
+    let (metadata, pixels) = decoder.decode_with::<u8>(data).map_err(decoding_error)?;
+    let image = RgbaImage::from_raw(metadata.width, metadata.height, pixels).ok_or_else(|| {
+        ImageError::Decoding(DecodingError::new(
+            Codec::JpegXl.format_hint(),
+            "Pixel buffer does not match the image size",
+        ))
+    })?;
+    let image = DynamicImage::ImageRgba8(image);
+    if metadata.has_alpha_channel {
+        Ok(image)
+    } else {
+        Ok(DynamicImage::ImageRgb8(image.to_rgb8()))
+    }
+}
+
+#[cfg(not(feature = "jxl"))]
+fn decode_jxl(_data: &[u8]) -> ImageResult<DynamicImage> {
+    Err(not_built(Codec::JpegXl.name()))
+}
+
+/// Decodes the primary image, with the rotation and mirroring of the container applied
+#[cfg(feature = "heif")]
+fn decode_heic(data: &[u8]) -> ImageResult<DynamicImage> {
+    let decoding_error = |e: libheif_rs::HeifError| {
+        ImageError::Decoding(DecodingError::new(ImageFormatHint::Name("HEIC".into()), e))
+    };
+    let context = HeifContext::read_from_bytes(data).map_err(decoding_error)?;
+    let handle = context.primary_image_handle().map_err(decoding_error)?;
+    let image = LibHeif::new()
+        .decode(&handle, HeifColorSpace::Rgb(RgbChroma::Rgba), None)
+        .map_err(decoding_error)?;
+
+    let plane = image.planes().interleaved.ok_or_else(|| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Name("HEIC".into()),
+            "No interleaved plane",
+        ))
+    })?;
+    let (width, height) = (plane.width, plane.height);
+    let row_length = width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_length * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_length]);
+    }
+    let image = RgbaImage::from_raw(width, height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| {
+            ImageError::Decoding(DecodingError::new(
+                ImageFormatHint::Name("HEIC".into()),
+                "Pixel buffer does not match the image size",
+            ))
+        })?;
+    if handle.has_alpha_channel() {
+        Ok(image)
+    } else {
+        Ok(DynamicImage::ImageRgb8(image.to_rgb8()))
+    }
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heic(_data: &[u8]) -> ImageResult<DynamicImage> {
+    Err(not_built("HEIC"))
+}
+
+/// Develops a camera RAW file with the default pipeline, which also applies its orientation
+fn decode_raw(data: &[u8]) -> ImageResult<DynamicImage> {
+    let decoding_error = |e: String| {
+        ImageError::Decoding(DecodingError::new(ImageFormatHint::Name("RAW".into()), e))
+    };
+    let raw =
+        rawloader::decode(&mut Cursor::new(data)).map_err(|e| decoding_error(e.to_string()))?;
+    let mut pipeline = imagepipe::Pipeline::new_from_source(imagepipe::ImageSource::Raw(raw))
+        .map_err(decoding_error)?;
+    let developed = pipeline.output_8bit(None).map_err(decoding_error)?;
+    RgbImage::from_raw(
+        developed.width as u32,
+        developed.height as u32,
+        developed.data,
+    )
+    .map(DynamicImage::ImageRgb8)
+    .ok_or_else(|| decoding_error("Pixel buffer does not match the image size".to_string()))
+}
+
+/// Size of a camera RAW image once developed, without developing it
+pub fn raw_dimensions(path: &Path) -> Option<(usize, usize)> {
+    let file = File::open(path).ok()?;
+    let raw = rawloader::decode(&mut BufReader::new(file)).ok()?;
+    let [top, right, bottom, left] = raw.crops;
+    let width = raw.width.saturating_sub(left + right);
+    let height = raw.height.saturating_sub(top + bottom);
+    match raw.orientation.to_u16() {
+        5..=8 => Some((height, width)),
+        _ => Some((width, height)),
+    }
+}
+
+/// Re-encodes an input libcaesium cannot read as PNG
+pub fn to_png(data: &[u8], mime_type: &str) -> ImageResult<Vec<u8>> {
+    encode_png(&decode_input(data, mime_type)?)
+}
+
+pub fn encode_png(image: &DynamicImage) -> ImageResult<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// EXIF orientation of a JPEG, PNG, WebP, TIFF or HEIF file, 1 when it has none
+pub fn exif_orientation(reader: &mut (impl BufRead + Seek)) -> u32 {
+    exif::Reader::new()
+        .read_from_container(reader)
+        .ok()
+        .and_then(|e| {
+            e.get_field(Tag::Orientation, In::PRIMARY)?
+                .value
+                .get_uint(0)
+        })
+        .unwrap_or(1)
+}
+
+/// Transformation that turns the pixels of an input upright, `None` when they already
+/// are. Decode-only, AVIF and JPEG XL inputs are left alone, their decoders apply the
+/// transformations of their own containers.
+pub fn pending_orientation(data: &[u8], mime_type: &str) -> Option<Orientation> {
+    if needs_decoding(mime_type) {
+        return None;
+    }
+    let orientation = exif_orientation(&mut Cursor::new(data));
+    Orientation::from_exif(u8::try_from(orientation).ok()?)
+        .filter(|o| *o != Orientation::NoTransforms)
+}
+
+/// Resizes like libcaesium does, a 0 width or height keeps the aspect ratio
+pub fn resize(image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let (original_width, original_height) = (image.width(), image.height());
+    let scale = |value: u32, target: u32, reference: u32| {
+        (f64::from(value) * f64::from(target) / f64::from(reference.max(1))).round() as u32
+    };
+    let (width, height) = match (width, height) {
+        (0, 0) => return image,
+        (0, h) => (scale(original_width, h, original_height), h),
+        (w, 0) => (w, scale(original_height, w, original_width)),
+        (w, h) => (w, h),
+    };
+    if (width, height) == (original_width, original_height) {
+        return image;
+    }
+    image.resize_exact(width.max(1), height.max(1), FilterType::Lanczos3)
+}
+
+/// Butteraugli distance matching a 0-100 quality, as libjxl maps them
+#[cfg(feature = "jxl")]
+fn jxl_distance(quality: u32) -> f32 {
+    let quality = quality.min(100) as f32;
+    if quality >= 100.0 {
+        0.0
+    } else if quality >= 30.0 {
+        0.1 + (100.0 - quality) * 0.09
+    } else {
+        53.0 / 3000.0 * quality * quality - 23.0 / 20.0 * quality + 25.0
+    }
+}
+
+#[cfg(feature = "jxl")]
+fn jxl_speed(effort: u32) -> EncoderSpeed {
+    match effort {
+        0 | 1 => EncoderSpeed::Lightning,
+        2 => EncoderSpeed::Thunder,
+        3 => EncoderSpeed::Falcon,
+        4 => EncoderSpeed::Cheetah,
+        5 => EncoderSpeed::Hare,
+        6 => EncoderSpeed::Wombat,
+        7 => EncoderSpeed::Squirrel,
+        8 => EncoderSpeed::Kitten,
+        _ => EncoderSpeed::Tortoise,
+    }
+}
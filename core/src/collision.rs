@@ -0,0 +1,321 @@
+use crate::errors::CompressionError;
+use crate::options::CollisionPolicy;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Output paths claimed by the images of a batch, so they never write to the same file.
+///
+/// Batches claim every path in input order before compression starts, so the first
+/// image always keeps the path no matter which worker gets there first.
+#[derive(Default)]
+pub struct OutputReservations {
+    inner: Mutex<Reservations>,
+}
+
+#[derive(Default)]
+struct Reservations {
+    /// Image id holding each path
+    owners: HashMap<PathBuf, String>,
+    /// Path assigned to each image id
+    paths: HashMap<String, PathBuf>,
+}
+
+/// Where an image goes once collisions are solved
+pub(crate) enum Resolution {
+    Write {
+        path: PathBuf,
+        /// Policy applied because the planned path was taken
+        collision: Option<CollisionPolicy>,
+    },
+    Skip {
+        reason: &'static str,
+        collision: CollisionPolicy,
+    },
+}
+
+impl OutputReservations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claims `path` for the image `id`, returns false if another image holds it
+    pub(crate) fn claim(&self, path: &Path, id: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let owner = inner
+            .owners
+            .entry(path_key(path))
+            .or_insert_with(|| id.to_string());
+        if owner != id {
+            return false;
+        }
+        inner
+            .paths
+            .entry(id.to_string())
+            .or_insert_with(|| path.to_path_buf());
+        true
+    }
+
+    /// Returns the renamed path already assigned to `id`, or claims the first free one
+    pub(crate) fn claim_renamed(&self, path: &Path, id: &str) -> PathBuf {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(assigned) = inner.paths.get(id).filter(|p| *p != path) {
+            return assigned.clone();
+        }
+        let renamed = first_free_path(path, |p| inner.owners.contains_key(&path_key(p)));
+        inner.owners.insert(path_key(&renamed), id.to_string());
+        inner.paths.insert(id.to_string(), renamed.clone());
+        renamed
+    }
+}
+
+/// Applies `policy` if another image of the batch claimed `planned` first
+pub(crate) fn resolve_batch_collision(
+    id: &str,
+    planned: PathBuf,
+    policy: CollisionPolicy,
+    reservations: Option<&OutputReservations>,
+) -> Result<Resolution, CompressionError> {
+    let reservations = match reservations {
+        Some(reservations) if !reservations.claim(&planned, id) => reservations,
+        _ => {
+            return Ok(Resolution::Write {
+                path: planned,
+                collision: None,
+            })
+        }
+    };
+    match policy {
+        CollisionPolicy::Rename => Ok(Resolution::Write {
+            path: reservations.claim_renamed(&planned, id),
+            collision: Some(policy),
+        }),
+        CollisionPolicy::Skip => Ok(Resolution::Skip {
+            reason: "Another image of the batch has the same output, skipping",
+            collision: policy,
+        }),
+        // Overwriting another output of the same job is never what was asked for
+        CollisionPolicy::Overwrite | CollisionPolicy::OverwriteIfOlder => Err(
+            CompressionError::OutputCollision(planned.display().to_string()),
+        ),
+    }
+}
+
+/// Applies `policy` if a file already exists at `planned`. The input itself does not
+/// count, replacing it is governed by the output folder options.
+pub(crate) fn resolve_existing_output(
+    id: &str,
+    input: &Path,
+    planned: &Path,
+    policy: CollisionPolicy,
+    reservations: Option<&OutputReservations>,
+) -> Result<Resolution, CompressionError> {
+    let planned = planned.to_path_buf();
+    if planned == input || !planned.exists() {
+        return Ok(Resolution::Write {
+            path: planned,
+            collision: None,
+        });
+    }
+    let resolution = match policy {
+        CollisionPolicy::Overwrite => Resolution::Write {
+            path: planned,
+            collision: Some(policy),
+        },
+        CollisionPolicy::Skip => Resolution::Skip {
+            reason: "The output file already exists, skipping",
+            collision: policy,
+        },
+        CollisionPolicy::OverwriteIfOlder => {
+            let modified = |path: &Path| {
+                fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .map_err(CompressionError::Metadata)
+            };
+            if modified(&planned)? < modified(input)? {
+                Resolution::Write {
+                    path: planned,
+                    collision: Some(policy),
+                }
+            } else {
+                Resolution::Skip {
+                    reason: "The output file is newer than the input, skipping",
+                    collision: policy,
+                }
+            }
+        }
+        CollisionPolicy::Rename => Resolution::Write {
+            path: match reservations {
+                Some(reservations) => reservations.claim_renamed(&planned, id),
+                None => first_free_path(&planned, |_| false),
+            },
+            collision: Some(policy),
+        },
+    };
+    Ok(resolution)
+}
+
+/// First `name_N.ext` next to `path` that does not exist and is not `taken`
+fn first_free_path(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| e.to_string_lossy());
+    (1..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{stem}_{n}.{extension}"),
+                None => format!("{stem}_{n}"),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !taken(candidate) && !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+// These file systems are case insensitive by default, so a.webp and A.WEBP are the same file
+#[cfg(any(windows, target_os = "macos"))]
+fn path_key(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().to_lowercase())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn path_key(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn written(resolution: Resolution) -> (PathBuf, Option<CollisionPolicy>) {
+        match resolution {
+            Resolution::Write { path, collision } => (path, collision),
+            Resolution::Skip { reason, .. } => panic!("skipped: {reason}"),
+        }
+    }
+
+    fn set_modified(path: &Path, modified: SystemTime) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn first_claim_keeps_the_path() {
+        let reservations = OutputReservations::new();
+        let path = Path::new("out/a.jpg");
+        assert!(reservations.claim(path, "1"));
+        assert!(reservations.claim(path, "1"));
+        assert!(!reservations.claim(path, "2"));
+    }
+
+    #[test]
+    fn renamed_paths_are_stable_for_each_image() {
+        let reservations = OutputReservations::new();
+        let path = Path::new("out/a.jpg");
+        reservations.claim(path, "1");
+
+        let second = reservations.claim_renamed(path, "2");
+        let third = reservations.claim_renamed(path, "3");
+        assert_eq!(second, Path::new("out/a_1.jpg"));
+        assert_eq!(third, Path::new("out/a_2.jpg"));
+        assert_eq!(reservations.claim_renamed(path, "2"), second);
+    }
+
+    #[test]
+    fn batch_collisions_follow_the_policy() {
+        let reservations = OutputReservations::new();
+        let path = PathBuf::from("out/a.png");
+        reservations.claim(&path, "1");
+
+        let resolve =
+            |policy| resolve_batch_collision("2", path.clone(), policy, Some(&reservations));
+        assert_eq!(
+            written(resolve(CollisionPolicy::Rename).unwrap()),
+            (PathBuf::from("out/a_1.png"), Some(CollisionPolicy::Rename))
+        );
+        assert!(matches!(
+            resolve(CollisionPolicy::Skip),
+            Ok(Resolution::Skip { .. })
+        ));
+        assert!(matches!(
+            resolve(CollisionPolicy::Overwrite),
+            Err(CompressionError::OutputCollision(_))
+        ));
+    }
+
+    #[test]
+    fn images_without_reservations_keep_their_path() {
+        let path = PathBuf::from("out/a.png");
+        let resolution = resolve_batch_collision("1", path.clone(), CollisionPolicy::Skip, None);
+        assert_eq!(written(resolution.unwrap()), (path, None));
+    }
+
+    #[test]
+    fn existing_outputs_follow_the_policy() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = directory.path().join("input.jpg");
+        let output = directory.path().join("output.jpg");
+        fs::write(&input, b"input").unwrap();
+
+        let resolve = |policy| resolve_existing_output("1", &input, &output, policy, None);
+        assert_eq!(
+            written(resolve(CollisionPolicy::Skip).unwrap()),
+            (output.clone(), None)
+        );
+
+        fs::write(&output, b"output").unwrap();
+        fs::write(directory.path().join("output_1.jpg"), b"output").unwrap();
+        assert_eq!(
+            written(resolve(CollisionPolicy::Rename).unwrap()),
+            (
+                directory.path().join("output_2.jpg"),
+                Some(CollisionPolicy::Rename)
+            )
+        );
+        assert!(matches!(
+            resolve(CollisionPolicy::Skip),
+            Ok(Resolution::Skip { .. })
+        ));
+        assert_eq!(
+            written(resolve(CollisionPolicy::Overwrite).unwrap()),
+            (output, Some(CollisionPolicy::Overwrite))
+        );
+    }
+
+    #[test]
+    fn older_outputs_are_overwritten() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = directory.path().join("input.jpg");
+        let output = directory.path().join("output.jpg");
+        fs::write(&input, b"input").unwrap();
+        fs::write(&output, b"output").unwrap();
+        let now = SystemTime::now();
+        let resolve = || {
+            resolve_existing_output(
+                "1",
+                &input,
+                &output,
+                CollisionPolicy::OverwriteIfOlder,
+                None,
+            )
+        };
+
+        set_modified(&output, now - Duration::from_secs(60));
+        set_modified(&input, now);
+        assert!(matches!(resolve(), Ok(Resolution::Write { .. })));
+
+        set_modified(&output, now + Duration::from_secs(60));
+        assert!(matches!(resolve(), Ok(Resolution::Skip { .. })));
+    }
+
+    #[test]
+    fn the_input_is_not_a_collision() {
+        let directory = tempfile::tempdir().unwrap();
+        let input = directory.path().join("input.jpg");
+        fs::write(&input, b"input").unwrap();
+
+        let resolution = resolve_existing_output("1", &input, &input, CollisionPolicy::Skip, None);
+        assert_eq!(written(resolution.unwrap()), (input, None));
+    }
+}
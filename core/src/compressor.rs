@@ -0,0 +1,1248 @@
+use crate::atomic_file::{copy_atomically, AtomicFile};
+use crate::cache::{cache_key, can_cache, file_sha256, CompressionCache};
+use crate::codecs::{self, Codec, SourceFormat};
+use crate::collision::{
+    resolve_batch_collision, resolve_existing_output, OutputReservations, Resolution,
+};
+use crate::errors::{CompressionError, CompressionErrorCode};
+use crate::journal::{Backups, JobJournal, OriginalAction};
+use crate::metadata::{self, RenderedFields};
+use crate::metrics::{measure, QualityMetrics, Reference};
+use crate::options::{
+    CollisionPolicy, CompressionMode, MoveOriginalFileMode, OptionsPayload, TargetQualityOptions,
+};
+use crate::resize::{self, Frame};
+use crate::rules::resolve_options;
+use crate::scan_files::get_real_resolution;
+use crate::target_quality;
+use crate::template::{FilenameTemplate, TemplateContext};
+use crate::watermark::Watermark;
+use crate::{CImage, ImageStatus};
+use caesium::error::CaesiumError;
+use caesium::parameters::CSParameters;
+use caesium::{
+    compress, compress_in_memory, compress_to_size, compress_to_size_in_memory, convert_in_memory,
+    SupportedFileTypes,
+};
+use chrono::{DateTime, Local};
+use image::metadata::Orientation;
+use image::DynamicImage;
+use serde_json::to_string;
+use sha2::{Digest, Sha256};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fs::{File, FileTimes, Metadata};
+use std::io::Read;
+#[cfg(windows)]
+use std::os::windows::fs::FileTimesExt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{absolute, Path, PathBuf};
+use std::{fs, io};
+
+pub(crate) const ENLARGE_SKIP_REASON: &str = "Cannot resize over original dimensions, skipping";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct CompressionResult {
+    pub status: CompressionStatus,
+    pub cimage: CImage,
+    /// The output of a previous run was reused
+    #[serde(default)]
+    pub cached: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct CompressionSummary {
+    pub total_images: usize,
+    pub total_success: usize,
+    pub total_skipped: usize,
+    pub total_errors: usize,
+    /// Images whose previous output was reused, also counted as success or skipped
+    pub total_cached: usize,
+    /// Number of failed images for each error code
+    pub errors_by_code: BTreeMap<CompressionErrorCode, usize>,
+    /// Journal that can revert the job, if it wrote anything
+    #[serde(default)]
+    pub job_id: Option<String>,
+    /// Images planned to the same output path as an earlier image of the batch
+    #[serde(default)]
+    pub output_collisions: usize,
+    pub original_size: usize,
+    pub compressed_size: usize,
+    pub total_time: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CompressionStatus {
+    Success,
+    Warning,
+    Error,
+}
+
+impl From<CompressionStatus> for ImageStatus {
+    fn from(status: CompressionStatus) -> Self {
+        match status {
+            CompressionStatus::Success => ImageStatus::Success,
+            CompressionStatus::Warning => ImageStatus::Warning,
+            CompressionStatus::Error => ImageStatus::Error,
+        }
+    }
+}
+
+impl CompressionResult {
+    fn error(cimage: &CImage, error: CompressionError) -> Self {
+        CompressionResult {
+            status: CompressionStatus::Error,
+            cimage: CImage {
+                status: ImageStatus::Error,
+                info: error.to_string(),
+                error_code: Some(error.code()),
+                compressed_width: cimage.width,
+                compressed_height: cimage.height,
+                compressed_size: cimage.size,
+                // Left over from an earlier compression of the same image, it may be gone
+                compressed_file_path: String::new(),
+                quality_metrics: None,
+                ..cimage.clone()
+            },
+            cached: false,
+        }
+    }
+
+    fn skipped(cimage: &CImage, info: &str) -> Self {
+        CompressionResult {
+            status: CompressionStatus::Warning,
+            cimage: CImage {
+                status: ImageStatus::Warning,
+                info: info.to_string(),
+                compressed_width: cimage.width,
+                compressed_height: cimage.height,
+                compressed_size: cimage.size,
+                // Left over from an earlier compression of the same image, it may be gone
+                compressed_file_path: String::new(),
+                quality_metrics: None,
+                ..cimage.clone()
+            },
+            cached: false,
+        }
+    }
+}
+
+/// Where and how a single image of a batch is compressed, besides its options
+pub struct CompressionContext<'a> {
+    pub base_folder: &'a str,
+    /// Files bigger than this are not read
+    pub max_file_size: u64,
+    pub cache: Option<&'a CompressionCache>,
+    pub journal: Option<&'a JobJournal>,
+    /// Output paths claimed by the other images of the batch
+    pub reservations: Option<&'a OutputReservations>,
+    /// Position of the image in the batch, starting from 1
+    pub counter: usize,
+    /// Start of the batch, used by the date placeholders of the filename template
+    pub started: DateTime<Local>,
+    /// Compare each output with its original, which decodes both
+    pub quality_metrics: bool,
+}
+
+impl<'a> CompressionContext<'a> {
+    pub fn new(base_folder: &'a str, max_file_size: u64) -> Self {
+        Self {
+            base_folder,
+            max_file_size,
+            cache: None,
+            journal: None,
+            reservations: None,
+            counter: 1,
+            started: Local::now(),
+            quality_metrics: false,
+        }
+    }
+}
+
+/// Compresses `cimage` with the options selected by the first matching rule, if any.
+/// Inputs already compressed with the same options reuse the output recorded in the
+/// context cache, and every write is recorded in the context journal, with backups of
+/// the files it replaces. Outputs that are already taken are handled according to
+/// the collision policy of the options.
+///
+/// Failures, panics included, are reported on the returned image and never abort the caller.
+pub fn compress_cimage(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    context: &CompressionContext,
+) -> CompressionResult {
+    let (options, applied_rule) = resolve_options(options, cimage);
+    let mut result = compress_to_output(cimage, &options, context);
+    result.cimage.applied_rule = applied_rule;
+    result
+}
+
+fn compress_to_output(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    context: &CompressionContext,
+) -> CompressionResult {
+    let policy = options.output_options.collision_policy;
+    let resolution = output_path(cimage, options, context)
+        .and_then(|path| resolve_batch_collision(&cimage.id, path, policy, context.reservations));
+    let (output_path, batch_collision) = match resolution {
+        Ok(Resolution::Write { path, collision }) => (path, collision),
+        Ok(Resolution::Skip { reason, collision }) => {
+            let mut result = CompressionResult::skipped(cimage, reason);
+            result.cimage.collision_policy = Some(collision);
+            return result;
+        }
+        Err(e) => return CompressionResult::error(cimage, e),
+    };
+
+    // The cache is looked up before existing files are considered, as the output of
+    // the previous run is expected to be there
+    let cache = context.cache.filter(|_| can_cache(options));
+    let input_sha256 = cache.and_then(|_| file_sha256(Path::new(&cimage.path)).ok());
+    let quality_metrics = context.quality_metrics;
+    let cached = match (cache, &input_sha256) {
+        (Some(cache), Some(hash)) => {
+            let key = cache_key(hash, options, &output_path, quality_metrics);
+            cache.lookup(&key, cimage)
+        }
+        _ => None,
+    };
+    let mut result = cached.unwrap_or_else(|| {
+        let result = catch_panic(cimage, || {
+            let input_path = Path::new(&cimage.path);
+            let reservations = context.reservations;
+            let result = match resolve_existing_output(
+                &cimage.id,
+                input_path,
+                &output_path,
+                policy,
+                reservations,
+            )? {
+                Resolution::Write { path, collision } => {
+                    let mut result = compress_with_options(cimage, options, &path, context)?;
+                    result.cimage.collision_policy = collision;
+                    result
+                }
+                Resolution::Skip { reason, collision } => {
+                    let mut result = CompressionResult::skipped(cimage, reason);
+                    result.cimage.collision_policy = Some(collision);
+                    result
+                }
+            };
+            Ok(result)
+        });
+        if let (Some(cache), Some(hash)) = (cache, &input_sha256) {
+            cache.record(hash, options, &output_path, quality_metrics, &result);
+        }
+        result
+    });
+    result.cimage.collision_policy = result.cimage.collision_policy.or(batch_collision);
+    result
+}
+
+/// Claims the output path of every image in input order, before the batch starts.
+/// Returns how many images have the same output as an earlier one.
+pub(crate) fn reserve_outputs<'a>(
+    images: &[CImage],
+    options: &OptionsPayload,
+    context: impl Fn(usize) -> CompressionContext<'a>,
+) -> usize {
+    let mut collisions = 0;
+    for (index, cimage) in images.iter().enumerate() {
+        let context = context(index);
+        let Some(reservations) = context.reservations else {
+            return 0;
+        };
+        let (options, _) = resolve_options(options, cimage);
+        // Images without an output path fail on their own later
+        let Ok(path) = output_path(cimage, &options, &context) else {
+            continue;
+        };
+        if reservations.claim(&path, &cimage.id) {
+            continue;
+        }
+        collisions += 1;
+        // Renamed paths are assigned in input order too, so they do not depend on timing
+        if options.output_options.collision_policy == CollisionPolicy::Rename {
+            reservations.claim_renamed(&path, &cimage.id);
+        }
+    }
+    collisions
+}
+
+/// Path `cimage` would be written to, with the options of the first matching rule
+pub fn planned_output_path(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    context: &CompressionContext,
+) -> Result<PathBuf, CompressionError> {
+    let (options, _) = resolve_options(options, cimage);
+    output_path(cimage, &options, context)
+}
+
+fn catch_panic(
+    cimage: &CImage,
+    f: impl FnOnce() -> Result<CompressionResult, CompressionError>,
+) -> CompressionResult {
+    let error = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(result)) => return result,
+        Ok(Err(e)) => e,
+        Err(payload) => CompressionError::Internal(panic_message(payload.as_ref())),
+    };
+    CompressionResult::error(cimage, error)
+}
+
+/// Text of a panic caught with [`panic::catch_unwind`]
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
+
+fn compress_with_options(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    output_full_path: &Path,
+    context: &CompressionContext,
+) -> Result<CompressionResult, CompressionError> {
+    let original_file_size = cimage.size;
+    let journal = context.journal;
+
+    if original_file_size > context.max_file_size {
+        return Err(CompressionError::TooLarge(context.max_file_size));
+    }
+
+    let output_full_path = output_full_path.to_path_buf();
+
+    let mut compression_parameters = parse_compression_options(options, cimage);
+
+    let input_path = Path::new(&cimage.path);
+    let will_overwrite_original = output_full_path == input_path;
+    let will_move_original =
+        options.output_options.move_original_file_enabled && !will_overwrite_original;
+    if refuses_to_enlarge(options, cimage) {
+        return Ok(CompressionResult::skipped(cimage, ENLARGE_SKIP_REASON));
+    }
+
+    let encoded = perform_image_compression(
+        cimage,
+        options,
+        &mut compression_parameters,
+        context.counter,
+        context.started,
+        context.quality_metrics,
+    )?;
+    let compressed_image = &encoded.data;
+
+    let output_file_size = compressed_image.len() as u64;
+
+    // Decode-only inputs cannot be copied as they are, the output must be converted, and
+    // the original has neither the metadata fields nor the edited pixels, and still has
+    // the metadata the filter removed
+    let can_keep_original = SourceFormat::from_mime_type(&cimage.mime_type).is_none()
+        && options.compression_options.metadata_fields.is_empty()
+        && !filters_metadata(options, cimage)
+        && !encoded.edited;
+    if original_file_size < output_file_size
+        && options.output_options.skip_if_output_is_bigger
+        && can_keep_original
+    {
+        if !will_overwrite_original {
+            let backups = journal
+                .map(|j| j.back_up(input_path, &output_full_path, false))
+                .transpose()
+                .map_err(CompressionError::Journal)?;
+            let copied = create_output_directory(&output_full_path).and_then(|()| {
+                copy_atomically(input_path, &output_full_path).map_err(CompressionError::write)
+            });
+            if let Err(e) = copied {
+                if let Some(backups) = backups {
+                    backups.discard();
+                }
+                return Err(e);
+            }
+            if let (Some(journal), Some(backups)) = (journal, backups) {
+                let original = OriginalAction::Kept;
+                record_written(journal, input_path, &output_full_path, original, backups);
+            }
+        }
+
+        return Ok(CompressionResult {
+            cimage: CImage {
+                compressed_file_path: output_full_path.display().to_string(),
+                ..CompressionResult::skipped(cimage, "Compressed file is bigger, skipping").cimage
+            },
+            status: CompressionStatus::Warning,
+            cached: false,
+        });
+    }
+
+    // Measured before the original can be replaced or moved. Edited pixels are measured
+    // while encoding, as the original does not have the edits.
+    let quality_metrics = match encoded.quality_metrics {
+        None if context.quality_metrics && !encoded.edited => measure(input_path, compressed_image)
+            .inspect_err(|e| log::warn!("Cannot measure the quality of {}: {e}", cimage.path))
+            .ok(),
+        metrics => metrics,
+    };
+
+    let backups = journal
+        .map(|j| {
+            j.back_up(
+                input_path,
+                &output_full_path,
+                will_overwrite_original || will_move_original,
+            )
+        })
+        .transpose()
+        .map_err(CompressionError::Journal)?;
+
+    // The original is only replaced once the new file is completely on disk
+    let written = create_output_directory(&output_full_path)
+        .and_then(|()| write_output_file(cimage, compressed_image, &output_full_path, options));
+    if let Err(e) = written {
+        if let Some(backups) = backups {
+            backups.discard();
+        }
+        return Err(e);
+    }
+
+    let mut new_width = cimage.width;
+    let mut new_height = cimage.height;
+    if options.resize_options.resize_enabled {
+        (new_width, new_height) = get_real_resolution(&output_full_path);
+    }
+
+    let moved_original = if will_move_original {
+        move_original_file(input_path, options.output_options.move_original_file_mode)
+    } else {
+        Ok(OriginalAction::Kept)
+    };
+    let mut not_undoable = None;
+    if let (Some(journal), Some(backups)) = (journal, backups) {
+        let original = match &moved_original {
+            _ if will_overwrite_original => OriginalAction::Replaced,
+            Ok(action) => *action,
+            Err(_) => OriginalAction::Kept,
+        };
+        not_undoable = record_written(journal, input_path, &output_full_path, original, backups);
+    }
+    moved_original?;
+
+    let (status, image_status, info) = match not_undoable {
+        Some(warning) if encoded.info.is_empty() => {
+            (CompressionStatus::Warning, ImageStatus::Warning, warning)
+        }
+        Some(warning) => (
+            CompressionStatus::Warning,
+            ImageStatus::Warning,
+            format!("{}. {warning}", encoded.info),
+        ),
+        None => (
+            CompressionStatus::Success,
+            ImageStatus::Success,
+            encoded.info,
+        ),
+    };
+    Ok(CompressionResult {
+        status,
+        cimage: CImage {
+            compressed_width: new_width,
+            compressed_height: new_height,
+            compressed_size: output_file_size,
+            compressed_file_path: output_full_path.display().to_string(),
+            info,
+            error_code: None,
+            status: image_status,
+            quality_metrics,
+            ..cimage.clone()
+        },
+        cached: false,
+    })
+}
+
+/// Creates the folder of `output` right before writing it, so that skipped files leave no
+/// empty folders behind
+fn create_output_directory(output: &Path) -> Result<(), CompressionError> {
+    match output.parent() {
+        Some(directory) => fs::create_dir_all(directory).map_err(CompressionError::write),
+        None => Ok(()),
+    }
+}
+
+/// Adds a written output to the journal. The output is already on disk, so a journal that
+/// cannot be updated is only a warning that this file cannot be undone.
+fn record_written(
+    journal: &JobJournal,
+    input: &Path,
+    output: &Path,
+    original: OriginalAction,
+    backups: Backups,
+) -> Option<String> {
+    let e = journal.record(input, output, original, backups).err()?;
+    log::warn!("Cannot add {} to the journal: {e}", output.display());
+    Some(format!(
+        "Written, but the run cannot be undone for this file: {e}"
+    ))
+}
+
+fn move_original_file(
+    path: &Path,
+    mode: MoveOriginalFileMode,
+) -> Result<OriginalAction, CompressionError> {
+    match mode {
+        MoveOriginalFileMode::Trash => trash::delete(path)
+            .map(|_| OriginalAction::Trashed)
+            .map_err(|e| CompressionError::MoveOriginal(e.to_string())),
+        MoveOriginalFileMode::Delete => fs::remove_file(path)
+            .map(|_| OriginalAction::Deleted)
+            .map_err(|e| CompressionError::MoveOriginal(e.to_string())),
+    }
+}
+
+fn write_output_file(
+    cimage: &CImage,
+    data: &[u8],
+    output_full_path: &Path,
+    options: &OptionsPayload,
+) -> Result<(), CompressionError> {
+    let mut output_file = AtomicFile::create(output_full_path).map_err(CompressionError::write)?;
+    output_file
+        .write_all(data)
+        .map_err(CompressionError::write)?;
+
+    if options.output_options.keep_file_dates_enabled {
+        let input_metadata = PathBuf::from(cimage.path.clone())
+            .metadata()
+            .map_err(CompressionError::Metadata)?;
+        preserve_file_times(output_file.file(), &input_metadata, options)
+            .map_err(CompressionError::Metadata)?;
+    }
+
+    output_file.commit().map_err(CompressionError::write)
+}
+
+// TODO I don't like using the payload here
+pub fn preview_cimage(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    preview_directory: &Path,
+) -> CompressionResult {
+    let (options, applied_rule) = resolve_options(options, cimage);
+    let mut result = catch_panic(cimage, || {
+        preview_with_options(cimage, &options, preview_directory)
+    });
+    result.cimage.applied_rule = applied_rule;
+    result
+}
+
+fn preview_with_options(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    preview_directory: &Path,
+) -> Result<CompressionResult, CompressionError> {
+    let filename = options_payload_to_sha256(&cimage.id, options);
+    let mut parameters = parse_compression_options(options, cimage);
+    let output_path = preview_directory.join(filename);
+
+    if refuses_to_enlarge(options, cimage) {
+        return Ok(CompressionResult::skipped(cimage, ENLARGE_SKIP_REASON));
+    }
+
+    let mut encoded = None;
+    let mode = options.compression_options.compression_mode;
+    let output_format = options.output_options.format_for(&cimage.mime_type);
+    let in_memory = mode == CompressionMode::TargetQuality
+        || Codec::for_output(output_format, &cimage.mime_type).is_some()
+        || codecs::needs_decoding(&cimage.mime_type)
+        || filters_metadata(options, cimage)
+        || !options.compression_options.metadata_fields.is_empty()
+        || options.resize_options.auto_orient
+        || options.watermark_options.enabled
+        || resize_frame(options, cimage).is_some();
+    match mode {
+        // The search, the metadata and the pixel edits need the encoded bytes and
+        // libcaesium cannot read all the inputs or write AVIF and JPEG XL, so they are
+        // written here
+        _ if in_memory => {
+            let output = perform_image_compression(
+                cimage,
+                options,
+                &mut parameters,
+                1,
+                Local::now(),
+                false,
+            )?;
+            fs::write(&output_path, &output.data).map_err(CompressionError::write)?;
+            encoded = Some(output);
+        }
+        CompressionMode::Size => {
+            let output_size = options.compression_options.max_size_value
+                * options.compression_options.max_size_unit;
+            compress_to_size(
+                cimage.path.clone(),
+                output_path.display().to_string(),
+                &mut parameters,
+                output_size,
+                true,
+            )
+            .map_err(encode_error)?
+        }
+        CompressionMode::Quality | CompressionMode::TargetQuality => compress(
+            cimage.path.clone(),
+            output_path.display().to_string(),
+            &parameters,
+        )
+        .map_err(encode_error)?,
+    }
+
+    let output_path = Path::new(&output_path);
+    let size = fs::metadata(output_path)
+        .map_err(CompressionError::read)?
+        .len();
+    let mut new_width = cimage.width;
+    let mut new_height = cimage.height;
+    if options.resize_options.resize_enabled {
+        (new_width, new_height) = get_real_resolution(output_path);
+    }
+
+    Ok(CompressionResult {
+        status: CompressionStatus::Success,
+        cimage: CImage {
+            compressed_width: new_width,
+            compressed_height: new_height,
+            compressed_size: size,
+            compressed_file_path: output_path.display().to_string(),
+            info: encoded.as_ref().map(|e| e.info.clone()).unwrap_or_default(),
+            error_code: None,
+            status: ImageStatus::Success,
+            quality_metrics: encoded.and_then(|e| e.quality_metrics),
+            ..cimage.clone()
+        },
+        cached: false,
+    })
+}
+
+fn parse_compression_options(options: &OptionsPayload, cimage: &CImage) -> CSParameters {
+    let mut parameters = CSParameters::new();
+
+    parameters.jpeg.optimize = options.compression_options.jpeg.optimize;
+    parameters.png.optimize = options.compression_options.png.optimize;
+    parameters.webp.lossless = options.compression_options.webp.lossless;
+
+    // libcaesium keeps all the metadata or none, the output is filtered afterwards when
+    // only some of it is kept
+    let compression = &options.compression_options;
+    let output_format = options.output_options.format_for(&cimage.mime_type);
+    parameters.keep_metadata = compression.keep_metadata
+        && (compression.metadata.keeps_everything()
+            || metadata::can_filter(output_format, &cimage.mime_type));
+
+    if let Some((width, height)) = resize_target(options, cimage) {
+        parameters.width = width;
+        parameters.height = height;
+    }
+
+    // -- JPEG --
+    parameters.jpeg.quality = options.compression_options.jpeg.quality;
+    parameters.jpeg.chroma_subsampling = options.compression_options.jpeg.chroma_subsampling.into();
+    parameters.jpeg.preserve_icc = options.compression_options.jpeg.preserve_icc;
+
+    // -- PNG --
+    parameters.png.quality = options.compression_options.png.quality;
+    parameters.png.optimization_level = options.compression_options.png.optimization_level as u8;
+    parameters.png.force_zopfli = false;
+
+    // -- GIF --
+    parameters.gif.quality = options.compression_options.gif.quality;
+    // -- WEBP --
+    parameters.webp.quality = options.compression_options.webp.quality;
+
+    // -- TIFF --
+    parameters.tiff.algorithm = options.compression_options.tiff.method.into();
+    parameters.tiff.deflate_level = options.compression_options.tiff.deflate_level.into();
+
+    parameters
+}
+
+/// Width and height passed to the encoder, 0 keeps the aspect ratio of the other one
+fn resize_target(options: &OptionsPayload, cimage: &CImage) -> Option<(u32, u32)> {
+    resize::scale_target(
+        &options.resize_options,
+        cimage.width as u32,
+        cimage.height as u32,
+    )
+}
+
+/// Crop or padding applied to the pixels after the encoder size is reached
+fn resize_frame(options: &OptionsPayload, cimage: &CImage) -> Option<Frame> {
+    resize::frame(
+        &options.resize_options,
+        cimage.width as u32,
+        cimage.height as u32,
+    )
+}
+
+/// The resize target is bigger than the image and the options forbid enlarging it
+pub(crate) fn refuses_to_enlarge(options: &OptionsPayload, cimage: &CImage) -> bool {
+    options.resize_options.do_not_enlarge
+        && resize_target(options, cimage)
+            .is_some_and(|(w, h)| w > cimage.width as u32 || h > cimage.height as u32)
+}
+
+/// Size of the output before it is written, used by the filename template
+pub(crate) fn expected_dimensions(options: &OptionsPayload, cimage: &CImage) -> (usize, usize) {
+    let (width, height) = (cimage.width, cimage.height);
+    let scale = |value: usize, target: u32, reference: usize| {
+        (value as f64 * f64::from(target) / reference.max(1) as f64).round() as usize
+    };
+    if let Some(frame) = resize_frame(options, cimage) {
+        let (width, height) = frame.size();
+        return (width as usize, height as usize);
+    }
+    match resize_target(options, cimage) {
+        None | Some((0, 0)) => (width, height),
+        Some((0, target)) => (scale(width, target, height), target as usize),
+        Some((target, 0)) => (target as usize, scale(height, target, width)),
+        Some((w, h)) => (w as usize, h as usize),
+    }
+}
+
+fn options_payload_to_sha256(id: &String, options: &OptionsPayload) -> String {
+    // Serialize the struct to a JSON string
+    let json_string = to_string(options).unwrap_or_default();
+
+    // Compute the SHA256 hash
+    let mut hasher = Sha256::new();
+    hasher.update(id);
+    hasher.update("|");
+    hasher.update(json_string);
+    let result = hasher.finalize();
+
+    // Convert the hash to a hexadecimal string
+    format!("{result:x}")
+}
+
+/// Compressed bytes, before they are written anywhere
+struct EncodedImage {
+    data: Vec<u8>,
+    /// Measured while searching for the target quality
+    quality_metrics: Option<QualityMetrics>,
+    info: String,
+    /// The pixels were changed before encoding, see [`PixelEdits`]
+    edited: bool,
+}
+
+impl EncodedImage {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            quality_metrics: None,
+            info: String::new(),
+            edited: false,
+        }
+    }
+}
+
+/// Changes made to the pixels before they are encoded
+struct PixelEdits {
+    orientation: Option<Orientation>,
+    frame: Option<Frame>,
+    watermark: Option<Watermark>,
+}
+
+impl PixelEdits {
+    fn new(
+        cimage: &CImage,
+        options: &OptionsPayload,
+        input: &[u8],
+    ) -> Result<Self, CompressionError> {
+        // Frames are computed from the upright size of the image, so its pixels are
+        // turned upright before being cropped or padded even without auto-orient
+        let frame = resize_frame(options, cimage);
+        let orientation = (options.resize_options.auto_orient || frame.is_some())
+            .then(|| codecs::pending_orientation(input, &cimage.mime_type))
+            .flatten();
+        let watermark = Watermark::load(&options.watermark_options)?;
+        Ok(Self {
+            orientation,
+            frame,
+            watermark,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.orientation.is_none() && self.frame.is_none() && self.watermark.is_none()
+    }
+
+    /// Turns the pixels upright, then resizes, crops or pads them and draws the watermark,
+    /// so that it keeps its proportions. Plain resizing is left to the encoder.
+    fn apply(&self, mut image: DynamicImage, parameters: &mut CSParameters) -> DynamicImage {
+        if let Some(orientation) = self.orientation {
+            image.apply_orientation(orientation);
+        }
+        if self.frame.is_none() && self.watermark.is_none() {
+            return image;
+        }
+        image = codecs::resize(image, parameters.width, parameters.height);
+        (parameters.width, parameters.height) = (0, 0);
+        if let Some(frame) = &self.frame {
+            image = frame.apply(image);
+        }
+        if let Some(watermark) = &self.watermark {
+            image = watermark.apply(image);
+        }
+        image
+    }
+}
+
+/// Compares an output with the edited pixels it was encoded from
+fn measure_edited(reference: Option<Reference>, data: &[u8]) -> Option<QualityMetrics> {
+    reference?
+        .compare(data)
+        .inspect_err(|e| log::warn!("Cannot measure the quality: {e}"))
+        .ok()
+}
+
+fn perform_image_compression(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    compression_parameters: &mut CSParameters,
+    counter: usize,
+    now: DateTime<Local>,
+    quality_metrics: bool,
+) -> Result<EncodedImage, CompressionError> {
+    let mut output = encode_image(cimage, options, compression_parameters, quality_metrics)?;
+    if filters_metadata(options, cimage) {
+        output.data = metadata::filter(output.data, &options.compression_options.metadata)?;
+    }
+    let fields = &options.compression_options.metadata_fields;
+    if !fields.is_empty() {
+        let input = Path::new(&cimage.path);
+        let extension = output_extension(cimage, options);
+        let (width, height) = expected_dimensions(options, cimage);
+        let context = TemplateContext {
+            input,
+            extension: &extension,
+            suffix: &options.output_options.suffix,
+            width,
+            height,
+            counter,
+            now,
+        };
+        let rendered = RenderedFields::render(fields, &context)?;
+        output.data = metadata::write_fields(output.data, &rendered)?;
+    }
+    Ok(output)
+}
+
+/// Only part of the metadata is kept, see [`parse_compression_options`]
+fn filters_metadata(options: &OptionsPayload, cimage: &CImage) -> bool {
+    let compression = &options.compression_options;
+    let output_format = options.output_options.format_for(&cimage.mime_type);
+    compression.keep_metadata
+        && !compression.metadata.keeps_everything()
+        && metadata::can_filter(output_format, &cimage.mime_type)
+}
+
+fn encode_image(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    compression_parameters: &mut CSParameters,
+    quality_metrics: bool,
+) -> Result<EncodedImage, CompressionError> {
+    let mut file = File::open(cimage.path.clone()).map_err(CompressionError::read)?;
+    let mut input_file_buffer = Vec::new();
+    file.read_to_end(&mut input_file_buffer)
+        .map_err(CompressionError::read)?;
+
+    // Cheap header check so broken files are told apart from encoder failures. Not all
+    // camera RAW headers are known to it, those fail while decoding instead.
+    if SourceFormat::from_mime_type(&cimage.mime_type) != Some(SourceFormat::Raw) {
+        imagesize::blob_size(&input_file_buffer)
+            .map_err(|e| CompressionError::Decode(e.to_string()))?;
+    }
+
+    let edits = PixelEdits::new(cimage, options, &input_file_buffer)?;
+    let output_format = options.output_options.format_for(&cimage.mime_type);
+    if let Some(codec) = Codec::for_output(output_format, &cimage.mime_type) {
+        return encode_with_codec(
+            cimage,
+            &input_file_buffer,
+            codec,
+            options,
+            compression_parameters,
+            &edits,
+            quality_metrics,
+        );
+    }
+    // Always converted to another format here, as AVIF and JPEG XL outputs are handled
+    // above and decode-only inputs have an output format
+    if codecs::needs_decoding(&cimage.mime_type) {
+        input_file_buffer =
+            codecs::to_png(&input_file_buffer, &cimage.mime_type).map_err(decode_error)?;
+    }
+
+    let mut output_file_type = output_format.supported_file_type();
+    // libcaesium is given the edited pixels as PNG, which are converted back to the
+    // format of the original, and the metadata is carried over from the original
+    let mut original = None;
+    let mut reference = None;
+    if !edits.is_empty() {
+        let image = codecs::decode(&input_file_buffer).map_err(decode_error)?;
+        let image = edits.apply(image, compression_parameters);
+        reference = quality_metrics.then(|| Reference::new(&image));
+        let edited = codecs::encode_png(&image).map_err(decode_error)?;
+        output_file_type = output_file_type.or_else(|| file_type_of(&cimage.mime_type));
+        original = Some(std::mem::replace(&mut input_file_buffer, edited));
+    }
+
+    let compression_options = &options.compression_options;
+    let mut encoded = match compression_options.compression_mode {
+        CompressionMode::Size => {
+            let converted = match output_file_type {
+                Some(file_type) => {
+                    convert_in_memory(input_file_buffer, compression_parameters, file_type)
+                }
+                None => Ok(input_file_buffer),
+            };
+            converted
+                .and_then(|buffer| {
+                    compress_to_size_in_memory(
+                        buffer,
+                        compression_parameters,
+                        compression_options.max_size_value * compression_options.max_size_unit,
+                        true,
+                    )
+                })
+                .map_err(encode_error)
+                .map(EncodedImage::new)?
+        }
+        CompressionMode::TargetQuality if has_tunable_quality(options, cimage) => {
+            let mut reference = Reference::from_memory(&input_file_buffer)
+                .map_err(|e| CompressionError::Decode(e.to_string()))?;
+            let target = &compression_options.target_quality;
+            let output = target_quality::search(&mut reference, target, |quality| {
+                set_quality(compression_parameters, quality);
+                encode(
+                    input_file_buffer.clone(),
+                    compression_parameters,
+                    output_file_type,
+                )
+            })?;
+            target_quality_result(output, target)
+        }
+        // Lossless outputs have no quality to tune
+        CompressionMode::Quality | CompressionMode::TargetQuality => EncodedImage::new(encode(
+            input_file_buffer,
+            compression_parameters,
+            output_file_type,
+        )?),
+    };
+
+    if let Some(original) = original {
+        if compression_parameters.keep_metadata {
+            encoded.data = metadata::carry_over(&original, encoded.data)?;
+        }
+        encoded.edited = true;
+        encoded.quality_metrics = encoded
+            .quality_metrics
+            .or_else(|| measure_edited(reference, &encoded.data));
+    }
+    Ok(encoded)
+}
+
+/// libcaesium type of an input with the given mime type
+fn file_type_of(mime_type: &str) -> Option<SupportedFileTypes> {
+    match mime_type {
+        "image/jpeg" => Some(SupportedFileTypes::Jpeg),
+        "image/png" => Some(SupportedFileTypes::Png),
+        "image/gif" => Some(SupportedFileTypes::Gif),
+        "image/webp" => Some(SupportedFileTypes::WebP),
+        "image/tiff" => Some(SupportedFileTypes::Tiff),
+        _ => None,
+    }
+}
+
+/// Decodes, resizes and encodes the image outside of libcaesium
+fn encode_with_codec(
+    cimage: &CImage,
+    input: &[u8],
+    codec: Codec,
+    options: &OptionsPayload,
+    parameters: &mut CSParameters,
+    edits: &PixelEdits,
+    quality_metrics: bool,
+) -> Result<EncodedImage, CompressionError> {
+    let compression_options = &options.compression_options;
+    let mode = compression_options.compression_mode;
+    let is_lossless = codec.is_lossless(compression_options);
+
+    let original = codecs::decode_input(input, &cimage.mime_type).map_err(decode_error)?;
+    let original = edits.apply(original, parameters);
+    // Taken before resizing, outputs are compared at their own resolution. The original
+    // file cannot be measured later when the pixels are edited.
+    let is_searched = mode == CompressionMode::TargetQuality && !is_lossless;
+    let mut reference =
+        (is_searched || (quality_metrics && !edits.is_empty())).then(|| Reference::new(&original));
+    let image = codecs::resize(original, parameters.width, parameters.height);
+    let encode = |quality| {
+        codec
+            .encode(&image, compression_options, quality)
+            .map_err(|e| CompressionError::Encode {
+                code: 0,
+                message: e.to_string(),
+            })
+    };
+
+    let mut encoded = match (mode, &mut reference) {
+        (CompressionMode::TargetQuality, Some(reference)) if is_searched => {
+            let target = &compression_options.target_quality;
+            let output = target_quality::search(reference, target, encode)?;
+            target_quality_result(output, target)
+        }
+        (CompressionMode::Size, _) if !is_lossless => EncodedImage::new(encode_to_size(
+            compression_options.max_size_value * compression_options.max_size_unit,
+            encode,
+        )?),
+        _ => EncodedImage::new(encode(codec.quality(compression_options))?),
+    };
+    encoded.edited = !edits.is_empty();
+    if encoded.edited && encoded.quality_metrics.is_none() {
+        encoded.quality_metrics = measure_edited(reference, &encoded.data);
+    }
+    Ok(encoded)
+}
+
+/// Bisects the quality for the biggest output that fits in `max_size`, like
+/// libcaesium does. The smallest output is returned if none fits.
+fn encode_to_size(
+    max_size: usize,
+    encode: impl Fn(u32) -> Result<Vec<u8>, CompressionError>,
+) -> Result<Vec<u8>, CompressionError> {
+    let (mut low, mut high) = (1, 100);
+    let mut fitting: Option<Vec<u8>> = None;
+    let mut smallest: Option<Vec<u8>> = None;
+    while low <= high {
+        let quality = (low + high) / 2;
+        let data = encode(quality)?;
+        if data.len() <= max_size {
+            low = quality + 1;
+            fitting = Some(data);
+        } else {
+            high = quality - 1;
+            if smallest.as_ref().is_none_or(|s| data.len() < s.len()) {
+                smallest = Some(data);
+            }
+        }
+    }
+    fitting
+        .or(smallest)
+        .ok_or_else(|| CompressionError::Internal("No quality was tried".to_string()))
+}
+
+fn target_quality_result(
+    output: target_quality::TargetQualityOutput,
+    target: &TargetQualityOptions,
+) -> EncodedImage {
+    let info = if output.reached {
+        String::new()
+    } else {
+        format!(
+            "Target SSIM of {} not reached, the closest is {:.4} at quality {}",
+            target.ssim, output.metrics.ssim, output.quality
+        )
+    };
+    EncodedImage {
+        data: output.data,
+        quality_metrics: Some(output.metrics),
+        info,
+        edited: false,
+    }
+}
+
+fn set_quality(parameters: &mut CSParameters, quality: u32) {
+    parameters.jpeg.quality = quality;
+    parameters.png.quality = quality;
+    parameters.gif.quality = quality;
+    parameters.webp.quality = quality;
+}
+
+/// Converts `input` to `file_type`, or compresses it in its own format if there is none
+fn encode(
+    input: Vec<u8>,
+    parameters: &CSParameters,
+    file_type: Option<SupportedFileTypes>,
+) -> Result<Vec<u8>, CompressionError> {
+    match file_type {
+        Some(file_type) => convert_in_memory(input, parameters, file_type),
+        None => compress_in_memory(input, parameters),
+    }
+    .map_err(encode_error)
+}
+
+fn decode_error(e: image::ImageError) -> CompressionError {
+    CompressionError::Decode(e.to_string())
+}
+
+fn encode_error(e: CaesiumError) -> CompressionError {
+    CompressionError::Encode {
+        code: e.code,
+        message: e.message,
+    }
+}
+
+/// The output format is lossy with the current options
+fn has_tunable_quality(options: &OptionsPayload, cimage: &CImage) -> bool {
+    let compression = &options.compression_options;
+    let output_type = options
+        .output_options
+        .format_for(&cimage.mime_type)
+        .supported_file_type();
+    match (output_type, cimage.mime_type.as_str()) {
+        (Some(SupportedFileTypes::Jpeg), _) | (None, "image/jpeg") => !compression.jpeg.optimize,
+        (Some(SupportedFileTypes::Png), _) | (None, "image/png") => !compression.png.optimize,
+        (Some(SupportedFileTypes::Gif), _) | (None, "image/gif") => true,
+        (Some(SupportedFileTypes::WebP), _) | (None, "image/webp") => !compression.webp.lossless,
+        _ => false,
+    }
+}
+
+pub(crate) fn output_path(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    context: &CompressionContext,
+) -> Result<PathBuf, CompressionError> {
+    let input_file = Path::new(&cimage.path);
+    let output_options = &options.output_options;
+    let output_directory = determine_output_directory(input_file, options).ok_or_else(|| {
+        CompressionError::OutputPath("the input has no parent folder".to_string())
+    })?;
+    let output_directory = compute_output_directory(
+        &output_directory,
+        input_file,
+        &PathBuf::from(context.base_folder),
+        output_options.keep_folder_structure,
+        output_options.same_folder_as_input,
+    )
+    .ok_or_else(|| {
+        CompressionError::OutputPath("the input is outside of the base folder".to_string())
+    })?;
+
+    let extension = output_extension(cimage, options);
+
+    if output_options.filename_template.is_empty() {
+        let mut output_file_name = input_file.file_stem().unwrap_or_default().to_os_string();
+        output_file_name.push(&output_options.suffix);
+        if !extension.is_empty() {
+            output_file_name.push(".");
+            output_file_name.push(extension);
+        }
+        return Ok(output_directory.join(output_file_name));
+    }
+
+    let (width, height) = expected_dimensions(options, cimage);
+    let file_name = FilenameTemplate::parse(&output_options.filename_template)
+        .and_then(|template| {
+            template.render(&TemplateContext {
+                input: input_file,
+                extension: &extension,
+                suffix: &output_options.suffix,
+                width,
+                height,
+                counter: context.counter,
+                now: context.started,
+            })
+        })
+        .map_err(|e| CompressionError::OutputPath(e.to_string()))?;
+    Ok(output_directory.join(file_name))
+}
+
+/// Extension of the output, without the dot, the input one when the format is kept
+fn output_extension(cimage: &CImage, options: &OptionsPayload) -> String {
+    match options
+        .output_options
+        .format_for(&cimage.mime_type)
+        .extension()
+    {
+        Some(e) => e.to_string(),
+        None => Path::new(&cimage.path)
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+    }
+}
+
+fn determine_output_directory(input_file: &Path, options: &OptionsPayload) -> Option<PathBuf> {
+    if options.output_options.same_folder_as_input {
+        input_file.parent().map(|p| p.to_path_buf())
+    } else {
+        let folder = options.output_options.output_folder.clone();
+        Some(PathBuf::from(folder))
+    }
+}
+
+fn compute_output_directory(
+    output_directory: &Path,
+    input_file_path: &Path,
+    base_directory: &PathBuf,
+    keep_structure: bool,
+    same_folder_as_input: bool,
+) -> Option<PathBuf> {
+    if !keep_structure {
+        return Some(PathBuf::from(output_directory));
+    }
+
+    let parent = match absolute(input_file_path.parent()?) {
+        Ok(p) => p,
+        Err(_) => return None,
+    };
+
+    if same_folder_as_input {
+        return Some(parent);
+    }
+
+    let output_path_prefix = if !base_directory.clone().into_os_string().is_empty() {
+        match parent.strip_prefix(base_directory) {
+            Ok(p) => p.to_path_buf(),
+            Err(_) => return None,
+        }
+    } else {
+        let prefix = parent.display().to_string().replace(":", "");
+        PathBuf::from(prefix)
+    };
+
+    Some(output_directory.join(output_path_prefix))
+}
+
+fn preserve_file_times(
+    output_file: &File,
+    original_file_metadata: &Metadata,
+    options: &OptionsPayload,
+) -> io::Result<()> {
+    let mut file_times = FileTimes::new();
+
+    #[cfg(target_os = "windows")]
+    {
+        if options.output_options.keep_creation_date {
+            let created_time = original_file_metadata.created()?;
+            file_times = file_times.set_created(created_time);
+        }
+    }
+
+    if options.output_options.keep_last_modified_date {
+        let modified_time = original_file_metadata.modified()?;
+        file_times = file_times.set_modified(modified_time);
+    }
+
+    if options.output_options.keep_last_access_date {
+        let accessed_time = original_file_metadata.accessed()?;
+        file_times = file_times.set_accessed(accessed_time);
+    }
+
+    output_file.set_times(file_times)?;
+    Ok(())
+}
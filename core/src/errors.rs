@@ -0,0 +1,104 @@
+use crate::metadata::MetadataError;
+use crate::options::OptionsError;
+use crate::watermark::WatermarkError;
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BatchError {
+    #[error(transparent)]
+    InvalidOptions(#[from] OptionsError),
+    #[error(transparent)]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+}
+
+/// Why a single image could not be compressed, the rest of the batch is not affected
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError {
+    #[error("File exceeds max size of {0} bytes")]
+    TooLarge(u64),
+    #[error("Permission denied: {0}")]
+    Permission(io::Error),
+    #[error("Cannot read the input file: {0}")]
+    Read(io::Error),
+    #[error("Cannot decode the image: {0}")]
+    Decode(String),
+    #[error("Cannot compress the image: {message} ({code})")]
+    Encode { code: u32, message: String },
+    #[error("Cannot compute the output path: {0}")]
+    OutputPath(String),
+    #[error("Another image of the batch is written to {0}")]
+    OutputCollision(String),
+    #[error("Cannot write the output file: {0}")]
+    Write(io::Error),
+    #[error("Cannot preserve file metadata: {0}")]
+    Metadata(io::Error),
+    #[error("Cannot update the image metadata: {0}")]
+    ImageMetadata(#[from] MetadataError),
+    #[error("Cannot draw the watermark: {0}")]
+    Watermark(#[from] WatermarkError),
+    #[error("Cannot move the original file: {0}")]
+    MoveOriginal(String),
+    #[error("Cannot record the changes needed to undo the job: {0}")]
+    Journal(io::Error),
+    #[error("Unexpected failure: {0}")]
+    Internal(String),
+}
+
+/// Machine-readable counterpart of [`CompressionError`], stored on the image
+#[derive(
+    serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionErrorCode {
+    TooLarge,
+    Permission,
+    Read,
+    Decode,
+    Encode,
+    OutputPath,
+    OutputCollision,
+    Write,
+    Metadata,
+    ImageMetadata,
+    Watermark,
+    MoveOriginal,
+    Journal,
+    Internal,
+}
+
+impl CompressionError {
+    pub fn code(&self) -> CompressionErrorCode {
+        match self {
+            CompressionError::TooLarge(_) => CompressionErrorCode::TooLarge,
+            CompressionError::Permission(_) => CompressionErrorCode::Permission,
+            CompressionError::Read(_) => CompressionErrorCode::Read,
+            CompressionError::Decode(_) => CompressionErrorCode::Decode,
+            CompressionError::Encode { .. } => CompressionErrorCode::Encode,
+            CompressionError::OutputPath(_) => CompressionErrorCode::OutputPath,
+            CompressionError::OutputCollision(_) => CompressionErrorCode::OutputCollision,
+            CompressionError::Write(_) => CompressionErrorCode::Write,
+            CompressionError::Metadata(_) => CompressionErrorCode::Metadata,
+            CompressionError::ImageMetadata(_) => CompressionErrorCode::ImageMetadata,
+            CompressionError::Watermark(_) => CompressionErrorCode::Watermark,
+            CompressionError::MoveOriginal(_) => CompressionErrorCode::MoveOriginal,
+            CompressionError::Journal(_) => CompressionErrorCode::Journal,
+            CompressionError::Internal(_) => CompressionErrorCode::Internal,
+        }
+    }
+
+    pub(crate) fn read(e: io::Error) -> Self {
+        Self::from_io(e, CompressionError::Read)
+    }
+
+    pub(crate) fn write(e: io::Error) -> Self {
+        Self::from_io(e, CompressionError::Write)
+    }
+
+    fn from_io(e: io::Error, fallback: fn(io::Error) -> Self) -> Self {
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            CompressionError::Permission(e)
+        } else {
+            fallback(e)
+        }
+    }
+}
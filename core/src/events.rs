@@ -0,0 +1,32 @@
+use crate::compressor::{CompressionResult, CompressionSummary};
+use crate::scan_files::{FileImportProgress, FileList, ImportFinishedResult};
+use std::path::Path;
+
+/// Receives the notifications produced while importing and compressing images.
+///
+/// Every method has an empty default implementation, so consumers only need to
+/// implement the events they are interested in.
+pub trait EventSink: Sync {
+    fn import_started(&self) {}
+
+    fn import_progress(&self, _progress: &FileImportProgress) {}
+
+    fn import_finished(&self, _result: &ImportFinishedResult, _file_list: &FileList) {}
+
+    /// Called when an image starts compressing and again with the final result
+    fn image_updated(&self, _result: &CompressionResult) {}
+
+    fn compression_progress(&self, _progress: usize) {}
+
+    fn compression_paused(&self) {}
+
+    fn compression_finished(&self, _summary: &CompressionSummary) {}
+
+    /// Called when an image dropped in a watched folder has been processed
+    fn watched_file_processed(&self, _folder: &Path, _result: &CompressionResult) {}
+}
+
+/// A sink that discards every event
+pub struct NoopEventSink;
+
+impl EventSink for NoopEventSink {}
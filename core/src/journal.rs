@@ -0,0 +1,559 @@
+use crate::atomic_file::copy_atomically;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+const BACKUP_FOLDER_NAME: &str = "files";
+
+/// Number of jobs whose backups are kept when no other limit is given
+pub const DEFAULT_RETAINED_JOBS: usize = 10;
+
+/// What a job did to the original file
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OriginalAction {
+    Kept,
+    Replaced,
+    Trashed,
+    Deleted,
+}
+
+/// One output written by a job, with everything needed to revert it
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct JournalEntry {
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+    pub original: OriginalAction,
+    /// Copy of the input taken before it was replaced or moved
+    pub original_backup: Option<PathBuf>,
+    /// Copy of the file that was at the output path before the job
+    pub previous_output_backup: Option<PathBuf>,
+    /// Size and modification time (ms since the epoch) of the output when it was written
+    pub output_size: u64,
+    pub output_modified: Option<u64>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct JobInfo {
+    pub id: String,
+    /// Start of the job, in ms since the epoch
+    pub created: u64,
+    pub entries: usize,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct UndoConflict {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct UndoResult {
+    /// Inputs that are back to their state before the job
+    pub restored: Vec<PathBuf>,
+    /// Entries left untouched, the job can be undone again once they are solved
+    pub conflicts: Vec<UndoConflict>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JournalError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Job not found: {0}")]
+    NotFound(String),
+}
+
+/// Copies taken before a write, see [`JobJournal::back_up`]
+pub(crate) struct Backups {
+    original: Option<PathBuf>,
+    previous_output: Option<PathBuf>,
+}
+
+impl Backups {
+    /// Removes the copies taken for a write that did not happen
+    pub(crate) fn discard(self) {
+        for backup in [self.original, self.previous_output].into_iter().flatten() {
+            if let Err(e) = fs::remove_file(&backup) {
+                log::warn!("Cannot remove the backup {}: {e}", backup.display());
+            }
+        }
+    }
+}
+
+/// Keeps the journal and backups of the last compression jobs, one folder per job
+pub struct BackupStore {
+    directory: PathBuf,
+    retained_jobs: usize,
+}
+
+impl BackupStore {
+    pub fn new(directory: impl Into<PathBuf>, retained_jobs: usize) -> Self {
+        Self {
+            directory: directory.into(),
+            retained_jobs,
+        }
+    }
+
+    pub fn start_job(&self) -> Result<JobJournal, JournalError> {
+        fs::create_dir_all(&self.directory)?;
+        let mut created = now_millis();
+        // Ids are zero padded timestamps, so they sort by age
+        loop {
+            let id = format!("{created:016}");
+            let directory = self.directory.join(&id);
+            match fs::create_dir(&directory) {
+                Ok(()) => {
+                    let file = File::create(directory.join(JOURNAL_FILE_NAME))?;
+                    return Ok(JobJournal {
+                        id,
+                        directory,
+                        file: Mutex::new(file),
+                        backups: AtomicUsize::new(0),
+                        entries: AtomicUsize::new(0),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => created += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Closes the journal, dropping it if the job did not write anything, and
+    /// removes the oldest jobs beyond the retention limit
+    pub fn finish_job(&self, journal: JobJournal) -> Result<Option<String>, JournalError> {
+        let is_empty = journal.is_empty();
+        let JobJournal {
+            id,
+            directory,
+            file,
+            ..
+        } = journal;
+        // Windows cannot remove a folder with open files
+        drop(file);
+        let id = if is_empty {
+            fs::remove_dir_all(&directory)?;
+            None
+        } else {
+            Some(id)
+        };
+
+        let mut ids = self.job_ids()?;
+        let excess = ids.len().saturating_sub(self.retained_jobs);
+        for id in ids.drain(..excess) {
+            fs::remove_dir_all(self.directory.join(id))?;
+        }
+        Ok(id)
+    }
+
+    /// Jobs that can be undone, newest first
+    pub fn jobs(&self) -> Result<Vec<JobInfo>, JournalError> {
+        let mut jobs = Vec::new();
+        for id in self.job_ids()?.into_iter().rev() {
+            let entries = read_entries(&self.directory.join(&id))?.len();
+            jobs.push(JobInfo {
+                created: id.parse().unwrap_or_default(),
+                id,
+                entries,
+            });
+        }
+        Ok(jobs)
+    }
+
+    /// Restores the originals touched by a job and removes its outputs.
+    ///
+    /// Entries whose files changed after the job are reported as conflicts and kept
+    /// in the journal, everything else is reverted and forgotten.
+    pub fn undo(&self, id: &str) -> Result<UndoResult, JournalError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(JournalError::NotFound(id.to_string()));
+        }
+        let directory = self.directory.join(id);
+        if !directory.is_dir() {
+            return Err(JournalError::NotFound(id.to_string()));
+        }
+
+        let mut result = UndoResult::default();
+        let mut remaining = Vec::new();
+        for entry in read_entries(&directory)?.into_iter().rev() {
+            match undo_entry(&entry) {
+                Ok(()) => result.restored.push(entry.input_path.clone()),
+                Err(reason) => {
+                    result.conflicts.push(UndoConflict {
+                        path: entry.input_path.clone(),
+                        reason,
+                    });
+                    remaining.push(entry);
+                }
+            }
+        }
+
+        if remaining.is_empty() {
+            fs::remove_dir_all(&directory)?;
+        } else {
+            remaining.reverse();
+            let mut content = Vec::new();
+            for entry in &remaining {
+                serde_json::to_writer(&mut content, entry)?;
+                content.push(b'\n');
+            }
+            fs::write(directory.join(JOURNAL_FILE_NAME), content)?;
+        }
+        Ok(result)
+    }
+
+    fn job_ids(&self) -> Result<Vec<String>, JournalError> {
+        let mut ids = Vec::new();
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(e) => e,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ids),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() && name.chars().all(|c| c.is_ascii_digit()) {
+                ids.push(name);
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+/// Journal of a running job, entries are appended as soon as each output is written
+pub struct JobJournal {
+    id: String,
+    directory: PathBuf,
+    file: Mutex<File>,
+    backups: AtomicUsize,
+    entries: AtomicUsize,
+}
+
+impl JobJournal {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the input if the job is going to replace or move it, and any file
+    /// already at the output path
+    pub(crate) fn back_up(
+        &self,
+        input: &Path,
+        output: &Path,
+        replaces_input: bool,
+    ) -> io::Result<Backups> {
+        let mut backups = Backups {
+            original: replaces_input
+                .then(|| self.back_up_file(input))
+                .transpose()?,
+            previous_output: None,
+        };
+        if output != input && output.exists() {
+            match self.back_up_file(output) {
+                Ok(backup) => backups.previous_output = Some(backup),
+                Err(e) => {
+                    backups.discard();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(backups)
+    }
+
+    pub(crate) fn record(
+        &self,
+        input: &Path,
+        output: &Path,
+        original: OriginalAction,
+        backups: Backups,
+    ) -> io::Result<()> {
+        let (output_size, output_modified) = file_stamp(output)?;
+        let mut line = serde_json::to_vec(&JournalEntry {
+            input_path: input.to_path_buf(),
+            output_path: output.to_path_buf(),
+            original,
+            original_backup: backups.original,
+            previous_output_backup: backups.previous_output,
+            output_size,
+            output_modified,
+        })?;
+        line.push(b'\n');
+
+        self.file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .write_all(&line)?;
+        self.entries.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn back_up_file(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut name = self.backups.fetch_add(1, Ordering::Relaxed).to_string();
+        if let Some(extension) = path.extension() {
+            name.push('.');
+            name.push_str(&extension.to_string_lossy());
+        }
+        let backup = self.directory.join(BACKUP_FOLDER_NAME).join(name);
+        fs::create_dir_all(self.directory.join(BACKUP_FOLDER_NAME))?;
+
+        // Outputs are renamed into place and originals are only unlinked, so a hard link
+        // keeps the old content without copying it
+        if fs::hard_link(path, &backup).is_err() {
+            fs::copy(path, &backup)?;
+        }
+        Ok(backup)
+    }
+}
+
+fn read_entries(directory: &Path) -> Result<Vec<JournalEntry>, JournalError> {
+    let file = File::open(directory.join(JOURNAL_FILE_NAME))?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        // A job interrupted while writing leaves a truncated last line
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) if !line.trim().is_empty() => log::warn!("Skipping journal entry: {e}"),
+            Err(_) => {}
+        }
+    }
+    Ok(entries)
+}
+
+fn undo_entry(entry: &JournalEntry) -> Result<(), String> {
+    let output = &entry.output_path;
+    let input = &entry.input_path;
+    let replaced_input = output == input;
+
+    match file_stamp(output) {
+        Ok(stamp) if stamp != (entry.output_size, entry.output_modified) => {
+            return Err("The output was modified after the job".to_string());
+        }
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.to_string()),
+    }
+    let moved_original = matches!(
+        entry.original,
+        OriginalAction::Trashed | OriginalAction::Deleted
+    );
+    if moved_original && input.exists() {
+        return Err("Another file is now at the original path".to_string());
+    }
+    let original_backup = match (&entry.original_backup, entry.original) {
+        (_, OriginalAction::Kept) => None,
+        (Some(backup), _) if backup.exists() => Some(backup),
+        _ => return Err("The backup of the original is missing".to_string()),
+    };
+
+    if !replaced_input {
+        match fs::remove_file(output) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        if let Some(backup) = &entry.previous_output_backup {
+            restore_file(backup, output).map_err(|e| e.to_string())?;
+        }
+    }
+    if let Some(backup) = original_backup {
+        restore_file(backup, input).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Moves a backup back into place, copying it when the store is on another volume
+fn restore_file(backup: &Path, target: &Path) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(backup, target).is_ok() {
+        return Ok(());
+    }
+    let modified = fs::metadata(backup)?.modified()?;
+    copy_atomically(backup, target)?;
+    File::options()
+        .write(true)
+        .open(target)?
+        .set_modified(modified)
+}
+
+fn file_stamp(path: &Path) -> io::Result<(u64, Option<u64>)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64);
+    Ok((metadata.len(), modified))
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atomic_file::AtomicFile;
+
+    /// A backup store and the folder its jobs write to
+    struct Fixture {
+        directory: tempfile::TempDir,
+        store: BackupStore,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let directory = tempfile::tempdir().unwrap();
+            let store = BackupStore::new(directory.path().join("backups"), DEFAULT_RETAINED_JOBS);
+            fs::create_dir(directory.path().join("files")).unwrap();
+            Self { directory, store }
+        }
+
+        fn file(&self, name: &str) -> PathBuf {
+            self.directory.path().join("files").join(name)
+        }
+
+        /// Writes `output` the way a job does, renaming the new content into place
+        fn write(
+            &self,
+            journal: &JobJournal,
+            input: &Path,
+            output: &Path,
+            original: OriginalAction,
+        ) {
+            let replaces_input = original != OriginalAction::Kept;
+            let backups = journal.back_up(input, output, replaces_input).unwrap();
+            let mut file = AtomicFile::create(output).unwrap();
+            file.write_all(b"compressed").unwrap();
+            file.commit().unwrap();
+            if matches!(original, OriginalAction::Deleted) {
+                fs::remove_file(input).unwrap();
+            }
+            journal.record(input, output, original, backups).unwrap();
+        }
+
+        fn backups(&self) -> usize {
+            fs::read_dir(self.directory.path().join("backups"))
+                .unwrap()
+                .map(|job| {
+                    let files = job.unwrap().path().join(BACKUP_FOLDER_NAME);
+                    fs::read_dir(files).map_or(0, |files| files.count())
+                })
+                .sum()
+        }
+    }
+
+    #[test]
+    fn undo_restores_overwritten_outputs() {
+        let fixture = Fixture::new();
+        let (input, output) = (fixture.file("a.jpg"), fixture.file("a.min.jpg"));
+        fs::write(&input, b"original").unwrap();
+        fs::write(&output, b"previous output").unwrap();
+
+        let journal = fixture.store.start_job().unwrap();
+        fixture.write(&journal, &input, &output, OriginalAction::Kept);
+        let id = fixture.store.finish_job(journal).unwrap().unwrap();
+        let result = fixture.store.undo(&id).unwrap();
+
+        assert_eq!(result.restored, [input.as_path()]);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(fs::read(&input).unwrap(), b"original");
+        assert_eq!(fs::read(&output).unwrap(), b"previous output");
+        assert!(fixture.store.jobs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn undo_restores_replaced_and_deleted_originals() {
+        let fixture = Fixture::new();
+        let (replaced, deleted) = (fixture.file("a.jpg"), fixture.file("b.jpg"));
+        let output = fixture.file("b.min.jpg");
+        fs::write(&replaced, b"original a").unwrap();
+        fs::write(&deleted, b"original b").unwrap();
+
+        let journal = fixture.store.start_job().unwrap();
+        fixture.write(&journal, &replaced, &replaced, OriginalAction::Replaced);
+        fixture.write(&journal, &deleted, &output, OriginalAction::Deleted);
+        let id = fixture.store.finish_job(journal).unwrap().unwrap();
+        assert_eq!(fs::read(&replaced).unwrap(), b"compressed");
+        assert!(!deleted.exists());
+
+        let result = fixture.store.undo(&id).unwrap();
+
+        assert_eq!(result.restored.len(), 2);
+        assert_eq!(fs::read(&replaced).unwrap(), b"original a");
+        assert_eq!(fs::read(&deleted).unwrap(), b"original b");
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn conflicting_entries_are_kept_for_a_later_undo() {
+        let fixture = Fixture::new();
+        let (a, b) = (fixture.file("a.jpg"), fixture.file("b.jpg"));
+        fs::write(&a, b"original a").unwrap();
+        fs::write(&b, b"original b").unwrap();
+
+        let journal = fixture.store.start_job().unwrap();
+        fixture.write(&journal, &a, &a, OriginalAction::Replaced);
+        fixture.write(&journal, &b, &b, OriginalAction::Replaced);
+        let id = fixture.store.finish_job(journal).unwrap().unwrap();
+        let written = fs::metadata(&b).unwrap().modified().unwrap();
+        fs::write(&b, b"edited after the job").unwrap();
+
+        let result = fixture.store.undo(&id).unwrap();
+        assert_eq!(result.restored, [a.as_path()]);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].path, b);
+        assert_eq!(fs::read(&a).unwrap(), b"original a");
+        assert_eq!(fs::read(&b).unwrap(), b"edited after the job");
+        assert_eq!(fixture.store.jobs().unwrap()[0].entries, 1);
+
+        // Once the output is back to what the job wrote, the rest can be undone
+        fs::write(&b, b"compressed").unwrap();
+        File::options()
+            .write(true)
+            .open(&b)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+        let result = fixture.store.undo(&id).unwrap();
+        assert_eq!(result.restored, [b.as_path()]);
+        assert_eq!(fs::read(&b).unwrap(), b"original b");
+    }
+
+    #[test]
+    fn backups_of_failed_writes_are_removed() {
+        let fixture = Fixture::new();
+        let (input, output) = (fixture.file("a.jpg"), fixture.file("a.min.jpg"));
+        fs::write(&input, b"original").unwrap();
+        fs::write(&output, b"previous output").unwrap();
+
+        let journal = fixture.store.start_job().unwrap();
+        let backups = journal.back_up(&input, &output, true).unwrap();
+        assert_eq!(fixture.backups(), 2);
+        backups.discard();
+
+        assert_eq!(fixture.backups(), 0);
+        assert_eq!(fs::read(&input).unwrap(), b"original");
+        assert_eq!(fs::read(&output).unwrap(), b"previous output");
+        assert_eq!(fixture.store.finish_job(journal).unwrap(), None);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::errors::CompressionErrorCode;
+use crate::metrics::QualityMetrics;
+use crate::options::CollisionPolicy;
+use serde_repr::*;
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+
+pub mod app_data;
+pub mod atomic_file;
+pub mod batch;
+pub mod cache;
+pub mod codecs;
+pub mod collision;
+pub mod compressor;
+pub mod errors;
+pub mod events;
+pub mod journal;
+pub mod memory;
+pub mod metadata;
+pub mod metrics;
+pub mod options;
+pub mod plan;
+pub mod presets;
+pub mod report;
+pub mod resize;
+pub mod rules;
+pub mod scan_files;
+pub mod session;
+pub mod target_quality;
+pub mod template;
+pub mod watch;
+pub mod watermark;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct CImage {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub directory: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub width: usize,
+    pub height: usize,
+
+    pub compressed_width: usize,
+    pub compressed_height: usize,
+    pub compressed_size: u64,
+    pub compressed_file_path: String,
+    pub info: String,
+    pub status: ImageStatus,
+    /// Name of the rule whose overrides produced the compressed file
+    #[serde(default)]
+    pub applied_rule: Option<String>,
+    /// Policy applied because the output path was already taken
+    #[serde(default)]
+    pub collision_policy: Option<CollisionPolicy>,
+    /// Set together with `info` when the status is `Error`
+    #[serde(default)]
+    pub error_code: Option<CompressionErrorCode>,
+    /// Similarity to the original, if measured during compression
+    #[serde(default)]
+    pub quality_metrics: Option<QualityMetrics>,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Clone, Debug, Default)]
+#[repr(i8)]
+pub enum ImageStatus {
+    #[default]
+    New = 0,
+    Success = 1,
+    Warning = 2,
+    Error = 3,
+    Compressing = -1,
+}
+
+// Equality and hashing only based on `id`
+impl PartialEq for CImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for CImage {}
+
+impl Hash for CImage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Borrow<str> for CImage {
+    fn borrow(&self) -> &str {
+        &self.id
+    }
+}
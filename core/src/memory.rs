@@ -0,0 +1,187 @@
+use crate::CImage;
+use std::sync::{Condvar, Mutex};
+
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 500 * 1024 * 1024;
+pub const DEFAULT_MEMORY_BUDGET: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Limits applied to every image of a batch
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug)]
+pub struct ResourceLimits {
+    /// Files bigger than this are reported as errors without being read
+    pub max_file_size: u64,
+    /// Estimated bytes all the workers can use at the same time, 0 means unlimited
+    pub memory_budget: u64,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+        }
+    }
+}
+
+/// Rough peak memory needed to compress `cimage`: the encoded input and output buffers
+/// plus the decoded RGBA bitmap, twice to account for resizing and conversions
+pub fn estimate_memory_usage(cimage: &CImage) -> u64 {
+    let pixels = cimage.width as u64 * cimage.height as u64;
+    cimage
+        .size
+        .saturating_mul(2)
+        .saturating_add(pixels.saturating_mul(4 * 2))
+}
+
+struct BudgetState {
+    in_use: u64,
+    next_ticket: u64,
+    now_serving: u64,
+}
+
+/// Shared byte budget reserved from before an image is handed to a batch worker
+pub struct MemoryBudget {
+    capacity: u64,
+    state: Mutex<BudgetState>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(BudgetState {
+                in_use: 0,
+                next_ticket: 0,
+                now_serving: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Blocks until `bytes` fit in the budget.
+    ///
+    /// Requests are served in arrival order, so a large file waits for memory to be
+    /// released instead of being starved by smaller ones. A request bigger than the
+    /// whole budget runs alone. Never call it from a thread the reservations are
+    /// released on, it would wait for itself.
+    pub fn acquire(&self, bytes: u64) -> MemoryReservation<'_> {
+        if self.capacity == 0 {
+            return MemoryReservation {
+                budget: self,
+                bytes: 0,
+            };
+        }
+
+        let bytes = bytes.min(self.capacity);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+
+        while state.now_serving != ticket || state.in_use + bytes > self.capacity {
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+
+        state.in_use += bytes;
+        state.now_serving += 1;
+        drop(state);
+        // Let the next ticket check if it fits as well
+        self.released.notify_all();
+
+        MemoryReservation {
+            budget: self,
+            bytes,
+        }
+    }
+
+    fn release(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.in_use -= bytes;
+        drop(state);
+        self.released.notify_all();
+    }
+}
+
+/// Bytes reserved from a [`MemoryBudget`], given back when dropped
+pub struct MemoryReservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for MemoryReservation<'_> {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn in_use(budget: &MemoryBudget) -> u64 {
+        budget.state.lock().unwrap().in_use
+    }
+
+    /// Waits for `tickets` requests to be queued
+    fn wait_for_tickets(budget: &MemoryBudget, tickets: u64) {
+        while budget.state.lock().unwrap().next_ticket < tickets {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn reservations_are_given_back_when_dropped() {
+        let budget = MemoryBudget::new(100);
+        let first = budget.acquire(60);
+        let second = budget.acquire(40);
+        assert_eq!(in_use(&budget), 100);
+
+        drop(first);
+        assert_eq!(in_use(&budget), 40);
+        drop(second);
+        assert_eq!(in_use(&budget), 0);
+    }
+
+    #[test]
+    fn requests_bigger_than_the_budget_run_alone() {
+        let budget = MemoryBudget::new(100);
+        let reservation = budget.acquire(1000);
+        assert_eq!(in_use(&budget), 100);
+
+        drop(reservation);
+        assert_eq!(in_use(&budget), 0);
+    }
+
+    #[test]
+    fn unlimited_budgets_never_wait() {
+        let budget = MemoryBudget::new(0);
+        let _first = budget.acquire(u64::MAX);
+        let _second = budget.acquire(u64::MAX);
+        assert_eq!(in_use(&budget), 0);
+    }
+
+    #[test]
+    fn requests_are_served_in_arrival_order() {
+        let budget = MemoryBudget::new(100);
+        thread::scope(|s| {
+            let held = budget.acquire(80);
+            let large = s.spawn(|| budget.acquire(50));
+            wait_for_tickets(&budget, 2);
+            // Fits next to the held bytes, but has to wait for the large request
+            let small = s.spawn(|| {
+                let _reservation = budget.acquire(10);
+                in_use(&budget)
+            });
+            wait_for_tickets(&budget, 3);
+
+            drop(held);
+            let large = large.join().unwrap();
+            assert_eq!(small.join().unwrap(), 60);
+            drop(large);
+        });
+        assert_eq!(in_use(&budget), 0);
+    }
+}
@@ -0,0 +1,1739 @@
+use crate::options::{MetadataFieldKind, MetadataFields, MetadataOptions, OutputFormat};
+use crate::template::{TemplateContext, TemplateError, TextTemplate};
+use exif::experimental::Writer;
+use exif::{Context, Exif, Field, In, Tag, Value};
+use std::fmt::Write as _;
+use std::io::Cursor;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_EXTENSION_HEADER: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+const ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+const PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+/// Keyword and compression method of the iCCP chunk
+const PNG_ICC_HEADER: &[u8] = b"ICC Profile\0\0";
+/// Longest part of an ICC profile in a JPEG segment, after the sequence number and count
+const ICC_MAX_CHUNK: usize = 0xFFFF - 2 - ICC_HEADER.len() - 2;
+
+const APP0: u8 = 0xE0;
+const APP1: u8 = 0xE1;
+const APP2: u8 = 0xE2;
+const APP13: u8 = 0xED;
+const COM: u8 = 0xFE;
+const SOS: u8 = 0xDA;
+const EOI: u8 = 0xD9;
+
+// VP8X flags
+const WEBP_ICC: u8 = 0x20;
+const WEBP_ALPHA: u8 = 0x10;
+const WEBP_EXIF: u8 = 0x08;
+const WEBP_XMP: u8 = 0x04;
+
+// TIFF tags and field types
+const TIFF_IMAGE_DESCRIPTION: u16 = 270;
+const TIFF_ARTIST: u16 = 315;
+const TIFF_XMP: u16 = 700;
+const TIFF_COPYRIGHT: u16 = 33432;
+const TIFF_IPTC: u16 = 33723;
+const TIFF_BYTE: u16 = 1;
+const TIFF_ASCII: u16 = 2;
+const TIFF_UNDEFINED: u16 = 7;
+
+/// Photoshop image resource holding the IPTC records
+const IPTC_RESOURCE: u16 = 0x0404;
+const IPTC_KEYWORDS: u8 = 25;
+const IPTC_BY_LINE: u8 = 80;
+const IPTC_COPYRIGHT_NOTICE: u8 = 116;
+const IPTC_CAPTION: u8 = 120;
+/// Longest value that fits in a dataset without an extended length
+const IPTC_MAX_LENGTH: usize = 0x7FFF;
+
+/// Namespaces of the XMP properties that can be written
+const XMP_NAMESPACES: &[(&str, &str)] = &[
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    (
+        "Iptc4xmpCore",
+        "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/",
+    ),
+    ("plus", "http://ns.useplus.org/ldf/xmp/1.0/"),
+];
+
+/// Datasets of the IPTC application record that can be written, by their ExifTool name
+const IPTC_DATASETS: &[(&str, u8)] = &[
+    ("ObjectName", 5),
+    ("Category", 15),
+    ("Keywords", IPTC_KEYWORDS),
+    ("SpecialInstructions", 40),
+    ("By-line", IPTC_BY_LINE),
+    ("By-lineTitle", 85),
+    ("City", 90),
+    ("Sub-location", 92),
+    ("Province-State", 95),
+    ("Country-PrimaryLocationCode", 100),
+    ("Country-PrimaryLocationName", 101),
+    ("OriginalTransmissionReference", 103),
+    ("Headline", 105),
+    ("Credit", 110),
+    ("Source", 115),
+    ("CopyrightNotice", IPTC_COPYRIGHT_NOTICE),
+    ("Contact", 118),
+    ("Caption-Abstract", IPTC_CAPTION),
+    ("Writer-Editor", 122),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetadataError {
+    #[error("Malformed {0} structure")]
+    Malformed(&'static str),
+    #[error("The metadata does not fit in a JPEG segment")]
+    SegmentTooLarge,
+    #[error("The TIFF file is too large to add metadata to it")]
+    TiffTooLarge,
+    #[error("Invalid metadata template: {0}")]
+    Template(#[from] TemplateError),
+    #[error("Metadata fields can only be written to JPEG, PNG, WebP and TIFF files")]
+    FieldsNotSupported,
+}
+
+/// Outputs whose metadata can be filtered, see [`filter`]
+pub(crate) fn can_filter(format: OutputFormat, input_mime_type: &str) -> bool {
+    match format {
+        OutputFormat::Jpeg | OutputFormat::Png | OutputFormat::WebP => true,
+        OutputFormat::Original => {
+            matches!(input_mime_type, "image/jpeg" | "image/png" | "image/webp")
+        }
+        _ => false,
+    }
+}
+
+/// Removes the metadata categories not kept by `options` from a JPEG, PNG or WebP file.
+/// Other formats are returned as they are.
+pub(crate) fn filter(data: Vec<u8>, options: &MetadataOptions) -> Result<Vec<u8>, MetadataError> {
+    let kept = options.effective();
+    if data.starts_with(&[0xFF, 0xD8]) {
+        filter_jpeg(&data, &kept)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        filter_png(&data, &kept)
+    } else if is_webp(&data) {
+        filter_webp(&data, &kept)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Writes `fields` into a JPEG, PNG, WebP or TIFF file, replacing the values already
+/// there. Other formats cannot hold them and fail.
+pub(crate) fn write_fields(
+    data: Vec<u8>,
+    fields: &RenderedFields,
+) -> Result<Vec<u8>, MetadataError> {
+    if fields.is_empty() {
+        Ok(data)
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        write_jpeg(&data, fields)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        write_png(&data, fields)
+    } else if is_webp(&data) {
+        write_webp(&data, fields)
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        write_tiff(&data, fields)
+    } else {
+        Err(MetadataError::FieldsNotSupported)
+    }
+}
+
+/// Copies the EXIF, XMP and ICC metadata of a JPEG, PNG or WebP `original` to `data`, a
+/// JPEG, PNG or WebP file encoded from its pixels turned upright, and resets the
+/// orientation. The IPTC and comments of a JPEG original only go to JPEG files. Other
+/// formats are returned as they are.
+pub(crate) fn carry_over(original: &[u8], data: Vec<u8>) -> Result<Vec<u8>, MetadataError> {
+    let carried = CarriedMetadata::read(original)?.upright();
+    if carried.is_empty() {
+        Ok(data)
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        carry_to_jpeg(&data, &carried)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        carry_to_png(&data, &carried)
+    } else if is_webp(&data) {
+        carry_to_webp(&data, &carried)
+    } else {
+        Ok(data)
+    }
+}
+
+/// `prefix:Name`, with the prefix of one of the namespaces that can be written
+pub(crate) fn is_known_xmp_property(name: &str) -> bool {
+    name.split_once(':').is_some_and(|(prefix, property)| {
+        xmp_namespace(prefix).is_some()
+            && property.starts_with(|c: char| c.is_ascii_alphabetic())
+            && property
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+/// Number of the IPTC dataset called `name`, ignoring the case
+pub(crate) fn iptc_dataset(name: &str) -> Option<u8> {
+    IPTC_DATASETS
+        .iter()
+        .find(|(dataset, _)| dataset.eq_ignore_ascii_case(name))
+        .map(|(_, number)| *number)
+}
+
+fn xmp_namespace(prefix: &str) -> Option<&'static str> {
+    XMP_NAMESPACES
+        .iter()
+        .find(|(p, _)| *p == prefix)
+        .map(|(_, uri)| *uri)
+}
+
+fn is_webp(data: &[u8]) -> bool {
+    data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP")
+}
+
+/// Values of [`MetadataFields`] rendered for one image, without the empty ones
+#[derive(Debug, Default)]
+pub(crate) struct RenderedFields {
+    artist: Option<String>,
+    copyright: Option<String>,
+    description: Option<String>,
+    /// XMP properties with their prefix, the custom ones override the base fields
+    xmp: Vec<(String, String)>,
+    /// Datasets of the IPTC application record
+    iptc: Vec<(u8, String)>,
+}
+
+impl RenderedFields {
+    pub(crate) fn render(
+        fields: &MetadataFields,
+        context: &TemplateContext,
+    ) -> Result<Self, MetadataError> {
+        let render = |template: &str| -> Result<Option<String>, MetadataError> {
+            let value = TextTemplate::parse(template)?.render(context)?;
+            Ok(Some(value).filter(|v| !v.is_empty()))
+        };
+        let artist = render(&fields.artist)?;
+        let copyright = render(&fields.copyright)?;
+        let description = render(&fields.description)?;
+
+        let base = [
+            ("dc:creator", IPTC_BY_LINE, &artist),
+            ("dc:rights", IPTC_COPYRIGHT_NOTICE, &copyright),
+            ("dc:description", IPTC_CAPTION, &description),
+        ];
+        let mut xmp = Vec::new();
+        let mut iptc = Vec::new();
+        for (property, dataset, value) in base {
+            if let Some(value) = value {
+                xmp.push((property.to_string(), value.clone()));
+                iptc.push((dataset, value.clone()));
+            }
+        }
+        for custom in &fields.custom {
+            let Some(value) = render(&custom.value)? else {
+                continue;
+            };
+            match custom.kind {
+                MetadataFieldKind::Xmp => set(&mut xmp, custom.name.clone(), value),
+                MetadataFieldKind::Iptc => {
+                    if let Some(dataset) = iptc_dataset(&custom.name) {
+                        set(&mut iptc, dataset, value);
+                    }
+                }
+            }
+        }
+        // Readers expect the datasets in ascending order
+        iptc.sort_by_key(|(dataset, _)| *dataset);
+
+        Ok(Self {
+            artist,
+            copyright,
+            description,
+            xmp,
+            iptc,
+        })
+    }
+
+    // The base fields are always in the XMP and IPTC ones too
+    fn is_empty(&self) -> bool {
+        self.xmp.is_empty() && self.iptc.is_empty()
+    }
+}
+
+/// Metadata blocks of an original, see [`carry_over`]
+#[derive(Default)]
+struct CarriedMetadata {
+    /// TIFF structure, without the JPEG header
+    exif: Option<Vec<u8>>,
+    xmp: Option<Vec<u8>>,
+    icc: Option<Vec<u8>>,
+    /// Photoshop resources holding the IPTC, without the JPEG header
+    photoshop: Option<Vec<u8>>,
+    comments: Vec<Vec<u8>>,
+}
+
+impl CarriedMetadata {
+    // The ICC profile of PNG files is compressed and is not read
+    fn read(original: &[u8]) -> Result<Self, MetadataError> {
+        if original.starts_with(&[0xFF, 0xD8]) {
+            let (segments, _) = jpeg_segments(original)?;
+            let mut icc_chunks: Vec<&[u8]> = segments
+                .iter()
+                .filter(|s| s.is_icc())
+                .filter_map(|s| s.payload.get(ICC_HEADER.len()..).filter(|c| c.len() > 2))
+                .collect();
+            // Sequence number, count and part of the profile
+            icc_chunks.sort_by_key(|chunk| chunk[0]);
+            Ok(Self {
+                exif: segments
+                    .iter()
+                    .find(|s| s.is_exif())
+                    .map(|s| s.payload[EXIF_HEADER.len()..].to_vec()),
+                xmp: segments
+                    .iter()
+                    .filter(|s| s.marker == APP1)
+                    .find_map(|s| s.payload.strip_prefix(XMP_HEADER))
+                    .map(<[u8]>::to_vec),
+                icc: (!icc_chunks.is_empty())
+                    .then(|| icc_chunks.iter().flat_map(|c| &c[2..]).copied().collect()),
+                photoshop: segments
+                    .iter()
+                    .find(|s| s.is_photoshop())
+                    .map(|s| s.payload[PHOTOSHOP_HEADER.len()..].to_vec()),
+                comments: segments
+                    .iter()
+                    .filter(|s| s.marker == COM)
+                    .map(|s| s.payload.to_vec())
+                    .collect(),
+            })
+        } else if original.starts_with(PNG_SIGNATURE) {
+            let chunks = png_chunks(original)?;
+            Ok(Self {
+                exif: chunks
+                    .iter()
+                    .find(|c| c.kind == b"eXIf")
+                    .map(|c| c.data.to_vec()),
+                xmp: chunks
+                    .iter()
+                    .filter(|c| c.kind == b"iTXt" && c.text_keyword() == Some(PNG_XMP_KEYWORD))
+                    .find_map(|c| itxt_text(c.data))
+                    .map(<[u8]>::to_vec),
+                ..Self::default()
+            })
+        } else if is_webp(original) {
+            let chunks = webp_chunks(original)?;
+            let find = |fourcc: &[u8; 4]| {
+                chunks
+                    .iter()
+                    .find(|(f, _)| f == fourcc)
+                    .map(|(_, chunk_data)| chunk_data.as_slice())
+            };
+            Ok(Self {
+                exif: find(b"EXIF").map(|e| e.strip_prefix(EXIF_HEADER).unwrap_or(e).to_vec()),
+                xmp: find(b"XMP ").map(<[u8]>::to_vec),
+                icc: find(b"ICCP").map(<[u8]>::to_vec),
+                ..Self::default()
+            })
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Same metadata with the orientation set to 1
+    fn upright(self) -> Self {
+        let orientation = Field {
+            tag: Tag::Orientation,
+            ifd_num: In::PRIMARY,
+            value: Value::Short(vec![1]),
+        };
+        Self {
+            exif: self
+                .exif
+                .and_then(|tiff| rewrite_exif(Some(&tiff), &[orientation])),
+            xmp: self.xmp.map(upright_xmp),
+            ..self
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.exif.is_none()
+            && self.xmp.is_none()
+            && self.icc.is_none()
+            && self.photoshop.is_none()
+            && self.comments.is_empty()
+    }
+}
+
+/// Replaces the value of `key`, or appends it
+fn set<K: PartialEq, V>(entries: &mut Vec<(K, V)>, key: K, value: V) {
+    match entries.iter_mut().find(|(k, _)| *k == key) {
+        Some(entry) => entry.1 = value,
+        None => entries.push((key, value)),
+    }
+}
+
+enum Block {
+    Keep,
+    Drop,
+    Replace(Vec<u8>),
+}
+
+fn keep_if(kept: bool) -> Block {
+    if kept {
+        Block::Keep
+    } else {
+        Block::Drop
+    }
+}
+
+/// Segment of a JPEG file before the scan
+struct Segment<'a> {
+    marker: u8,
+    payload: &'a [u8],
+}
+
+impl Segment<'_> {
+    fn is_exif(&self) -> bool {
+        self.marker == APP1 && self.payload.starts_with(EXIF_HEADER)
+    }
+
+    fn is_xmp(&self) -> bool {
+        self.marker == APP1
+            && (self.payload.starts_with(XMP_HEADER)
+                || self.payload.starts_with(XMP_EXTENSION_HEADER))
+    }
+
+    /// Photoshop resources, where IPTC is stored
+    fn is_photoshop(&self) -> bool {
+        self.marker == APP13 && self.payload.starts_with(PHOTOSHOP_HEADER)
+    }
+
+    /// Part of the ICC profile
+    fn is_icc(&self) -> bool {
+        self.marker == APP2 && self.payload.starts_with(ICC_HEADER)
+    }
+}
+
+/// Splits a JPEG file into the segments before the scan and the rest of the file, which
+/// cannot hold metadata. Markers without a length have no place there and are dropped.
+fn jpeg_segments(data: &[u8]) -> Result<(Vec<Segment<'_>>, &[u8]), MetadataError> {
+    let malformed = || MetadataError::Malformed("JPEG");
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    loop {
+        let (&prefix, &marker) = data.get(pos).zip(data.get(pos + 1)).ok_or_else(malformed)?;
+        if prefix != 0xFF {
+            return Err(malformed());
+        }
+        match marker {
+            // Fill byte
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            0x01 | 0xD0..=0xD8 => {
+                pos += 2;
+                continue;
+            }
+            SOS | EOI => return Ok((segments, &data[pos..])),
+            _ => {}
+        }
+
+        let length = data
+            .get(pos + 2..pos + 4)
+            .map(|l| u16::from_be_bytes([l[0], l[1]]) as usize)
+            .filter(|l| *l >= 2)
+            .ok_or_else(malformed)?;
+        let payload = data.get(pos + 4..pos + 2 + length).ok_or_else(malformed)?;
+        segments.push(Segment { marker, payload });
+        pos += 2 + length;
+    }
+}
+
+fn push_segment(output: &mut Vec<u8>, marker: u8, parts: &[&[u8]]) -> Result<(), MetadataError> {
+    let size: usize = parts.iter().map(|p| p.len()).sum();
+    let length = u16::try_from(size + 2).map_err(|_| MetadataError::SegmentTooLarge)?;
+    output.extend_from_slice(&[0xFF, marker]);
+    output.extend_from_slice(&length.to_be_bytes());
+    for part in parts {
+        output.extend_from_slice(part);
+    }
+    Ok(())
+}
+
+fn filter_jpeg(data: &[u8], kept: &MetadataOptions) -> Result<Vec<u8>, MetadataError> {
+    let (segments, scan) = jpeg_segments(data)?;
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&data[..2]);
+    for segment in &segments {
+        let block = if segment.is_exif() {
+            match filter_exif(&segment.payload[EXIF_HEADER.len()..], kept) {
+                Block::Replace(exif) => Block::Replace([EXIF_HEADER, &exif].concat()),
+                block => block,
+            }
+        } else if segment.is_xmp() {
+            keep_if(kept.xmp)
+        } else if segment.is_icc() {
+            keep_if(kept.icc)
+        } else if segment.is_photoshop() {
+            keep_if(kept.iptc)
+        } else if segment.marker == COM {
+            keep_if(kept.comments)
+        } else {
+            Block::Keep
+        };
+        match block {
+            Block::Keep => push_segment(&mut output, segment.marker, &[segment.payload])?,
+            Block::Drop => {}
+            Block::Replace(payload) => push_segment(&mut output, segment.marker, &[&payload])?,
+        }
+    }
+    output.extend_from_slice(scan);
+    Ok(output)
+}
+
+// EXIF, XMP and the IPTC resource go right after the JFIF header, replacing the old ones
+fn write_jpeg(data: &[u8], fields: &RenderedFields) -> Result<Vec<u8>, MetadataError> {
+    let (segments, scan) = jpeg_segments(data)?;
+    let existing_exif = segments
+        .iter()
+        .find(|s| s.is_exif())
+        .map(|s| &s.payload[EXIF_HEADER.len()..]);
+    let existing_photoshop = segments
+        .iter()
+        .find(|s| s.is_photoshop())
+        .map(|s| &s.payload[PHOTOSHOP_HEADER.len()..]);
+    let exif = merge_exif(existing_exif, fields);
+    let xmp = xmp_packet(&fields.xmp);
+    let photoshop =
+        iptc_records(&fields.iptc).map(|iptc| photoshop_resources(existing_photoshop, &iptc));
+    let is_replaced = |s: &Segment| {
+        (exif.is_some() && s.is_exif())
+            || (xmp.is_some() && s.is_xmp())
+            || (photoshop.is_some() && s.is_photoshop())
+    };
+
+    let mut blocks = Vec::new();
+    if let Some(exif) = &exif {
+        blocks.push((APP1, [EXIF_HEADER, exif].concat()));
+    }
+    if let Some(xmp) = &xmp {
+        blocks.push((APP1, [XMP_HEADER, xmp].concat()));
+    }
+    if let Some(photoshop) = &photoshop {
+        blocks.push((APP13, [PHOTOSHOP_HEADER, photoshop].concat()));
+    }
+    splice_jpeg(&segments, scan, &blocks, is_replaced)
+}
+
+// The ICC profile is split into numbered segments
+fn carry_to_jpeg(data: &[u8], carried: &CarriedMetadata) -> Result<Vec<u8>, MetadataError> {
+    let (segments, scan) = jpeg_segments(data)?;
+    let is_replaced = |s: &Segment| {
+        (carried.exif.is_some() && s.is_exif())
+            || (carried.xmp.is_some() && s.is_xmp())
+            || (carried.icc.is_some() && s.is_icc())
+            || (carried.photoshop.is_some() && s.is_photoshop())
+            || (!carried.comments.is_empty() && s.marker == COM)
+    };
+
+    let mut blocks = Vec::new();
+    if let Some(exif) = &carried.exif {
+        blocks.push((APP1, [EXIF_HEADER, exif].concat()));
+    }
+    if let Some(xmp) = &carried.xmp {
+        blocks.push((APP1, [XMP_HEADER, xmp].concat()));
+    }
+    if let Some(icc) = &carried.icc {
+        let parts = icc.chunks(ICC_MAX_CHUNK);
+        let count = u8::try_from(parts.len()).map_err(|_| MetadataError::SegmentTooLarge)?;
+        for (sequence, part) in (1..=count).zip(parts) {
+            blocks.push((APP2, [ICC_HEADER, &[sequence, count], part].concat()));
+        }
+    }
+    if let Some(photoshop) = &carried.photoshop {
+        blocks.push((APP13, [PHOTOSHOP_HEADER, photoshop].concat()));
+    }
+    for comment in &carried.comments {
+        blocks.push((COM, comment.clone()));
+    }
+    splice_jpeg(&segments, scan, &blocks, is_replaced)
+}
+
+/// JPEG file with the new `blocks` right after the JFIF header, and without the segments
+/// they replace
+fn splice_jpeg(
+    segments: &[Segment],
+    scan: &[u8],
+    blocks: &[(u8, Vec<u8>)],
+    is_replaced: impl Fn(&Segment) -> bool,
+) -> Result<Vec<u8>, MetadataError> {
+    let mut output = vec![0xFF, 0xD8];
+    for segment in segments.iter().filter(|s| s.marker == APP0) {
+        push_segment(&mut output, segment.marker, &[segment.payload])?;
+    }
+    for (marker, payload) in blocks {
+        push_segment(&mut output, *marker, &[payload])?;
+    }
+    for segment in segments
+        .iter()
+        .filter(|s| s.marker != APP0 && !is_replaced(s))
+    {
+        push_segment(&mut output, segment.marker, &[segment.payload])?;
+    }
+    output.extend_from_slice(scan);
+    Ok(output)
+}
+
+struct Chunk<'a> {
+    kind: &'a [u8],
+    data: &'a [u8],
+    /// Whole chunk, with its length and CRC
+    raw: &'a [u8],
+}
+
+impl Chunk<'_> {
+    fn text_keyword(&self) -> Option<&[u8]> {
+        matches!(self.kind, b"tEXt" | b"zTXt" | b"iTXt")
+            .then(|| self.data.split(|b| *b == 0).next().unwrap_or_default())
+    }
+}
+
+fn png_chunks(data: &[u8]) -> Result<Vec<Chunk<'_>>, MetadataError> {
+    let malformed = || MetadataError::Malformed("PNG");
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < data.len() {
+        let length = data
+            .get(pos..pos + 4)
+            .map(|l| u32::from_be_bytes([l[0], l[1], l[2], l[3]]) as usize)
+            .ok_or_else(malformed)?;
+        // Length, type, data and CRC
+        let raw = data.get(pos..pos + 12 + length).ok_or_else(malformed)?;
+        chunks.push(Chunk {
+            kind: &raw[4..8],
+            data: &raw[8..8 + length],
+            raw,
+        });
+        pos += raw.len();
+    }
+    Ok(chunks)
+}
+
+fn push_png_chunk(output: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+    output.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Uncompressed iTXt chunk data, without a language
+fn png_itxt(keyword: &[u8], text: &[u8]) -> Vec<u8> {
+    [keyword, b"\0\0\0\0\0", text].concat()
+}
+
+/// Text of an uncompressed iTXt chunk
+fn itxt_text(data: &[u8]) -> Option<&[u8]> {
+    let keyword_end = data.iter().position(|b| *b == 0)?;
+    if data.get(keyword_end + 1) != Some(&0) {
+        return None;
+    }
+    // Skips the compression method, then the language and the translated keyword
+    let mut text = data.get(keyword_end + 3..)?;
+    for _ in 0..2 {
+        let end = text.iter().position(|b| *b == 0)?;
+        text = &text[end + 1..];
+    }
+    Some(text)
+}
+
+/// iCCP chunk data. The profile has to be compressed, it is stored in a zlib stream made
+/// of uncompressed blocks.
+fn png_iccp(icc: &[u8]) -> Vec<u8> {
+    let mut chunk_data = PNG_ICC_HEADER.to_vec();
+    chunk_data.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = icc.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        let length = block.len() as u16;
+        chunk_data.push(u8::from(blocks.peek().is_none()));
+        chunk_data.extend_from_slice(&length.to_le_bytes());
+        chunk_data.extend_from_slice(&(!length).to_le_bytes());
+        chunk_data.extend_from_slice(block);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in icc {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    chunk_data.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    chunk_data
+}
+
+fn filter_png(data: &[u8], kept: &MetadataOptions) -> Result<Vec<u8>, MetadataError> {
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(PNG_SIGNATURE);
+    for chunk in png_chunks(data)? {
+        let block = match (chunk.kind, chunk.text_keyword()) {
+            (b"eXIf", _) => filter_exif(chunk.data, kept),
+            (b"iCCP", _) => keep_if(kept.icc),
+            (_, Some(keyword)) => match keyword {
+                PNG_XMP_KEYWORD | b"Raw profile type xmp" => keep_if(kept.xmp),
+                // Written by ImageMagick, the EXIF in them is not filtered
+                b"Raw profile type exif" | b"Raw profile type APP1" => {
+                    keep_if(kept.keeps_all_exif())
+                }
+                b"Raw profile type iptc" => keep_if(kept.iptc),
+                b"Raw profile type icc" | b"Raw profile type icm" => keep_if(kept.icc),
+                _ => keep_if(kept.comments),
+            },
+            _ => Block::Keep,
+        };
+        match block {
+            Block::Keep => output.extend_from_slice(chunk.raw),
+            Block::Drop => {}
+            Block::Replace(chunk_data) => push_png_chunk(&mut output, chunk.kind, &chunk_data),
+        }
+    }
+    Ok(output)
+}
+
+// The new chunks go right after the header, the text chunks with the same keywords are
+// replaced
+fn write_png(data: &[u8], fields: &RenderedFields) -> Result<Vec<u8>, MetadataError> {
+    let chunks = png_chunks(data)?;
+    let (header, chunks) = chunks
+        .split_first()
+        .filter(|(header, _)| header.kind == b"IHDR")
+        .ok_or(MetadataError::Malformed("PNG"))?;
+    let existing_exif = chunks.iter().find(|c| c.kind == b"eXIf").map(|c| c.data);
+    let exif = merge_exif(existing_exif, fields);
+    let xmp = xmp_packet(&fields.xmp);
+    let texts: Vec<(&[u8], &String)> = [
+        (b"Author".as_slice(), &fields.artist),
+        (b"Copyright", &fields.copyright),
+        (b"Description", &fields.description),
+    ]
+    .into_iter()
+    .filter_map(|(keyword, value)| Some((keyword, value.as_ref()?)))
+    .collect();
+    let is_replaced = |chunk: &Chunk| match chunk.text_keyword() {
+        Some(keyword) => {
+            (xmp.is_some() && keyword == PNG_XMP_KEYWORD)
+                || texts.iter().any(|(k, _)| *k == keyword)
+        }
+        None => exif.is_some() && chunk.kind == b"eXIf",
+    };
+
+    let mut blocks = Vec::new();
+    if let Some(exif) = &exif {
+        blocks.push((b"eXIf", exif.clone()));
+    }
+    if let Some(xmp) = &xmp {
+        blocks.push((b"iTXt", png_itxt(PNG_XMP_KEYWORD, xmp)));
+    }
+    for (keyword, value) in &texts {
+        blocks.push((b"iTXt", png_itxt(keyword, value.as_bytes())));
+    }
+    Ok(splice_png(header, chunks, &blocks, is_replaced))
+}
+
+// The ICC profile replaces the sRGB chunk, which cannot be there at the same time
+fn carry_to_png(data: &[u8], carried: &CarriedMetadata) -> Result<Vec<u8>, MetadataError> {
+    let chunks = png_chunks(data)?;
+    let (header, chunks) = chunks
+        .split_first()
+        .filter(|(header, _)| header.kind == b"IHDR")
+        .ok_or(MetadataError::Malformed("PNG"))?;
+    let is_replaced = |chunk: &Chunk| match chunk.kind {
+        b"eXIf" => carried.exif.is_some(),
+        b"iCCP" | b"sRGB" => carried.icc.is_some(),
+        _ => carried.xmp.is_some() && chunk.text_keyword() == Some(PNG_XMP_KEYWORD),
+    };
+
+    let mut blocks = Vec::new();
+    if let Some(icc) = &carried.icc {
+        blocks.push((b"iCCP", png_iccp(icc)));
+    }
+    if let Some(exif) = &carried.exif {
+        blocks.push((b"eXIf", exif.clone()));
+    }
+    if let Some(xmp) = &carried.xmp {
+        blocks.push((b"iTXt", png_itxt(PNG_XMP_KEYWORD, xmp)));
+    }
+    Ok(splice_png(header, chunks, &blocks, is_replaced))
+}
+
+/// PNG file with the new `blocks` right after the header, and without the chunks they
+/// replace
+fn splice_png(
+    header: &Chunk,
+    chunks: &[Chunk],
+    blocks: &[(&[u8; 4], Vec<u8>)],
+    is_replaced: impl Fn(&Chunk) -> bool,
+) -> Vec<u8> {
+    let mut output = Vec::new();
+    output.extend_from_slice(PNG_SIGNATURE);
+    output.extend_from_slice(header.raw);
+    for (kind, chunk_data) in blocks {
+        push_png_chunk(&mut output, *kind, chunk_data);
+    }
+    for chunk in chunks.iter().filter(|c| !is_replaced(c)) {
+        output.extend_from_slice(chunk.raw);
+    }
+    output
+}
+
+/// FourCC and data of a WebP chunk
+type WebpChunk = ([u8; 4], Vec<u8>);
+
+fn webp_chunks(data: &[u8]) -> Result<Vec<WebpChunk>, MetadataError> {
+    let malformed = || MetadataError::Malformed("WebP");
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos < data.len() {
+        let header = data.get(pos..pos + 8).ok_or_else(malformed)?;
+        let fourcc = [header[0], header[1], header[2], header[3]];
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let chunk_data = data.get(pos + 8..pos + 8 + size).ok_or_else(malformed)?;
+        chunks.push((fourcc, chunk_data.to_vec()));
+        // Chunks are padded to an even size
+        pos += 8 + size + (size & 1);
+    }
+    Ok(chunks)
+}
+
+// The VP8X flags and the RIFF size are updated to match the chunks
+fn webp_file(mut chunks: Vec<WebpChunk>) -> Result<Vec<u8>, MetadataError> {
+    let has_chunk = |fourcc: &[u8; 4]| chunks.iter().any(|(f, _)| f == fourcc);
+    let mut flags = 0;
+    for (fourcc, flag) in [
+        (b"ICCP", WEBP_ICC),
+        (b"EXIF", WEBP_EXIF),
+        (b"XMP ", WEBP_XMP),
+    ] {
+        if has_chunk(fourcc) {
+            flags |= flag;
+        }
+    }
+    if let Some((_, vp8x)) = chunks.iter_mut().find(|(f, _)| f == b"VP8X") {
+        let first = vp8x.first_mut().ok_or(MetadataError::Malformed("WebP"))?;
+        *first = (*first & !(WEBP_ICC | WEBP_EXIF | WEBP_XMP)) | flags;
+    }
+
+    let mut output = Vec::new();
+    output.extend_from_slice(b"RIFF\0\0\0\0WEBP");
+    for (fourcc, chunk_data) in &chunks {
+        output.extend_from_slice(fourcc);
+        output.extend_from_slice(&(chunk_data.len() as u32).to_le_bytes());
+        output.extend_from_slice(chunk_data);
+        if chunk_data.len() % 2 == 1 {
+            output.push(0);
+        }
+    }
+    let riff_size = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(output)
+}
+
+fn filter_webp(data: &[u8], kept: &MetadataOptions) -> Result<Vec<u8>, MetadataError> {
+    let mut chunks = Vec::new();
+    for (fourcc, chunk_data) in webp_chunks(data)? {
+        let block = match &fourcc {
+            // Some encoders keep the JPEG header in front of the EXIF data
+            b"EXIF" => match chunk_data.strip_prefix(EXIF_HEADER) {
+                Some(exif) => match filter_exif(exif, kept) {
+                    Block::Replace(exif) => Block::Replace([EXIF_HEADER, &exif].concat()),
+                    block => block,
+                },
+                None => filter_exif(&chunk_data, kept),
+            },
+            b"XMP " => keep_if(kept.xmp),
+            b"ICCP" => keep_if(kept.icc),
+            _ => Block::Keep,
+        };
+        match block {
+            Block::Keep => chunks.push((fourcc, chunk_data)),
+            Block::Drop => {}
+            Block::Replace(chunk_data) => chunks.push((fourcc, chunk_data)),
+        }
+    }
+    webp_file(chunks)
+}
+
+// Metadata chunks go at the end of the file, which is converted to the extended format
+// when it is a simple one
+fn write_webp(data: &[u8], fields: &RenderedFields) -> Result<Vec<u8>, MetadataError> {
+    let mut chunks = webp_chunks(data)?;
+    let existing_exif = chunks
+        .iter()
+        .find(|(f, _)| f == b"EXIF")
+        .map(|(_, exif)| exif.strip_prefix(EXIF_HEADER).unwrap_or(exif));
+    let exif = merge_exif(existing_exif, fields);
+    let xmp = xmp_packet(&fields.xmp);
+    if exif.is_none() && xmp.is_none() {
+        return Ok(data.to_vec());
+    }
+
+    extend_webp(data, &mut chunks)?;
+    for (fourcc, chunk_data) in [(*b"EXIF", exif), (*b"XMP ", xmp)] {
+        if let Some(chunk_data) = chunk_data {
+            chunks.retain(|(f, _)| *f != fourcc);
+            chunks.push((fourcc, chunk_data));
+        }
+    }
+    webp_file(chunks)
+}
+
+// The ICC profile must come right after the VP8X chunk, the rest goes at the end
+fn carry_to_webp(data: &[u8], carried: &CarriedMetadata) -> Result<Vec<u8>, MetadataError> {
+    let mut chunks = webp_chunks(data)?;
+    extend_webp(data, &mut chunks)?;
+    if let Some(icc) = &carried.icc {
+        chunks.retain(|(f, _)| f != b"ICCP");
+        let position = chunks.iter().position(|(f, _)| f == b"VP8X").unwrap_or(0);
+        chunks.insert(position + 1, (*b"ICCP", icc.clone()));
+    }
+    for (fourcc, chunk_data) in [(*b"EXIF", &carried.exif), (*b"XMP ", &carried.xmp)] {
+        if let Some(chunk_data) = chunk_data {
+            chunks.retain(|(f, _)| *f != fourcc);
+            chunks.push((fourcc, chunk_data.clone()));
+        }
+    }
+    webp_file(chunks)
+}
+
+/// Converts a simple WebP file to the extended format, which can hold metadata
+fn extend_webp(data: &[u8], chunks: &mut Vec<WebpChunk>) -> Result<(), MetadataError> {
+    if !chunks.iter().any(|(f, _)| f == b"VP8X") {
+        let header = webp_extended_header(data, chunks)?;
+        chunks.insert(0, (*b"VP8X", header));
+    }
+    Ok(())
+}
+
+/// VP8X chunk data for a simple WebP file
+fn webp_extended_header(data: &[u8], chunks: &[WebpChunk]) -> Result<Vec<u8>, MetadataError> {
+    let size = imagesize::blob_size(data).map_err(|_| MetadataError::Malformed("WebP"))?;
+    // Only lossless images can have alpha without the extended format
+    let has_alpha = chunks
+        .iter()
+        .find(|(f, _)| f == b"VP8L")
+        .and_then(|(_, vp8l)| vp8l.get(1..5))
+        .is_some_and(|h| u32::from_le_bytes([h[0], h[1], h[2], h[3]]) & (1 << 28) != 0);
+
+    let mut header = vec![if has_alpha { WEBP_ALPHA } else { 0 }, 0, 0, 0];
+    for side in [size.width, size.height] {
+        let side = (side.saturating_sub(1) as u32).to_le_bytes();
+        header.extend_from_slice(&side[..3]);
+    }
+    Ok(header)
+}
+
+// Appends a new first IFD, made of the entries of the old one with the fields replaced,
+// and points the header to it. The old IFD is left in the file, unreferenced.
+fn write_tiff(data: &[u8], fields: &RenderedFields) -> Result<Vec<u8>, MetadataError> {
+    let malformed = || MetadataError::Malformed("TIFF");
+    let little_endian = data.starts_with(b"II");
+    let read_u16 = |pos: usize| {
+        let b = data.get(pos..pos + 2)?;
+        Some(if little_endian {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    };
+    let u16_bytes = |v: u16| {
+        if little_endian {
+            v.to_le_bytes()
+        } else {
+            v.to_be_bytes()
+        }
+    };
+    let u32_bytes = |v: u32| {
+        if little_endian {
+            v.to_le_bytes()
+        } else {
+            v.to_be_bytes()
+        }
+    };
+
+    let ifd = data
+        .get(4..8)
+        .map(|b| {
+            let b = [b[0], b[1], b[2], b[3]];
+            if little_endian {
+                u32::from_le_bytes(b)
+            } else {
+                u32::from_be_bytes(b)
+            }
+        })
+        .ok_or_else(malformed)? as usize;
+    let count = read_u16(ifd).ok_or_else(malformed)? as usize;
+    let entries_end = ifd + 2 + count * 12;
+    let next_ifd = data
+        .get(entries_end..entries_end + 4)
+        .ok_or_else(malformed)?;
+
+    let ascii = |value: &Option<String>| value.as_ref().map(|v| [v.as_bytes(), b"\0"].concat());
+    let values = [
+        (
+            TIFF_IMAGE_DESCRIPTION,
+            TIFF_ASCII,
+            ascii(&fields.description),
+        ),
+        (TIFF_ARTIST, TIFF_ASCII, ascii(&fields.artist)),
+        (TIFF_XMP, TIFF_BYTE, xmp_packet(&fields.xmp)),
+        (TIFF_COPYRIGHT, TIFF_ASCII, ascii(&fields.copyright)),
+        (TIFF_IPTC, TIFF_UNDEFINED, iptc_records(&fields.iptc)),
+    ];
+    let mut entries: Vec<(u16, Vec<u8>)> = (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .map(|pos| {
+            (
+                read_u16(pos).unwrap_or_default(),
+                data[pos..pos + 12].to_vec(),
+            )
+        })
+        .filter(|(tag, _)| !values.iter().any(|(t, _, v)| t == tag && v.is_some()))
+        .collect();
+
+    let mut output = data.to_vec();
+    let offset =
+        |output: &Vec<u8>| u32::try_from(output.len()).map_err(|_| MetadataError::TiffTooLarge);
+    for (tag, kind, value) in values {
+        let Some(value) = value else {
+            continue;
+        };
+        let length = u32::try_from(value.len()).map_err(|_| MetadataError::TiffTooLarge)?;
+        let mut entry = Vec::with_capacity(12);
+        entry.extend_from_slice(&u16_bytes(tag));
+        entry.extend_from_slice(&u16_bytes(kind));
+        entry.extend_from_slice(&u32_bytes(length));
+        if value.len() <= 4 {
+            entry.extend_from_slice(&value);
+            entry.resize(12, 0);
+        } else {
+            // Values start on a word boundary
+            if output.len() % 2 == 1 {
+                output.push(0);
+            }
+            entry.extend_from_slice(&u32_bytes(offset(&output)?));
+            output.extend_from_slice(&value);
+        }
+        entries.push((tag, entry));
+    }
+    entries.sort_by_key(|(tag, _)| *tag);
+
+    if output.len() % 2 == 1 {
+        output.push(0);
+    }
+    let new_ifd = offset(&output)?;
+    let count = u16::try_from(entries.len()).map_err(|_| malformed())?;
+    output.extend_from_slice(&u16_bytes(count));
+    for (_, entry) in &entries {
+        output.extend_from_slice(entry);
+    }
+    output.extend_from_slice(next_ifd);
+    output[4..8].copy_from_slice(&u32_bytes(new_ifd));
+    Ok(output)
+}
+
+/// EXIF block with the artist, copyright and description replaced, keeping the rest of
+/// `existing` when it can be read. None when there is nothing to write or the block
+/// cannot be built, in which case the old one is left alone.
+fn merge_exif(existing: Option<&[u8]>, fields: &RenderedFields) -> Option<Vec<u8>> {
+    let new_fields: Vec<Field> = [
+        (Tag::Artist, &fields.artist),
+        (Tag::Copyright, &fields.copyright),
+        (Tag::ImageDescription, &fields.description),
+    ]
+    .into_iter()
+    .filter_map(|(tag, value)| {
+        Some(Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_ref()?.as_bytes().to_vec()]),
+        })
+    })
+    .collect();
+    if new_fields.is_empty() {
+        return None;
+    }
+    rewrite_exif(existing, &new_fields)
+}
+
+/// EXIF block with `new_fields` in place of the ones with the same tags, keeping the rest
+/// of `existing` when it can be read. None when the block cannot be built.
+fn rewrite_exif(existing: Option<&[u8]>, new_fields: &[Field]) -> Option<Vec<u8>> {
+    let exif = existing.and_then(|tiff| match exif::Reader::new().read_raw(tiff.to_vec()) {
+        Ok(exif) => Some(exif),
+        Err(e) => {
+            log::warn!("Replacing unreadable EXIF metadata: {e}");
+            None
+        }
+    });
+
+    let mut writer = Writer::new();
+    let mut little_endian = false;
+    if let Some(exif) = &exif {
+        little_endian = exif.little_endian();
+        let thumbnail = thumbnail(exif);
+        for field in exif.fields() {
+            let is_replaced = new_fields
+                .iter()
+                .any(|f| f.tag == field.tag && f.ifd_num == field.ifd_num);
+            if is_field_kept(field, &MetadataOptions::default(), thumbnail.is_some())
+                && !is_replaced
+            {
+                writer.push_field(field);
+            }
+        }
+        if let Some(thumbnail) = thumbnail {
+            writer.set_jpeg(thumbnail, In::THUMBNAIL);
+        }
+    }
+    for field in new_fields {
+        writer.push_field(field);
+    }
+    let mut output = Cursor::new(Vec::new());
+    match writer.write(&mut output, little_endian) {
+        Ok(()) => Some(output.into_inner()),
+        Err(e) => {
+            log::warn!("Cannot write the EXIF metadata: {e}");
+            None
+        }
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// XMP packet holding only `properties`. Keywords in `dc:subject` are comma separated.
+fn xmp_packet(properties: &[(String, String)]) -> Option<Vec<u8>> {
+    if properties.is_empty() {
+        return None;
+    }
+    let mut prefixes: Vec<&str> = properties
+        .iter()
+        .filter_map(|(name, _)| name.split_once(':').map(|(prefix, _)| prefix))
+        .collect();
+    prefixes.sort_unstable();
+    prefixes.dedup();
+
+    let mut xml = String::from(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         <rdf:Description rdf:about=\"\"",
+    );
+    for prefix in prefixes {
+        let uri = xmp_namespace(prefix).unwrap_or_default();
+        let _ = write!(xml, "\n xmlns:{prefix}=\"{uri}\"");
+    }
+    xml.push_str(">\n");
+    for (name, value) in properties {
+        let escaped = xml_escape(value);
+        let _ = match name.as_str() {
+            "dc:creator" => writeln!(
+                xml,
+                "<{name}><rdf:Seq><rdf:li>{escaped}</rdf:li></rdf:Seq></{name}>"
+            ),
+            "dc:title" | "dc:rights" | "dc:description" | "xmpRights:UsageTerms" => writeln!(
+                xml,
+                "<{name}><rdf:Alt><rdf:li xml:lang=\"x-default\">{escaped}</rdf:li></rdf:Alt></{name}>"
+            ),
+            "dc:subject" => {
+                let keywords: String = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(|k| format!("<rdf:li>{}</rdf:li>", xml_escape(k)))
+                    .collect();
+                writeln!(xml, "<{name}><rdf:Bag>{keywords}</rdf:Bag></{name}>")
+            }
+            _ => writeln!(xml, "<{name}>{escaped}</{name}>"),
+        };
+    }
+    xml.push_str("</rdf:Description>\n</rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>");
+    Some(xml.into_bytes())
+}
+
+/// IPTC records holding only `datasets`, in UTF-8. Keywords are comma separated.
+/// XMP packet with the orientation set to 1, written as an attribute or as an element
+fn upright_xmp(xmp: Vec<u8>) -> Vec<u8> {
+    let mut packet = match String::from_utf8(xmp) {
+        Ok(packet) => packet,
+        Err(e) => return e.into_bytes(),
+    };
+    for orientation in 2..=8 {
+        for quote in ['"', '\''] {
+            packet = packet.replace(
+                &format!("tiff:Orientation={quote}{orientation}{quote}"),
+                &format!("tiff:Orientation={quote}1{quote}"),
+            );
+        }
+        packet = packet.replace(
+            &format!("<tiff:Orientation>{orientation}<"),
+            "<tiff:Orientation>1<",
+        );
+    }
+    packet.into_bytes()
+}
+
+fn iptc_records(datasets: &[(u8, String)]) -> Option<Vec<u8>> {
+    if datasets.is_empty() {
+        return None;
+    }
+    let mut records = Vec::new();
+    // Coded character set, then the version of the application record
+    push_dataset(&mut records, 1, 90, b"\x1b%G");
+    push_dataset(&mut records, 2, 0, &[0, 4]);
+    for (dataset, value) in datasets {
+        if *dataset == IPTC_KEYWORDS {
+            for keyword in value.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                push_dataset(&mut records, 2, *dataset, truncate(keyword).as_bytes());
+            }
+        } else {
+            push_dataset(&mut records, 2, *dataset, truncate(value).as_bytes());
+        }
+    }
+    Some(records)
+}
+
+fn push_dataset(records: &mut Vec<u8>, record: u8, dataset: u8, data: &[u8]) {
+    records.extend_from_slice(&[0x1C, record, dataset]);
+    records.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    records.extend_from_slice(data);
+}
+
+fn truncate(value: &str) -> &str {
+    let mut end = value.len().min(IPTC_MAX_LENGTH);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// Photoshop image resources of `existing`, with the IPTC one replaced by `iptc`.
+/// Anything after a resource that cannot be read is dropped.
+fn photoshop_resources(existing: Option<&[u8]>, iptc: &[u8]) -> Vec<u8> {
+    let existing = existing.unwrap_or_default();
+    let mut output = Vec::new();
+    let mut pos = 0;
+    while let Some(header) = existing
+        .get(pos..pos + 7)
+        .filter(|h| h.starts_with(b"8BIM"))
+    {
+        let id = u16::from_be_bytes([header[4], header[5]]);
+        // Pascal string padded to an even size
+        let name_size = (header[6] as usize + 2) & !1;
+        let data_start = pos + 10 + name_size;
+        let Some(size) = existing
+            .get(data_start - 4..data_start)
+            .map(|s| u32::from_be_bytes([s[0], s[1], s[2], s[3]]) as usize)
+        else {
+            break;
+        };
+        let Some(resource) = existing.get(pos..data_start + size) else {
+            break;
+        };
+        if id != IPTC_RESOURCE {
+            output.extend_from_slice(resource);
+            if size % 2 == 1 {
+                output.push(0);
+            }
+        }
+        pos = data_start + size + (size & 1);
+    }
+
+    output.extend_from_slice(b"8BIM");
+    output.extend_from_slice(&IPTC_RESOURCE.to_be_bytes());
+    output.extend_from_slice(&[0, 0]);
+    output.extend_from_slice(&(iptc.len() as u32).to_be_bytes());
+    output.extend_from_slice(iptc);
+    if iptc.len() % 2 == 1 {
+        output.push(0);
+    }
+    output
+}
+
+/// Rewrites the EXIF block, in TIFF format, without the categories that are not kept.
+/// A block that cannot be read is dropped, as it cannot be told what it holds.
+fn filter_exif(tiff: &[u8], kept: &MetadataOptions) -> Block {
+    if kept.keeps_all_exif() {
+        return Block::Keep;
+    }
+    let exif = match exif::Reader::new().read_raw(tiff.to_vec()) {
+        Ok(exif) => exif,
+        Err(e) => {
+            log::warn!("Dropping unreadable EXIF metadata: {e}");
+            return Block::Drop;
+        }
+    };
+
+    let thumbnail = if kept.thumbnail {
+        thumbnail(&exif)
+    } else {
+        None
+    };
+    let fields: Vec<&Field> = exif
+        .fields()
+        .filter(|f| is_field_kept(f, kept, thumbnail.is_some()))
+        .collect();
+    // A thumbnail alone is of no use
+    if !fields.iter().any(|f| f.ifd_num == In::PRIMARY) {
+        return Block::Drop;
+    }
+
+    let mut writer = Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    if let Some(thumbnail) = thumbnail {
+        writer.set_jpeg(thumbnail, In::THUMBNAIL);
+    }
+    let mut output = Cursor::new(Vec::new());
+    match writer.write(&mut output, exif.little_endian()) {
+        Ok(()) => Block::Replace(output.into_inner()),
+        Err(e) => {
+            log::warn!("Dropping EXIF metadata that cannot be rewritten: {e}");
+            Block::Drop
+        }
+    }
+}
+
+fn is_field_kept(field: &Field, kept: &MetadataOptions, has_thumbnail: bool) -> bool {
+    // Fields the reader could not make sense of cannot be written back
+    if matches!(field.value, Value::Unknown(..)) {
+        return false;
+    }
+    if field.ifd_num != In::PRIMARY {
+        return has_thumbnail;
+    }
+    match field.tag {
+        Tag(Context::Gps, _) => kept.gps,
+        Tag::Orientation => kept.orientation,
+        Tag::UserComment => kept.comments,
+        Tag::BodySerialNumber | Tag::LensSerialNumber | Tag::CameraOwnerName | Tag::MakerNote => {
+            kept.exif && kept.device_ids
+        }
+        _ => kept.exif,
+    }
+}
+
+fn thumbnail(exif: &Exif) -> Option<&[u8]> {
+    let offset = exif
+        .get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    let length = exif
+        .get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    exif.buf().get(offset..offset + length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{CustomMetadataField, MetadataPreset};
+    use chrono::Local;
+    use std::path::Path;
+
+    const SCAN: &[u8] = &[0xFF, SOS, 0, 2, 0x12, 0x34, 0xFF, EOI];
+
+    fn ascii(tag: Tag, value: &str) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        }
+    }
+
+    /// EXIF block with a camera tag, a location, a serial number and the orientation
+    fn exif_block() -> Vec<u8> {
+        let fields = [
+            ascii(Tag::Make, "Caesium"),
+            ascii(Tag::GPSLatitudeRef, "N"),
+            ascii(Tag::BodySerialNumber, "123456"),
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut output = Cursor::new(Vec::new());
+        writer.write(&mut output, false).unwrap();
+        output.into_inner()
+    }
+
+    fn exif_tags(tiff: &[u8]) -> Vec<Tag> {
+        let exif = exif::Reader::new().read_raw(tiff.to_vec()).unwrap();
+        exif.fields()
+            .filter(|f| f.ifd_num == In::PRIMARY)
+            .map(|f| f.tag)
+            .filter(|t| !matches!(*t, Tag::ExifIFDPointer | Tag::GPSInfoIFDPointer))
+            .collect()
+    }
+
+    fn jpeg(segments: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        for (marker, payload) in segments {
+            push_segment(&mut data, *marker, &[payload]).unwrap();
+        }
+        data.extend_from_slice(SCAN);
+        data
+    }
+
+    fn png(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        for (kind, chunk_data) in chunks {
+            push_png_chunk(&mut data, *kind, chunk_data);
+        }
+        data
+    }
+
+    /// Lossless WebP file of 4×3 pixels, without alpha
+    fn simple_webp() -> Vec<u8> {
+        webp_file(vec![(*b"VP8L", vec![0x2F, 0x03, 0x80, 0x00, 0x00, 0x00])]).unwrap()
+    }
+
+    fn fourccs(data: &[u8]) -> Vec<[u8; 4]> {
+        webp_chunks(data)
+            .unwrap()
+            .into_iter()
+            .map(|(fourcc, _)| fourcc)
+            .collect()
+    }
+
+    fn rendered(fields: &MetadataFields) -> RenderedFields {
+        let context = TemplateContext {
+            input: Path::new("photos/IMG_1.jpg"),
+            extension: "jpg",
+            suffix: "",
+            width: 4,
+            height: 3,
+            counter: 1,
+            now: Local::now(),
+        };
+        RenderedFields::render(fields, &context).unwrap()
+    }
+
+    fn artist(artist: &str) -> MetadataFields {
+        MetadataFields {
+            artist: artist.to_string(),
+            ..MetadataFields::default()
+        }
+    }
+
+    fn privacy() -> MetadataOptions {
+        MetadataOptions {
+            preset: MetadataPreset::Privacy,
+            ..MetadataOptions::default()
+        }
+    }
+
+    #[test]
+    fn keeping_everything_leaves_jpeg_files_untouched() {
+        let data = jpeg(&[
+            (APP1, [EXIF_HEADER, &exif_block()].concat()),
+            (COM, b"comment".to_vec()),
+        ]);
+        assert_eq!(
+            filter(data.clone(), &MetadataOptions::default()).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn privacy_filters_jpeg_segments() {
+        let data = jpeg(&[
+            (APP0, b"JFIF\0\x01\x02\0\0\x01\0\x01\0\0".to_vec()),
+            (APP1, [EXIF_HEADER, &exif_block()].concat()),
+            (APP1, [XMP_HEADER, b"<x:xmpmeta/>"].concat()),
+            (APP2, [ICC_HEADER, b"\x01\x01profile"].concat()),
+            (APP13, [PHOTOSHOP_HEADER, b"8BIM"].concat()),
+            (COM, b"comment".to_vec()),
+        ]);
+
+        let filtered = filter(data, &privacy()).unwrap();
+        let (segments, scan) = jpeg_segments(&filtered).unwrap();
+        let markers: Vec<u8> = segments.iter().map(|s| s.marker).collect();
+        assert_eq!(markers, [APP0, APP1, APP2]);
+        assert_eq!(
+            exif_tags(&segments[1].payload[EXIF_HEADER.len()..]),
+            [Tag::Make, Tag::Orientation]
+        );
+        assert_eq!(scan, SCAN);
+    }
+
+    #[test]
+    fn rejects_truncated_jpeg_files() {
+        let data = jpeg(&[(COM, b"comment".to_vec())]);
+        assert!(matches!(
+            filter(data[..8].to_vec(), &privacy()),
+            Err(MetadataError::Malformed("JPEG"))
+        ));
+    }
+
+    #[test]
+    fn filters_png_chunks() {
+        let data = png(&[
+            (b"IHDR", vec![0; 13]),
+            (b"eXIf", exif_block()),
+            (b"iTXt", png_itxt(PNG_XMP_KEYWORD, b"<x:xmpmeta/>")),
+            (b"tEXt", b"Comment\0hello".to_vec()),
+            (b"IDAT", vec![1, 2, 3]),
+            (b"IEND", vec![]),
+        ]);
+        let kept = MetadataOptions {
+            preset: MetadataPreset::Custom,
+            gps: false,
+            xmp: false,
+            comments: false,
+            ..MetadataOptions::default()
+        };
+
+        let filtered = filter(data, &kept).unwrap();
+        let chunks = png_chunks(&filtered).unwrap();
+        let kinds: Vec<&[u8]> = chunks.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"eXIf", b"IDAT", b"IEND"]);
+        assert_eq!(
+            exif_tags(chunks[1].data),
+            [Tag::Make, Tag::Orientation, Tag::BodySerialNumber]
+        );
+    }
+
+    #[test]
+    fn filters_webp_chunks_and_updates_the_header() {
+        let data = webp_file(vec![
+            (
+                *b"VP8X",
+                vec![WEBP_ICC | WEBP_EXIF | WEBP_XMP, 0, 0, 0, 3, 0, 0, 2, 0, 0],
+            ),
+            (*b"ICCP", b"profile".to_vec()),
+            (*b"VP8L", vec![0x2F, 0x03, 0x80, 0x00, 0x00, 0x00]),
+            (*b"EXIF", [EXIF_HEADER, &exif_block()].concat()),
+            (*b"XMP ", b"<x:xmpmeta/>".to_vec()),
+        ])
+        .unwrap();
+
+        let filtered = filter(data, &privacy()).unwrap();
+        assert_eq!(fourccs(&filtered), [*b"VP8X", *b"ICCP", *b"VP8L", *b"EXIF"]);
+        let chunks = webp_chunks(&filtered).unwrap();
+        assert_eq!(chunks[0].1[0], WEBP_ICC | WEBP_EXIF);
+        assert!(chunks[3].1.starts_with(EXIF_HEADER));
+        let riff_size = u32::from_le_bytes([filtered[4], filtered[5], filtered[6], filtered[7]]);
+        assert_eq!(riff_size as usize, filtered.len() - 8);
+    }
+
+    #[test]
+    fn custom_fields_override_the_base_ones() {
+        let fields = MetadataFields {
+            artist: "Jane".to_string(),
+            description: "{name}".to_string(),
+            custom: vec![
+                CustomMetadataField {
+                    kind: MetadataFieldKind::Xmp,
+                    name: "dc:creator".to_string(),
+                    value: "Jane Doe".to_string(),
+                },
+                CustomMetadataField {
+                    kind: MetadataFieldKind::Iptc,
+                    name: "credit".to_string(),
+                    value: "Caesium".to_string(),
+                },
+                CustomMetadataField {
+                    kind: MetadataFieldKind::Xmp,
+                    name: "photoshop:Credit".to_string(),
+                    value: String::new(),
+                },
+            ],
+            ..MetadataFields::default()
+        };
+
+        let rendered = rendered(&fields);
+        assert_eq!(rendered.description.as_deref(), Some("IMG_1"));
+        assert_eq!(
+            rendered.xmp,
+            [
+                ("dc:creator".to_string(), "Jane Doe".to_string()),
+                ("dc:description".to_string(), "IMG_1".to_string()),
+            ]
+        );
+        assert_eq!(
+            rendered.iptc,
+            [
+                (IPTC_BY_LINE, "Jane".to_string()),
+                (110, "Caesium".to_string()),
+                (IPTC_CAPTION, "IMG_1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_fields_into_jpeg_files() {
+        let data = jpeg(&[
+            (APP1, [EXIF_HEADER, &exif_block()].concat()),
+            (APP1, [XMP_HEADER, b"<old/>"].concat()),
+            (COM, b"comment".to_vec()),
+        ]);
+
+        let written = write_fields(data, &rendered(&artist("Jane & co"))).unwrap();
+        let (segments, _) = jpeg_segments(&written).unwrap();
+        let markers: Vec<u8> = segments.iter().map(|s| s.marker).collect();
+        assert_eq!(markers, [APP1, APP1, APP13, COM]);
+
+        let exif = exif::Reader::new()
+            .read_raw(segments[0].payload[EXIF_HEADER.len()..].to_vec())
+            .unwrap();
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+        let artist = exif.get_field(Tag::Artist, In::PRIMARY).unwrap();
+        assert_eq!(artist.display_value().to_string(), "\"Jane & co\"");
+
+        let xmp = String::from_utf8_lossy(&segments[1].payload[XMP_HEADER.len()..]);
+        assert!(xmp.contains("<rdf:li>Jane &amp; co</rdf:li>"));
+        assert!(!xmp.contains("<old/>"));
+
+        let iptc = &segments[2].payload[PHOTOSHOP_HEADER.len()..];
+        assert!(iptc.starts_with(b"8BIM\x04\x04"));
+        assert!(iptc.windows(3).any(|w| w == [0x1C, 2, IPTC_BY_LINE]));
+    }
+
+    #[test]
+    fn empty_fields_leave_the_file_untouched() {
+        let data = jpeg(&[(COM, b"comment".to_vec())]);
+        let written = write_fields(data.clone(), &rendered(&artist(""))).unwrap();
+        assert_eq!(written, data);
+    }
+
+    #[test]
+    fn fields_cannot_be_written_to_other_formats() {
+        let gif = b"GIF89a\x01\0\x01\0\0\0\0\x3B".to_vec();
+        assert!(matches!(
+            write_fields(gif.clone(), &rendered(&artist("Jane"))),
+            Err(MetadataError::FieldsNotSupported)
+        ));
+        assert_eq!(
+            write_fields(gif.clone(), &rendered(&artist(""))).unwrap(),
+            gif
+        );
+    }
+
+    #[test]
+    fn writes_fields_after_the_png_header() {
+        let data = png(&[
+            (b"IHDR", vec![0; 13]),
+            (b"iTXt", png_itxt(b"Author", b"Someone else")),
+            (b"IDAT", vec![1, 2, 3]),
+            (b"IEND", vec![]),
+        ]);
+
+        let written = write_fields(data, &rendered(&artist("Jane"))).unwrap();
+        let chunks = png_chunks(&written).unwrap();
+        let keywords: Vec<Option<&[u8]>> = chunks.iter().map(|c| c.text_keyword()).collect();
+        assert_eq!(
+            keywords,
+            [
+                None,
+                None,
+                Some(PNG_XMP_KEYWORD),
+                Some(b"Author".as_slice()),
+                None,
+                None
+            ]
+        );
+        assert_eq!(chunks[1].kind, b"eXIf");
+        assert!(chunks[3].data.ends_with(b"\0Jane"));
+    }
+
+    #[test]
+    fn simple_webp_files_are_extended_to_hold_fields() {
+        let written = write_fields(simple_webp(), &rendered(&artist("Jane"))).unwrap();
+        assert_eq!(fourccs(&written), [*b"VP8X", *b"VP8L", *b"EXIF", *b"XMP "]);
+        let chunks = webp_chunks(&written).unwrap();
+        assert_eq!(
+            chunks[0].1,
+            [WEBP_EXIF | WEBP_XMP, 0, 0, 0, 3, 0, 0, 2, 0, 0]
+        );
+    }
+
+    #[test]
+    fn carries_metadata_over_with_the_orientation_reset() {
+        let original = jpeg(&[
+            (APP1, [EXIF_HEADER, &exif_block()].concat()),
+            (
+                APP1,
+                [XMP_HEADER, b"<rdf:Description tiff:Orientation='6'/>"].concat(),
+            ),
+        ]);
+        let encoded = jpeg(&[(APP0, b"JFIF\0\x01\x02\0\0\x01\0\x01\0\0".to_vec())]);
+
+        let carried = carry_over(&original, encoded).unwrap();
+        let (segments, scan) = jpeg_segments(&carried).unwrap();
+        let markers: Vec<u8> = segments.iter().map(|s| s.marker).collect();
+        assert_eq!(markers, [APP0, APP1, APP1]);
+        let exif = exif::Reader::new()
+            .read_raw(segments[1].payload[EXIF_HEADER.len()..].to_vec())
+            .unwrap();
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+        let orientation = exif.get_field(Tag::Orientation, In::PRIMARY).unwrap();
+        assert_eq!(orientation.value.get_uint(0), Some(1));
+        assert_eq!(
+            &segments[2].payload[XMP_HEADER.len()..],
+            b"<rdf:Description tiff:Orientation='1'/>"
+        );
+        assert_eq!(scan, SCAN);
+    }
+
+    #[test]
+    fn carries_the_iptc_and_the_comments_of_jpeg_files() {
+        let photoshop = [
+            PHOTOSHOP_HEADER,
+            b"8BIM\x04\x04\0\0\0\0\0\x04\x1C\x02\x78\0",
+        ]
+        .concat();
+        let original = jpeg(&[(APP13, photoshop.clone()), (COM, b"comment".to_vec())]);
+        let encoded = jpeg(&[(COM, b"encoder".to_vec())]);
+
+        let carried = carry_over(&original, encoded).unwrap();
+        let (segments, _) = jpeg_segments(&carried).unwrap();
+        let blocks: Vec<(u8, &[u8])> = segments.iter().map(|s| (s.marker, s.payload)).collect();
+        assert_eq!(
+            blocks,
+            [(APP13, photoshop.as_slice()), (COM, b"comment".as_slice())]
+        );
+        let png = png(&[(b"IHDR", vec![0; 13]), (b"IEND", vec![])]);
+        assert_eq!(carry_over(&original, png.clone()).unwrap(), png);
+    }
+
+    #[test]
+    fn knows_the_writable_properties() {
+        assert!(is_known_xmp_property("photoshop:Credit"));
+        assert!(is_known_xmp_property("Iptc4xmpCore:Location"));
+        assert!(!is_known_xmp_property("Credit"));
+        assert!(!is_known_xmp_property("exif:Make"));
+        assert!(!is_known_xmp_property("dc:1title"));
+        assert_eq!(iptc_dataset("by-line"), Some(IPTC_BY_LINE));
+        assert_eq!(iptc_dataset("Unknown"), None);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::codecs;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageError, RgbImage};
+use std::fs;
+use std::path::Path;
+
+/// PSNR reported for identical images, whose actual value is infinite
+pub const MAX_PSNR: f64 = 100.0;
+
+const SSIM_WINDOW: usize = 8;
+const SSIM_STEP: usize = 4;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetricsError {
+    #[error("Cannot decode {what}: {source}")]
+    Decode {
+        what: &'static str,
+        #[source]
+        source: image::ImageError,
+    },
+}
+
+/// How close a compressed image is to its original
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct QualityMetrics {
+    /// Structural similarity of the luma, 1 for identical images
+    pub ssim: f64,
+    /// Peak signal-to-noise ratio of the RGB channels in dB, capped at [`MAX_PSNR`]
+    pub psnr: f64,
+}
+
+/// Compares the decoded `original` file with the `compressed` output
+pub fn measure(original: &Path, compressed: &[u8]) -> Result<QualityMetrics, MetricsError> {
+    Reference::open(original)?.compare(compressed)
+}
+
+/// Decoded original, kept around to compare several outputs with it
+pub struct Reference {
+    image: RgbImage,
+}
+
+impl Reference {
+    pub fn new(image: &DynamicImage) -> Self {
+        Self {
+            image: image.to_rgb8(),
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Self, MetricsError> {
+        let data = fs::read(path).map_err(|e| MetricsError::Decode {
+            what: "the original image",
+            source: ImageError::IoError(e),
+        })?;
+        let mime_type = codecs::raw_mime_type(path).unwrap_or_default();
+        let image =
+            codecs::decode_input(&data, mime_type).map_err(|source| MetricsError::Decode {
+                what: "the original image",
+                source,
+            })?;
+        Ok(Self::new(&image))
+    }
+
+    pub fn from_memory(data: &[u8]) -> Result<Self, MetricsError> {
+        let image = codecs::decode(data).map_err(|source| MetricsError::Decode {
+            what: "the original image",
+            source,
+        })?;
+        Ok(Self::new(&image))
+    }
+
+    /// Resized outputs are compared at their own resolution, the reference is scaled
+    /// down to it the first time and reused as is for the next outputs of the same size.
+    pub fn compare(&mut self, compressed: &[u8]) -> Result<QualityMetrics, MetricsError> {
+        let compressed = codecs::decode(compressed)
+            .map_err(|source| MetricsError::Decode {
+                what: "the compressed image",
+                source,
+            })?
+            .to_rgb8();
+
+        let (width, height) = (compressed.width(), compressed.height());
+        if (self.image.width(), self.image.height()) != (width, height) {
+            self.image = imageops::resize(&self.image, width, height, FilterType::Triangle);
+        }
+
+        Ok(QualityMetrics {
+            ssim: ssim(
+                &luma(&self.image),
+                &luma(&compressed),
+                width as usize,
+                height as usize,
+            ),
+            psnr: psnr(self.image.as_raw(), compressed.as_raw()),
+        })
+    }
+}
+
+fn psnr(original: &[u8], compressed: &[u8]) -> f64 {
+    let squared_error: f64 = original
+        .iter()
+        .zip(compressed)
+        .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+        .sum();
+    if squared_error == 0.0 {
+        return MAX_PSNR;
+    }
+    let mse = squared_error / original.len() as f64;
+    (10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR)
+}
+
+// Rec. 601 weights, as in the reference SSIM implementation
+fn luma(image: &RgbImage) -> Vec<f64> {
+    image
+        .as_raw()
+        .chunks_exact(3)
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect()
+}
+
+/// Mean SSIM of the 8x8 windows taken every 4 pixels
+fn ssim(original: &[f64], compressed: &[f64], width: usize, height: usize) -> f64 {
+    let window = Window {
+        width: SSIM_WINDOW.min(width),
+        height: SSIM_WINDOW.min(height),
+        stride: width,
+    };
+    if window.width == 0 || window.height == 0 {
+        return 1.0;
+    }
+
+    let mut total = 0.0;
+    let mut count = 0;
+    for y in (0..=height - window.height).step_by(SSIM_STEP) {
+        for x in (0..=width - window.width).step_by(SSIM_STEP) {
+            total += window.ssim(original, compressed, x, y);
+            count += 1;
+        }
+    }
+    total / count as f64
+}
+
+struct Window {
+    width: usize,
+    height: usize,
+    /// Width of the whole image
+    stride: usize,
+}
+
+impl Window {
+    fn ssim(&self, a: &[f64], b: &[f64], x: usize, y: usize) -> f64 {
+        let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for row in y..y + self.height {
+            let start = row * self.stride + x;
+            for (va, vb) in a[start..start + self.width]
+                .iter()
+                .zip(&b[start..start + self.width])
+            {
+                sum_a += va;
+                sum_b += vb;
+                sum_aa += va * va;
+                sum_bb += vb * vb;
+                sum_ab += va * vb;
+            }
+        }
+
+        let n = (self.width * self.height) as f64;
+        let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+        let variance_a = sum_aa / n - mean_a * mean_a;
+        let variance_b = sum_bb / n - mean_b * mean_b;
+        let covariance = sum_ab / n - mean_a * mean_b;
+
+        ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+            / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (variance_a + variance_b + SSIM_C2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Luma of a `width` x `height` diagonal gradient
+    fn gradient(width: usize, height: usize) -> Vec<f64> {
+        (0..width * height)
+            .map(|i| ((i % width + i / width) * 8 % 256) as f64)
+            .collect()
+    }
+
+    #[test]
+    fn identical_images_are_perfect() {
+        let image = gradient(16, 16);
+
+        assert_eq!(psnr(&[10, 20, 30], &[10, 20, 30]), MAX_PSNR);
+        assert!((ssim(&image, &image, 16, 16) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn psnr_follows_the_mean_squared_error() {
+        let psnr = psnr(&[10, 20, 30, 40], &[11, 19, 31, 39]);
+        assert!((psnr - 48.13).abs() < 0.01, "{psnr}");
+    }
+
+    #[test]
+    fn ssim_drops_with_the_structure() {
+        let original = gradient(16, 16);
+        let brighter: Vec<f64> = original.iter().map(|v| v + 4.0).collect();
+        let inverted: Vec<f64> = original.iter().map(|v| 255.0 - v).collect();
+
+        let slightly_different = ssim(&original, &brighter, 16, 16);
+        let very_different = ssim(&original, &inverted, 16, 16);
+        assert!(slightly_different < 1.0 && slightly_different > 0.9);
+        assert!(very_different < 0.0);
+    }
+
+    #[test]
+    fn images_smaller_than_the_window_are_compared_whole() {
+        let original = gradient(3, 2);
+        let inverted: Vec<f64> = original.iter().map(|v| 255.0 - v).collect();
+
+        assert!((ssim(&original, &original, 3, 2) - 1.0).abs() < 1e-9);
+        assert!(ssim(&original, &inverted, 3, 2) < 0.0);
+        assert_eq!(ssim(&[], &[], 0, 0), 1.0);
+    }
+}
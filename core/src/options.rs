@@ -0,0 +1,1273 @@
+use crate::codecs::SourceFormat;
+use crate::metadata;
+use crate::rules::Rule;
+use crate::template::{FilenameTemplate, TextTemplate};
+use caesium::parameters::{ChromaSubsampling, TiffCompression};
+use caesium::SupportedFileTypes;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum JpegChromaSubsampling {
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "4:4:4")]
+    CS444,
+    #[serde(rename = "4:2:2")]
+    CS422,
+    #[serde(rename = "4:2:0")]
+    CS420,
+    #[serde(rename = "4:1:1")]
+    CS411,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TiffMethod {
+    #[serde(alias = "uncompressed")]
+    None,
+    Lzw,
+    #[default]
+    Deflate,
+    Packbits,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(u8)]
+pub enum TiffDeflateLevel {
+    Fast = 0,
+    #[default]
+    Balanced = 1,
+    Best = 2,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Clone, Copy, Debug, Default, PartialEq)]
+#[repr(u8)]
+pub enum CompressionMode {
+    #[default]
+    Quality = 0,
+    Size = 1,
+    /// Lowest encoder quality whose output reaches the target SSIM
+    TargetQuality = 2,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeMode {
+    #[default]
+    None,
+    Dimensions,
+    Width,
+    Height,
+    LongEdge,
+    ShortEdge,
+    Percentage,
+    /// Crops to `aspect_ratio_width:aspect_ratio_height` without scaling
+    AspectRatio,
+    /// Scales down to at most `max_megapixels`
+    MaxMegapixels,
+}
+
+/// How [`ResizeMode::Dimensions`] fits the image in the box when the aspect ratio is kept
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeFit {
+    /// Fits inside the box, one side can be shorter
+    #[default]
+    Contain,
+    /// Fills the box and crops around the focal point
+    Cover,
+    /// Fits inside the box, then pads it to its exact size with the background color
+    Pad,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveOriginalFileMode {
+    #[default]
+    Trash,
+    Delete,
+}
+
+/// What happens when the output path is already taken, by an existing file or by
+/// another image of the same batch
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    #[default]
+    Overwrite,
+    Skip,
+    /// Writes next to the existing file with a numeric suffix
+    Rename,
+    /// Replaces the existing file only if it was modified before the input
+    OverwriteIfOlder,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkKind {
+    /// A PNG logo
+    #[default]
+    Image,
+    Text,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+impl FromStr for WatermarkPosition {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top_left" => Ok(WatermarkPosition::TopLeft),
+            "top" => Ok(WatermarkPosition::Top),
+            "top_right" => Ok(WatermarkPosition::TopRight),
+            "left" => Ok(WatermarkPosition::Left),
+            "center" => Ok(WatermarkPosition::Center),
+            "right" => Ok(WatermarkPosition::Right),
+            "bottom_left" => Ok(WatermarkPosition::BottomLeft),
+            "bottom" => Ok(WatermarkPosition::Bottom),
+            "bottom_right" => Ok(WatermarkPosition::BottomRight),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(CollisionPolicy::Overwrite),
+            "skip" => Ok(CollisionPolicy::Skip),
+            "rename" => Ok(CollisionPolicy::Rename),
+            "overwrite_if_older" => Ok(CollisionPolicy::OverwriteIfOlder),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Original,
+    #[serde(rename = "jpg", alias = "jpeg")]
+    Jpeg,
+    Png,
+    #[serde(rename = "webp")]
+    WebP,
+    #[serde(alias = "tif")]
+    Tiff,
+    Avif,
+    #[serde(rename = "jxl")]
+    JpegXl,
+}
+
+impl OutputFormat {
+    /// Extension of the output file, `None` when the original one must be kept
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Original => None,
+            OutputFormat::Jpeg => Some("jpg"),
+            OutputFormat::Png => Some("png"),
+            OutputFormat::WebP => Some("webp"),
+            OutputFormat::Tiff => Some("tiff"),
+            OutputFormat::Avif => Some("avif"),
+            OutputFormat::JpegXl => Some("jxl"),
+        }
+    }
+
+    /// File type libcaesium converts to, `None` for the original format and for the
+    /// formats encoded by [`Codec`](crate::codecs::Codec)
+    pub fn supported_file_type(&self) -> Option<SupportedFileTypes> {
+        match self {
+            OutputFormat::Original | OutputFormat::Avif | OutputFormat::JpegXl => None,
+            OutputFormat::Jpeg => Some(SupportedFileTypes::Jpeg),
+            OutputFormat::Png => Some(SupportedFileTypes::Png),
+            OutputFormat::WebP => Some(SupportedFileTypes::WebP),
+            OutputFormat::Tiff => Some(SupportedFileTypes::Tiff),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "original" => Ok(OutputFormat::Original),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            "png" => Ok(OutputFormat::Png),
+            "webp" => Ok(OutputFormat::WebP),
+            "tif" | "tiff" => Ok(OutputFormat::Tiff),
+            "avif" => Ok(OutputFormat::Avif),
+            "jxl" => Ok(OutputFormat::JpegXl),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<JpegChromaSubsampling> for ChromaSubsampling {
+    fn from(value: JpegChromaSubsampling) -> Self {
+        match value {
+            JpegChromaSubsampling::Auto => ChromaSubsampling::Auto,
+            JpegChromaSubsampling::CS444 => ChromaSubsampling::CS444,
+            JpegChromaSubsampling::CS422 => ChromaSubsampling::CS422,
+            JpegChromaSubsampling::CS420 => ChromaSubsampling::CS420,
+            JpegChromaSubsampling::CS411 => ChromaSubsampling::CS411,
+        }
+    }
+}
+
+impl From<TiffMethod> for TiffCompression {
+    fn from(value: TiffMethod) -> Self {
+        match value {
+            TiffMethod::None => TiffCompression::Uncompressed,
+            TiffMethod::Lzw => TiffCompression::Lzw,
+            TiffMethod::Deflate => TiffCompression::Deflate,
+            TiffMethod::Packbits => TiffCompression::Packbits,
+        }
+    }
+}
+
+impl From<TiffDeflateLevel> for caesium::parameters::TiffDeflateLevel {
+    fn from(value: TiffDeflateLevel) -> Self {
+        match value {
+            TiffDeflateLevel::Fast => caesium::parameters::TiffDeflateLevel::Fast,
+            TiffDeflateLevel::Balanced => caesium::parameters::TiffDeflateLevel::Balanced,
+            TiffDeflateLevel::Best => caesium::parameters::TiffDeflateLevel::Best,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct JPEGOptions {
+    pub quality: u32,
+    pub chroma_subsampling: JpegChromaSubsampling,
+    pub progressive: bool,
+    pub optimize: bool,
+    pub preserve_icc: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PNGOptions {
+    pub quality: u32,
+    pub optimization_level: u32,
+    pub optimize: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GIFOptions {
+    pub quality: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WebPOptions {
+    pub quality: u32,
+    pub lossless: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TIFFOptions {
+    pub method: TiffMethod,
+    pub deflate_level: TiffDeflateLevel,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AVIFOptions {
+    pub quality: u32,
+    /// From 1 (slowest, smallest output) to 10
+    pub speed: u32,
+    /// Not supported by the AVIF encoder, rejected by validation
+    pub lossless: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct JXLOptions {
+    pub quality: u32,
+    /// From 1 (fastest) to 9 (slowest, smallest output)
+    pub effort: u32,
+    pub lossless: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TargetQualityOptions {
+    /// Minimum SSIM of the output against the original
+    pub ssim: f64,
+    /// Encodes tried per image before settling for the best one
+    pub max_iterations: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataPreset {
+    /// Everything the encoder can carry over
+    #[default]
+    All,
+    /// Drops the location, device identifiers, thumbnails, comments, XMP and IPTC, which
+    /// can also hold the location. Keeps the other EXIF tags, copyright included, the
+    /// orientation and the color profile.
+    Privacy,
+    /// Only the categories enabled in [`MetadataOptions`]
+    Custom,
+}
+
+/// Metadata categories kept in JPEG, PNG and WebP outputs. Other outputs keep all of it
+/// with [`MetadataPreset::All`] and none of it otherwise.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetadataOptions {
+    pub preset: MetadataPreset,
+    /// Camera, capture settings, dates and copyright EXIF tags
+    pub exif: bool,
+    pub gps: bool,
+    /// Body and lens serial numbers, owner name and maker notes
+    pub device_ids: bool,
+    pub xmp: bool,
+    pub iptc: bool,
+    pub icc: bool,
+    pub orientation: bool,
+    /// The EXIF preview image
+    pub thumbnail: bool,
+    /// JPEG comments, PNG text chunks and the EXIF user comment
+    pub comments: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataFieldKind {
+    Xmp,
+    Iptc,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CustomMetadataField {
+    pub kind: MetadataFieldKind,
+    /// XMP property with its prefix, like `photoshop:Credit`, or IPTC dataset name, like
+    /// `Credit`
+    pub name: String,
+    /// See [`TextTemplate`]
+    pub value: String,
+}
+
+/// Metadata written into every JPEG, PNG, WebP and TIFF output after the retained one is
+/// filtered. Values are templates, see [`TextTemplate`], and empty ones are not written.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetadataFields {
+    /// Written to EXIF, XMP and IPTC
+    pub artist: String,
+    pub copyright: String,
+    pub description: String,
+    pub custom: Vec<CustomMetadataField>,
+}
+
+impl MetadataFields {
+    pub fn is_empty(&self) -> bool {
+        self.artist.is_empty()
+            && self.copyright.is_empty()
+            && self.description.is_empty()
+            && self.custom.iter().all(|f| f.value.is_empty())
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CompressionOptions {
+    pub jpeg: JPEGOptions,
+    pub png: PNGOptions,
+    pub gif: GIFOptions,
+    pub webp: WebPOptions,
+    pub tiff: TIFFOptions,
+    #[serde(default)]
+    pub avif: AVIFOptions,
+    #[serde(default)]
+    pub jxl: JXLOptions,
+    pub compression_mode: CompressionMode,
+    pub keep_metadata: bool,
+    /// Which metadata is kept when `keep_metadata` is enabled
+    #[serde(default)]
+    pub metadata: MetadataOptions,
+    #[serde(default)]
+    pub metadata_fields: MetadataFields,
+    pub max_size_value: usize,
+    pub max_size_unit: usize,
+    #[serde(default)]
+    pub target_quality: TargetQualityOptions,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ResizeOptions {
+    pub resize_enabled: bool,
+    pub resize_mode: ResizeMode,
+    pub keep_aspect_ratio: bool,
+    pub do_not_enlarge: bool,
+    pub width: u32,
+    pub height: u32,
+    pub width_percentage: u32,
+    pub height_percentage: u32,
+    pub long_edge: u32,
+    pub short_edge: u32,
+    /// Turns the pixels upright from the EXIF orientation before resizing, and resets the
+    /// orientation tag of the kept metadata
+    #[serde(default)]
+    pub auto_orient: bool,
+    #[serde(default)]
+    pub fit: ResizeFit,
+    /// Center of the crops, in percent of the width and height of the image
+    #[serde(default = "default_focal_point")]
+    pub focal_point_x: u32,
+    #[serde(default = "default_focal_point")]
+    pub focal_point_y: u32,
+    /// Color of the padding, `#RRGGBB` or `#RRGGBBAA`
+    #[serde(default = "default_background")]
+    pub background: String,
+    #[serde(default = "default_aspect_ratio")]
+    pub aspect_ratio_width: u32,
+    #[serde(default = "default_aspect_ratio")]
+    pub aspect_ratio_height: u32,
+    #[serde(default = "default_max_megapixels")]
+    pub max_megapixels: f64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OutputOptions {
+    pub output_folder: String,
+    pub same_folder_as_input: bool,
+    pub keep_folder_structure: bool,
+    pub skip_if_output_is_bigger: bool,
+    pub move_original_file_enabled: bool,
+    pub move_original_file_mode: MoveOriginalFileMode,
+    pub keep_file_dates_enabled: bool,
+    pub keep_creation_date: bool,
+    pub keep_last_modified_date: bool,
+    pub keep_last_access_date: bool,
+    pub output_format: OutputFormat,
+    pub suffix: String,
+    /// Output name relative to the output folder, see [`FilenameTemplate`]. Empty uses
+    /// the input name with the suffix
+    #[serde(default)]
+    pub filename_template: String,
+    #[serde(default)]
+    pub collision_policy: CollisionPolicy,
+    /// Format of the outputs of HEIC, BMP and RAW inputs when `output_format` is
+    /// `Original`, as they cannot be written back
+    #[serde(default = "default_decode_only_format")]
+    pub decode_only_format: OutputFormat,
+}
+
+/// Logo or text drawn over every image after it is resized, so it keeps the same
+/// proportions whatever the output size
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatermarkOptions {
+    pub enabled: bool,
+    pub kind: WatermarkKind,
+    /// Logo drawn with [`WatermarkKind::Image`]
+    pub image_path: String,
+    /// Single line drawn with [`WatermarkKind::Text`]
+    pub text: String,
+    /// TrueType or OpenType font of the text
+    pub font_path: String,
+    /// Color of the text, `#RRGGBB` or `#RRGGBBAA`
+    pub color: String,
+    pub position: WatermarkPosition,
+    /// Distance from the edges of the image, in percent of its short edge
+    pub margin: u32,
+    /// From 0 (invisible) to 100
+    pub opacity: u32,
+    /// Width of the watermark, in percent of the short edge of the image
+    pub scale: u32,
+    /// Repeats the watermark over the whole image, `margin` apart, instead of placing it
+    /// at `position`
+    pub tiled: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct OptionsPayload {
+    pub compression_options: CompressionOptions,
+    pub resize_options: ResizeOptions,
+    pub output_options: OutputOptions,
+    #[serde(default)]
+    pub watermark_options: WatermarkOptions,
+    /// Evaluated in order, the first matching rule overrides the options above
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+// Defaults mirror the ones used by the frontend stores
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            jpeg: JPEGOptions {
+                quality: 80,
+                chroma_subsampling: JpegChromaSubsampling::Auto,
+                progressive: true,
+                optimize: false,
+                preserve_icc: true,
+            },
+            png: PNGOptions {
+                quality: 80,
+                optimization_level: 3,
+                optimize: false,
+            },
+            gif: GIFOptions { quality: 80 },
+            webp: WebPOptions {
+                quality: 80,
+                lossless: false,
+            },
+            tiff: TIFFOptions {
+                method: TiffMethod::Deflate,
+                deflate_level: TiffDeflateLevel::Balanced,
+            },
+            avif: AVIFOptions::default(),
+            jxl: JXLOptions::default(),
+            compression_mode: CompressionMode::Quality,
+            keep_metadata: true,
+            metadata: MetadataOptions::default(),
+            metadata_fields: MetadataFields::default(),
+            max_size_value: 500,
+            max_size_unit: 1024,
+            target_quality: TargetQualityOptions::default(),
+        }
+    }
+}
+
+impl Default for MetadataOptions {
+    fn default() -> Self {
+        Self {
+            preset: MetadataPreset::All,
+            exif: true,
+            gps: true,
+            device_ids: true,
+            xmp: true,
+            iptc: true,
+            icc: true,
+            orientation: true,
+            thumbnail: true,
+            comments: true,
+        }
+    }
+}
+
+impl MetadataOptions {
+    /// Categories kept with the selected preset
+    pub fn effective(&self) -> Self {
+        match self.preset {
+            MetadataPreset::All => Self::default(),
+            MetadataPreset::Privacy => Self {
+                preset: MetadataPreset::Privacy,
+                exif: true,
+                gps: false,
+                device_ids: false,
+                xmp: false,
+                iptc: false,
+                icc: true,
+                orientation: true,
+                thumbnail: false,
+                comments: false,
+            },
+            MetadataPreset::Custom => *self,
+        }
+    }
+
+    pub fn keeps_everything(&self) -> bool {
+        let kept = self.effective();
+        kept.keeps_all_exif() && kept.xmp && kept.iptc && kept.icc
+    }
+
+    /// Every EXIF category is kept, so the EXIF block can be copied as is
+    pub(crate) fn keeps_all_exif(&self) -> bool {
+        self.exif
+            && self.gps
+            && self.device_ids
+            && self.orientation
+            && self.thumbnail
+            && self.comments
+    }
+}
+
+impl Default for AVIFOptions {
+    fn default() -> Self {
+        Self {
+            quality: 70,
+            speed: 6,
+            lossless: false,
+        }
+    }
+}
+
+impl Default for JXLOptions {
+    fn default() -> Self {
+        Self {
+            quality: 80,
+            effort: 7,
+            lossless: false,
+        }
+    }
+}
+
+impl Default for TargetQualityOptions {
+    fn default() -> Self {
+        Self {
+            ssim: 0.98,
+            max_iterations: 7,
+        }
+    }
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kind: WatermarkKind::Image,
+            image_path: String::new(),
+            text: String::new(),
+            font_path: String::new(),
+            color: "#FFFFFF".to_string(),
+            position: WatermarkPosition::BottomRight,
+            margin: 3,
+            opacity: 50,
+            scale: 25,
+            tiled: false,
+        }
+    }
+}
+
+impl Default for ResizeOptions {
+    fn default() -> Self {
+        Self {
+            resize_enabled: false,
+            resize_mode: ResizeMode::None,
+            keep_aspect_ratio: true,
+            do_not_enlarge: true,
+            width: 500,
+            height: 500,
+            width_percentage: 100,
+            height_percentage: 100,
+            long_edge: 500,
+            short_edge: 500,
+            auto_orient: false,
+            fit: ResizeFit::Contain,
+            focal_point_x: default_focal_point(),
+            focal_point_y: default_focal_point(),
+            background: default_background(),
+            aspect_ratio_width: default_aspect_ratio(),
+            aspect_ratio_height: default_aspect_ratio(),
+            max_megapixels: default_max_megapixels(),
+        }
+    }
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            output_folder: String::new(),
+            same_folder_as_input: false,
+            keep_folder_structure: true,
+            skip_if_output_is_bigger: true,
+            move_original_file_enabled: false,
+            move_original_file_mode: MoveOriginalFileMode::Trash,
+            keep_file_dates_enabled: true,
+            keep_creation_date: true,
+            keep_last_modified_date: true,
+            keep_last_access_date: true,
+            output_format: OutputFormat::Original,
+            suffix: String::new(),
+            filename_template: String::new(),
+            collision_policy: CollisionPolicy::Overwrite,
+            decode_only_format: default_decode_only_format(),
+        }
+    }
+}
+
+fn default_decode_only_format() -> OutputFormat {
+    OutputFormat::Jpeg
+}
+
+fn default_focal_point() -> u32 {
+    50
+}
+
+fn default_background() -> String {
+    "#FFFFFF".to_string()
+}
+
+fn default_aspect_ratio() -> u32 {
+    1
+}
+
+fn default_max_megapixels() -> f64 {
+    12.0
+}
+
+/// RGBA channels of a `#RRGGBB` or `#RRGGBBAA` color
+pub(crate) fn parse_color(value: &str) -> Option<[u8; 4]> {
+    let hex = value.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |start: usize| u8::from_str_radix(hex.get(start..start + 2)?, 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some([channel(0)?, channel(2)?, channel(4)?, alpha])
+}
+
+impl OutputOptions {
+    /// Format written for an input of type `mime_type`
+    pub fn format_for(&self, mime_type: &str) -> OutputFormat {
+        match self.output_format {
+            OutputFormat::Original if SourceFormat::from_mime_type(mime_type).is_some() => {
+                self.decode_only_format
+            }
+            format => format,
+        }
+    }
+}
+
+const MAX_QUALITY: u32 = 100;
+const MAX_PNG_OPTIMIZATION_LEVEL: u32 = 6;
+const MAX_TARGET_ITERATIONS: u32 = 20;
+const MAX_AVIF_SPEED: u32 = 10;
+const MAX_JXL_EFFORT: u32 = 9;
+const MAX_WATERMARK_MARGIN: u32 = 50;
+
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Every problem found by [`OptionsPayload::validate`]
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct OptionsError {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid options: ")?;
+        for (i, e) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{} {}", e.field, e.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+impl OptionsPayload {
+    /// Rejects combinations that cannot produce a meaningful batch
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        let mut errors = vec![];
+        let mut check = |failed: bool, field: &str, message: &str| {
+            if failed {
+                errors.push(FieldError {
+                    field: field.to_string(),
+                    message: message.to_string(),
+                });
+            }
+        };
+
+        let compression = &self.compression_options;
+        let quality_message = format!("must be between 0 and {MAX_QUALITY}");
+        check(
+            compression.jpeg.quality > MAX_QUALITY,
+            "compression_options.jpeg.quality",
+            &quality_message,
+        );
+        check(
+            compression.png.quality > MAX_QUALITY,
+            "compression_options.png.quality",
+            &quality_message,
+        );
+        check(
+            compression.gif.quality > MAX_QUALITY,
+            "compression_options.gif.quality",
+            &quality_message,
+        );
+        check(
+            compression.webp.quality > MAX_QUALITY,
+            "compression_options.webp.quality",
+            &quality_message,
+        );
+        check(
+            compression.avif.quality > MAX_QUALITY,
+            "compression_options.avif.quality",
+            &quality_message,
+        );
+        check(
+            compression.jxl.quality > MAX_QUALITY,
+            "compression_options.jxl.quality",
+            &quality_message,
+        );
+        check(
+            !(1..=MAX_AVIF_SPEED).contains(&compression.avif.speed),
+            "compression_options.avif.speed",
+            &format!("must be between 1 and {MAX_AVIF_SPEED}"),
+        );
+        check(
+            compression.avif.lossless,
+            "compression_options.avif.lossless",
+            "is not supported by the AVIF encoder",
+        );
+        check(
+            !(1..=MAX_JXL_EFFORT).contains(&compression.jxl.effort),
+            "compression_options.jxl.effort",
+            &format!("must be between 1 and {MAX_JXL_EFFORT}"),
+        );
+        check(
+            compression.png.optimization_level > MAX_PNG_OPTIMIZATION_LEVEL,
+            "compression_options.png.optimization_level",
+            &format!("must be between 0 and {MAX_PNG_OPTIMIZATION_LEVEL}"),
+        );
+        if compression.compression_mode == CompressionMode::Size {
+            check(
+                compression.max_size_value == 0,
+                "compression_options.max_size_value",
+                "must be greater than 0",
+            );
+            check(
+                compression.max_size_unit == 0,
+                "compression_options.max_size_unit",
+                "must be greater than 0",
+            );
+        }
+        if compression.compression_mode == CompressionMode::TargetQuality {
+            let target = &compression.target_quality;
+            check(
+                !(target.ssim > 0.0 && target.ssim <= 1.0),
+                "compression_options.target_quality.ssim",
+                "must be greater than 0 and at most 1",
+            );
+            check(
+                target.max_iterations == 0 || target.max_iterations > MAX_TARGET_ITERATIONS,
+                "compression_options.target_quality.max_iterations",
+                &format!("must be between 1 and {MAX_TARGET_ITERATIONS}"),
+            );
+        }
+
+        let resize = &self.resize_options;
+        if resize.resize_enabled {
+            match resize.resize_mode {
+                ResizeMode::None => {}
+                ResizeMode::Dimensions => {
+                    check(
+                        resize.width == 0,
+                        "resize_options.width",
+                        "must be greater than 0",
+                    );
+                    check(
+                        resize.height == 0,
+                        "resize_options.height",
+                        "must be greater than 0",
+                    );
+                    check(
+                        resize.keep_aspect_ratio
+                            && resize.fit == ResizeFit::Pad
+                            && parse_color(&resize.background).is_none(),
+                        "resize_options.background",
+                        "must be a #RRGGBB or #RRGGBBAA color",
+                    );
+                }
+                ResizeMode::Width => check(
+                    resize.width == 0,
+                    "resize_options.width",
+                    "must be greater than 0",
+                ),
+                ResizeMode::Height => check(
+                    resize.height == 0,
+                    "resize_options.height",
+                    "must be greater than 0",
+                ),
+                ResizeMode::LongEdge => check(
+                    resize.long_edge == 0,
+                    "resize_options.long_edge",
+                    "must be greater than 0",
+                ),
+                ResizeMode::ShortEdge => check(
+                    resize.short_edge == 0,
+                    "resize_options.short_edge",
+                    "must be greater than 0",
+                ),
+                ResizeMode::Percentage => {
+                    check(
+                        resize.width_percentage == 0,
+                        "resize_options.width_percentage",
+                        "must be greater than 0",
+                    );
+                    check(
+                        resize.height_percentage == 0,
+                        "resize_options.height_percentage",
+                        "must be greater than 0",
+                    );
+                }
+                ResizeMode::AspectRatio => {
+                    check(
+                        resize.aspect_ratio_width == 0,
+                        "resize_options.aspect_ratio_width",
+                        "must be greater than 0",
+                    );
+                    check(
+                        resize.aspect_ratio_height == 0,
+                        "resize_options.aspect_ratio_height",
+                        "must be greater than 0",
+                    );
+                }
+                ResizeMode::MaxMegapixels => check(
+                    !(resize.max_megapixels > 0.0 && resize.max_megapixels.is_finite()),
+                    "resize_options.max_megapixels",
+                    "must be greater than 0",
+                ),
+            }
+            check(
+                resize.focal_point_x > 100,
+                "resize_options.focal_point_x",
+                "must be at most 100",
+            );
+            check(
+                resize.focal_point_y > 100,
+                "resize_options.focal_point_y",
+                "must be at most 100",
+            );
+        }
+
+        let fields = &compression.metadata_fields;
+        let templates = [
+            ("artist", &fields.artist),
+            ("copyright", &fields.copyright),
+            ("description", &fields.description),
+        ];
+        for (name, template) in templates {
+            if let Err(e) = TextTemplate::parse(template) {
+                let field = format!("compression_options.metadata_fields.{name}");
+                check(true, &field, &e.to_string());
+            }
+        }
+        for (index, custom) in fields.custom.iter().enumerate() {
+            let prefix = format!("compression_options.metadata_fields.custom[{index}]");
+            let (is_known, message) = match custom.kind {
+                MetadataFieldKind::Xmp => (
+                    metadata::is_known_xmp_property(&custom.name),
+                    "must be a property of a known XMP namespace, like photoshop:Credit",
+                ),
+                MetadataFieldKind::Iptc => (
+                    metadata::iptc_dataset(&custom.name).is_some(),
+                    "must be a known IPTC dataset, like Credit",
+                ),
+            };
+            check(!is_known, &format!("{prefix}.name"), message);
+            if let Err(e) = TextTemplate::parse(&custom.value) {
+                check(true, &format!("{prefix}.value"), &e.to_string());
+            }
+        }
+        check(
+            !fields.is_empty()
+                && matches!(
+                    self.output_options.output_format,
+                    OutputFormat::Avif | OutputFormat::JpegXl
+                ),
+            "compression_options.metadata_fields",
+            "cannot be written to AVIF and JPEG XL outputs",
+        );
+
+        let watermark = &self.watermark_options;
+        if watermark.enabled {
+            match watermark.kind {
+                WatermarkKind::Image => check(
+                    watermark.image_path.trim().is_empty(),
+                    "watermark_options.image_path",
+                    "is required for an image watermark",
+                ),
+                WatermarkKind::Text => {
+                    check(
+                        watermark.text.trim().is_empty(),
+                        "watermark_options.text",
+                        "is required for a text watermark",
+                    );
+                    check(
+                        watermark.font_path.trim().is_empty(),
+                        "watermark_options.font_path",
+                        "is required for a text watermark",
+                    );
+                    check(
+                        parse_color(&watermark.color).is_none(),
+                        "watermark_options.color",
+                        "must be #RRGGBB or #RRGGBBAA",
+                    );
+                }
+            }
+            check(
+                watermark.opacity > 100,
+                "watermark_options.opacity",
+                "must be between 0 and 100",
+            );
+            check(
+                !(1..=100).contains(&watermark.scale),
+                "watermark_options.scale",
+                "must be between 1 and 100",
+            );
+            check(
+                watermark.margin > MAX_WATERMARK_MARGIN,
+                "watermark_options.margin",
+                &format!("must be between 0 and {MAX_WATERMARK_MARGIN}"),
+            );
+        }
+
+        let output = &self.output_options;
+        check(
+            !output.same_folder_as_input && output.output_folder.trim().is_empty(),
+            "output_options.output_folder",
+            "is required when not saving next to the original files",
+        );
+        check(
+            output.decode_only_format == OutputFormat::Original,
+            "output_options.decode_only_format",
+            "must be a format that can be written",
+        );
+        let jxl_message = "needs caesium-core built with the jxl feature";
+        check(
+            !cfg!(feature = "jxl") && output.output_format == OutputFormat::JpegXl,
+            "output_options.output_format",
+            jxl_message,
+        );
+        check(
+            !cfg!(feature = "jxl") && output.decode_only_format == OutputFormat::JpegXl,
+            "output_options.decode_only_format",
+            jxl_message,
+        );
+
+        if !output.filename_template.is_empty() {
+            if let Err(e) = FilenameTemplate::parse(&output.filename_template) {
+                check(true, "output_options.filename_template", &e.to_string());
+            }
+        }
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            errors.extend(rule.validate(index, self));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(OptionsError { errors })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn options() -> OptionsPayload {
+        OptionsPayload {
+            output_options: OutputOptions {
+                same_folder_as_input: true,
+                ..OutputOptions::default()
+            },
+            ..OptionsPayload::default()
+        }
+    }
+
+    fn invalid_fields(options: &OptionsPayload) -> Vec<String> {
+        match options.validate() {
+            Ok(()) => vec![],
+            Err(e) => e.errors.into_iter().map(|e| e.field).collect(),
+        }
+    }
+
+    #[test]
+    fn default_options_are_valid() {
+        assert!(options().validate().is_ok());
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let mut options = options();
+        options.output_options.same_folder_as_input = false;
+        options.compression_options.jpeg.quality = 101;
+        options.compression_options.avif.speed = 0;
+        options.compression_options.avif.lossless = true;
+        options.compression_options.png.optimization_level = 7;
+
+        assert_eq!(
+            invalid_fields(&options),
+            [
+                "compression_options.jpeg.quality",
+                "compression_options.avif.speed",
+                "compression_options.avif.lossless",
+                "compression_options.png.optimization_level",
+                "output_options.output_folder",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "jxl"))]
+    fn jpeg_xl_outputs_need_the_jxl_feature() {
+        let mut options = options();
+        options.output_options.output_format = OutputFormat::JpegXl;
+        options.output_options.decode_only_format = OutputFormat::JpegXl;
+
+        assert_eq!(
+            invalid_fields(&options),
+            [
+                "output_options.output_format",
+                "output_options.decode_only_format",
+            ]
+        );
+    }
+
+    #[test]
+    fn mode_settings_are_only_checked_in_their_mode() {
+        let mut options = options();
+        options.compression_options.max_size_value = 0;
+        options.compression_options.target_quality.ssim = 1.5;
+        assert!(options.validate().is_ok());
+
+        options.compression_options.compression_mode = CompressionMode::Size;
+        assert_eq!(
+            invalid_fields(&options),
+            ["compression_options.max_size_value"]
+        );
+
+        options.compression_options.compression_mode = CompressionMode::TargetQuality;
+        assert_eq!(
+            invalid_fields(&options),
+            ["compression_options.target_quality.ssim"]
+        );
+    }
+
+    #[test]
+    fn resize_checks_the_fields_of_the_mode() {
+        let mut options = options();
+        options.resize_options = ResizeOptions {
+            resize_enabled: true,
+            resize_mode: ResizeMode::Dimensions,
+            width: 0,
+            height_percentage: 0,
+            fit: ResizeFit::Pad,
+            background: "white".to_string(),
+            focal_point_x: 101,
+            ..ResizeOptions::default()
+        };
+        assert_eq!(
+            invalid_fields(&options),
+            [
+                "resize_options.width",
+                "resize_options.background",
+                "resize_options.focal_point_x",
+            ]
+        );
+
+        options.resize_options.resize_enabled = false;
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn rule_overrides_are_validated_with_the_rule_prefix() {
+        let mut options = options();
+        options.rules = vec![Rule {
+            name: "Thumbnails".to_string(),
+            enabled: true,
+            conditions: vec![],
+            overrides: json!({"compression_options": {"jpeg": {"quality": 200}}}),
+        }];
+        assert_eq!(
+            invalid_fields(&options),
+            ["rules[0].overrides.compression_options.jpeg.quality"]
+        );
+    }
+
+    #[test]
+    fn templates_are_parsed() {
+        let mut options = options();
+        options.output_options.filename_template = "{name}/{unknown}".to_string();
+        options.compression_options.metadata_fields.artist = "{name".to_string();
+        options
+            .compression_options
+            .metadata_fields
+            .custom
+            .push(CustomMetadataField {
+                kind: MetadataFieldKind::Xmp,
+                name: "Credit".to_string(),
+                value: "Jane Doe".to_string(),
+            });
+        assert_eq!(
+            invalid_fields(&options),
+            [
+                "compression_options.metadata_fields.artist",
+                "compression_options.metadata_fields.custom[0].name",
+                "output_options.filename_template",
+            ]
+        );
+    }
+
+    #[test]
+    fn metadata_fields_need_an_output_that_holds_them() {
+        let mut options = options();
+        options.compression_options.metadata_fields.artist = "Jane Doe".to_string();
+        options.output_options.output_format = OutputFormat::Avif;
+        assert_eq!(
+            invalid_fields(&options),
+            ["compression_options.metadata_fields"]
+        );
+
+        options.output_options.output_format = OutputFormat::WebP;
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn text_watermarks_need_a_text_a_font_and_a_color() {
+        let mut options = options();
+        options.watermark_options = WatermarkOptions {
+            enabled: true,
+            kind: WatermarkKind::Text,
+            color: "#12345".to_string(),
+            ..WatermarkOptions::default()
+        };
+        assert_eq!(
+            invalid_fields(&options),
+            [
+                "watermark_options.text",
+                "watermark_options.font_path",
+                "watermark_options.color",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("#FF8000"), Some([255, 128, 0, 255]));
+        assert_eq!(parse_color("#ff800080"), Some([255, 128, 0, 128]));
+        assert_eq!(parse_color("FF8000"), None);
+        assert_eq!(parse_color("#FF80"), None);
+        assert_eq!(parse_color("#GG8000"), None);
+    }
+}
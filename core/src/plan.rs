@@ -0,0 +1,298 @@
+use crate::collision::{
+    resolve_batch_collision, resolve_existing_output, OutputReservations, Resolution,
+};
+use crate::compressor::{
+    expected_dimensions, output_path, refuses_to_enlarge, reserve_outputs, CompressionContext,
+    ENLARGE_SKIP_REASON,
+};
+use crate::errors::{CompressionError, CompressionErrorCode};
+use crate::journal::OriginalAction;
+use crate::options::{CollisionPolicy, MoveOriginalFileMode, OptionsError, OptionsPayload};
+use crate::rules::resolve_options;
+use crate::CImage;
+use chrono::Local;
+use std::path::Path;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedAction {
+    Compress,
+    Skip,
+    Fail,
+}
+
+/// What compressing one image would do
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ImagePlan {
+    pub id: String,
+    pub input_path: String,
+    /// Output after the collision policy, empty if it cannot be computed
+    pub output_path: String,
+    pub action: PlannedAction,
+    /// Why the image would be skipped or fail
+    pub reason: String,
+    pub error_code: Option<CompressionErrorCode>,
+    pub applied_rule: Option<String>,
+    /// Policy that would be applied because the output path is taken
+    pub collision_policy: Option<CollisionPolicy>,
+    /// Expected size of the output, the input size when it is not resized
+    pub output_width: usize,
+    pub output_height: usize,
+    pub original: OriginalAction,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct CompressionPlan {
+    pub images: Vec<ImagePlan>,
+    pub total_compress: usize,
+    pub total_skip: usize,
+    pub total_fail: usize,
+    /// Images planned to the same output path as an earlier image of the batch
+    pub output_collisions: usize,
+    /// Originals that would be replaced, trashed or deleted
+    pub total_originals_affected: usize,
+}
+
+/// Computes what [`compress_images`](crate::batch::compress_images) would do with
+/// `images`, without creating folders or writing files.
+///
+/// Outputs reused from the cache are planned as if they were compressed again, and
+/// images that end up skipped because the output is bigger than the input cannot be
+/// known in advance.
+pub fn plan_compression(
+    images: &[CImage],
+    options: &OptionsPayload,
+    base_folder: &str,
+    max_file_size: u64,
+) -> Result<CompressionPlan, OptionsError> {
+    options.validate()?;
+
+    let started = Local::now();
+    let reservations = OutputReservations::new();
+    let context = |index: usize| CompressionContext {
+        reservations: Some(&reservations),
+        counter: index + 1,
+        started,
+        ..CompressionContext::new(base_folder, max_file_size)
+    };
+
+    let mut plan = CompressionPlan {
+        output_collisions: reserve_outputs(images, options, context),
+        ..CompressionPlan::default()
+    };
+    for (index, cimage) in images.iter().enumerate() {
+        let image = plan_cimage(cimage, options, &context(index));
+        match image.action {
+            PlannedAction::Compress => plan.total_compress += 1,
+            PlannedAction::Skip => plan.total_skip += 1,
+            PlannedAction::Fail => plan.total_fail += 1,
+        }
+        if image.original != OriginalAction::Kept {
+            plan.total_originals_affected += 1;
+        }
+        plan.images.push(image);
+    }
+    Ok(plan)
+}
+
+fn plan_cimage(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    context: &CompressionContext,
+) -> ImagePlan {
+    let (options, applied_rule) = resolve_options(options, cimage);
+    let (output_width, output_height) = expected_dimensions(&options, cimage);
+    let mut plan = ImagePlan {
+        id: cimage.id.clone(),
+        input_path: cimage.path.clone(),
+        output_path: String::new(),
+        action: PlannedAction::Compress,
+        reason: String::new(),
+        error_code: None,
+        applied_rule,
+        collision_policy: None,
+        output_width,
+        output_height,
+        original: OriginalAction::Kept,
+    };
+    if let Err(e) = plan_output(cimage, &options, context, &mut plan) {
+        plan.action = PlannedAction::Fail;
+        plan.reason = e.to_string();
+        plan.error_code = Some(e.code());
+    }
+    plan
+}
+
+// Follows the same steps as compress_cimage, stopping before anything is written
+fn plan_output(
+    cimage: &CImage,
+    options: &OptionsPayload,
+    context: &CompressionContext,
+    plan: &mut ImagePlan,
+) -> Result<(), CompressionError> {
+    let policy = options.output_options.collision_policy;
+    let input = Path::new(&cimage.path);
+
+    let path = output_path(cimage, options, context)?;
+    let path = match resolve_batch_collision(&cimage.id, path, policy, context.reservations)? {
+        Resolution::Write { path, collision } => {
+            plan.collision_policy = collision;
+            path
+        }
+        Resolution::Skip { reason, collision } => {
+            plan.collision_policy = Some(collision);
+            plan.skip(reason);
+            return Ok(());
+        }
+    };
+    let reservations = context.reservations;
+    let path = match resolve_existing_output(&cimage.id, input, &path, policy, reservations)? {
+        Resolution::Write { path, collision } => {
+            plan.collision_policy = collision.or(plan.collision_policy);
+            path
+        }
+        Resolution::Skip { reason, collision } => {
+            plan.collision_policy = Some(collision);
+            plan.output_path = path.display().to_string();
+            plan.skip(reason);
+            return Ok(());
+        }
+    };
+    plan.output_path = path.display().to_string();
+
+    if cimage.size > context.max_file_size {
+        return Err(CompressionError::TooLarge(context.max_file_size));
+    }
+    if refuses_to_enlarge(options, cimage) {
+        plan.skip(ENLARGE_SKIP_REASON);
+        return Ok(());
+    }
+
+    let output_options = &options.output_options;
+    plan.original = if path == input {
+        OriginalAction::Replaced
+    } else if output_options.move_original_file_enabled {
+        match output_options.move_original_file_mode {
+            MoveOriginalFileMode::Trash => OriginalAction::Trashed,
+            MoveOriginalFileMode::Delete => OriginalAction::Deleted,
+        }
+    } else {
+        OriginalAction::Kept
+    };
+    Ok(())
+}
+
+impl ImagePlan {
+    fn skip(&mut self, reason: &str) {
+        self.action = PlannedAction::Skip;
+        self.reason = reason.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OutputOptions;
+    use crate::scan_files::map_file;
+    use std::fs;
+
+    const MAX_FILE_SIZE: u64 = 10;
+
+    /// Images written in `input/` with the given sizes, planned into `output/`
+    struct Fixture {
+        root: tempfile::TempDir,
+        images: Vec<CImage>,
+        options: OptionsPayload,
+    }
+
+    impl Fixture {
+        fn new(files: &[(&str, usize)]) -> Self {
+            let root = tempfile::tempdir().unwrap();
+            fs::create_dir(root.path().join("input")).unwrap();
+            let images = files
+                .iter()
+                .map(|(name, size)| {
+                    let path = root.path().join("input").join(name);
+                    fs::write(&path, vec![0; *size]).unwrap();
+                    map_file(&path).unwrap()
+                })
+                .collect();
+            let options = OptionsPayload {
+                output_options: OutputOptions {
+                    output_folder: root.path().join("output").display().to_string(),
+                    ..OutputOptions::default()
+                },
+                ..OptionsPayload::default()
+            };
+            Self {
+                root,
+                images,
+                options,
+            }
+        }
+
+        fn output(&self) -> std::path::PathBuf {
+            self.root.path().join("output")
+        }
+
+        fn plan(&self) -> CompressionPlan {
+            let input = self.root.path().join("input").display().to_string();
+            plan_compression(&self.images, &self.options, &input, MAX_FILE_SIZE).unwrap()
+        }
+    }
+
+    #[test]
+    fn plans_every_image_without_writing_anything() {
+        let fixture = Fixture::new(&[("a.jpg", 5), ("big.jpg", 20)]);
+
+        let plan = fixture.plan();
+
+        assert_eq!(
+            (plan.total_compress, plan.total_skip, plan.total_fail),
+            (1, 0, 1)
+        );
+        let [a, big] = &plan.images[..] else {
+            unreachable!();
+        };
+        assert_eq!(a.action, PlannedAction::Compress);
+        assert_eq!(
+            a.output_path,
+            fixture.output().join("a.jpg").display().to_string()
+        );
+        assert_eq!(a.original, OriginalAction::Kept);
+        assert_eq!(big.action, PlannedAction::Fail);
+        assert_eq!(big.error_code, Some(CompressionErrorCode::TooLarge));
+        assert!(!fixture.output().exists());
+    }
+
+    #[test]
+    fn existing_outputs_follow_the_collision_policy() {
+        let mut fixture = Fixture::new(&[("a.jpg", 5)]);
+        fs::create_dir(fixture.output()).unwrap();
+        fs::write(fixture.output().join("a.jpg"), b"old").unwrap();
+        fixture.options.output_options.collision_policy = CollisionPolicy::Skip;
+
+        let plan = fixture.plan();
+
+        assert_eq!(plan.total_skip, 1);
+        assert_eq!(plan.images[0].action, PlannedAction::Skip);
+        assert_eq!(plan.images[0].collision_policy, Some(CollisionPolicy::Skip));
+    }
+
+    #[test]
+    fn affected_originals_are_counted() {
+        let mut fixture = Fixture::new(&[("a.jpg", 5), ("b.jpg", 5)]);
+        fixture.options.output_options.same_folder_as_input = true;
+
+        let plan = fixture.plan();
+        assert_eq!(plan.total_originals_affected, 2);
+        assert_eq!(plan.images[0].original, OriginalAction::Replaced);
+
+        fixture.options.output_options.same_folder_as_input = false;
+        fixture.options.output_options.move_original_file_enabled = true;
+        fixture.options.output_options.move_original_file_mode = MoveOriginalFileMode::Delete;
+        let plan = fixture.plan();
+        assert_eq!(plan.total_originals_affected, 2);
+        assert_eq!(plan.images[0].original, OriginalAction::Deleted);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::options::{OptionsError, OptionsPayload};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version written in every preset file, bump it and extend [`migrate`] when the schema changes
+pub const PRESET_SCHEMA_VERSION: u32 = 1;
+
+const PRESET_EXTENSION: &str = "json";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub options: OptionsPayload,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PresetFile {
+    version: u32,
+    #[serde(flatten)]
+    preset: Preset,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PresetError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    TomlDeserialize(#[from] toml::de::Error),
+    #[error(transparent)]
+    TomlSerialize(#[from] toml::ser::Error),
+    #[error(transparent)]
+    InvalidOptions(#[from] OptionsError),
+    #[error("Preset not found: {0}")]
+    NotFound(String),
+    #[error("A preset named {0} already exists")]
+    AlreadyExists(String),
+    #[error("Invalid preset name")]
+    InvalidName,
+    #[error("Unsupported preset version {0}")]
+    UnsupportedVersion(u64),
+    #[error("Unsupported preset file format: {0}")]
+    UnsupportedFormat(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PresetFormat {
+    Json,
+    Toml,
+}
+
+impl PresetFormat {
+    pub fn from_path(path: &Path) -> Result<Self, PresetError> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "json" => Ok(PresetFormat::Json),
+            "toml" => Ok(PresetFormat::Toml),
+            _ => Err(PresetError::UnsupportedFormat(extension)),
+        }
+    }
+}
+
+/// Presets saved as one JSON file each inside `directory`
+pub struct PresetStore {
+    directory: PathBuf,
+}
+
+impl PresetStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<Preset>, PresetError> {
+        if !self.directory.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut presets = vec![];
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != PRESET_EXTENSION) {
+                continue;
+            }
+            match read_preset_file(&path, PresetFormat::Json) {
+                Ok(p) => presets.push(p),
+                Err(e) => log::warn!("Skipping preset {}: {e}", path.display()),
+            }
+        }
+        presets.sort_by_key(|p| p.name.to_lowercase());
+        Ok(presets)
+    }
+
+    pub fn get(&self, name: &str) -> Result<Preset, PresetError> {
+        let path = self.preset_path(name)?;
+        if !path.exists() {
+            return Err(PresetError::NotFound(name.to_string()));
+        }
+        read_preset_file(&path, PresetFormat::Json)
+    }
+
+    pub fn create(&self, preset: Preset) -> Result<Preset, PresetError> {
+        let path = self.preset_path(&preset.name)?;
+        if path.exists() {
+            return Err(PresetError::AlreadyExists(preset.name));
+        }
+        self.save(&path, preset)
+    }
+
+    pub fn update(&self, name: &str, preset: Preset) -> Result<Preset, PresetError> {
+        let old_path = self.preset_path(name)?;
+        if !old_path.exists() {
+            return Err(PresetError::NotFound(name.to_string()));
+        }
+        let new_path = self.preset_path(&preset.name)?;
+        if new_path != old_path && new_path.exists() {
+            return Err(PresetError::AlreadyExists(preset.name));
+        }
+
+        let preset = self.save(&new_path, preset)?;
+        if new_path != old_path {
+            fs::remove_file(old_path)?;
+        }
+        Ok(preset)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), PresetError> {
+        let path = self.preset_path(name)?;
+        if !path.exists() {
+            return Err(PresetError::NotFound(name.to_string()));
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    pub fn duplicate(&self, name: &str, new_name: &str) -> Result<Preset, PresetError> {
+        let preset = self.get(name)?;
+        self.create(Preset {
+            name: new_name.to_string(),
+            ..preset
+        })
+    }
+
+    /// Adds the preset stored in `path` (JSON or TOML) to the store
+    pub fn import(&self, path: &Path) -> Result<Preset, PresetError> {
+        let preset = load_preset_file(path)?;
+        self.create(preset)
+    }
+
+    /// Writes the preset `name` to `path`, the format is picked from the extension
+    pub fn export(&self, name: &str, path: &Path) -> Result<(), PresetError> {
+        let preset = self.get(name)?;
+        write_preset_file(path, &preset, PresetFormat::from_path(path)?)
+    }
+
+    fn save(&self, path: &Path, preset: Preset) -> Result<Preset, PresetError> {
+        preset.options.validate()?;
+        fs::create_dir_all(&self.directory)?;
+        write_preset_file(path, &preset, PresetFormat::Json)?;
+        Ok(preset)
+    }
+
+    fn preset_path(&self, name: &str) -> Result<PathBuf, PresetError> {
+        let file_stem = slugify(name);
+        if file_stem.is_empty() {
+            return Err(PresetError::InvalidName);
+        }
+        Ok(self
+            .directory
+            .join(file_stem)
+            .with_extension(PRESET_EXTENSION))
+    }
+}
+
+/// Reads a JSON or TOML preset file, migrating it to the current schema
+pub fn load_preset_file(path: &Path) -> Result<Preset, PresetError> {
+    read_preset_file(path, PresetFormat::from_path(path)?)
+}
+
+fn read_preset_file(path: &Path, format: PresetFormat) -> Result<Preset, PresetError> {
+    let content = fs::read_to_string(path)?;
+    let value: Value = match format {
+        PresetFormat::Json => serde_json::from_str(&content)?,
+        PresetFormat::Toml => {
+            let value: toml::Value = toml::from_str(&content)?;
+            serde_json::to_value(value)?
+        }
+    };
+    let fallback_name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let file: PresetFile = serde_json::from_value(migrate(value, &fallback_name)?)?;
+    Ok(file.preset)
+}
+
+fn write_preset_file(
+    path: &Path,
+    preset: &Preset,
+    format: PresetFormat,
+) -> Result<(), PresetError> {
+    let file = PresetFile {
+        version: PRESET_SCHEMA_VERSION,
+        preset: preset.clone(),
+    };
+    let content = match format {
+        PresetFormat::Json => serde_json::to_string_pretty(&file)?,
+        PresetFormat::Toml => toml::to_string_pretty(&file)?,
+    };
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Brings a preset document up to [`PRESET_SCHEMA_VERSION`].
+///
+/// Documents without a version are bare `OptionsPayload` objects, as sent by the
+/// app or used as CLI config, and get wrapped using `fallback_name`.
+fn migrate(mut value: Value, fallback_name: &str) -> Result<Value, PresetError> {
+    let version = match value.get("version") {
+        Some(v) => v.as_u64().ok_or(PresetError::UnsupportedVersion(0))?,
+        None => 0,
+    };
+
+    if version > PRESET_SCHEMA_VERSION as u64 {
+        return Err(PresetError::UnsupportedVersion(version));
+    }
+
+    if version == 0 {
+        value = serde_json::json!({
+            "version": 1,
+            "name": fallback_name,
+            "options": value,
+        });
+    }
+
+    Ok(value)
+}
+
+fn slugify(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,456 @@
+use crate::atomic_file::AtomicFile;
+use crate::compressor::{CompressionResult, CompressionStatus, CompressionSummary};
+use crate::errors::CompressionErrorCode;
+use chrono::Local;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    Csv,
+    Json,
+    Html,
+}
+
+impl ReportFormat {
+    /// Guesses the format from the extension of `path`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.to_lowercase().parse().ok()
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            "html" | "htm" => Ok(ReportFormat::Html),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Outcome of one image of the batch
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ReportEntry {
+    pub input_path: String,
+    pub output_path: String,
+    pub status: CompressionStatus,
+    pub info: String,
+    pub error_code: Option<CompressionErrorCode>,
+    pub applied_rule: Option<String>,
+    /// The output of a previous run was reused
+    pub cached: bool,
+    pub original_size: u64,
+    pub compressed_size: u64,
+    /// Negative when the output is bigger than the input
+    pub saved_size: i64,
+    pub saved_percentage: f64,
+    pub original_width: usize,
+    pub original_height: usize,
+    pub compressed_width: usize,
+    pub compressed_height: usize,
+    /// Time spent on the image, in ms
+    pub elapsed: u64,
+    /// Only when quality metrics were measured
+    pub ssim: Option<f64>,
+    pub psnr: Option<f64>,
+}
+
+/// Per-image results and totals of a finished batch
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct CompressionReport {
+    /// Start of the batch, in RFC 3339 format
+    pub started: String,
+    pub summary: CompressionSummary,
+    /// Images in input order, those not reached before a cancellation are missing
+    pub images: Vec<ReportEntry>,
+}
+
+/// Collects the results of a running batch, see [`BatchSettings::report`](crate::batch::BatchSettings::report)
+pub struct ReportRecorder {
+    started: String,
+    entries: Mutex<Vec<(usize, ReportEntry)>>,
+}
+
+impl Default for ReportRecorder {
+    fn default() -> Self {
+        Self {
+            started: Local::now().to_rfc3339(),
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl ReportRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the result of the image at `index` in the batch
+    pub(crate) fn record(&self, index: usize, result: &CompressionResult, elapsed: Duration) {
+        let cimage = &result.cimage;
+        let saved_size = cimage.size as i64 - cimage.compressed_size as i64;
+        let saved_percentage = if cimage.size == 0 {
+            0.0
+        } else {
+            saved_size as f64 / cimage.size as f64 * 100.0
+        };
+        let entry = ReportEntry {
+            input_path: cimage.path.clone(),
+            output_path: cimage.compressed_file_path.clone(),
+            status: result.status,
+            info: cimage.info.clone(),
+            error_code: cimage.error_code,
+            applied_rule: cimage.applied_rule.clone(),
+            cached: result.cached,
+            original_size: cimage.size,
+            compressed_size: cimage.compressed_size,
+            saved_size,
+            saved_percentage,
+            original_width: cimage.width,
+            original_height: cimage.height,
+            compressed_width: cimage.compressed_width,
+            compressed_height: cimage.compressed_height,
+            elapsed: elapsed.as_millis() as u64,
+            ssim: cimage.quality_metrics.map(|m| m.ssim),
+            psnr: cimage.quality_metrics.map(|m| m.psnr),
+        };
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((index, entry));
+    }
+
+    pub fn finish(self, summary: &CompressionSummary) -> CompressionReport {
+        let mut entries = self.entries.into_inner().unwrap_or_else(|e| e.into_inner());
+        entries.sort_by_key(|(index, _)| *index);
+        CompressionReport {
+            started: self.started,
+            summary: summary.clone(),
+            images: entries.into_iter().map(|(_, entry)| entry).collect(),
+        }
+    }
+}
+
+impl CompressionReport {
+    pub fn render(&self, format: ReportFormat) -> Result<String, ReportError> {
+        match format {
+            ReportFormat::Csv => Ok(self.to_csv()),
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ReportFormat::Html => Ok(self.to_html()),
+        }
+    }
+
+    pub fn save(&self, path: &Path, format: ReportFormat) -> Result<(), ReportError> {
+        let content = self.render(format)?;
+        let mut file = AtomicFile::create(path)?;
+        file.write_all(content.as_bytes())?;
+        file.commit()?;
+        Ok(())
+    }
+
+    // One row per image, totals are left to the spreadsheet
+    fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "input_path,output_path,status,info,error_code,applied_rule,cached,original_size,\
+             compressed_size,saved_size,saved_percentage,original_width,original_height,\
+             compressed_width,compressed_height,elapsed_ms,ssim,psnr\n",
+        );
+        for entry in &self.images {
+            let fields = [
+                entry.input_path.clone(),
+                entry.output_path.clone(),
+                status_label(entry.status).to_string(),
+                entry.info.clone(),
+                entry.error_code.map(code_label).unwrap_or_default(),
+                entry.applied_rule.clone().unwrap_or_default(),
+                entry.cached.to_string(),
+                entry.original_size.to_string(),
+                entry.compressed_size.to_string(),
+                entry.saved_size.to_string(),
+                format!("{:.2}", entry.saved_percentage),
+                entry.original_width.to_string(),
+                entry.original_height.to_string(),
+                entry.compressed_width.to_string(),
+                entry.compressed_height.to_string(),
+                entry.elapsed.to_string(),
+                entry.ssim.map(|v| format!("{v:.4}")).unwrap_or_default(),
+                entry.psnr.map(|v| format!("{v:.2}")).unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    fn to_html(&self) -> String {
+        let summary = &self.summary;
+        let saved = summary.original_size as i64 - summary.compressed_size as i64;
+        let saved_percentage = if summary.original_size == 0 {
+            0.0
+        } else {
+            saved as f64 / summary.original_size as f64 * 100.0
+        };
+
+        let mut rows = String::new();
+        for entry in &self.images {
+            let _ = writeln!(
+                rows,
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                 <td>{:.2}%</td><td>{}x{}</td><td>{}x{}</td><td>{} ms</td><td>{}</td><td>{}</td></tr>",
+                status_label(entry.status),
+                html_escape(&entry.input_path),
+                html_escape(&entry.output_path),
+                status_label(entry.status),
+                format_size(entry.original_size),
+                format_size(entry.compressed_size),
+                entry.saved_percentage,
+                entry.original_width,
+                entry.original_height,
+                entry.compressed_width,
+                entry.compressed_height,
+                entry.elapsed,
+                format_quality(entry.ssim, entry.psnr),
+                html_escape(&entry.info),
+            );
+        }
+
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Compression report {started}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem; color: #222; }}
+table {{ border-collapse: collapse; width: 100%; font-size: 0.85rem; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 0.4rem; text-align: left; }}
+th {{ background: #f4f4f4; }}
+.totals td {{ border: none; padding: 0.2rem 1rem 0.2rem 0; }}
+tr.warning td {{ background: #fff8e1; }}
+tr.error td {{ background: #fdecea; }}
+</style>
+</head>
+<body>
+<h1>Compression report</h1>
+<table class="totals">
+<tr><td>Started</td><td>{started}</td></tr>
+<tr><td>Images</td><td>{total} ({success} compressed, {skipped} skipped, {errors} failed, {cached} unchanged)</td></tr>
+<tr><td>Original size</td><td>{original}</td></tr>
+<tr><td>Compressed size</td><td>{compressed}</td></tr>
+<tr><td>Saved</td><td>{saved} ({saved_percentage:.2}%)</td></tr>
+<tr><td>Time</td><td>{time} ms</td></tr>
+</table>
+<h2>Images</h2>
+<table>
+<tr><th>Input</th><th>Output</th><th>Status</th><th>Original size</th><th>Compressed size</th><th>Saved</th><th>Original resolution</th><th>Compressed resolution</th><th>Time</th><th>SSIM / PSNR</th><th>Info</th></tr>
+{rows}</table>
+</body>
+</html>
+"#,
+            started = html_escape(&self.started),
+            total = summary.total_images,
+            success = summary.total_success,
+            skipped = summary.total_skipped,
+            errors = summary.total_errors,
+            cached = summary.total_cached,
+            original = format_size(summary.original_size as u64),
+            compressed = format_size(summary.compressed_size as u64),
+            saved = format_signed_size(saved),
+            time = summary.total_time,
+        )
+    }
+}
+
+fn status_label(status: CompressionStatus) -> &'static str {
+    match status {
+        CompressionStatus::Success => "success",
+        CompressionStatus::Warning => "warning",
+        CompressionStatus::Error => "error",
+    }
+}
+
+fn code_label(code: CompressionErrorCode) -> String {
+    serde_json::to_value(code)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.2} {}", UNITS[unit])
+}
+
+fn format_quality(ssim: Option<f64>, psnr: Option<f64>) -> String {
+    match (ssim, psnr) {
+        (Some(ssim), Some(psnr)) => format!("{ssim:.4} / {psnr:.2} dB"),
+        _ => String::from("-"),
+    }
+}
+
+fn format_signed_size(bytes: i64) -> String {
+    let size = format_size(bytes.unsigned_abs());
+    if bytes < 0 {
+        format!("-{size}")
+    } else {
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CImage;
+    use std::collections::BTreeMap;
+
+    fn result(path: &str, size: u64, compressed_size: u64, info: &str) -> CompressionResult {
+        CompressionResult {
+            status: CompressionStatus::Success,
+            cimage: CImage {
+                path: path.to_string(),
+                size,
+                compressed_size,
+                info: info.to_string(),
+                ..CImage::default()
+            },
+            cached: false,
+        }
+    }
+
+    fn summary() -> CompressionSummary {
+        CompressionSummary {
+            total_images: 2,
+            total_success: 2,
+            total_skipped: 0,
+            total_errors: 0,
+            total_cached: 0,
+            errors_by_code: BTreeMap::new(),
+            job_id: None,
+            output_collisions: 0,
+            original_size: 300,
+            compressed_size: 300,
+            total_time: 10,
+        }
+    }
+
+    /// A report of two images recorded out of order
+    fn report() -> CompressionReport {
+        let recorder = ReportRecorder::new();
+        let mut failed = result("b.png", 100, 250, "Too large, \"skipped\"");
+        failed.status = CompressionStatus::Error;
+        failed.cimage.error_code = Some(CompressionErrorCode::TooLarge);
+        recorder.record(1, &failed, Duration::from_millis(3));
+        recorder.record(0, &result("<a>.jpg", 200, 50, ""), Duration::from_millis(7));
+        recorder.finish(&summary())
+    }
+
+    #[test]
+    fn images_are_listed_in_input_order() {
+        let report = report();
+
+        let [a, b] = &report.images[..] else {
+            unreachable!();
+        };
+        assert_eq!(a.input_path, "<a>.jpg");
+        assert_eq!(a.saved_size, 150);
+        assert_eq!(a.saved_percentage, 75.0);
+        assert_eq!(a.elapsed, 7);
+        assert_eq!(b.input_path, "b.png");
+        assert_eq!(b.saved_size, -150);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        let csv = report().render(ReportFormat::Csv).unwrap();
+
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].starts_with("<a>.jpg,,success,,,,false,200,50,150,75.00,"));
+        assert!(rows[2].starts_with(
+            "b.png,,error,\"Too large, \"\"skipped\"\"\",too_large,,false,100,250,-150,-150.00,"
+        ));
+    }
+
+    #[test]
+    fn html_values_are_escaped() {
+        let html = report().render(ReportFormat::Html).unwrap();
+
+        assert!(html.contains("<td>&lt;a&gt;.jpg</td>"));
+        assert!(html.contains("Too large, &quot;skipped&quot;"));
+        assert!(!html.contains("<a>"));
+    }
+
+    #[test]
+    fn json_reports_are_read_back() {
+        let json = report().render(ReportFormat::Json).unwrap();
+
+        let report: CompressionReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(report.images.len(), 2);
+        assert_eq!(report.summary.total_images, 2);
+    }
+
+    #[test]
+    fn formats_are_guessed_from_the_extension() {
+        assert_eq!(
+            ReportFormat::from_path(Path::new("report.HTM")),
+            Some(ReportFormat::Html)
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("report.csv")),
+            Some(ReportFormat::Csv)
+        );
+        assert_eq!(ReportFormat::from_path(Path::new("report.txt")), None);
+        assert_eq!(ReportFormat::from_path(Path::new("report")), None);
+    }
+
+    #[test]
+    fn sizes_are_human_readable() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.50 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.00 MB");
+        assert_eq!(format_signed_size(-2048), "-2.00 KB");
+    }
+}
@@ -0,0 +1,366 @@
+use crate::options::{parse_color, ResizeFit, ResizeMode, ResizeOptions};
+use image::imageops;
+use image::{DynamicImage, Rgba, RgbaImage};
+
+/// Crop or padding applied to the scaled image, which gives the exact output size
+#[derive(Clone, Debug, PartialEq)]
+pub enum Frame {
+    /// Keeps the part of the image around the focal point, in percent of its width and height
+    Crop {
+        width: u32,
+        height: u32,
+        focal_point: (u32, u32),
+    },
+    /// Centers the image on a canvas of the background color
+    Pad {
+        width: u32,
+        height: u32,
+        background: [u8; 4],
+    },
+}
+
+impl Frame {
+    pub fn size(&self) -> (u32, u32) {
+        match *self {
+            Frame::Crop { width, height, .. } | Frame::Pad { width, height, .. } => (width, height),
+        }
+    }
+
+    /// Images without an alpha channel only get one when padded with a translucent color
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        match *self {
+            Frame::Crop {
+                width,
+                height,
+                focal_point: (x, y),
+            } => {
+                let width = width.min(image.width());
+                let height = height.min(image.height());
+                let left = window_start(image.width(), width, x);
+                let top = window_start(image.height(), height, y);
+                image.crop_imm(left, top, width, height)
+            }
+            Frame::Pad {
+                width,
+                height,
+                background,
+            } => {
+                let has_alpha = image.color().has_alpha() || background[3] < u8::MAX;
+                let mut canvas = RgbaImage::from_pixel(width, height, Rgba(background));
+                let x = (i64::from(width) - i64::from(image.width())) / 2;
+                let y = (i64::from(height) - i64::from(image.height())) / 2;
+                imageops::overlay(&mut canvas, &image.to_rgba8(), x, y);
+                if has_alpha {
+                    DynamicImage::ImageRgba8(canvas)
+                } else {
+                    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+                }
+            }
+        }
+    }
+}
+
+/// Size a `width`×`height` image is scaled to before its [`Frame`] is applied, `None`
+/// when it is not scaled. A 0 width or height keeps the aspect ratio of the other one.
+pub fn scale_target(resize: &ResizeOptions, width: u32, height: u32) -> Option<(u32, u32)> {
+    if !resize.resize_enabled {
+        return None;
+    }
+    let is_landscape = width > height;
+    let target = match resize.resize_mode {
+        ResizeMode::None | ResizeMode::AspectRatio => return None,
+        ResizeMode::Dimensions if !resize.keep_aspect_ratio => (resize.width, resize.height),
+        ResizeMode::Dimensions => {
+            let width_ratio = f64::from(resize.width) / f64::from(width.max(1));
+            let height_ratio = f64::from(resize.height) / f64::from(height.max(1));
+            let ratio = match resize.fit {
+                ResizeFit::Contain => width_ratio.min(height_ratio),
+                ResizeFit::Cover => width_ratio.max(height_ratio),
+                // The padding fills the box, so the pixels do not need to be enlarged
+                ResizeFit::Pad if resize.do_not_enlarge => width_ratio.min(height_ratio).min(1.0),
+                ResizeFit::Pad => width_ratio.min(height_ratio),
+            };
+            let (scaled_width, scaled_height) = scale(width, height, ratio);
+            // Rounding must not leave a gap in the box, or overflow it
+            match resize.fit {
+                ResizeFit::Cover => (
+                    scaled_width.max(resize.width),
+                    scaled_height.max(resize.height),
+                ),
+                ResizeFit::Contain | ResizeFit::Pad => (
+                    scaled_width.min(resize.width),
+                    scaled_height.min(resize.height),
+                ),
+            }
+        }
+        ResizeMode::Percentage => {
+            let height_percentage = if resize.keep_aspect_ratio {
+                resize.width_percentage
+            } else {
+                resize.height_percentage
+            };
+            (
+                f64::from(resize.width_percentage * width / 100).round() as u32,
+                f64::from(height_percentage * height / 100).round() as u32,
+            )
+        }
+        ResizeMode::ShortEdge if is_landscape => (0, resize.short_edge),
+        ResizeMode::ShortEdge => (resize.short_edge, 0),
+        ResizeMode::LongEdge if is_landscape => (resize.long_edge, 0),
+        ResizeMode::LongEdge => (0, resize.long_edge),
+        ResizeMode::Width => (resize.width, 0),
+        ResizeMode::Height => (0, resize.height),
+        ResizeMode::MaxMegapixels => {
+            let pixels = f64::from(width) * f64::from(height);
+            let max_pixels = resize.max_megapixels * 1_000_000.0;
+            if pixels <= max_pixels {
+                return None;
+            }
+            // Rounded down so that the output never goes over the limit
+            let ratio = (max_pixels / pixels).sqrt();
+            let scaled = |value: u32| ((f64::from(value) * ratio) as u32).max(1);
+            (scaled(width), scaled(height))
+        }
+    };
+    Some(target)
+}
+
+/// Crop or padding of a `width`×`height` image once scaled, `None` when the scaled
+/// image is the output
+pub fn frame(resize: &ResizeOptions, width: u32, height: u32) -> Option<Frame> {
+    if !resize.resize_enabled {
+        return None;
+    }
+    let focal_point = (resize.focal_point_x.min(100), resize.focal_point_y.min(100));
+    match resize.resize_mode {
+        ResizeMode::Dimensions if resize.keep_aspect_ratio => match resize.fit {
+            ResizeFit::Contain => None,
+            ResizeFit::Cover => Some(Frame::Crop {
+                width: resize.width,
+                height: resize.height,
+                focal_point,
+            }),
+            ResizeFit::Pad => Some(Frame::Pad {
+                width: resize.width,
+                height: resize.height,
+                background: parse_color(&resize.background)?,
+            }),
+        },
+        ResizeMode::AspectRatio => {
+            let ratio_width = u64::from(resize.aspect_ratio_width);
+            let ratio_height = u64::from(resize.aspect_ratio_height);
+            if ratio_width == 0 || ratio_height == 0 {
+                return None;
+            }
+            // Largest area of the ratio that fits in the image
+            let (source_width, source_height) = (u64::from(width), u64::from(height));
+            let (crop_width, crop_height) =
+                if source_width * ratio_height > source_height * ratio_width {
+                    (source_height * ratio_width / ratio_height, source_height)
+                } else {
+                    (source_width, source_width * ratio_height / ratio_width)
+                };
+            let crop = (crop_width.max(1) as u32, crop_height.max(1) as u32);
+            (crop != (width, height)).then_some(Frame::Crop {
+                width: crop.0,
+                height: crop.1,
+                focal_point,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn scale(width: u32, height: u32, ratio: f64) -> (u32, u32) {
+    let scaled = |value: u32| ((f64::from(value) * ratio).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+/// Start of a `length` window centered on `focal_point` percent of `total`, kept inside it
+fn window_start(total: u32, length: u32, focal_point: u32) -> u32 {
+    let center = u64::from(total) * u64::from(focal_point) / 100;
+    let start = center.saturating_sub(u64::from(length / 2));
+    start.min(u64::from(total - length)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resize(resize_mode: ResizeMode) -> ResizeOptions {
+        ResizeOptions {
+            resize_enabled: true,
+            resize_mode,
+            width: 400,
+            height: 300,
+            ..ResizeOptions::default()
+        }
+    }
+
+    fn fitted(fit: ResizeFit) -> ResizeOptions {
+        ResizeOptions {
+            fit,
+            ..resize(ResizeMode::Dimensions)
+        }
+    }
+
+    #[test]
+    fn disabled_resize_keeps_the_size() {
+        let options = ResizeOptions {
+            resize_enabled: false,
+            ..fitted(ResizeFit::Cover)
+        };
+        assert_eq!(scale_target(&options, 1000, 1000), None);
+        assert_eq!(frame(&options, 1000, 1000), None);
+    }
+
+    #[test]
+    fn fits_the_dimensions() {
+        assert_eq!(
+            scale_target(&fitted(ResizeFit::Contain), 1000, 1000),
+            Some((300, 300))
+        );
+        assert_eq!(
+            scale_target(&fitted(ResizeFit::Cover), 1000, 1000),
+            Some((400, 400))
+        );
+        assert_eq!(
+            scale_target(&fitted(ResizeFit::Pad), 1000, 1000),
+            Some((300, 300))
+        );
+        let stretched = ResizeOptions {
+            keep_aspect_ratio: false,
+            ..fitted(ResizeFit::Contain)
+        };
+        assert_eq!(scale_target(&stretched, 1000, 1000), Some((400, 300)));
+    }
+
+    #[test]
+    fn padding_does_not_enlarge_small_images() {
+        assert_eq!(
+            scale_target(&fitted(ResizeFit::Pad), 100, 50),
+            Some((100, 50))
+        );
+        let enlarged = ResizeOptions {
+            do_not_enlarge: false,
+            ..fitted(ResizeFit::Pad)
+        };
+        assert_eq!(scale_target(&enlarged, 100, 50), Some((400, 200)));
+    }
+
+    #[test]
+    fn scaled_images_cover_or_fit_in_the_box() {
+        assert_eq!(
+            scale_target(&fitted(ResizeFit::Cover), 999, 1000),
+            Some((400, 400))
+        );
+        assert_eq!(
+            scale_target(&fitted(ResizeFit::Contain), 999, 1000),
+            Some((300, 300))
+        );
+    }
+
+    #[test]
+    fn edges_follow_the_orientation() {
+        let short_edge = ResizeOptions {
+            short_edge: 100,
+            ..resize(ResizeMode::ShortEdge)
+        };
+        assert_eq!(scale_target(&short_edge, 800, 600), Some((0, 100)));
+        assert_eq!(scale_target(&short_edge, 600, 800), Some((100, 0)));
+        let long_edge = ResizeOptions {
+            long_edge: 100,
+            ..resize(ResizeMode::LongEdge)
+        };
+        assert_eq!(scale_target(&long_edge, 800, 600), Some((100, 0)));
+        assert_eq!(scale_target(&long_edge, 600, 800), Some((0, 100)));
+    }
+
+    #[test]
+    fn percentages_keep_the_aspect_ratio() {
+        let options = ResizeOptions {
+            width_percentage: 50,
+            height_percentage: 10,
+            ..resize(ResizeMode::Percentage)
+        };
+        assert_eq!(scale_target(&options, 800, 600), Some((400, 300)));
+        let stretched = ResizeOptions {
+            keep_aspect_ratio: false,
+            ..options
+        };
+        assert_eq!(scale_target(&stretched, 800, 600), Some((400, 60)));
+    }
+
+    #[test]
+    fn megapixels_are_a_maximum() {
+        let options = ResizeOptions {
+            max_megapixels: 1.0,
+            ..resize(ResizeMode::MaxMegapixels)
+        };
+        assert_eq!(scale_target(&options, 1000, 1000), None);
+        let (width, height) = scale_target(&options, 4000, 3000).unwrap();
+        assert!(width * height <= 1_000_000);
+        assert_eq!((width, height), (1154, 866));
+    }
+
+    #[test]
+    fn cover_and_pad_frame_the_box() {
+        assert_eq!(frame(&fitted(ResizeFit::Contain), 1000, 1000), None);
+        assert_eq!(
+            frame(&fitted(ResizeFit::Cover), 1000, 1000),
+            Some(Frame::Crop {
+                width: 400,
+                height: 300,
+                focal_point: (50, 50),
+            })
+        );
+        let padded = ResizeOptions {
+            background: "#00000080".to_string(),
+            ..fitted(ResizeFit::Pad)
+        };
+        assert_eq!(
+            frame(&padded, 1000, 1000),
+            Some(Frame::Pad {
+                width: 400,
+                height: 300,
+                background: [0, 0, 0, 128],
+            })
+        );
+    }
+
+    #[test]
+    fn aspect_ratio_crops_the_largest_area() {
+        let options = ResizeOptions {
+            aspect_ratio_width: 16,
+            aspect_ratio_height: 9,
+            focal_point_x: 150,
+            ..resize(ResizeMode::AspectRatio)
+        };
+        assert_eq!(scale_target(&options, 4000, 4000), None);
+        assert_eq!(
+            frame(&options, 4000, 4000),
+            Some(Frame::Crop {
+                width: 4000,
+                height: 2250,
+                focal_point: (100, 50),
+            })
+        );
+        assert_eq!(
+            frame(&options, 1000, 300),
+            Some(Frame::Crop {
+                width: 533,
+                height: 300,
+                focal_point: (100, 50),
+            })
+        );
+        assert_eq!(frame(&options, 1600, 900), None);
+    }
+
+    #[test]
+    fn crop_windows_stay_inside_the_image() {
+        assert_eq!(window_start(1000, 400, 50), 300);
+        assert_eq!(window_start(1000, 400, 0), 0);
+        assert_eq!(window_start(1000, 400, 100), 600);
+        assert_eq!(window_start(400, 400, 50), 0);
+    }
+}
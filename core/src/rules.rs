@@ -0,0 +1,598 @@
+use crate::options::{FieldError, OptionsPayload};
+use crate::CImage;
+use glob::{MatchOptions, Pattern};
+use image::{ImageDecoder, ImageReader};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+// Meta boxes only hold item descriptions, bigger ones are not worth reading
+const MAX_META_BOX_SIZE: u64 = 1024 * 1024;
+const ALPHA_AUXILIARY_TYPES: [&[u8]; 2] = [
+    b"urn:mpeg:mpegB:cicp:systems:auxiliary:alpha",
+    b"urn:mpeg:hevc:2015:auxid:1",
+];
+
+/// Options overrides applied to the images matching every condition.
+///
+/// `overrides` is a partial `OptionsPayload` merged over the batch options, e.g.
+/// `{"output_options": {"output_format": "jpg"}, "compression_options": {"jpeg": {"quality": 80}}}`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub conditions: Vec<RuleCondition>,
+    pub overrides: Value,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    LessThan,
+    GreaterThan,
+    EqualTo,
+}
+
+impl Comparison {
+    fn compare(&self, left: u64, right: u64) -> bool {
+        match self {
+            Comparison::LessThan => left < right,
+            Comparison::GreaterThan => left > right,
+            Comparison::EqualTo => left == right,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RuleCondition {
+    /// Matches any of the given mime types, e.g. `image/png`
+    MimeType {
+        values: Vec<String>,
+    },
+    /// File size in bytes
+    Size {
+        comparison: Comparison,
+        value: u64,
+    },
+    Width {
+        comparison: Comparison,
+        value: u64,
+    },
+    Height {
+        comparison: Comparison,
+        value: u64,
+    },
+    LongEdge {
+        comparison: Comparison,
+        value: u64,
+    },
+    /// Case-insensitive glob matched against the full path, e.g. `**/products/*.png`
+    PathGlob {
+        pattern: String,
+    },
+    HasAlpha {
+        value: bool,
+    },
+}
+
+impl RuleCondition {
+    fn matches(&self, cimage: &CImage) -> bool {
+        match self {
+            RuleCondition::MimeType { values } => values
+                .iter()
+                .any(|v| v.eq_ignore_ascii_case(&cimage.mime_type)),
+            RuleCondition::Size { comparison, value } => comparison.compare(cimage.size, *value),
+            RuleCondition::Width { comparison, value } => {
+                comparison.compare(cimage.width as u64, *value)
+            }
+            RuleCondition::Height { comparison, value } => {
+                comparison.compare(cimage.height as u64, *value)
+            }
+            RuleCondition::LongEdge { comparison, value } => {
+                comparison.compare(cimage.width.max(cimage.height) as u64, *value)
+            }
+            RuleCondition::PathGlob { pattern } => match Pattern::new(pattern) {
+                Ok(p) => p.matches_path_with(
+                    Path::new(&cimage.path),
+                    MatchOptions {
+                        case_sensitive: false,
+                        ..MatchOptions::new()
+                    },
+                ),
+                Err(_) => false,
+            },
+            RuleCondition::HasAlpha { value } => has_alpha(cimage) == *value,
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, cimage: &CImage) -> bool {
+        self.enabled && self.conditions.iter().all(|c| c.matches(cimage))
+    }
+
+    /// Returns `options` with the overrides of this rule merged in
+    pub fn apply(&self, options: &OptionsPayload) -> Result<OptionsPayload, serde_json::Error> {
+        let mut value = serde_json::to_value(OptionsPayload {
+            rules: vec![],
+            ..options.clone()
+        })?;
+        merge(&mut value, &self.overrides);
+        let mut merged: OptionsPayload = serde_json::from_value(value)?;
+        merged.rules = vec![];
+        Ok(merged)
+    }
+
+    pub(crate) fn validate(&self, index: usize, options: &OptionsPayload) -> Vec<FieldError> {
+        let prefix = format!("rules[{index}]");
+        let mut errors = vec![];
+        if self.name.trim().is_empty() {
+            errors.push(FieldError {
+                field: format!("{prefix}.name"),
+                message: "is required".to_string(),
+            });
+        }
+        if !self.overrides.is_object() {
+            errors.push(FieldError {
+                field: format!("{prefix}.overrides"),
+                message: "must be an object".to_string(),
+            });
+        }
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if let RuleCondition::PathGlob { pattern } = condition {
+                if let Err(e) = Pattern::new(pattern) {
+                    errors.push(FieldError {
+                        field: format!("{prefix}.conditions[{i}].pattern"),
+                        message: e.msg.to_string(),
+                    });
+                }
+            }
+        }
+        if self.overrides.get("rules").is_some() {
+            errors.push(FieldError {
+                field: format!("{prefix}.overrides.rules"),
+                message: "rules cannot be nested".to_string(),
+            });
+        }
+        if !errors.is_empty() {
+            return errors;
+        }
+
+        match self.apply(options) {
+            Ok(merged) => {
+                if let Err(e) = merged.validate() {
+                    errors.extend(e.errors.into_iter().map(|e| FieldError {
+                        field: format!("{prefix}.overrides.{}", e.field),
+                        message: e.message,
+                    }));
+                }
+            }
+            Err(e) => errors.push(FieldError {
+                field: format!("{prefix}.overrides"),
+                message: e.to_string(),
+            }),
+        }
+        errors
+    }
+}
+
+/// Picks the first enabled rule matching `cimage` and returns the options to use for it
+/// along with the name of the rule that was applied
+pub fn resolve_options<'a>(
+    options: &'a OptionsPayload,
+    cimage: &CImage,
+) -> (Cow<'a, OptionsPayload>, Option<String>) {
+    let Some(rule) = options.rules.iter().find(|r| r.matches(cimage)) else {
+        return (Cow::Borrowed(options), None);
+    };
+
+    match rule.apply(options) {
+        Ok(merged) => (Cow::Owned(merged), Some(rule.name.clone())),
+        Err(e) => {
+            log::error!("Cannot apply rule {} to {}: {e}", rule.name, cimage.path);
+            (Cow::Borrowed(options), None)
+        }
+    }
+}
+
+fn merge(base: &mut Value, patch: &Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
+
+/// Reads just enough of the file to know if the image has an alpha channel or
+/// transparent pixels. Unreadable files and other formats are reported as opaque.
+fn has_alpha(cimage: &CImage) -> bool {
+    let path = Path::new(&cimage.path);
+    let open = || File::open(path).map(BufReader::new);
+    let has_alpha = match cimage.mime_type.as_str() {
+        "image/png" => open().and_then(png_has_alpha),
+        "image/webp" => open().and_then(webp_has_alpha),
+        "image/gif" => open().and_then(gif_has_transparency),
+        "image/avif" | "image/heic" | "image/heif" => open().and_then(isobmff_has_alpha),
+        "image/tiff" => Ok(ImageReader::open(path)
+            .ok()
+            .and_then(|reader| reader.into_decoder().ok())
+            .is_some_and(|decoder| decoder.color_type().has_alpha())),
+        _ => Ok(false),
+    };
+    has_alpha.unwrap_or(false)
+}
+
+fn png_has_alpha(mut reader: BufReader<File>) -> io::Result<bool> {
+    reader.seek_relative(8)?;
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        match &header[4..] {
+            b"IHDR" => {
+                let mut ihdr = [0u8; 13];
+                reader.read_exact(&mut ihdr)?;
+                // Color type 4 is grayscale + alpha, 6 is RGBA
+                if matches!(ihdr[9], 4 | 6) {
+                    return Ok(true);
+                }
+                reader.seek_relative(i64::from(length) - 13 + 4)?;
+            }
+            // Transparent palette entries, or a color drawn as transparent
+            b"tRNS" => return Ok(true),
+            b"IDAT" | b"IEND" => return Ok(false),
+            _ => reader.seek_relative(i64::from(length) + 4)?,
+        }
+    }
+}
+
+fn webp_has_alpha(mut reader: BufReader<File>) -> io::Result<bool> {
+    let mut header = [0u8; 25];
+    reader.read_exact(&mut header)?;
+    Ok(match &header[12..16] {
+        b"VP8X" => header[20] & 0x10 != 0,
+        b"VP8L" => header[24] & 0x10 != 0,
+        _ => false,
+    })
+}
+
+/// Looks for a graphic control extension with a transparent color in any frame
+fn gif_has_transparency(mut reader: BufReader<File>) -> io::Result<bool> {
+    let mut header = [0u8; 13];
+    reader.read_exact(&mut header)?;
+    skip_gif_color_table(&mut reader, header[10])?;
+    loop {
+        match read_byte(&mut reader)? {
+            0x21 => {
+                if read_byte(&mut reader)? == 0xF9 {
+                    let mut block = [0u8; 2];
+                    reader.read_exact(&mut block)?;
+                    if block[1] & 0x01 != 0 {
+                        return Ok(true);
+                    }
+                    reader.seek_relative(i64::from(block[0]) - 1)?;
+                }
+                skip_gif_sub_blocks(&mut reader)?;
+            }
+            0x2C => {
+                let mut descriptor = [0u8; 9];
+                reader.read_exact(&mut descriptor)?;
+                skip_gif_color_table(&mut reader, descriptor[8])?;
+                // LZW minimum code size, then the image data
+                read_byte(&mut reader)?;
+                skip_gif_sub_blocks(&mut reader)?;
+            }
+            _ => return Ok(false),
+        }
+    }
+}
+
+fn skip_gif_color_table(reader: &mut BufReader<File>, flags: u8) -> io::Result<()> {
+    if flags & 0x80 != 0 {
+        reader.seek_relative(3 << ((flags & 0x07) + 1))?;
+    }
+    Ok(())
+}
+
+fn skip_gif_sub_blocks(reader: &mut BufReader<File>) -> io::Result<()> {
+    loop {
+        match read_byte(reader)? {
+            0 => return Ok(()),
+            size => reader.seek_relative(i64::from(size))?,
+        }
+    }
+}
+
+fn read_byte(reader: &mut BufReader<File>) -> io::Result<u8> {
+    let mut byte = [0u8];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// The alpha plane of AVIF and HEIF images is an auxiliary image declared in the
+/// top-level meta box
+fn isobmff_has_alpha(mut reader: BufReader<File>) -> io::Result<bool> {
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let mut size = u64::from(u32::from_be_bytes([
+            header[0], header[1], header[2], header[3],
+        ]));
+        let mut header_size = 8;
+        if size == 1 {
+            let mut large_size = [0u8; 8];
+            reader.read_exact(&mut large_size)?;
+            size = u64::from_be_bytes(large_size);
+            header_size = 16;
+        }
+        let Some(content_size) = size.checked_sub(header_size) else {
+            // Either the last box or a broken one
+            return Ok(false);
+        };
+
+        if &header[4..] == b"meta" {
+            if content_size > MAX_META_BOX_SIZE {
+                return Ok(false);
+            }
+            let mut meta = vec![0; content_size as usize];
+            reader.read_exact(&mut meta)?;
+            return Ok(ALPHA_AUXILIARY_TYPES
+                .iter()
+                .any(|urn| meta.windows(urn.len()).any(|w| w == *urn)));
+        }
+        let content_size = i64::try_from(content_size).map_err(io::Error::other)?;
+        reader.seek_relative(content_size)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ExtendedColorType;
+    use serde_json::json;
+    use std::fs;
+
+    fn cimage(path: &str, mime_type: &str) -> CImage {
+        CImage {
+            path: path.to_string(),
+            mime_type: mime_type.to_string(),
+            size: 2_000_000,
+            width: 4000,
+            height: 3000,
+            ..CImage::default()
+        }
+    }
+
+    fn rule(name: &str, conditions: Value, overrides: Value) -> Rule {
+        serde_json::from_value(json!({
+            "name": name,
+            "conditions": conditions,
+            "overrides": overrides,
+        }))
+        .unwrap()
+    }
+
+    /// PNG signature and header of a 1×1 image with the given color type
+    fn png_header(color_type: u8) -> Vec<u8> {
+        let mut header = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x01\0\0\0\x01\x08".to_vec();
+        header.extend_from_slice(&[color_type, 0, 0, 0]);
+        header
+    }
+
+    /// A PNG chunk with its length and CRC
+    fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+        chunk
+    }
+
+    /// A 1×1 palette PNG, `transparent` adding a tRNS chunk to it
+    fn palette_png(transparent: bool) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]));
+        png.extend(png_chunk(b"PLTE", &[255, 0, 0]));
+        if transparent {
+            png.extend(png_chunk(b"tRNS", &[0]));
+        }
+        png.extend(png_chunk(b"IDAT", &[]));
+        png.extend(png_chunk(b"IEND", &[]));
+        png
+    }
+
+    /// A 1×1 GIF with a global palette, `transparent` flagging its color as transparent
+    fn gif(transparent: bool) -> Vec<u8> {
+        let mut gif = b"GIF89a\x01\0\x01\0\x80\0\0".to_vec();
+        gif.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        gif.extend_from_slice(&[0x21, 0xF9, 4, u8::from(transparent), 0, 0, 0, 0]);
+        gif.extend_from_slice(&[0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0]);
+        gif.extend_from_slice(&[2, 2, 0x4C, 0x01, 0, 0x3B]);
+        gif
+    }
+
+    /// An ISOBMFF box
+    fn isobmff_box(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bmff_box = ((data.len() + 8) as u32).to_be_bytes().to_vec();
+        bmff_box.extend_from_slice(kind);
+        bmff_box.extend_from_slice(data);
+        bmff_box
+    }
+
+    #[test]
+    fn matches_when_every_condition_does() {
+        let rule = rule(
+            "Large photos",
+            json!([
+                {"type": "mime_type", "values": ["IMAGE/JPEG"]},
+                {"type": "size", "comparison": "greater_than", "value": 1_000_000},
+                {"type": "long_edge", "comparison": "equal_to", "value": 4000},
+                {"type": "path_glob", "pattern": "**/photos/*.jpg"},
+            ]),
+            json!({}),
+        );
+        assert!(rule.matches(&cimage("/home/me/Photos/a.JPG", "image/jpeg")));
+        assert!(!rule.matches(&cimage("/home/me/Photos/a.png", "image/jpeg")));
+        assert!(!rule.matches(&cimage("/home/me/Photos/a.jpg", "image/png")));
+    }
+
+    #[test]
+    fn disabled_rules_never_match() {
+        let mut rule = rule("Everything", json!([]), json!({}));
+        assert!(rule.matches(&cimage("a.jpg", "image/jpeg")));
+        rule.enabled = false;
+        assert!(!rule.matches(&cimage("a.jpg", "image/jpeg")));
+    }
+
+    #[test]
+    fn first_matching_rule_is_merged_over_the_options() {
+        let mut options = OptionsPayload::default();
+        options.compression_options.jpeg.progressive = true;
+        options.rules = vec![
+            rule(
+                "PNG",
+                json!([{"type": "mime_type", "values": ["image/png"]}]),
+                json!({"compression_options": {"png": {"quality": 10}}}),
+            ),
+            rule(
+                "Small",
+                json!([{"type": "width", "comparison": "less_than", "value": 5000}]),
+                json!({"compression_options": {"jpeg": {"quality": 40}}}),
+            ),
+            rule("Unreachable", json!([]), json!({})),
+        ];
+
+        let (resolved, name) = resolve_options(&options, &cimage("a.jpg", "image/jpeg"));
+        assert_eq!(name.as_deref(), Some("Small"));
+        assert_eq!(resolved.compression_options.jpeg.quality, 40);
+        assert!(resolved.compression_options.jpeg.progressive);
+        assert!(resolved.rules.is_empty());
+    }
+
+    #[test]
+    fn options_are_kept_without_a_matching_rule() {
+        let options = OptionsPayload {
+            rules: vec![rule(
+                "PNG",
+                json!([{"type": "mime_type", "values": ["image/png"]}]),
+                json!({"compression_options": {"png": {"quality": 10}}}),
+            )],
+            ..OptionsPayload::default()
+        };
+
+        let (resolved, name) = resolve_options(&options, &cimage("a.jpg", "image/jpeg"));
+        assert!(matches!(resolved, Cow::Borrowed(_)));
+        assert_eq!(name, None);
+    }
+
+    #[test]
+    fn reads_the_alpha_channel_from_the_header() {
+        let directory = tempfile::tempdir().unwrap();
+        let rgba = directory.path().join("rgba.png");
+        let rgb = directory.path().join("rgb.png");
+        fs::write(&rgba, png_header(6)).unwrap();
+        fs::write(&rgb, png_header(2)).unwrap();
+        let rule = rule(
+            "Transparent",
+            json!([{"type": "has_alpha", "value": true}]),
+            json!({}),
+        );
+
+        assert!(rule.matches(&cimage(&rgba.to_string_lossy(), "image/png")));
+        assert!(!rule.matches(&cimage(&rgb.to_string_lossy(), "image/png")));
+        assert!(!rule.matches(&cimage("missing.png", "image/png")));
+    }
+
+    #[test]
+    fn detects_transparent_pixels_in_every_format() {
+        let directory = tempfile::tempdir().unwrap();
+        let write = |name: &str, mime_type: &str, content: &[u8]| {
+            let path = directory.path().join(name);
+            fs::write(&path, content).unwrap();
+            cimage(&path.to_string_lossy(), mime_type)
+        };
+        let avif = |auxiliary_type: &[u8]| {
+            let mut avif = isobmff_box(b"ftyp", b"avifmif1");
+            avif.extend(isobmff_box(
+                b"meta",
+                &[b"\0\0\0\0auxC\0\0\0\0", auxiliary_type].concat(),
+            ));
+            avif
+        };
+        let tiff = |color_type: ExtendedColorType, name: &str| {
+            let path = directory.path().join(name);
+            let pixel = vec![0; color_type.bits_per_pixel() as usize / 8];
+            image::save_buffer(&path, &pixel, 1, 1, color_type).unwrap();
+            cimage(&path.to_string_lossy(), "image/tiff")
+        };
+
+        let transparent = [
+            write("palette.png", "image/png", &palette_png(true)),
+            write("transparent.gif", "image/gif", &gif(true)),
+            write(
+                "alpha.avif",
+                "image/avif",
+                &avif(b"urn:mpeg:mpegB:cicp:systems:auxiliary:alpha\0"),
+            ),
+            tiff(ExtendedColorType::Rgba8, "rgba.tiff"),
+        ];
+        let opaque = [
+            write("opaque.png", "image/png", &palette_png(false)),
+            write("opaque.gif", "image/gif", &gif(false)),
+            write(
+                "opaque.avif",
+                "image/avif",
+                &avif(b"urn:mpeg:mpegB:cicp:systems:auxiliary:depth\0"),
+            ),
+            tiff(ExtendedColorType::Rgb8, "rgb.tiff"),
+        ];
+        for cimage in &transparent {
+            assert!(has_alpha(cimage), "{}", cimage.path);
+        }
+        for cimage in &opaque {
+            assert!(!has_alpha(cimage), "{}", cimage.path);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_rules() {
+        let options = OptionsPayload::default();
+        let invalid = rule(
+            " ",
+            json!([{"type": "path_glob", "pattern": "a/***"}]),
+            json!({"rules": []}),
+        );
+        let fields: Vec<String> = invalid
+            .validate(2, &options)
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "rules[2].name",
+                "rules[2].conditions[0].pattern",
+                "rules[2].overrides.rules",
+            ]
+        );
+
+        let not_an_object = rule("Name", json!([]), json!([]));
+        assert_eq!(
+            not_an_object.validate(0, &options)[0].field,
+            "rules[0].overrides"
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{AppData, CImage, ImageStatus};
+use crate::app_data::AppData;
+use crate::codecs::{self, SourceFormat};
+use crate::events::EventSink;
+use crate::{CImage, ImageStatus};
 use file_format::FileFormat;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
@@ -8,31 +11,31 @@ use std::{
     io::BufReader,
     path::{absolute, Path, PathBuf},
 };
-use tauri::{Emitter, Manager};
-use tauri_plugin_dialog::FilePath;
 use walkdir::WalkDir;
 
 #[derive(serde::Serialize, Clone)]
 pub struct FileImportProgress {
-    pub(crate) progress: usize,
-    pub(crate) total: usize,
+    pub progress: usize,
+    pub total: usize,
 }
 #[derive(Serialize, Clone)]
 pub struct FileList {
-    pub(crate) files: Vec<CImage>,
-    pub(crate) base_folder: String,
-    pub(crate) total_files: usize,
+    pub files: Vec<CImage>,
+    pub base_folder: String,
+    pub total_files: usize,
 }
 
 #[derive(Serialize, Clone)]
 pub struct ImportFinishedResult {
-    original_list_length: usize,
-    new_list_length: usize,
+    pub original_list_length: usize,
+    pub new_list_length: usize,
 }
 
 fn is_filetype_supported(path: &Path) -> bool {
-    let fmt = get_file_mime_type(path);
-    let mime_type = fmt.media_type();
+    let Some(mime_type) = get_media_type(path) else {
+        return false;
+    };
+    let mime_type = mime_type.as_str();
 
     if mime_type == "image/tiff" {
         let extension = match path.extension() {
@@ -44,31 +47,54 @@ fn is_filetype_supported(path: &Path) -> bool {
         }
     }
 
-    matches!(
-        mime_type,
-        "image/jpeg" | "image/png" | "image/gif" | "image/webp" | "image/tiff"
-    )
+    match mime_type {
+        "image/jpeg" | "image/png" | "image/gif" | "image/webp" | "image/tiff" => true,
+        // Decoded with native libraries, left out of the default build
+        "image/avif" => cfg!(feature = "avif-native"),
+        "image/jxl" => cfg!(feature = "jxl"),
+        "image/heic" | "image/heif" => cfg!(feature = "heif"),
+        _ => SourceFormat::from_mime_type(mime_type).is_some(),
+    }
+}
+
+/// Mime type of `path`, guessed from the extension for camera RAW files and from the
+/// content for everything else. `None` when the file cannot be read.
+pub fn get_media_type(path: &Path) -> Option<String> {
+    match codecs::raw_mime_type(path) {
+        Some(mime_type) => Some(mime_type.to_string()),
+        None => get_file_mime_type(path).map(|f| f.media_type().to_string()),
+    }
 }
 
-pub fn get_file_mime_type(path: &Path) -> FileFormat {
-    FileFormat::from_file(path).unwrap() //TODO
+/// `None` when the file was removed, renamed or is locked by another process
+pub fn get_file_mime_type(path: &Path) -> Option<FileFormat> {
+    FileFormat::from_file(path).ok()
 }
 
-fn is_valid(entry: &Path) -> bool {
+/// Unreadable files are not valid, a watched folder checks them again once they change
+pub(crate) fn is_valid(entry: &Path) -> bool {
     entry.exists() && entry.is_file() && is_filetype_supported(entry)
 }
 
-pub fn process_files(app: &tauri::AppHandle, file_paths: Vec<FilePath>, recursive: bool) {
-    app.emit("fileImporter:importStarted", ()).unwrap(); //TODO
-    let state = app.state::<Mutex<AppData>>();
-    let mut state = state.lock().unwrap();
+pub fn import_files(
+    app_data: &Mutex<AppData>,
+    file_paths: &[PathBuf],
+    recursive: bool,
+    sink: &dyn EventSink,
+) {
+    sink.import_started();
+    let mut state = match app_data.lock() {
+        Ok(s) => s,
+        Err(_) => {
+            log::error!("Cannot import files, application state is poisoned");
+            return;
+        }
+    };
     let original_list_length = state.file_list.len();
-    let (base_folder, imported_files) = scan_files(&file_paths, state.base_path.clone(), recursive);
+    let (base_folder, imported_files) = scan_files(file_paths, state.base_path.clone(), recursive);
 
     state.base_path = base_folder;
 
-    //app.emit("fileImporter:scanFinished", ()).unwrap(); //TODO
-
     let total = imported_files.len();
     if total > 0 {
         let mut progress = 0;
@@ -77,11 +103,7 @@ pub fn process_files(app: &tauri::AppHandle, file_paths: Vec<FilePath>, recursiv
 
             if progress != new_progress {
                 progress = new_progress;
-                app.emit(
-                    "fileImporter:importProgress",
-                    FileImportProgress { progress, total },
-                )
-                .unwrap();
+                sink.import_progress(&FileImportProgress { progress, total });
             }
 
             let cimage = match map_file(f) {
@@ -95,31 +117,17 @@ pub fn process_files(app: &tauri::AppHandle, file_paths: Vec<FilePath>, recursiv
         state.file_list.sort_list();
     }
 
-    app.emit(
-        "fileImporter:importFinished",
-        ImportFinishedResult {
+    sink.import_finished(
+        &ImportFinishedResult {
             original_list_length,
             new_list_length: state.file_list.len(),
         },
-    )
-    .unwrap(); //TODO
-    app.emit(
-        "fileList:getList",
-        FileList {
-            files: state.file_list.paged_list.clone(),
-            total_files: state.file_list.len(),
-            base_folder: absolute(state.base_path.clone().unwrap()) //TODO
-                .unwrap_or_default()
-                .to_str()
-                .unwrap() //TODO
-                .to_string(),
-        },
-    )
-    .unwrap(); //TODO
+        &state.file_list_payload(),
+    );
 }
 
 pub fn scan_files(
-    args: &[FilePath],
+    args: &[PathBuf],
     initial_base_path: Option<PathBuf>,
     recursive: bool,
 ) -> (Option<PathBuf>, Vec<PathBuf>) {
@@ -130,7 +138,7 @@ pub fn scan_files(
     let mut base_path = initial_base_path;
 
     for path in args.iter() {
-        let input = PathBuf::from(path.as_path().unwrap()); //TODO
+        let input = path.clone();
         if input.exists() && input.is_dir() {
             let mut walk_dir = WalkDir::new(input);
             if !recursive {
@@ -221,15 +229,15 @@ pub fn map_file(file: &Path) -> Option<CImage> {
     let path = file.to_str()?.to_string();
     let id = base16ct::lower::encode_string(&Sha256::digest(path.as_bytes()));
 
-    let mime_type = get_file_mime_type(file);
-    let (width, height) = get_real_resolution(file, mime_type.media_type());
+    let mime_type = get_media_type(file)?;
+    let (width, height) = get_real_resolution(file);
 
     let cimage = CImage {
         id,
         name,
         path,
         directory,
-        mime_type: mime_type.media_type().to_string(),
+        mime_type,
         size,
         width,
         height,
@@ -239,31 +247,30 @@ pub fn map_file(file: &Path) -> Option<CImage> {
         compressed_file_path: String::new(),
         info: String::new(),
         status: ImageStatus::New,
+        applied_rule: None,
+        collision_policy: None,
+        error_code: None,
+        quality_metrics: None,
     };
 
     Some(cimage)
 }
 
-pub fn get_real_resolution(file: &Path, mime_type: &str) -> (usize, usize) {
+/// Size of the image once its EXIF orientation is applied
+pub fn get_real_resolution(file: &Path) -> (usize, usize) {
+    if codecs::raw_mime_type(file).is_some() {
+        return codecs::raw_dimensions(file).unwrap_or((0, 0));
+    }
     let resolution = match imagesize::size(file) {
         Ok(r) => r,
         Err(_) => return (0, 0),
     };
-    let mut orientation = 1;
-    if mime_type == "image/jpeg" {
-        let f = File::open(file).unwrap(); //TODO
-        if let Ok(e) = exif::Reader::new().read_from_container(&mut BufReader::new(&f)) {
-            let exif_field = match e.get_field(exif::Tag::Orientation, exif::In::PRIMARY) {
-                Some(f) => f,
-                None => return (resolution.width, resolution.height),
-            };
-            orientation = exif_field.value.get_uint(0).unwrap_or(1);
-        };
-    }
-    let (width, height) = match orientation {
+    let orientation = match File::open(file) {
+        Ok(f) => codecs::exif_orientation(&mut BufReader::new(f)),
+        Err(_) => 1,
+    };
+    match orientation {
         5..=8 => (resolution.height, resolution.width),
         _ => (resolution.width, resolution.height),
-    };
-
-    (width, height)
+    }
 }
@@ -0,0 +1,256 @@
+use crate::app_data::{AppData, AppDataFileListSorting};
+use crate::atomic_file::AtomicFile;
+use crate::scan_files::map_file;
+use crate::{CImage, ImageStatus};
+use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Version written in every session and project file
+pub const SESSION_SCHEMA_VERSION: u32 = 1;
+
+/// Snapshot of the file list, used both for the automatic session and for project files
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Session {
+    pub version: u32,
+    pub base_path: Option<PathBuf>,
+    pub sorting: AppDataFileListSorting,
+    pub search_query: String,
+    pub current_page: usize,
+    pub files: Vec<SessionEntry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct SessionEntry {
+    pub image: CImage,
+    /// Size and modification time (ms since the epoch) of the file when the session was saved
+    pub file_size: Option<u64>,
+    pub modified: Option<u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported session version {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// What [`Session::revalidate`] found on disk
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct RevalidationResult {
+    pub total: usize,
+    pub missing: usize,
+    pub changed: usize,
+}
+
+impl Session {
+    pub fn from_app_data(app_data: &AppData) -> Self {
+        let file_list = &app_data.file_list;
+        let files = file_list
+            .list
+            .par_iter()
+            .map(|image| {
+                let (file_size, modified) = file_stamp(Path::new(&image.path)).unzip();
+                SessionEntry {
+                    image: image.clone(),
+                    file_size,
+                    modified,
+                }
+            })
+            .collect();
+
+        Self {
+            version: SESSION_SCHEMA_VERSION,
+            base_path: app_data.base_path.clone(),
+            sorting: file_list.sorting.clone(),
+            search_query: file_list.search_query.clone(),
+            current_page: file_list.current_page,
+            files,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SessionError> {
+        let content = fs::read(path)?;
+        let session: Session = serde_json::from_slice(&content)?;
+        if session.version > SESSION_SCHEMA_VERSION {
+            return Err(SessionError::UnsupportedVersion(session.version));
+        }
+        Ok(session)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SessionError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = AtomicFile::create(path)?;
+        file.write_all(&serde_json::to_vec(self)?)?;
+        file.commit()?;
+        Ok(())
+    }
+
+    /// Checks every entry against the file on disk.
+    ///
+    /// Missing files are marked as errors, files modified since the session was saved
+    /// are read again and reset to new so their old results are not trusted.
+    pub fn revalidate(&mut self) -> RevalidationResult {
+        let total = self.files.len();
+        let (missing, changed) = self
+            .files
+            .par_iter_mut()
+            .map(|entry| {
+                let path = PathBuf::from(&entry.image.path);
+                let Some((file_size, modified)) = file_stamp(&path) else {
+                    entry.image = CImage {
+                        status: ImageStatus::Error,
+                        info: "File not found".to_string(),
+                        ..entry.image.clone()
+                    };
+                    return (1, 0);
+                };
+
+                if entry.file_size == Some(file_size) && entry.modified == Some(modified) {
+                    return (0, 0);
+                }
+
+                entry.image = match map_file(&path) {
+                    Some(image) => CImage {
+                        info: "File changed since the list was saved".to_string(),
+                        ..image
+                    },
+                    None => CImage {
+                        status: ImageStatus::Error,
+                        info: "File is no longer readable".to_string(),
+                        ..entry.image.clone()
+                    },
+                };
+                entry.file_size = Some(file_size);
+                entry.modified = Some(modified);
+                (0, 1)
+            })
+            .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+
+        RevalidationResult {
+            total,
+            missing,
+            changed,
+        }
+    }
+}
+
+impl AppData {
+    /// Replaces the file list with the content of `session`
+    pub fn restore_session(&mut self, session: Session) {
+        self.file_list.clear();
+        for entry in session.files {
+            self.file_list.list.insert(entry.image);
+        }
+        self.base_path = session.base_path;
+        self.file_list.sorting = session.sorting;
+        self.file_list.current_page = session.current_page.max(1);
+        self.file_list.search_query = session.search_query;
+        self.file_list.sort_list();
+    }
+}
+
+fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    Some((metadata.len(), modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A session listing `names`, each written in `directory` as it is when saved
+    fn session(directory: &Path, names: &[&str]) -> Session {
+        let files = names
+            .iter()
+            .map(|name| {
+                let path = directory.join(name);
+                fs::write(&path, b"image").unwrap();
+                let (file_size, modified) = file_stamp(&path).unzip();
+                SessionEntry {
+                    image: CImage {
+                        status: ImageStatus::Success,
+                        ..map_file(&path).unwrap()
+                    },
+                    file_size,
+                    modified,
+                }
+            })
+            .collect();
+        Session {
+            version: SESSION_SCHEMA_VERSION,
+            base_path: Some(directory.to_path_buf()),
+            sorting: AppDataFileListSorting::default(),
+            search_query: "a".to_string(),
+            current_page: 2,
+            files,
+        }
+    }
+
+    #[test]
+    fn saved_sessions_load_back() {
+        let directory = tempfile::tempdir().unwrap();
+        let saved = session(directory.path(), &["a.jpg", "b.png"]);
+        let path = directory.path().join("sessions/last.json");
+
+        saved.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+
+        assert_eq!(loaded.base_path, saved.base_path);
+        assert_eq!(loaded.search_query, "a");
+        assert_eq!(loaded.current_page, 2);
+        assert_eq!(loaded.files.len(), 2);
+        for (loaded, saved) in loaded.files.iter().zip(&saved.files) {
+            assert_eq!(loaded.image, saved.image);
+            assert_eq!(loaded.file_size, saved.file_size);
+            assert_eq!(loaded.modified, saved.modified);
+        }
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut session = session(directory.path(), &[]);
+        session.version = SESSION_SCHEMA_VERSION + 1;
+        let path = directory.path().join("session.json");
+        session.save(&path).unwrap();
+
+        assert!(matches!(
+            Session::load(&path),
+            Err(SessionError::UnsupportedVersion(v)) if v == SESSION_SCHEMA_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn revalidation_resets_changed_files_and_flags_missing_ones() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut session = session(directory.path(), &["kept.jpg", "changed.jpg", "gone.jpg"]);
+        fs::write(directory.path().join("changed.jpg"), b"edited image").unwrap();
+        fs::remove_file(directory.path().join("gone.jpg")).unwrap();
+
+        let result = session.revalidate();
+
+        assert_eq!(result.total, 3);
+        assert_eq!(result.missing, 1);
+        assert_eq!(result.changed, 1);
+        let [kept, changed, gone] = &session.files[..] else {
+            unreachable!();
+        };
+        assert!(matches!(kept.image.status, ImageStatus::Success));
+        assert!(matches!(changed.image.status, ImageStatus::New));
+        assert_eq!(changed.file_size, Some(12));
+        assert!(matches!(gone.image.status, ImageStatus::Error));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::errors::CompressionError;
+use crate::metrics::{QualityMetrics, Reference};
+use crate::options::TargetQualityOptions;
+
+const MIN_QUALITY: u32 = 1;
+const MAX_QUALITY: u32 = 100;
+
+/// Output picked by [`search`]
+pub(crate) struct TargetQualityOutput {
+    pub data: Vec<u8>,
+    /// Encoder quality that produced `data`
+    pub quality: u32,
+    pub metrics: QualityMetrics,
+    /// False when no quality tried reached the target
+    pub reached: bool,
+}
+
+/// Bisects the quality passed to `encode` to find the smallest output whose SSIM
+/// against `reference` is at least `target.ssim`, encoding at most `target.max_iterations`
+/// times. If none reaches the target, the closest output is returned.
+///
+/// Assumes that a higher quality never lowers the SSIM, which holds for the lossy
+/// encoders well enough for a bisection.
+pub(crate) fn search(
+    reference: &mut Reference,
+    target: &TargetQualityOptions,
+    mut encode: impl FnMut(u32) -> Result<Vec<u8>, CompressionError>,
+) -> Result<TargetQualityOutput, CompressionError> {
+    let (mut low, mut high) = (MIN_QUALITY, MAX_QUALITY);
+    let mut smallest: Option<TargetQualityOutput> = None;
+    let mut closest: Option<TargetQualityOutput> = None;
+    for _ in 0..target.max_iterations {
+        if low > high {
+            break;
+        }
+        let quality = (low + high) / 2;
+        let data = encode(quality)?;
+        let metrics = reference
+            .compare(&data)
+            .map_err(|e| CompressionError::Decode(e.to_string()))?;
+        let reached = metrics.ssim >= target.ssim;
+        let output = TargetQualityOutput {
+            data,
+            quality,
+            metrics,
+            reached,
+        };
+
+        if reached {
+            high = quality - 1;
+            if smallest
+                .as_ref()
+                .is_none_or(|s| output.data.len() < s.data.len())
+            {
+                smallest = Some(output);
+            }
+        } else {
+            low = quality + 1;
+            if closest
+                .as_ref()
+                .is_none_or(|c| output.metrics.ssim > c.metrics.ssim)
+            {
+                closest = Some(output);
+            }
+        }
+    }
+
+    smallest
+        .or(closest)
+        .ok_or_else(|| CompressionError::Internal("No quality was tried".to_string()))
+}
@@ -0,0 +1,540 @@
+use crate::cache::file_sha256;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::cell::OnceCell;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Component, Path, PathBuf};
+
+const DEFAULT_HASH_LENGTH: usize = 8;
+const MAX_HASH_LENGTH: usize = 64;
+const MISSING_VALUE: &str = "unknown";
+const EXIF_DATE_FORMATS: [&str; 2] = ["%Y:%m:%d %H:%M:%S", "%Y-%m-%d %H:%M:%S"];
+
+/// Output name built from placeholders, relative to the output folder.
+///
+/// Supported placeholders are `{name}`, `{ext}`, `{suffix}`, `{width}`, `{height}`,
+/// `{parent}`, `{counter}` (`{counter:04}` pads with zeros), `{date:FORMAT}`,
+/// `{exif:TAG}` or `{exif:TAG:FORMAT}` for date tags, and `{hash}` (`{hash:N}` keeps
+/// the first N hex digits of the SHA-256 of the input). `{{` and `}}` are literal
+/// braces, and `/` separates subfolders. The extension is appended when the template
+/// does not contain `{ext}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilenameTemplate {
+    parts: Vec<Part>,
+}
+
+/// Text built from the same placeholders as [`FilenameTemplate`], used for metadata
+/// values. Nothing is replaced in the rendered values and missing EXIF tags are left
+/// empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Path,
+    Text,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Name,
+    Extension,
+    Suffix,
+    Width,
+    Height,
+    Parent,
+    Counter { width: usize },
+    Date { format: String },
+    Exif { tag: String, format: Option<String> },
+    Hash { length: usize },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("The template is empty")]
+    Empty,
+    #[error("Unclosed placeholder at position {0}")]
+    Unclosed(usize),
+    #[error("Unexpected '}}' at position {0}, use '}}}}' for a literal brace")]
+    UnexpectedClose(usize),
+    #[error("Unknown placeholder {{{0}}}")]
+    UnknownPlaceholder(String),
+    #[error("Invalid argument for {{{placeholder}}}: {message}")]
+    InvalidArgument {
+        placeholder: String,
+        message: String,
+    },
+    #[error("The output must be a relative path without '.' or '..' components")]
+    InvalidPath,
+    #[error("Cannot read the input file: {0}")]
+    Read(#[from] io::Error),
+}
+
+/// Values a template is rendered with, file content is only read if a placeholder needs it
+pub struct TemplateContext<'a> {
+    pub input: &'a Path,
+    /// Extension of the output, without the dot
+    pub extension: &'a str,
+    pub suffix: &'a str,
+    /// Expected size of the output
+    pub width: usize,
+    pub height: usize,
+    /// Position of the image in the batch, starting from 1
+    pub counter: usize,
+    pub now: DateTime<Local>,
+}
+
+impl FilenameTemplate {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        if template.trim().is_empty() {
+            return Err(TemplateError::Empty);
+        }
+
+        let template = Self {
+            parts: parse_parts(template)?,
+        };
+        template.validate_literals()?;
+        Ok(template)
+    }
+
+    /// The path changes with the time or the position of the image in the batch
+    pub fn varies_between_runs(&self) -> bool {
+        varies_between_runs(&self.parts)
+    }
+
+    /// Returns the relative output path for one image
+    pub fn render(&self, context: &TemplateContext) -> Result<PathBuf, TemplateError> {
+        let mut output = render_parts(&self.parts, context, Target::Path)?;
+        if !self.parts.contains(&Part::Extension) && !context.extension.is_empty() {
+            output.push('.');
+            output.push_str(context.extension);
+        }
+
+        let path = PathBuf::from(output);
+        let is_relative = path.file_name().is_some()
+            && path.components().all(|c| matches!(c, Component::Normal(_)));
+        if !is_relative {
+            return Err(TemplateError::InvalidPath);
+        }
+        Ok(path)
+    }
+
+    fn validate_literals(&self) -> Result<(), TemplateError> {
+        // Placeholders stand for any name here, the rendered path is checked again
+        let skeleton: String = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.as_str(),
+                _ => "x",
+            })
+            .collect();
+        let is_absolute = skeleton.starts_with(['/', '\\']) || skeleton.chars().nth(1) == Some(':');
+        let has_dot_components = skeleton.split(['/', '\\']).any(|c| c == "." || c == "..");
+        if is_absolute || has_dot_components {
+            return Err(TemplateError::InvalidPath);
+        }
+        Ok(())
+    }
+}
+
+impl TextTemplate {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        Ok(Self {
+            parts: parse_parts(template)?,
+        })
+    }
+
+    pub fn render(&self, context: &TemplateContext) -> Result<String, TemplateError> {
+        render_parts(&self.parts, context, Target::Text)
+    }
+
+    /// The text changes with the time or the position of the image in the batch
+    pub fn varies_between_runs(&self) -> bool {
+        varies_between_runs(&self.parts)
+    }
+}
+
+impl Target {
+    fn clean(self, value: &str) -> String {
+        match self {
+            Target::Path => sanitize(value),
+            Target::Text => value.trim().to_string(),
+        }
+    }
+
+    fn clean_date(self, value: &str) -> String {
+        match self {
+            Target::Path => sanitize_date(value),
+            Target::Text => value.to_string(),
+        }
+    }
+
+    fn missing(self) -> &'static str {
+        match self {
+            Target::Path => MISSING_VALUE,
+            Target::Text => "",
+        }
+    }
+}
+
+fn parse_parts(template: &str) -> Result<Vec<Part>, TemplateError> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '{' if chars.peek().is_some_and(|(_, next)| *next == '{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek().is_some_and(|(_, next)| *next == '}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => return Err(TemplateError::UnexpectedClose(position)),
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '}')) => break,
+                        Some((_, c)) => placeholder.push(c),
+                        None => return Err(TemplateError::Unclosed(position)),
+                    }
+                }
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(parse_placeholder(&placeholder)?);
+            }
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+    Ok(parts)
+}
+
+fn varies_between_runs(parts: &[Part]) -> bool {
+    parts
+        .iter()
+        .any(|part| matches!(part, Part::Counter { .. } | Part::Date { .. }))
+}
+
+fn render_parts(
+    parts: &[Part],
+    context: &TemplateContext,
+    target: Target,
+) -> Result<String, TemplateError> {
+    let exif = OnceCell::new();
+    let hash = OnceCell::new();
+
+    let mut output = String::new();
+    for part in parts {
+        match part {
+            Part::Literal(text) => output.push_str(text),
+            Part::Name => output.push_str(&target.clean(&file_stem(context.input))),
+            Part::Extension => output.push_str(context.extension),
+            Part::Suffix => output.push_str(&target.clean(context.suffix)),
+            Part::Width => output.push_str(&context.width.to_string()),
+            Part::Height => output.push_str(&context.height.to_string()),
+            Part::Parent => {
+                let parent = context
+                    .input
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .map(|n| target.clean(&n.to_string_lossy()))
+                    .unwrap_or_else(|| target.missing().to_string());
+                output.push_str(&parent);
+            }
+            Part::Counter { width } => {
+                output.push_str(&format!("{:0width$}", context.counter, width = *width))
+            }
+            Part::Date { format } => {
+                output.push_str(&target.clean_date(&context.now.format(format).to_string()))
+            }
+            Part::Exif { tag, format } => {
+                let exif = exif.get_or_init(|| read_exif(context.input));
+                output.push_str(&exif_value(exif.as_ref(), tag, format.as_deref(), target));
+            }
+            Part::Hash { length } => {
+                if hash.get().is_none() {
+                    let _ = hash.set(file_sha256(context.input)?);
+                }
+                let hash = hash.get().map(String::as_str).unwrap_or_default();
+                output.push_str(&hash[..*length]);
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part, TemplateError> {
+    let (name, argument) = match placeholder.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument)),
+        None => (placeholder.trim(), None),
+    };
+    let invalid = |message: &str| TemplateError::InvalidArgument {
+        placeholder: name.to_string(),
+        message: message.to_string(),
+    };
+
+    let part = match (name, argument) {
+        ("name", None) => Part::Name,
+        ("ext", None) => Part::Extension,
+        ("suffix", None) => Part::Suffix,
+        ("width", None) => Part::Width,
+        ("height", None) => Part::Height,
+        ("parent", None) => Part::Parent,
+        ("counter", None) => Part::Counter { width: 1 },
+        ("counter", Some(width)) => Part::Counter {
+            width: width
+                .parse()
+                .ok()
+                .filter(|w| (1..=10).contains(w))
+                .ok_or_else(|| invalid("the width must be a number between 1 and 10"))?,
+        },
+        ("date", None) => Part::Date {
+            format: "%Y-%m-%d".to_string(),
+        },
+        ("date", Some(format)) => Part::Date {
+            format: validate_date_format(format).map_err(|m| invalid(&m))?,
+        },
+        ("exif", Some(argument)) => {
+            let (tag, format) = match argument.split_once(':') {
+                Some((tag, format)) => (
+                    tag,
+                    Some(validate_date_format(format).map_err(|m| invalid(&m))?),
+                ),
+                None => (argument, None),
+            };
+            if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(invalid(
+                    "expected an EXIF tag name such as DateTimeOriginal",
+                ));
+            }
+            Part::Exif {
+                tag: tag.to_string(),
+                format,
+            }
+        }
+        ("exif", None) => return Err(invalid("expected an EXIF tag name")),
+        ("hash", None) => Part::Hash {
+            length: DEFAULT_HASH_LENGTH,
+        },
+        ("hash", Some(length)) => Part::Hash {
+            length: length
+                .parse()
+                .ok()
+                .filter(|l| (1..=MAX_HASH_LENGTH).contains(l))
+                .ok_or_else(|| {
+                    invalid(&format!(
+                        "the length must be a number between 1 and {MAX_HASH_LENGTH}"
+                    ))
+                })?,
+        },
+        ("name" | "ext" | "suffix" | "width" | "height" | "parent", Some(_)) => {
+            return Err(invalid("does not take arguments"))
+        }
+        _ => return Err(TemplateError::UnknownPlaceholder(placeholder.to_string())),
+    };
+    Ok(part)
+}
+
+fn validate_date_format(format: &str) -> Result<String, String> {
+    if format.is_empty() {
+        return Err("the date format is empty".to_string());
+    }
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid date format {format}"));
+    }
+    Ok(format.to_string())
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Replaces the characters that are not allowed in a file name
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+// Dates may create subfolders on purpose, like %Y/%m
+fn sanitize_date(value: &str) -> String {
+    value.split('/').map(sanitize).collect::<Vec<_>>().join("/")
+}
+
+fn read_exif(path: &Path) -> Option<exif::Exif> {
+    let file = File::open(path).ok()?;
+    exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()
+}
+
+fn exif_value(
+    exif: Option<&exif::Exif>,
+    tag: &str,
+    format: Option<&str>,
+    target: Target,
+) -> String {
+    let Some(field) = exif.and_then(|e| {
+        e.fields()
+            .find(|f| f.ifd_num == exif::In::PRIMARY && f.tag.to_string() == tag)
+    }) else {
+        return target.missing().to_string();
+    };
+
+    let value = match &field.value {
+        exif::Value::Ascii(values) => values
+            .first()
+            .map(|v| String::from_utf8_lossy(v).trim().to_string())
+            .unwrap_or_default(),
+        _ => field.display_value().to_string(),
+    };
+    let Some(format) = format else {
+        return target.clean(&value);
+    };
+    EXIF_DATE_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(&value, f).ok())
+        .map(|date| target.clean_date(&date.format(format).to_string()))
+        .unwrap_or_else(|| target.missing().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context(input: &Path) -> TemplateContext<'_> {
+        TemplateContext {
+            input,
+            extension: "webp",
+            suffix: "_min",
+            width: 640,
+            height: 480,
+            counter: 7,
+            now: Local.with_ymd_and_hms(2024, 3, 9, 14, 30, 0).unwrap(),
+        }
+    }
+
+    fn render(template: &str) -> Result<PathBuf, TemplateError> {
+        FilenameTemplate::parse(template)?.render(&context(Path::new("photos/trip/IMG 1.jpg")))
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        assert_eq!(
+            render("{parent}/{name}{suffix}_{width}x{height}").unwrap(),
+            Path::new("trip/IMG 1_min_640x480.webp")
+        );
+        assert_eq!(
+            render("{counter:04}-{date:%Y/%m}.{ext}").unwrap(),
+            Path::new("0007-2024/03.webp")
+        );
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{{name}}}").unwrap(), Path::new("{IMG 1}.webp"));
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(matches!(render(" "), Err(TemplateError::Empty)));
+        assert!(matches!(render("a{name"), Err(TemplateError::Unclosed(1))));
+        assert!(matches!(
+            render("name}"),
+            Err(TemplateError::UnexpectedClose(4))
+        ));
+        assert!(matches!(
+            render("{size}"),
+            Err(TemplateError::UnknownPlaceholder(p)) if p == "size"
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for template in [
+            "{counter:0}",
+            "{counter:x}",
+            "{hash:65}",
+            "{name:upper}",
+            "{exif}",
+            "{exif:Date Time}",
+            "{date:%Q}",
+        ] {
+            assert!(
+                matches!(render(template), Err(TemplateError::InvalidArgument { .. })),
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn outputs_stay_inside_the_output_folder() {
+        for template in ["/{name}", "../{name}", "a/./{name}", "C:{name}"] {
+            assert!(
+                matches!(render(template), Err(TemplateError::InvalidPath)),
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn values_are_sanitized_in_paths_only() {
+        let input = Path::new("photos/a:b?.jpg");
+        let template = "{name}";
+        assert_eq!(
+            FilenameTemplate::parse(template)
+                .unwrap()
+                .render(&context(input))
+                .unwrap(),
+            Path::new("a_b_.webp")
+        );
+        assert_eq!(
+            TextTemplate::parse(template)
+                .unwrap()
+                .render(&context(input))
+                .unwrap(),
+            "a:b?"
+        );
+    }
+
+    #[test]
+    fn missing_exif_tags_are_replaced() {
+        let input = Path::new("missing/IMG.jpg");
+        let template = "{exif:DateTimeOriginal:%Y}";
+        assert_eq!(
+            FilenameTemplate::parse(template)
+                .unwrap()
+                .render(&context(input))
+                .unwrap(),
+            Path::new("unknown.webp")
+        );
+        assert_eq!(
+            TextTemplate::parse(template)
+                .unwrap()
+                .render(&context(input))
+                .unwrap(),
+            ""
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use crate::atomic_file::AtomicFile;
+use crate::cache::CompressionCache;
+use crate::compressor::{compress_cimage, panic_message, CompressionContext, CompressionStatus};
+use crate::events::EventSink;
+use crate::memory::DEFAULT_MAX_FILE_SIZE;
+use crate::options::{OptionsError, OptionsPayload};
+use crate::scan_files::{is_valid, map_file};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+
+/// Version written in the watch folders file
+pub const WATCH_FOLDERS_SCHEMA_VERSION: u32 = 1;
+
+/// How long a file must stay unchanged before it is considered fully written
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Outputs are remembered until their own notifications are handled, the ones that are
+// never notified, like those outside the watched folders, are forgotten after this
+const WRITTEN_EXPIRY: Duration = Duration::from_secs(60);
+
+// Extensions used by browsers and copy tools while a file is still being written
+const PARTIAL_EXTENSIONS: [&str; 5] = ["tmp", "part", "partial", "crdownload", "download"];
+
+/// A folder registered for automatic compression with the options of a preset
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct WatchFolder {
+    pub path: PathBuf,
+    pub preset: String,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WatchFoldersFile {
+    version: u32,
+    folders: Vec<WatchFolder>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Notify(#[from] notify::Error),
+    #[error(transparent)]
+    InvalidOptions(#[from] OptionsError),
+    #[error("Unsupported watch folders version {0}")]
+    UnsupportedVersion(u32),
+    #[error("{0} is not a folder")]
+    NotAFolder(PathBuf),
+    #[error("{0} is already watched")]
+    AlreadyWatched(PathBuf),
+    #[error("{0} is not watched")]
+    NotWatched(PathBuf),
+}
+
+impl WatchFolder {
+    /// Reads the registered folders, an empty list if the file does not exist yet
+    pub fn load_all(path: &Path) -> Result<Vec<Self>, WatchError> {
+        let content = match fs::read(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let file: WatchFoldersFile = serde_json::from_slice(&content)?;
+        if file.version > WATCH_FOLDERS_SCHEMA_VERSION {
+            return Err(WatchError::UnsupportedVersion(file.version));
+        }
+        Ok(file.folders)
+    }
+
+    pub fn save_all(path: &Path, folders: &[Self]) -> Result<(), WatchError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_vec_pretty(&WatchFoldersFile {
+            version: WATCH_FOLDERS_SCHEMA_VERSION,
+            folders: folders.to_vec(),
+        })?;
+        let mut file = AtomicFile::create(path)?;
+        file.write_all(&content)?;
+        file.commit()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WatchSettings {
+    pub max_file_size: u64,
+    pub settle_time: Duration,
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            settle_time: DEFAULT_SETTLE_TIME,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ActiveFolder {
+    path: PathBuf,
+    recursive: bool,
+    options: OptionsPayload,
+}
+
+type FileStamp = (u64, Option<SystemTime>);
+
+struct PendingFile {
+    stamp: Option<FileStamp>,
+    since: Instant,
+}
+
+struct WrittenFile {
+    stamp: FileStamp,
+    since: Instant,
+}
+
+/// Compresses the images that appear in the watched folders.
+///
+/// Filesystem notifications only queue the files: a background thread compresses each
+/// of them once its size and modification time have not changed for the settle time,
+/// one file at a time. Results are reported through [`EventSink::watched_file_processed`].
+pub struct FolderWatcher {
+    watcher: RecommendedWatcher,
+    folders: Arc<Mutex<Vec<ActiveFolder>>>,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FolderWatcher {
+    pub fn new(
+        settings: WatchSettings,
+        cache: Option<Arc<CompressionCache>>,
+        sink: Arc<dyn EventSink + Send>,
+    ) -> Result<Self, WatchError> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in event.paths {
+                        // The worker is gone only while the watcher is being dropped
+                        let _ = sender.send(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Watch folder notification error: {e}"),
+            }
+        })?;
+
+        let folders = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = Worker {
+            receiver,
+            folders: folders.clone(),
+            stop: stop.clone(),
+            settings,
+            cache,
+            sink,
+            pending: HashMap::new(),
+            written: HashMap::new(),
+            processed: 0,
+        };
+
+        Ok(Self {
+            watcher,
+            folders,
+            stop,
+            worker: Some(std::thread::spawn(move || worker.run())),
+        })
+    }
+
+    /// Starts compressing the images added to `path` with `options`, returns the watched path
+    pub fn watch(
+        &mut self,
+        path: &Path,
+        recursive: bool,
+        options: OptionsPayload,
+    ) -> Result<PathBuf, WatchError> {
+        options.validate()?;
+        if !path.is_dir() {
+            return Err(WatchError::NotAFolder(path.to_path_buf()));
+        }
+        // Notifications report canonical paths on some platforms
+        let path = fs::canonicalize(path)?;
+        if self.lock().iter().any(|f| f.path == path) {
+            return Err(WatchError::AlreadyWatched(path));
+        }
+
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        self.watcher.watch(&path, mode)?;
+        self.lock().push(ActiveFolder {
+            path: path.clone(),
+            recursive,
+            options,
+        });
+        log::info!("Watching {}", path.display());
+        Ok(path)
+    }
+
+    pub fn unwatch(&mut self, path: &Path) -> Result<(), WatchError> {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let folder = {
+            let mut folders = self.lock();
+            let index = folders
+                .iter()
+                .position(|f| f.path == canonical || f.path == path)
+                .ok_or_else(|| WatchError::NotWatched(path.to_path_buf()))?;
+            folders.remove(index)
+        };
+
+        // Fails if the folder was deleted, in which case there is nothing left to watch
+        if let Err(e) = self.watcher.unwatch(&folder.path) {
+            log::warn!("Cannot unwatch {}: {e}", folder.path.display());
+        }
+        log::info!("Stopped watching {}", folder.path.display());
+        Ok(())
+    }
+
+    pub fn watched_folders(&self) -> Vec<PathBuf> {
+        self.lock().iter().map(|f| f.path.clone()).collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<ActiveFolder>> {
+        self.folders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for FolderWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                log::error!("Watch folder worker panicked");
+            }
+        }
+    }
+}
+
+struct Worker {
+    receiver: Receiver<PathBuf>,
+    folders: Arc<Mutex<Vec<ActiveFolder>>>,
+    stop: Arc<AtomicBool>,
+    settings: WatchSettings,
+    cache: Option<Arc<CompressionCache>>,
+    sink: Arc<dyn EventSink + Send>,
+    pending: HashMap<PathBuf, PendingFile>,
+    // Outputs written by the watcher, so they are not compressed again
+    written: HashMap<PathBuf, WrittenFile>,
+    processed: usize,
+}
+
+impl Worker {
+    fn run(mut self) {
+        let mut last_check = Instant::now();
+        while !self.stop.load(Ordering::Relaxed) {
+            match self.receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(path) => self.queue(path),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            while let Ok(path) = self.receiver.try_recv() {
+                self.queue(path);
+            }
+
+            if last_check.elapsed() < POLL_INTERVAL {
+                continue;
+            }
+            last_check = Instant::now();
+            let ready = self.settled_files();
+            for path in &ready {
+                if self.stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                self.process(path);
+            }
+            if !ready.is_empty() {
+                if let Some(Err(e)) = self.cache.as_ref().map(|c| c.save()) {
+                    log::error!("Cannot save the watch folder cache: {e}");
+                }
+            }
+            self.forget_written_files();
+        }
+    }
+
+    fn queue(&mut self, path: PathBuf) {
+        if path.is_dir() {
+            // Folders moved inside a recursive watch do not notify their content
+            if self.folder_for(&path).is_some_and(|f| f.recursive) {
+                for entry in WalkDir::new(&path).into_iter().filter_map(|e| e.ok()) {
+                    if entry.file_type().is_file() {
+                        self.queue(entry.into_path());
+                    }
+                }
+            }
+            return;
+        }
+        self.pending.insert(
+            path,
+            PendingFile {
+                stamp: None,
+                since: Instant::now(),
+            },
+        );
+    }
+
+    /// Removes and returns the files that did not change for the settle time
+    fn settled_files(&mut self) -> Vec<PathBuf> {
+        let settle_time = self.settings.settle_time;
+        let mut ready = Vec::new();
+        self.pending.retain(|path, pending| {
+            let Some(stamp) = file_stamp(path) else {
+                return false;
+            };
+            if pending.stamp != Some(stamp) {
+                pending.stamp = Some(stamp);
+                pending.since = Instant::now();
+                return true;
+            }
+            // Writers on Windows keep the file locked until they are done
+            if pending.since.elapsed() < settle_time || File::open(path).is_err() {
+                return true;
+            }
+            ready.push(path.clone());
+            false
+        });
+        ready
+    }
+
+    /// Outputs still waiting to settle are kept, the others expire
+    fn forget_written_files(&mut self) {
+        let pending = &self.pending;
+        self.written.retain(|path, written| {
+            pending.contains_key(path) || written.since.elapsed() < WRITTEN_EXPIRY
+        });
+    }
+
+    /// A panic only loses this file, the worker keeps watching the folders
+    fn process(&mut self, path: &Path) {
+        let processed = panic::catch_unwind(AssertUnwindSafe(|| self.process_file(path)));
+        if let Err(payload) = processed {
+            log::error!(
+                "Watch folder: panic while processing {}: {}",
+                path.display(),
+                panic_message(payload.as_ref())
+            );
+        }
+    }
+
+    fn process_file(&mut self, path: &Path) {
+        let stamp = file_stamp(path);
+        let written = self.written.remove(path);
+        if stamp.is_some() && written.map(|w| w.stamp) == stamp {
+            return;
+        }
+
+        let Some(folder) = self.folder_for(path).filter(|f| !is_ignored(f, path)) else {
+            return;
+        };
+        if !is_valid(path) {
+            return;
+        }
+        let Some(cimage) = map_file(path) else {
+            return;
+        };
+
+        self.processed += 1;
+        let base_folder = folder.path.display().to_string();
+        let result = compress_cimage(
+            &cimage,
+            &folder.options,
+            &CompressionContext {
+                cache: self.cache.as_deref(),
+                counter: self.processed,
+                ..CompressionContext::new(&base_folder, self.settings.max_file_size)
+            },
+        );
+
+        let c = &result.cimage;
+        match result.status {
+            CompressionStatus::Error => {
+                log::error!("Watch folder: cannot compress {}: {}", c.path, c.info)
+            }
+            _ => log::info!(
+                "Watch folder: {} -> {} ({} -> {} bytes)",
+                c.path,
+                c.compressed_file_path,
+                c.size,
+                c.compressed_size
+            ),
+        }
+        if !c.compressed_file_path.is_empty() {
+            let output = PathBuf::from(&c.compressed_file_path);
+            if let Some(stamp) = file_stamp(&output) {
+                let since = Instant::now();
+                self.written.insert(output, WrittenFile { stamp, since });
+            }
+        }
+        self.sink.watched_file_processed(&folder.path, &result);
+    }
+
+    fn folder_for(&self, path: &Path) -> Option<ActiveFolder> {
+        let folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        folders
+            .iter()
+            .filter(|f| {
+                if f.recursive {
+                    path.starts_with(&f.path)
+                } else {
+                    path.parent() == Some(f.path.as_path())
+                }
+            })
+            .max_by_key(|f| f.path.components().count())
+            .cloned()
+    }
+}
+
+/// Hidden and partially downloaded files, and anything inside the output folder
+fn is_ignored(folder: &ActiveFolder, path: &Path) -> bool {
+    let is_hidden = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+    let is_partial = path.extension().is_some_and(|e| {
+        let extension = e.to_string_lossy().to_lowercase();
+        PARTIAL_EXTENSIONS.contains(&extension.as_str())
+    });
+
+    let output = &folder.options.output_options;
+    let is_output = !output.same_folder_as_input
+        && fs::canonicalize(&output.output_folder).is_ok_and(|o| path.starts_with(o));
+
+    is_hidden || is_partial || is_output
+}
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    metadata
+        .is_file()
+        .then(|| (metadata.len(), metadata.modified().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::NoopEventSink;
+    use std::thread;
+
+    fn worker(settle_time: Duration) -> Worker {
+        let (_, receiver) = mpsc::channel();
+        Worker {
+            receiver,
+            folders: Arc::default(),
+            stop: Arc::default(),
+            settings: WatchSettings {
+                settle_time,
+                ..WatchSettings::default()
+            },
+            cache: None,
+            sink: Arc::new(NoopEventSink),
+            pending: HashMap::new(),
+            written: HashMap::new(),
+            processed: 0,
+        }
+    }
+
+    #[test]
+    fn files_are_ready_once_unchanged_for_the_settle_time() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("a.jpg");
+        fs::write(&path, b"image").unwrap();
+        let mut worker = worker(Duration::from_millis(100));
+
+        worker.queue(path.clone());
+        assert!(worker.settled_files().is_empty());
+        assert!(worker.settled_files().is_empty());
+
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(worker.settled_files(), [path]);
+        assert!(worker.pending.is_empty());
+    }
+
+    #[test]
+    fn changes_restart_the_settle_time() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("a.jpg");
+        fs::write(&path, b"ima").unwrap();
+        let mut worker = worker(Duration::from_millis(200));
+
+        worker.queue(path.clone());
+        assert!(worker.settled_files().is_empty());
+        thread::sleep(Duration::from_millis(120));
+        fs::write(&path, b"image").unwrap();
+        assert!(worker.settled_files().is_empty());
+
+        thread::sleep(Duration::from_millis(120));
+        assert!(worker.settled_files().is_empty());
+        thread::sleep(Duration::from_millis(120));
+        assert_eq!(worker.settled_files(), [path]);
+    }
+
+    #[test]
+    fn deleted_files_are_forgotten() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("a.jpg");
+        fs::write(&path, b"image").unwrap();
+        let mut worker = worker(Duration::ZERO);
+
+        worker.queue(path.clone());
+        fs::remove_file(&path).unwrap();
+        assert!(worker.settled_files().is_empty());
+        assert!(worker.pending.is_empty());
+    }
+
+    #[test]
+    fn outputs_are_forgotten_once_handled_or_expired() {
+        let directory = tempfile::tempdir().unwrap();
+        let output = directory.path().join("a.min.jpg");
+        fs::write(&output, b"compressed").unwrap();
+        let mut worker = worker(Duration::ZERO);
+        let expired = Instant::now() - WRITTEN_EXPIRY;
+        let stamp = file_stamp(&output).unwrap();
+        for (name, since) in [("a.min.jpg", Instant::now()), ("b.min.jpg", expired)] {
+            let path = directory.path().join(name);
+            worker.written.insert(path, WrittenFile { stamp, since });
+        }
+
+        worker.forget_written_files();
+        assert_eq!(worker.written.len(), 1);
+
+        worker.process_file(&output);
+        assert!(worker.written.is_empty());
+    }
+
+    #[test]
+    fn hidden_and_partial_files_are_ignored() {
+        let directory = tempfile::tempdir().unwrap();
+        let folder = ActiveFolder {
+            path: directory.path().to_path_buf(),
+            recursive: false,
+            options: OptionsPayload::default(),
+        };
+
+        let ignored = |name: &str| is_ignored(&folder, &directory.path().join(name));
+        assert!(!ignored("a.jpg"));
+        assert!(ignored(".a.jpg"));
+        assert!(ignored("a.jpg.crdownload"));
+        assert!(ignored("a.PART"));
+    }
+}
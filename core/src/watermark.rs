@@ -0,0 +1,284 @@
+use crate::options::{parse_color, WatermarkKind, WatermarkOptions, WatermarkPosition};
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageError, Rgba, RgbaImage};
+use std::{fs, io};
+
+/// Height the text is rendered at, before it is scaled like a logo
+const TEXT_HEIGHT: f32 = 256.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatermarkError {
+    #[error("Cannot open the watermark image {path}: {source}")]
+    Image { path: String, source: ImageError },
+    #[error("Cannot read the font {path}: {source}")]
+    FontRead { path: String, source: io::Error },
+    #[error("{0} is not a TrueType or OpenType font")]
+    InvalidFont(String),
+    #[error("Invalid text color {0}")]
+    InvalidColor(String),
+}
+
+/// Logo or text drawn over the images, see [`WatermarkOptions`]
+pub struct Watermark {
+    /// Logo or rendered text at full size
+    mark: RgbaImage,
+    options: WatermarkOptions,
+}
+
+impl Watermark {
+    /// `None` when the watermark is disabled
+    pub fn load(options: &WatermarkOptions) -> Result<Option<Self>, WatermarkError> {
+        if !options.enabled {
+            return Ok(None);
+        }
+        let mark = match options.kind {
+            WatermarkKind::Image => image::open(&options.image_path)
+                .map_err(|source| WatermarkError::Image {
+                    path: options.image_path.clone(),
+                    source,
+                })?
+                .to_rgba8(),
+            WatermarkKind::Text => {
+                let data =
+                    fs::read(&options.font_path).map_err(|source| WatermarkError::FontRead {
+                        path: options.font_path.clone(),
+                        source,
+                    })?;
+                let font = FontVec::try_from_vec(data)
+                    .map_err(|_| WatermarkError::InvalidFont(options.font_path.clone()))?;
+                let color = parse_color(&options.color)
+                    .ok_or_else(|| WatermarkError::InvalidColor(options.color.clone()))?;
+                render_text(&font, &options.text, Rgba(color))
+            }
+        };
+        Ok(Some(Self {
+            mark,
+            options: options.clone(),
+        }))
+    }
+
+    /// Draws the watermark over `image`, sized from its short edge. Images without an
+    /// alpha channel stay without one.
+    pub fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let has_alpha = image.color().has_alpha();
+        let mut canvas = image.to_rgba8();
+        let short_edge = canvas.width().min(canvas.height());
+        let mark = self.scaled_mark(short_edge);
+
+        let margin = i64::from(short_edge * self.options.margin / 100);
+        let (width, height) = (i64::from(canvas.width()), i64::from(canvas.height()));
+        let (mark_width, mark_height) = (i64::from(mark.width()), i64::from(mark.height()));
+        if self.options.tiled {
+            let mut y = margin;
+            while y < height {
+                let mut x = margin;
+                while x < width {
+                    imageops::overlay(&mut canvas, &mark, x, y);
+                    x += mark_width + margin;
+                }
+                y += mark_height + margin;
+            }
+        } else {
+            let (horizontal, vertical) = alignment(self.options.position);
+            let place = |free: i64, alignment: u8| match alignment {
+                0 => margin,
+                1 => free / 2,
+                _ => free - margin,
+            };
+            let x = place(width - mark_width, horizontal);
+            let y = place(height - mark_height, vertical);
+            imageops::overlay(&mut canvas, &mark, x, y);
+        }
+
+        if has_alpha {
+            DynamicImage::ImageRgba8(canvas)
+        } else {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+        }
+    }
+
+    /// Mark resized for an image with the given short edge, with the opacity applied
+    fn scaled_mark(&self, short_edge: u32) -> RgbaImage {
+        let width = (short_edge * self.options.scale / 100).max(1);
+        let height =
+            u64::from(self.mark.height()) * u64::from(width) / u64::from(self.mark.width().max(1));
+        let height = u32::try_from(height).unwrap_or(u32::MAX).max(1);
+        let mut mark = imageops::resize(&self.mark, width, height, FilterType::Triangle);
+        let opacity = self.options.opacity.min(100);
+        for pixel in mark.pixels_mut() {
+            pixel.0[3] = (u32::from(pixel.0[3]) * opacity / 100) as u8;
+        }
+        mark
+    }
+}
+
+/// Horizontal and vertical alignment: 0 at the start, 1 centered, 2 at the end
+fn alignment(position: WatermarkPosition) -> (u8, u8) {
+    match position {
+        WatermarkPosition::TopLeft => (0, 0),
+        WatermarkPosition::Top => (1, 0),
+        WatermarkPosition::TopRight => (2, 0),
+        WatermarkPosition::Left => (0, 1),
+        WatermarkPosition::Center => (1, 1),
+        WatermarkPosition::Right => (2, 1),
+        WatermarkPosition::BottomLeft => (0, 2),
+        WatermarkPosition::Bottom => (1, 2),
+        WatermarkPosition::BottomRight => (2, 2),
+    }
+}
+
+/// `text` on a single line, on a transparent background
+fn render_text(font: &FontVec, text: &str, color: Rgba<u8>) -> RgbaImage {
+    let font = font.as_scaled(PxScale::from(TEXT_HEIGHT));
+    let mut glyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(font.scale(), point(caret, font.ascent())));
+        caret += font.h_advance(id);
+        previous = Some(id);
+    }
+
+    let width = caret.ceil().max(1.0) as u32;
+    let height = (font.ascent() - font.descent()).ceil().max(1.0) as u32;
+    let mut mark = RgbaImage::new(width, height);
+    for glyph in glyphs {
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|x, y, coverage| {
+            let x = bounds.min.x as i64 + i64::from(x);
+            let y = bounds.min.y as i64 + i64::from(y);
+            let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) else {
+                return;
+            };
+            if let Some(pixel) = mark.get_pixel_mut_checked(x, y) {
+                let alpha = (coverage.clamp(0.0, 1.0) * f32::from(color.0[3])) as u8;
+                *pixel = Rgba([color.0[0], color.0[1], color.0[2], pixel.0[3].max(alpha)]);
+            }
+        });
+    }
+    mark
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    /// A solid red square, drawn 10x10 with a margin of 5 on a 100x100 image
+    fn watermark(options: WatermarkOptions) -> Watermark {
+        Watermark {
+            mark: RgbaImage::from_pixel(20, 20, RED),
+            options: WatermarkOptions {
+                enabled: true,
+                margin: 5,
+                scale: 10,
+                ..options
+            },
+        }
+    }
+
+    fn black_image() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::new(100, 100))
+    }
+
+    fn pixel(image: &DynamicImage, x: u32, y: u32) -> [u8; 3] {
+        image.to_rgb8().get_pixel(x, y).0
+    }
+
+    #[test]
+    fn marks_are_placed_at_their_position() {
+        let cases = [
+            (WatermarkPosition::TopLeft, (5, 5)),
+            (WatermarkPosition::Center, (45, 45)),
+            (WatermarkPosition::Right, (85, 45)),
+            (WatermarkPosition::BottomRight, (85, 85)),
+        ];
+        for (position, (x, y)) in cases {
+            let watermark = watermark(WatermarkOptions {
+                position,
+                opacity: 100,
+                ..WatermarkOptions::default()
+            });
+
+            let image = watermark.apply(black_image());
+
+            assert_eq!(pixel(&image, x, y), [255, 0, 0], "{position:?}");
+            assert_eq!(pixel(&image, x + 9, y + 9), [255, 0, 0], "{position:?}");
+            assert_eq!(pixel(&image, x - 1, y - 1), [0, 0, 0], "{position:?}");
+            assert_eq!(pixel(&image, x + 10, y + 10), [0, 0, 0], "{position:?}");
+        }
+    }
+
+    #[test]
+    fn opacity_blends_the_mark() {
+        let watermark = watermark(WatermarkOptions {
+            position: WatermarkPosition::TopLeft,
+            opacity: 50,
+            ..WatermarkOptions::default()
+        });
+
+        let [red, green, blue] = pixel(&watermark.apply(black_image()), 10, 10);
+
+        assert!((120..=135).contains(&red), "{red}");
+        assert_eq!((green, blue), (0, 0));
+    }
+
+    #[test]
+    fn tiled_marks_repeat_margin_apart() {
+        let watermark = watermark(WatermarkOptions {
+            opacity: 100,
+            tiled: true,
+            ..WatermarkOptions::default()
+        });
+
+        let image = watermark.apply(black_image());
+
+        for (x, y) in [(5, 5), (20, 5), (95, 5), (5, 95), (50, 80)] {
+            assert_eq!(pixel(&image, x, y), [255, 0, 0], "{x}x{y}");
+        }
+        for (x, y) in [(0, 0), (15, 5), (19, 19), (5, 15)] {
+            assert_eq!(pixel(&image, x, y), [0, 0, 0], "{x}x{y}");
+        }
+    }
+
+    #[test]
+    fn the_alpha_channel_is_kept_only_if_present() {
+        let watermark = watermark(WatermarkOptions {
+            opacity: 100,
+            ..WatermarkOptions::default()
+        });
+
+        assert!(!watermark.apply(black_image()).color().has_alpha());
+        let transparent = DynamicImage::ImageRgba8(RgbaImage::new(100, 100));
+        assert!(watermark.apply(transparent).color().has_alpha());
+    }
+
+    #[test]
+    fn disabled_watermarks_are_not_loaded() {
+        let options = WatermarkOptions {
+            enabled: false,
+            image_path: "missing.png".to_string(),
+            ..WatermarkOptions::default()
+        };
+        assert!(Watermark::load(&options).unwrap().is_none());
+
+        let options = WatermarkOptions {
+            enabled: true,
+            ..options
+        };
+        assert!(matches!(
+            Watermark::load(&options),
+            Err(WatermarkError::Image { .. })
+        ));
+    }
+}
@@ -0,0 +1,417 @@
+use caesium_core::app_data::CompressionStatus as BatchStatus;
+use caesium_core::batch::{compress_images, BatchSettings};
+use caesium_core::compressor::{CompressionResult, CompressionStatus, CompressionSummary};
+use caesium_core::errors::{BatchError, CompressionErrorCode};
+use caesium_core::events::EventSink;
+use caesium_core::memory::ResourceLimits;
+use caesium_core::options::{
+    CollisionPolicy, MetadataPreset, OptionsPayload, OutputOptions, WatermarkOptions,
+};
+use caesium_core::scan_files::map_file;
+use caesium_core::{CImage, ImageStatus};
+use exif::experimental::Writer;
+use exif::{Field, In, Tag, Value};
+use image::codecs::jpeg::JpegEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+const FIXTURES: [&str; 3] = ["gradient.jpg", "gradient.png", "translucent.png"];
+
+enum Event {
+    ImageUpdated(CompressionResult),
+    Progress(usize),
+    Finished(CompressionSummary),
+}
+
+/// Keeps every event of a batch, in the order they were sent
+#[derive(Default)]
+struct RecordingSink {
+    events: Mutex<Vec<Event>>,
+}
+
+impl RecordingSink {
+    fn push(&self, event: Event) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl EventSink for RecordingSink {
+    fn image_updated(&self, result: &CompressionResult) {
+        self.push(Event::ImageUpdated(result.clone()));
+    }
+
+    fn compression_progress(&self, progress: usize) {
+        self.push(Event::Progress(progress));
+    }
+
+    fn compression_finished(&self, summary: &CompressionSummary) {
+        self.push(Event::Finished(summary.clone()));
+    }
+}
+
+/// Copies of the fixtures in `input/`, compressed into `output/` with the default options
+/// unless a test changes them before [`Batch::run`]
+struct Batch {
+    root: TempDir,
+    images: Vec<CImage>,
+    options: OptionsPayload,
+    limits: ResourceLimits,
+    status: BatchStatus,
+}
+
+impl Batch {
+    /// Every fixture, directly in `input/`
+    fn new() -> Self {
+        Self::with(&FIXTURES.map(|fixture| (fixture, "")))
+    }
+
+    /// Each fixture in the given subfolder of `input/`
+    fn with(fixtures: &[(&str, &str)]) -> Self {
+        let root = tempfile::tempdir().unwrap();
+        let options = OptionsPayload {
+            output_options: OutputOptions {
+                output_folder: root.path().join("output").display().to_string(),
+                ..OutputOptions::default()
+            },
+            ..OptionsPayload::default()
+        };
+        let mut batch = Self {
+            root,
+            images: vec![],
+            options,
+            limits: ResourceLimits::default(),
+            status: BatchStatus::default(),
+        };
+        batch.images = fixtures
+            .iter()
+            .map(|(fixture, folder)| batch.add(fixture, folder))
+            .collect();
+        batch
+    }
+
+    fn input(&self) -> PathBuf {
+        self.root.path().join("input")
+    }
+
+    fn output(&self) -> PathBuf {
+        self.root.path().join("output")
+    }
+
+    fn add(&self, fixture: &str, folder: &str) -> CImage {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let folder = self.input().join(folder);
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join(fixture);
+        fs::copy(fixtures.join(fixture), &path).unwrap();
+        map_file(&path).unwrap()
+    }
+
+    fn run(&self) -> (Result<CompressionSummary, BatchError>, Vec<Event>) {
+        let sink = RecordingSink::default();
+        let input = self.input().display().to_string();
+        let settings = BatchSettings {
+            threads: 2,
+            limits: self.limits,
+            cache: None,
+            journal: None,
+            report: None,
+            quality_metrics: false,
+        };
+        let summary = compress_images(
+            &self.images,
+            &self.options,
+            &input,
+            &settings,
+            &self.status,
+            &sink,
+        );
+        (summary, sink.events.into_inner().unwrap())
+    }
+}
+
+/// Noisy JPEG saved at a low quality, with the camera make and a location in its EXIF
+fn jpeg_with_location() -> Vec<u8> {
+    let pixels: Vec<u8> = (0..64 * 64 * 3u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, 10)
+        .write_image(&pixels, 64, 64, ExtendedColorType::Rgb8)
+        .unwrap();
+
+    let ascii = |tag, value: &str| Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    };
+    let fields = [ascii(Tag::Make, "Caesium"), ascii(Tag::GPSLatitudeRef, "N")];
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).unwrap();
+
+    let mut app1 = b"Exif\0\0".to_vec();
+    app1.extend_from_slice(tiff.get_ref());
+    let length = u16::try_from(app1.len() + 2).unwrap();
+    let mut data = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    data.extend_from_slice(&length.to_be_bytes());
+    data.extend_from_slice(&app1);
+    data.extend_from_slice(&jpeg[2..]);
+    data
+}
+
+/// Final result of each image, in the order they finished
+fn final_results(events: &[Event]) -> Vec<&CompressionResult> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::ImageUpdated(result)
+                if !matches!(result.cimage.status, ImageStatus::Compressing) =>
+            {
+                Some(result)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn compresses_every_image_and_reports_each_step() {
+    let batch = Batch::new();
+
+    let (summary, events) = batch.run();
+    let summary = summary.unwrap();
+
+    assert_eq!(summary.total_images, 3);
+    assert_eq!(summary.total_errors, 0);
+    assert_eq!(summary.total_success + summary.total_skipped, 3);
+    assert!(matches!(events.first(), Some(Event::Progress(0))));
+    assert!(matches!(events.last(), Some(Event::Finished(s)) if s.total_images == 3));
+    let progress: Vec<usize> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Progress(progress) => Some(*progress),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(progress, [0, 1, 2, 3]);
+
+    let results = final_results(&events);
+    assert_eq!(results.len(), 3);
+    for result in results {
+        let output = Path::new(&result.cimage.compressed_file_path);
+        assert!(!matches!(result.status, CompressionStatus::Error));
+        assert_eq!(output.parent(), Some(batch.output().as_path()));
+        assert_eq!(
+            fs::metadata(output).unwrap().len(),
+            result.cimage.compressed_size
+        );
+    }
+    for fixture in FIXTURES {
+        assert!(batch.input().join(fixture).exists());
+    }
+}
+
+#[test]
+fn keeps_the_folder_structure() {
+    let batch = Batch::with(&[("gradient.jpg", ""), ("gradient.png", "nested/folder")]);
+
+    let (summary, _) = batch.run();
+
+    assert_eq!(summary.unwrap().total_errors, 0);
+    assert!(batch.output().join("gradient.jpg").exists());
+    assert!(batch.output().join("nested/folder/gradient.png").exists());
+}
+
+#[test]
+fn colliding_outputs_are_renamed_in_input_order() {
+    let mut batch = Batch::with(&[("gradient.jpg", "a"), ("gradient.jpg", "b")]);
+    batch.options.output_options.keep_folder_structure = false;
+    batch.options.output_options.collision_policy = CollisionPolicy::Rename;
+
+    let (summary, events) = batch.run();
+
+    assert_eq!(summary.unwrap().output_collisions, 1);
+    let results = final_results(&events);
+    let output_of = |image: &CImage| {
+        let result = results.iter().find(|r| r.cimage.id == image.id).unwrap();
+        assert_eq!(
+            result.cimage.collision_policy.is_some(),
+            image.id == batch.images[1].id
+        );
+        PathBuf::from(&result.cimage.compressed_file_path)
+    };
+    assert_eq!(
+        output_of(&batch.images[0]),
+        batch.output().join("gradient.jpg")
+    );
+    assert_eq!(
+        output_of(&batch.images[1]),
+        batch.output().join("gradient_1.jpg")
+    );
+}
+
+#[test]
+fn oversized_files_are_errors_and_write_nothing() {
+    let mut batch = Batch::new();
+    batch.limits.max_file_size = 1;
+
+    let (summary, events) = batch.run();
+    let summary = summary.unwrap();
+
+    assert_eq!(summary.total_errors, 3);
+    assert_eq!(
+        summary.errors_by_code.get(&CompressionErrorCode::TooLarge),
+        Some(&3)
+    );
+    for result in final_results(&events) {
+        assert!(matches!(result.cimage.status, ImageStatus::Error));
+        assert_eq!(
+            result.cimage.error_code,
+            Some(CompressionErrorCode::TooLarge)
+        );
+    }
+    assert!(!batch.output().exists());
+}
+
+#[test]
+fn invalid_options_stop_the_batch_before_any_event() {
+    let mut batch = Batch::with(&[("gradient.jpg", "")]);
+    batch.options.compression_options.jpeg.quality = 101;
+
+    let (summary, events) = batch.run();
+
+    let Err(BatchError::InvalidOptions(e)) = summary else {
+        panic!("the options were accepted");
+    };
+    assert_eq!(e.errors[0].field, "compression_options.jpeg.quality");
+    assert!(events.is_empty());
+    assert!(!batch.output().exists());
+}
+
+#[test]
+fn cancelled_batches_compress_nothing() {
+    let batch = Batch::new();
+    batch
+        .status
+        .is_compression_cancelled
+        .store(true, Ordering::Relaxed);
+
+    let (summary, events) = batch.run();
+    let summary = summary.unwrap();
+
+    assert_eq!(summary.total_images, 3);
+    assert_eq!(
+        summary.total_success + summary.total_skipped + summary.total_errors,
+        0
+    );
+    assert!(final_results(&events).is_empty());
+    assert!(!batch.output().exists());
+}
+
+#[test]
+fn bigger_outputs_still_drop_the_filtered_metadata() {
+    let mut batch = Batch::with(&[]);
+    let input = batch.input().join("location.jpg");
+    fs::create_dir_all(batch.input()).unwrap();
+    fs::write(&input, jpeg_with_location()).unwrap();
+    batch.images.push(map_file(&input).unwrap());
+    let compression = &mut batch.options.compression_options;
+    compression.jpeg.quality = 100;
+    compression.metadata.preset = MetadataPreset::Privacy;
+
+    let (summary, events) = batch.run();
+
+    assert_eq!(summary.unwrap().total_errors, 0);
+    let result = final_results(&events)[0];
+    assert!(result.cimage.compressed_size > result.cimage.size);
+    let output = fs::read(&result.cimage.compressed_file_path).unwrap();
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(output))
+        .unwrap();
+    assert!(exif.get_field(Tag::Make, In::PRIMARY).is_some());
+    assert!(exif.fields().all(|f| f.tag != Tag::GPSLatitudeRef));
+}
+
+#[test]
+fn metadata_fields_fail_the_images_that_cannot_hold_them() {
+    let mut batch = Batch::with(&[("gradient.jpg", "")]);
+    let input = batch.input().join("still.gif");
+    image::RgbImage::new(8, 8).save(&input).unwrap();
+    batch.images.push(map_file(&input).unwrap());
+    batch.options.compression_options.metadata_fields.artist = "Jane Doe".to_string();
+
+    let (summary, events) = batch.run();
+
+    let summary = summary.unwrap();
+    assert_eq!((summary.total_success, summary.total_errors), (1, 1));
+    let gif = final_results(&events)
+        .into_iter()
+        .find(|r| r.cimage.path.ends_with("still.gif"))
+        .unwrap();
+    assert_eq!(
+        gif.cimage.error_code,
+        Some(CompressionErrorCode::ImageMetadata)
+    );
+    assert!(!batch.output().join("still.gif").exists());
+}
+
+#[test]
+fn watermarked_images_keep_their_iptc() {
+    let mut batch = Batch::with(&[]);
+    fs::create_dir_all(batch.input()).unwrap();
+    let fixture =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/gradient.jpg"))
+            .unwrap();
+    // Photoshop resources holding the IPTC caption "Rome"
+    let iptc = b"Photoshop 3.0\08BIM\x04\x04\0\0\0\0\0\x09\x1C\x02\x78\0\x04Rome\0";
+    let input = batch.input().join("captioned.jpg");
+    let length = u16::try_from(iptc.len() + 2).unwrap().to_be_bytes();
+    fs::write(
+        &input,
+        [&fixture[..2], &[0xFF, 0xED], &length, iptc, &fixture[2..]].concat(),
+    )
+    .unwrap();
+    batch.images.push(map_file(&input).unwrap());
+    let mark = batch.root.path().join("mark.png");
+    image::RgbaImage::new(4, 4).save(&mark).unwrap();
+    batch.options.watermark_options = WatermarkOptions {
+        enabled: true,
+        image_path: mark.display().to_string(),
+        ..WatermarkOptions::default()
+    };
+
+    let (summary, events) = batch.run();
+
+    assert_eq!(summary.unwrap().total_errors, 0);
+    let output = fs::read(&final_results(&events)[0].cimage.compressed_file_path).unwrap();
+    assert!(output.windows(iptc.len()).any(|w| w == iptc));
+}
+
+#[test]
+fn images_bigger_than_the_memory_budget_run_one_at_a_time() {
+    let mut batch = Batch::new();
+    batch.limits.memory_budget = 1;
+
+    let (summary, events) = batch.run();
+
+    assert_eq!(summary.unwrap().total_errors, 0);
+    let mut running = 0;
+    for event in &events {
+        if let Event::ImageUpdated(result) = event {
+            running += match result.cimage.status {
+                ImageStatus::Compressing => 1,
+                _ => -1,
+            };
+            assert!(running <= 1);
+        }
+    }
+    assert_eq!(final_results(&events).len(), 3);
+}
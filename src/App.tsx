@@ -26,6 +26,7 @@ import { error, info } from '@tauri-apps/plugin-log';
 import CompressionProgressDialog from '@/components/dialogs/CompressionProgressDialog.tsx';
 import { showNotification } from '@/utils/notification-manager.ts';
 import PromptOnExitDialog from '@/components/dialogs/PromptOnExitDialog.tsx';
+import PlanDialog from '@/components/dialogs/PlanDialog.tsx';
 
 function App() {
   const {
@@ -36,6 +37,7 @@ function App() {
     currentPage,
     updateList,
     finishCompression,
+    restoreSession,
     setIsCompressionPaused,
     setIsCompressionCancelling,
   } = useFileListStore();
@@ -132,6 +134,7 @@ function App() {
 
     const compressionFinishedListener = listen<CompressionFinished>('fileList:compressionFinished', (event) => {
       finishCompression();
+      useFileListStore.getState().setLastJobId(event.payload.job_id);
       void showNotification({
         title: t('compression_report.compression_finished'),
         body: t('compression_report.saved_long', {
@@ -178,7 +181,21 @@ function App() {
       setIsCompressionCancelling(false);
     });
 
-    invokeBackend<FileListPayload>('change_page', { page: currentPage }).then((payload) => updateList(payload));
+    restoreSession().then((restored) => {
+      if (!restored) {
+        return invokeBackend<FileListPayload>('change_page', { page: currentPage }).then((payload) =>
+          updateList(payload),
+        );
+      }
+      const { missing, changed } = restored.revalidation;
+      if (missing > 0 || changed > 0) {
+        addToast({
+          title: t('session.restored'),
+          description: t('session.revalidation', { missing, changed }),
+          color: 'warning',
+        });
+      }
+    });
 
     if (checkUpdatesAtStartup) {
       void info('Checking for updates at startup...');
@@ -229,6 +246,7 @@ function App() {
       <AboutDialog></AboutDialog>
       <CheckForUpdatesDialog></CheckForUpdatesDialog>
       <AdvancedImportDialog></AdvancedImportDialog>
+      <PlanDialog></PlanDialog>
       <CompressionProgressDialog></CompressionProgressDialog>
       <PromptOnExitDialog
         onCancel={() => {
@@ -1,7 +1,9 @@
-import { DropdownItem, DropdownMenu } from '@heroui/react';
+import { addToast, DropdownItem, DropdownMenu } from '@heroui/react';
 import { useTranslation } from 'react-i18next';
 import useUIStore from '@/stores/ui.store.ts';
-import { Heart, Import, Info, RefreshCcw } from 'lucide-react';
+import { FileText, FolderOpen, Heart, Import, Info, ListChecks, RefreshCcw, Save, Undo2 } from 'lucide-react';
+import { open, save } from '@tauri-apps/plugin-dialog';
+import useFileListStore from '@/stores/file-list.store.ts';
 
 function AppMenu() {
   const { t } = useTranslation();
@@ -17,7 +19,16 @@ function AppMenu() {
     setCheckForUpdatesDialogOpen,
     getAppMenuDisabledItems,
     setAdvancedImportDialogOpen,
+    setPlanDialogOpen,
   } = useUIStore();
+  const { openProject, saveProject, exportReport, undoLastJob } = useFileListStore();
+
+  const projectFilters = [{ name: t('project_file'), extensions: ['caesium'] }];
+  const reportFilters = [
+    { name: 'CSV', extensions: ['csv'] },
+    { name: 'JSON', extensions: ['json'] },
+    { name: 'HTML', extensions: ['html'] },
+  ];
 
   return (
     <DropdownMenu
@@ -29,12 +40,77 @@ function AppMenu() {
       <DropdownItem
         key="advancedImport"
         hideSelectedIcon
-        showDivider
         startContent={<Import className="size-4" />}
         onPress={() => setAdvancedImportDialogOpen(true)}
       >
         {t('actions.advanced_import')}
       </DropdownItem>
+      <DropdownItem
+        key="openProject"
+        hideSelectedIcon
+        startContent={<FolderOpen className="size-4" />}
+        onPress={async () => {
+          const path = await open({ multiple: false, directory: false, filters: projectFilters });
+          if (path) {
+            await openProject(path);
+          }
+        }}
+      >
+        {t('actions.open_project')}
+      </DropdownItem>
+      <DropdownItem
+        key="saveProject"
+        hideSelectedIcon
+        startContent={<Save className="size-4" />}
+        onPress={async () => {
+          const path = await save({ filters: projectFilters });
+          if (path) {
+            await saveProject(path);
+          }
+        }}
+      >
+        {t('actions.save_project')}
+      </DropdownItem>
+      <DropdownItem
+        key="planCompression"
+        hideSelectedIcon
+        startContent={<ListChecks className="size-4" />}
+        onPress={() => setPlanDialogOpen(true)}
+      >
+        {t('actions.plan_compression')}
+      </DropdownItem>
+      <DropdownItem
+        key="exportReport"
+        hideSelectedIcon
+        startContent={<FileText className="size-4" />}
+        onPress={async () => {
+          const path = await save({ defaultPath: 'compression-report.csv', filters: reportFilters });
+          if (path) {
+            await exportReport(path);
+          }
+        }}
+      >
+        {t('actions.export_report')}
+      </DropdownItem>
+      <DropdownItem
+        key="undoLastJob"
+        hideSelectedIcon
+        showDivider
+        startContent={<Undo2 className="size-4" />}
+        onPress={async () => {
+          const result = await undoLastJob();
+          if (result) {
+            const { restored, conflicts } = result.undo;
+            addToast({
+              title: t('undo.finished'),
+              description: t('undo.summary', { restored: restored.length, conflicts: conflicts.length }),
+              color: conflicts.length > 0 ? 'warning' : 'success',
+            });
+          }
+        }}
+      >
+        {t('actions.undo_last_job')}
+      </DropdownItem>
       <DropdownItem
         key="showPreview"
         disableAnimation
@@ -0,0 +1,165 @@
+import {
+  Button,
+  Chip,
+  Modal,
+  ModalBody,
+  ModalContent,
+  ModalFooter,
+  ModalHeader,
+  Spinner,
+  Table,
+  TableBody,
+  TableCell,
+  TableColumn,
+  TableHeader,
+  TableRow,
+} from '@heroui/react';
+import useUIStore from '@/stores/ui.store.ts';
+import useFileListStore from '@/stores/file-list.store.ts';
+import { useTranslation } from 'react-i18next';
+import { useEffect, useState } from 'react';
+import { COLLISION_POLICY, CompressionPlan, ORIGINAL_ACTION, PLANNED_ACTION } from '@/types.ts';
+
+function PlanDialog() {
+  const { planDialogOpen, setPlanDialogOpen } = useUIStore();
+  const { planCompression, invokeCompress } = useFileListStore();
+  const { t } = useTranslation();
+
+  const [plan, setPlan] = useState<CompressionPlan | null>(null);
+
+  useEffect(() => {
+    if (!planDialogOpen) {
+      setPlan(null);
+      return;
+    }
+    planCompression()
+      .then(setPlan)
+      .catch(() => setPlanDialogOpen(false));
+  }, [planDialogOpen]);
+
+  const actionColors = {
+    [PLANNED_ACTION.COMPRESS]: 'success',
+    [PLANNED_ACTION.SKIP]: 'warning',
+    [PLANNED_ACTION.FAIL]: 'danger',
+  } as const;
+
+  const rows = (plan?.images || []).map((image) => (
+    <TableRow key={image.id}>
+      <TableCell>{image.input_path}</TableCell>
+      <TableCell>
+        <div className="flex flex-col">
+          <span>{image.output_path || '-'}</span>
+          {image.collision_policy === COLLISION_POLICY.RENAME && (
+            <small className="text-default-400">{t('plan_dialog.renamed')}</small>
+          )}
+          {image.collision_policy === COLLISION_POLICY.OVERWRITE && (
+            <small className="text-warning">{t('plan_dialog.overwrites')}</small>
+          )}
+        </div>
+      </TableCell>
+      <TableCell>
+        <div className="flex flex-col">
+          <Chip color={actionColors[image.action]} size="sm" variant="flat">
+            {t(`plan_dialog.actions.${image.action}`)}
+          </Chip>
+          {image.reason && <small className="text-default-400">{image.reason}</small>}
+        </div>
+      </TableCell>
+      <TableCell>
+        <span className={image.original === ORIGINAL_ACTION.KEPT ? '' : 'text-warning'}>
+          {t(`plan_dialog.original.${image.original}`)}
+        </span>
+      </TableCell>
+    </TableRow>
+  ));
+
+  return (
+    <Modal
+      backdrop="blur"
+      classNames={{
+        backdrop: 'bg-content3/50',
+      }}
+      isOpen={planDialogOpen}
+      shadow="none"
+      size="5xl"
+      onClose={() => setPlanDialogOpen(false)}
+    >
+      <ModalContent>
+        <ModalHeader className="flex flex-col gap-1">{t('plan_dialog.title')}</ModalHeader>
+        <ModalBody>
+          {plan ? (
+            <div className="flex flex-col gap-2">
+              <span className="text-sm">
+                {t('plan_dialog.summary', {
+                  compress: plan.total_compress,
+                  skip: plan.total_skip,
+                  fail: plan.total_fail,
+                })}
+              </span>
+              {plan.total_originals_affected > 0 && (
+                <span className="text-warning text-sm">
+                  {t('plan_dialog.originals_affected', { count: plan.total_originals_affected })}
+                </span>
+              )}
+              {plan.output_collisions > 0 && (
+                <span className="text-warning text-sm">
+                  {t('plan_dialog.output_collisions', { count: plan.output_collisions })}
+                </span>
+              )}
+              <div className="relative block h-[400px] w-full overflow-auto">
+                <Table
+                  fullWidth
+                  isHeaderSticky
+                  removeWrapper
+                  aria-label="Compression plan"
+                  className="rounded-t-sm"
+                  classNames={{
+                    base: 'h-full justify-between overflow-auto bg-background',
+                    th: 'h-8 first:rounded-b-none first:rounded-t-none last:rounded-b-none last:rounded-t-none',
+                    td: 'text-nowrap text-xs',
+                  }}
+                  layout="auto"
+                  radius="sm"
+                  shadow="none"
+                >
+                  <TableHeader>
+                    <TableColumn>{t('plan_dialog.input')}</TableColumn>
+                    <TableColumn>{t('plan_dialog.output')}</TableColumn>
+                    <TableColumn>{t('plan_dialog.action')}</TableColumn>
+                    <TableColumn>{t('plan_dialog.original_file')}</TableColumn>
+                  </TableHeader>
+                  <TableBody>{rows}</TableBody>
+                </Table>
+              </div>
+            </div>
+          ) : (
+            <div className="flex h-[400px] items-center justify-center">
+              <Spinner size="sm" />
+            </div>
+          )}
+        </ModalBody>
+
+        <ModalFooter>
+          <div className="flex w-full justify-end gap-2">
+            <Button
+              disableRipple
+              color="primary"
+              isDisabled={!plan || plan.total_compress === 0}
+              onPress={() => {
+                setPlanDialogOpen(false);
+                invokeCompress();
+              }}
+            >
+              {t('actions.compress')}
+            </Button>
+            <Button disableRipple variant="flat" onPress={() => setPlanDialogOpen(false)}>
+              {t('cancel')}
+            </Button>
+          </div>
+        </ModalFooter>
+      </ModalContent>
+    </Modal>
+  );
+}
+
+export default PlanDialog;
@@ -1,7 +1,7 @@
 import { useTranslation } from 'react-i18next';
 import useSettingsStore from '@/stores/settings.store.ts';
 import { DIRECT_IMPORT_ACTION, POST_COMPRESSION_ACTION } from '@/types.ts';
-import { Select, SelectItem, Slider, Tooltip } from '@heroui/react';
+import { NumberInput, Select, SelectItem, Slider, Switch, Tooltip } from '@heroui/react';
 import { TriangleAlert } from 'lucide-react';
 
 function AdvancedSettings() {
@@ -12,10 +12,20 @@ function AdvancedSettings() {
     threadsCount,
     // threadsPriority,
     maxThreads,
+    maxFileSize,
+    memoryBudget,
+    qualityMetrics,
+    keepBackups,
+    useCache,
     setDirectImportAction,
     setPostCompressionAction,
     setThreadsCount,
     // setThreadsPriority,
+    setMaxFileSize,
+    setMemoryBudget,
+    setQualityMetrics,
+    setKeepBackups,
+    setUseCache,
   } = useSettingsStore();
 
   const directImportActions = [
@@ -129,6 +139,59 @@ function AdvancedSettings() {
             }}
           />
         </div>
+        <div className="flex w-full items-center justify-between gap-2">
+          <div className="flex flex-col">
+            <span>{t('settings.max_file_size')}</span>
+            <span className="text-default-500 text-sm">{t('settings.max_file_size_help')}</span>
+          </div>
+          <NumberInput
+            aria-label={t('settings.max_file_size')}
+            className="max-w-[250px]"
+            endContent={<span className="text-default-500 text-sm">MB</span>}
+            minValue={1}
+            size="sm"
+            value={maxFileSize}
+            variant="faded"
+            onValueChange={(v) => setMaxFileSize(v || 1)}
+          ></NumberInput>
+        </div>
+        <div className="flex w-full items-center justify-between gap-2">
+          <div className="flex flex-col">
+            <span>{t('settings.memory_budget')}</span>
+            <span className="text-default-500 text-sm">{t('settings.memory_budget_help')}</span>
+          </div>
+          <NumberInput
+            aria-label={t('settings.memory_budget')}
+            className="max-w-[250px]"
+            endContent={<span className="text-default-500 text-sm">MB</span>}
+            minValue={0}
+            size="sm"
+            value={memoryBudget}
+            variant="faded"
+            onValueChange={(v) => setMemoryBudget(v || 0)}
+          ></NumberInput>
+        </div>
+        <div className="flex w-full items-center justify-between gap-2">
+          <div className="flex flex-col">
+            <span>{t('settings.quality_metrics')}</span>
+            <span className="text-default-500 text-sm">{t('settings.quality_metrics_help')}</span>
+          </div>
+          <Switch isSelected={qualityMetrics} size="sm" onValueChange={setQualityMetrics}></Switch>
+        </div>
+        <div className="flex w-full items-center justify-between gap-2">
+          <div className="flex flex-col">
+            <span>{t('settings.keep_backups')}</span>
+            <span className="text-default-500 text-sm">{t('settings.keep_backups_help')}</span>
+          </div>
+          <Switch isSelected={keepBackups} size="sm" onValueChange={setKeepBackups}></Switch>
+        </div>
+        <div className="flex w-full items-center justify-between gap-2">
+          <div className="flex flex-col">
+            <span>{t('settings.use_cache')}</span>
+            <span className="text-default-500 text-sm">{t('settings.use_cache_help')}</span>
+          </div>
+          <Switch isSelected={useCache} size="sm" onValueChange={setUseCache}></Switch>
+        </div>
         {/*<div className="flex w-full items-center justify-between gap-2">*/}
         {/*  <div className="flex flex-col">*/}
         {/*    <span>{t('settings.threads_priority')}</span>*/}
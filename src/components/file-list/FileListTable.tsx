@@ -6,7 +6,7 @@ import usePreviewStore from '@/stores/preview.store.ts';
 import { useTranslation } from 'react-i18next';
 import { sep } from '@tauri-apps/api/path';
 import { Selection } from '@react-types/shared';
-import { CImage, FileListPayload, IMAGE_STATUS } from '@/types.ts';
+import { CImage, COLLISION_POLICY, FileListPayload, IMAGE_STATUS } from '@/types.ts';
 import { invokeBackend } from '@/utils/invoker.tsx';
 import { SavedLabel } from '@/components/SavedLabel.tsx';
 
@@ -109,6 +109,9 @@ function FileListTable() {
           <TableColumn key="saved" allowsSorting>
             {t('file_list.saved')}
           </TableColumn>
+          <TableColumn key="quality" allowsSorting>
+            {t('file_list.quality')}
+          </TableColumn>
           <TableColumn key="info">{t('file_list.additional_info')}</TableColumn>
           <TableColumn key="actions" width={40}>
             {t('file_list.actions')}
@@ -166,7 +169,26 @@ function FileListTable() {
                 <SavedLabel cImage={cImage} />
               </TableCell>
               <TableCell>
-                <span className="text-nowrap">{cImage.info}</span>
+                {cImage.quality_metrics ? (
+                  <span
+                    className="text-nowrap"
+                    title={t('file_list.quality_details', {
+                      ssim: cImage.quality_metrics.ssim.toFixed(4),
+                      psnr: cImage.quality_metrics.psnr.toFixed(2),
+                    })}
+                  >
+                    {cImage.quality_metrics.ssim.toFixed(4)}
+                  </span>
+                ) : (
+                  <span className="text-default-400">-</span>
+                )}
+              </TableCell>
+              <TableCell>
+                <span className="text-nowrap">
+                  {cImage.collision_policy === COLLISION_POLICY.RENAME && cImage.info.length === 0
+                    ? t('file_list.renamed_output')
+                    : cImage.info}
+                </span>
               </TableCell>
               <TableCell>
                 <div className="flex items-center justify-between gap-1">
@@ -23,7 +23,9 @@ const setImageToCanvas = (
   }
 
   // TODO ignoring the DIMENSIONS resize mode for now, because it's not working properly
-  if (type === 'compressed' && originalImage && resizeMode !== RESIZE_MODE.DIMENSIONS) {
+  // Crops change the aspect ratio, so they cannot be stretched over the original either
+  const changesAspectRatio = [RESIZE_MODE.DIMENSIONS, RESIZE_MODE.ASPECT_RATIO].includes(resizeMode);
+  if (type === 'compressed' && originalImage && !changesAspectRatio) {
     canvas.width = originalImage.width < image.width ? image.width : originalImage.width;
     canvas.height = originalImage.height < image.height ? image.height : originalImage.height;
   } else {
@@ -18,6 +18,10 @@ import TiffOptions from '@/components/side-panel/compression-options/TiffOptions
 import useCompressionOptionsStore from '@/stores/compression-options.store.ts';
 import type { Selection } from '@react-types/shared';
 import GifOptions from '@/components/side-panel/compression-options/GifOptions.tsx';
+import AvifOptions from '@/components/side-panel/compression-options/AvifOptions.tsx';
+import JxlOptions from '@/components/side-panel/compression-options/JxlOptions.tsx';
+import MetadataOptions from '@/components/side-panel/compression-options/MetadataOptions.tsx';
+import MetadataFields from '@/components/side-panel/compression-options/MetadataFields.tsx';
 import { Key } from 'react';
 import { COMPRESSION_MODE, FILE_SIZE_UNIT } from '@/types.ts';
 
@@ -27,6 +31,8 @@ enum ACCORDION_KEY {
   GIF = 'gif_accordion',
   WEBP = 'webp_accordion',
   TIFF = 'tiff_accordion',
+  AVIF = 'avif_accordion',
+  JXL = 'jxl_accordion',
 }
 
 function CompressionOptions() {
@@ -37,11 +43,15 @@ function CompressionOptions() {
     gifAccordionOpen,
     webpAccordionOpen,
     tiffAccordionOpen,
+    avifAccordionOpen,
+    jxlAccordionOpen,
     setJpegAccordionOpen,
     setPngAccordionOpen,
     setGifAccordionOpen,
     setWebpAccordionOpen,
     setTiffAccordionOpen,
+    setAvifAccordionOpen,
+    setJxlAccordionOpen,
   } = useUIStore();
 
   const {
@@ -53,6 +63,8 @@ function CompressionOptions() {
     setMaxSizeUnit,
     setCompressionMode,
     compressionMode,
+    targetQualityOptions,
+    setTargetQualityOptions,
   } = useCompressionOptionsStore();
 
   const handleChange = (value: SharedSelection) => {
@@ -94,6 +106,12 @@ function CompressionOptions() {
   if (tiffAccordionOpen) {
     defaultAccordionOpen.push(ACCORDION_KEY.TIFF);
   }
+  if (avifAccordionOpen) {
+    defaultAccordionOpen.push(ACCORDION_KEY.AVIF);
+  }
+  if (jxlAccordionOpen) {
+    defaultAccordionOpen.push(ACCORDION_KEY.JXL);
+  }
 
   const handleAccordionOpen = (keys: Selection) => {
     if (keys === 'all') {
@@ -102,6 +120,8 @@ function CompressionOptions() {
       setGifAccordionOpen(true);
       setWebpAccordionOpen(true);
       setTiffAccordionOpen(true);
+      setAvifAccordionOpen(true);
+      setJxlAccordionOpen(true);
 
       return;
     }
@@ -111,6 +131,8 @@ function CompressionOptions() {
     setGifAccordionOpen(keys.has(ACCORDION_KEY.GIF));
     setWebpAccordionOpen(keys.has(ACCORDION_KEY.WEBP));
     setTiffAccordionOpen(keys.has(ACCORDION_KEY.TIFF));
+    setAvifAccordionOpen(keys.has(ACCORDION_KEY.AVIF));
+    setJxlAccordionOpen(keys.has(ACCORDION_KEY.JXL));
   };
 
   const handleCompressionModeChange = (key: Key) => {
@@ -118,16 +140,27 @@ function CompressionOptions() {
       setCompressionMode(COMPRESSION_MODE.SIZE);
       return;
     }
+    if (key === 'target_quality') {
+      setCompressionMode(COMPRESSION_MODE.TARGET_QUALITY);
+      return;
+    }
 
     setCompressionMode(COMPRESSION_MODE.QUALITY);
   };
 
+  let compressionModeKey = 'quality';
+  if (compressionMode === COMPRESSION_MODE.SIZE) {
+    compressionModeKey = 'size';
+  } else if (compressionMode === COMPRESSION_MODE.TARGET_QUALITY) {
+    compressionModeKey = 'target_quality';
+  }
+
   return (
     <div className="size-full overflow-auto">
       <div className="p-2 text-sm">
         <Tabs
           fullWidth
-          selectedKey={compressionMode === COMPRESSION_MODE.SIZE ? 'size' : 'quality'}
+          selectedKey={compressionModeKey}
           size="sm"
           onSelectionChange={handleCompressionModeChange}
         >
@@ -161,6 +194,12 @@ function CompressionOptions() {
                 <AccordionItem key={ACCORDION_KEY.TIFF} aria-label={t('formats.tiff')} title={t('formats.tiff')}>
                   <TiffOptions></TiffOptions>
                 </AccordionItem>
+                <AccordionItem key={ACCORDION_KEY.AVIF} aria-label={t('formats.avif')} title={t('formats.avif')}>
+                  <AvifOptions></AvifOptions>
+                </AccordionItem>
+                <AccordionItem key={ACCORDION_KEY.JXL} aria-label={t('formats.jxl')} title={t('formats.jxl')}>
+                  <JxlOptions></JxlOptions>
+                </AccordionItem>
               </Accordion>
 
               <div className="flex w-full items-center justify-between">
@@ -169,6 +208,8 @@ function CompressionOptions() {
                 </div>
                 <Switch isSelected={keepMetadata} size="sm" onValueChange={setKeepMetadata}></Switch>
               </div>
+              {keepMetadata && <MetadataOptions></MetadataOptions>}
+              <MetadataFields></MetadataFields>
             </div>
           </Tab>
           <Tab key="size" title={t('size')}>
@@ -207,6 +248,34 @@ function CompressionOptions() {
               onValueChange={(v) => setMaxSize(v)}
             />
           </Tab>
+          <Tab key="target_quality" title={t('target_quality')}>
+            <div className="flex flex-col gap-4">
+              <NumberInput
+                description={t('compression_options.target_ssim_description')}
+                formatOptions={{ maximumFractionDigits: 3 }}
+                label={t('compression_options.target_ssim')}
+                labelPlacement="outside"
+                maxValue={1}
+                minValue={0.5}
+                size="sm"
+                step={0.005}
+                value={targetQualityOptions.ssim}
+                variant="faded"
+                onValueChange={(v) => setTargetQualityOptions({ ssim: v })}
+              />
+              <NumberInput
+                label={t('compression_options.target_max_iterations')}
+                labelPlacement="outside"
+                maxValue={20}
+                minValue={1}
+                size="sm"
+                step={1}
+                value={targetQualityOptions.maxIterations}
+                variant="faded"
+                onValueChange={(v) => setTargetQualityOptions({ maxIterations: v })}
+              />
+            </div>
+          </Tab>
         </Tabs>
       </div>
     </div>
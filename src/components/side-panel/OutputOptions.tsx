@@ -2,8 +2,14 @@ import { Alert, Button, Checkbox, Divider, Input, Select, SelectItem, Switch } f
 import { useTranslation } from 'react-i18next';
 import useOutputOptionsStore from '@/stores/output-options.store.ts';
 import { open } from '@tauri-apps/plugin-dialog';
-import { FILE_DATE, MOVE_ORIGINAL_FILE, OUTPUT_FORMAT } from '@/types.ts';
+import { COLLISION_POLICY, FILE_DATE, MOVE_ORIGINAL_FILE, OUTPUT_FORMAT, OutputPathPreview } from '@/types.ts';
 import { TriangleAlert } from 'lucide-react';
+import { useEffect, useState } from 'react';
+import useFileListStore from '@/stores/file-list.store.ts';
+import useCompressionOptionsStore from '@/stores/compression-options.store.ts';
+import useResizeOptionsStore from '@/stores/resize-options.store.ts';
+import { invoke } from '@tauri-apps/api/core';
+import { describeError } from '@/utils/invoker.tsx';
 
 function OutputOptions() {
   const { t } = useTranslation();
@@ -17,7 +23,10 @@ function OutputOptions() {
     moveOriginalFileType,
     keepFileDates,
     outputFormat,
+    decodeOnlyFormat,
     suffix,
+    filenameTemplate,
+    collisionPolicy,
     setKeepFolderStructure,
     setSkipIfOutputIsBigger,
     setSameFolderAsInput,
@@ -26,8 +35,49 @@ function OutputOptions() {
     setMoveOriginalFileType,
     setKeepFileDates,
     setOutputFormat,
+    setDecodeOnlyFormat,
     setSuffix,
+    setFilenameTemplate,
+    setCollisionPolicy,
+    getOutputOptions,
   } = useOutputOptionsStore();
+  const { fileList, selectedItems, baseFolder } = useFileListStore();
+  const [templatePreview, setTemplatePreview] = useState<OutputPathPreview | null>(null);
+
+  // Resolves the template against the first selected image, or the first one in the list
+  const previewImage = selectedItems[0] || fileList[0];
+  useEffect(() => {
+    if (filenameTemplate.length === 0 || !previewImage) {
+      setTemplatePreview(null);
+      return;
+    }
+    // Invalid templates are shown under the field, so errors are not raised as toasts
+    const timeout = setTimeout(() => {
+      invoke<OutputPathPreview[]>('preview_output_paths', {
+        ids: [previewImage.id],
+        options: {
+          compression_options: useCompressionOptionsStore.getState().getCompressionOptions(),
+          resize_options: useResizeOptionsStore.getState().getResizeOptions(),
+          output_options: getOutputOptions(),
+        },
+        baseFolder: baseFolder || '',
+      })
+        .then((previews) => setTemplatePreview(previews[0] || null))
+        .catch((e) => setTemplatePreview({ id: previewImage.id, path: null, error: describeError(e) }));
+    }, 300);
+    return () => clearTimeout(timeout);
+  }, [
+    filenameTemplate,
+    suffix,
+    outputFormat,
+    decodeOnlyFormat,
+    outputFolder,
+    sameFolderAsInput,
+    keepFolderStructure,
+    previewImage,
+    baseFolder,
+    getOutputOptions,
+  ]);
 
   const toggleFileDateCreation = (type: FILE_DATE, enabled: boolean) => {
     const selection = [...keepFileDates];
@@ -50,6 +100,17 @@ function OutputOptions() {
     { key: OUTPUT_FORMAT.PNG, label: t('formats.png') },
     { key: OUTPUT_FORMAT.WEBP, label: t('formats.webp') },
     { key: OUTPUT_FORMAT.TIFF, label: t('formats.tiff') },
+    { key: OUTPUT_FORMAT.AVIF, label: t('formats.avif') },
+    { key: OUTPUT_FORMAT.JXL, label: t('formats.jxl') },
+  ];
+  // HEIC, BMP and RAW inputs cannot be written back in their own format
+  const decodeOnlyFormats = outputFormats.filter((f) => f.key !== OUTPUT_FORMAT.ORIGINAL);
+
+  const collisionPolicies = [
+    { key: COLLISION_POLICY.OVERWRITE, label: t('collision_policies.overwrite') },
+    { key: COLLISION_POLICY.SKIP, label: t('collision_policies.skip') },
+    { key: COLLISION_POLICY.RENAME, label: t('collision_policies.rename') },
+    { key: COLLISION_POLICY.OVERWRITE_IF_OLDER, label: t('collision_policies.overwrite_if_older') },
   ];
 
   const moveOriginalFileWarning = moveOriginalFileType === MOVE_ORIGINAL_FILE.DELETE && (
@@ -108,7 +169,7 @@ function OutputOptions() {
             </div>
             <Switch isSelected={sameFolderAsInput} size="sm" onValueChange={setSameFolderAsInput}></Switch>
           </div>
-          {sameFolderAsInput && suffix.length === 0 && (
+          {sameFolderAsInput && suffix.length === 0 && filenameTemplate.length === 0 && (
             <Alert
               hideIcon
               hideIconWrapper
@@ -238,6 +299,31 @@ function OutputOptions() {
             <SelectItem key={t.key}>{t.label}</SelectItem>
           ))}
         </Select>
+        {outputFormat === OUTPUT_FORMAT.ORIGINAL && (
+          <Select
+            disallowEmptySelection
+            aria-label={t('compression_options.output_options.decode_only_format')}
+            classNames={{
+              label: 'text-md',
+              trigger: 'shadow-none',
+              popoverContent: 'bg-content2 border-2 border-content1',
+            }}
+            description={t('compression_options.output_options.decode_only_format_description')}
+            label={t('compression_options.output_options.decode_only_format')}
+            labelPlacement="outside"
+            selectedKeys={[decodeOnlyFormat]}
+            selectionMode="single"
+            size="sm"
+            variant="faded"
+            onSelectionChange={(value) =>
+              setDecodeOnlyFormat((value.currentKey as OUTPUT_FORMAT) || OUTPUT_FORMAT.JPEG)
+            }
+          >
+            {decodeOnlyFormats.map((t) => (
+              <SelectItem key={t.key}>{t.label}</SelectItem>
+            ))}
+          </Select>
+        )}
 
         <Input
           isClearable
@@ -252,6 +338,51 @@ function OutputOptions() {
           variant="faded"
           onValueChange={(v) => setSuffix(v)}
         />
+
+        <Input
+          isClearable
+          classNames={{
+            inputWrapper: 'shadow-none',
+          }}
+          description={
+            templatePreview?.path
+              ? t('compression_options.output_options.filename_template_preview', { path: templatePreview.path })
+              : t('compression_options.output_options.filename_template_description')
+          }
+          errorMessage={templatePreview?.error}
+          isInvalid={!!templatePreview?.error}
+          label={t('compression_options.output_options.filename_template')}
+          labelPlacement="outside"
+          placeholder="{name}_{width}w.{ext}"
+          size="sm"
+          value={filenameTemplate}
+          variant="faded"
+          onValueChange={(v) => setFilenameTemplate(v)}
+        />
+
+        <Select
+          disallowEmptySelection
+          aria-label={t('compression_options.output_options.collision_policy')}
+          classNames={{
+            label: 'text-md',
+            trigger: 'shadow-none',
+            popoverContent: 'bg-content2 border-2 border-content1',
+          }}
+          description={t('compression_options.output_options.collision_policy_description')}
+          label={t('compression_options.output_options.collision_policy')}
+          labelPlacement="outside"
+          selectedKeys={[collisionPolicy]}
+          selectionMode="single"
+          size="sm"
+          variant="faded"
+          onSelectionChange={(value) =>
+            setCollisionPolicy((value.currentKey as COLLISION_POLICY) || COLLISION_POLICY.OVERWRITE)
+          }
+        >
+          {collisionPolicies.map((t) => (
+            <SelectItem key={t.key}>{t.label}</SelectItem>
+          ))}
+        </Select>
       </div>
     </div>
   );
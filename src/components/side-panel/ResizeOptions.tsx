@@ -1,5 +1,5 @@
-import { RESIZE_MODE } from '@/types.ts';
-import { NumberInput, Select, SelectItem, Switch } from '@heroui/react';
+import { RESIZE_FIT, RESIZE_MODE } from '@/types.ts';
+import { Input, NumberInput, Select, SelectItem, Switch } from '@heroui/react';
 import { useTranslation } from 'react-i18next';
 import useResizeOptionsStore from '@/stores/resize-options.store.ts';
 
@@ -14,6 +14,14 @@ function ResizeOptions() {
     dimension,
     keepAspectRatio,
     doNotEnlarge,
+    autoOrient,
+    fit,
+    focalPointX,
+    focalPointY,
+    background,
+    aspectRatioWidth,
+    aspectRatioHeight,
+    maxMegapixels,
     setResizeMode,
     setWidth,
     setHeight,
@@ -22,6 +30,14 @@ function ResizeOptions() {
     setDimension,
     setKeepAspectRatio,
     setDoNotEnlarge,
+    setAutoOrient,
+    setFit,
+    setFocalPointX,
+    setFocalPointY,
+    setBackground,
+    setAspectRatioWidth,
+    setAspectRatioHeight,
+    setMaxMegapixels,
   } = useResizeOptionsStore();
 
   const resizeModes = [
@@ -50,6 +66,20 @@ function ResizeOptions() {
       key: RESIZE_MODE.SHORT_EDGE,
       label: t('resize_modes.short_edge'),
     },
+    {
+      key: RESIZE_MODE.ASPECT_RATIO,
+      label: t('resize_modes.aspect_ratio'),
+    },
+    {
+      key: RESIZE_MODE.MAX_MEGAPIXELS,
+      label: t('resize_modes.max_megapixels'),
+    },
+  ];
+
+  const fits = [
+    { key: RESIZE_FIT.CONTAIN, label: t('resize_fits.contain') },
+    { key: RESIZE_FIT.COVER, label: t('resize_fits.cover') },
+    { key: RESIZE_FIT.PAD, label: t('resize_fits.pad') },
   ];
 
   const showPixelDimensionsInputs = [RESIZE_MODE.DIMENSIONS, RESIZE_MODE.WIDTH, RESIZE_MODE.HEIGHT].includes(
@@ -59,12 +89,17 @@ function ResizeOptions() {
   const showDimensionInput = [RESIZE_MODE.LONG_EDGE, RESIZE_MODE.SHORT_EDGE].includes(resizeMode);
   const showResizeControls = resizeMode !== RESIZE_MODE.NONE;
   const keepAspectRatioDisabled = [
-    RESIZE_MODE.DIMENSIONS,
     RESIZE_MODE.WIDTH,
     RESIZE_MODE.HEIGHT,
     RESIZE_MODE.LONG_EDGE,
     RESIZE_MODE.SHORT_EDGE,
+    RESIZE_MODE.ASPECT_RATIO,
+    RESIZE_MODE.MAX_MEGAPIXELS,
   ].includes(resizeMode);
+  const showFitSelect = resizeMode === RESIZE_MODE.DIMENSIONS && keepAspectRatio;
+  const showBackgroundInput = showFitSelect && fit === RESIZE_FIT.PAD;
+  const showFocalPointInputs = (showFitSelect && fit === RESIZE_FIT.COVER) || resizeMode === RESIZE_MODE.ASPECT_RATIO;
+  const showDoNotEnlarge = ![RESIZE_MODE.ASPECT_RATIO, RESIZE_MODE.MAX_MEGAPIXELS].includes(resizeMode);
 
   let dimensionLabel = '';
   switch (resizeMode) {
@@ -106,6 +141,15 @@ function ResizeOptions() {
             <SelectItem key={r.key}>{r.label}</SelectItem>
           ))}
         </Select>
+        <div className="flex w-full items-center justify-between">
+          <div className="flex flex-col">
+            <span>{t('compression_options.resize_options.auto_orient')}</span>
+            <span className="text-default-500 text-xs">
+              {t('compression_options.resize_options.auto_orient_help')}
+            </span>
+          </div>
+          <Switch isSelected={autoOrient} size="sm" onValueChange={setAutoOrient}></Switch>
+        </div>
         {showResizeControls && (
           <>
             {showPixelDimensionsInputs && (
@@ -255,26 +299,198 @@ function ResizeOptions() {
                 onValueChange={(value) => setDimension(value)}
               ></NumberInput>
             )}
-            <div className="flex w-full items-center justify-between">
-              <div className="flex flex-col">
-                <span>{t('compression_options.resize_options.do_not_enlarge')}</span>
-              </div>
-              <Switch
-                isSelected={doNotEnlarge}
+            {showFitSelect && (
+              <Select
+                disallowEmptySelection
+                classNames={{
+                  label: 'text-md',
+                  trigger: 'shadow-none',
+                  popoverContent: 'bg-content2 border-2 border-content1',
+                }}
+                label={t('compression_options.resize_options.fit')}
+                labelPlacement="outside"
+                selectedKeys={[fit]}
+                selectionMode="single"
                 size="sm"
-                onValueChange={(enabled) => {
-                  setDoNotEnlarge(enabled);
-                  if (enabled) {
-                    if (widthPercentage > 100) {
-                      setWidthPercentage(100);
+                variant="faded"
+                onSelectionChange={(value) => setFit((value.currentKey as RESIZE_FIT) || RESIZE_FIT.CONTAIN)}
+              >
+                {fits.map((f) => (
+                  <SelectItem key={f.key}>{f.label}</SelectItem>
+                ))}
+              </Select>
+            )}
+            {showBackgroundInput && (
+              <Input
+                classNames={{ inputWrapper: 'shadow-none' }}
+                description={t('compression_options.resize_options.background_description')}
+                label={t('compression_options.resize_options.background')}
+                labelPlacement="outside"
+                placeholder="#FFFFFF"
+                size="sm"
+                startContent={
+                  <div className="border-default-300 size-4 shrink-0 rounded border" style={{ background }} />
+                }
+                value={background}
+                variant="faded"
+                onValueChange={setBackground}
+              />
+            )}
+            {resizeMode === RESIZE_MODE.ASPECT_RATIO && (
+              <div className="flex w-full items-center justify-between gap-2">
+                <NumberInput
+                  disableAnimation
+                  aria-label={t('width')}
+                  className="min-w-32"
+                  classNames={{
+                    inputWrapper: 'p-1 h-8 shadow-none',
+                    input: 'text-right',
+                  }}
+                  formatOptions={{
+                    useGrouping: false,
+                  }}
+                  label={t('width')}
+                  labelPlacement="outside"
+                  maxValue={999}
+                  minValue={1}
+                  size="sm"
+                  value={aspectRatioWidth}
+                  variant="faded"
+                  onValueChange={(value) => setAspectRatioWidth(value)}
+                ></NumberInput>
+                <span className="self-end pb-1.5">:</span>
+                <NumberInput
+                  disableAnimation
+                  aria-label={t('height')}
+                  className="min-w-32"
+                  classNames={{
+                    inputWrapper: 'p-1 h-8 shadow-none',
+                    input: 'text-right',
+                  }}
+                  formatOptions={{
+                    useGrouping: false,
+                  }}
+                  label={t('height')}
+                  labelPlacement="outside"
+                  maxValue={999}
+                  minValue={1}
+                  size="sm"
+                  value={aspectRatioHeight}
+                  variant="faded"
+                  onValueChange={(value) => setAspectRatioHeight(value)}
+                ></NumberInput>
+              </div>
+            )}
+            {showFocalPointInputs && (
+              <div className="flex flex-col gap-1">
+                <div className="flex w-full items-center justify-between gap-2">
+                  <NumberInput
+                    disableAnimation
+                    aria-label={t('compression_options.resize_options.focal_point_x')}
+                    className="min-w-32"
+                    classNames={{
+                      inputWrapper: 'p-1 h-8 shadow-none',
+                      input: 'text-right',
+                    }}
+                    endContent={
+                      <div className="pointer-events-none flex items-center">
+                        <span className="text-default-400 text-small">%</span>
+                      </div>
                     }
-                    if (heightPercentage > 100) {
-                      setHeightPercentage(100);
+                    formatOptions={{
+                      useGrouping: false,
+                    }}
+                    label={t('compression_options.resize_options.focal_point_x')}
+                    labelPlacement="outside"
+                    maxValue={100}
+                    minValue={0}
+                    size="sm"
+                    value={focalPointX}
+                    variant="faded"
+                    onValueChange={(value) => setFocalPointX(value)}
+                  ></NumberInput>
+                  <NumberInput
+                    disableAnimation
+                    aria-label={t('compression_options.resize_options.focal_point_y')}
+                    className="min-w-32"
+                    classNames={{
+                      inputWrapper: 'p-1 h-8 shadow-none',
+                      input: 'text-right',
+                    }}
+                    endContent={
+                      <div className="pointer-events-none flex items-center">
+                        <span className="text-default-400 text-small">%</span>
+                      </div>
                     }
-                  }
+                    formatOptions={{
+                      useGrouping: false,
+                    }}
+                    label={t('compression_options.resize_options.focal_point_y')}
+                    labelPlacement="outside"
+                    maxValue={100}
+                    minValue={0}
+                    size="sm"
+                    value={focalPointY}
+                    variant="faded"
+                    onValueChange={(value) => setFocalPointY(value)}
+                  ></NumberInput>
+                </div>
+                <span className="text-default-500 text-xs">
+                  {t('compression_options.resize_options.focal_point_help')}
+                </span>
+              </div>
+            )}
+            {resizeMode === RESIZE_MODE.MAX_MEGAPIXELS && (
+              <NumberInput
+                disableAnimation
+                aria-label={t('resize_modes.max_megapixels')}
+                className="min-w-32"
+                classNames={{
+                  inputWrapper: 'p-1 h-8 shadow-none',
+                  input: 'text-right',
                 }}
-              ></Switch>
-            </div>
+                endContent={
+                  <div className="pointer-events-none flex items-center">
+                    <span className="text-default-400 text-small">MP</span>
+                  </div>
+                }
+                formatOptions={{
+                  useGrouping: false,
+                  maximumFractionDigits: 1,
+                }}
+                label={t('resize_modes.max_megapixels')}
+                labelPlacement="outside"
+                maxValue={999}
+                minValue={0.1}
+                size="sm"
+                step={0.1}
+                value={maxMegapixels}
+                variant="faded"
+                onValueChange={(value) => setMaxMegapixels(value)}
+              ></NumberInput>
+            )}
+            {showDoNotEnlarge && (
+              <div className="flex w-full items-center justify-between">
+                <div className="flex flex-col">
+                  <span>{t('compression_options.resize_options.do_not_enlarge')}</span>
+                </div>
+                <Switch
+                  isSelected={doNotEnlarge}
+                  size="sm"
+                  onValueChange={(enabled) => {
+                    setDoNotEnlarge(enabled);
+                    if (enabled) {
+                      if (widthPercentage > 100) {
+                        setWidthPercentage(100);
+                      }
+                      if (heightPercentage > 100) {
+                        setHeightPercentage(100);
+                      }
+                    }
+                  }}
+                ></Switch>
+              </div>
+            )}
 
             <div className="flex w-full items-center justify-between">
               <div className="flex flex-col">
@@ -1,5 +1,5 @@
 import { Tab, Tabs } from '@heroui/react';
-import { Folder, Scaling, SlidersHorizontal } from 'lucide-react';
+import { Folder, Scaling, SlidersHorizontal, Stamp } from 'lucide-react';
 import CompressionOptions from '@/components/side-panel/CompressionOptions.tsx';
 import { useTranslation } from 'react-i18next';
 import OutputOptions from '@/components/side-panel/OutputOptions.tsx';
@@ -7,6 +7,7 @@ import { SIDE_PANEL_TAB } from '@/types.ts';
 import useUIStore from '@/stores/ui.store.ts';
 import { Key } from 'react';
 import ResizeOptions from '@/components/side-panel/ResizeOptions.tsx';
+import WatermarkOptions from '@/components/side-panel/WatermarkOptions.tsx';
 import useFileListStore from '@/stores/file-list.store.ts';
 
 function SidePanel() {
@@ -55,6 +56,18 @@ function SidePanel() {
           >
             <ResizeOptions></ResizeOptions>
           </Tab>
+          <Tab
+            key={SIDE_PANEL_TAB.WATERMARK}
+            className={isCompressing ? 'opacity-disabled pointer-events-none' : ''} //TODO should be done in an attribute
+            title={
+              <div className="flex items-center gap-2">
+                <Stamp className="size-4"></Stamp>
+                <span className="text-xs font-semibold">{t('compression_options.watermark')}</span>
+              </div>
+            }
+          >
+            <WatermarkOptions></WatermarkOptions>
+          </Tab>
           <Tab
             key={SIDE_PANEL_TAB.OUTPUT}
             className={isCompressing ? 'opacity-disabled pointer-events-none' : ''} //TODO should be done in an attribute
@@ -0,0 +1,200 @@
+import { Button, Input, NumberInput, Select, SelectItem, Switch } from '@heroui/react';
+import { useTranslation } from 'react-i18next';
+import { open } from '@tauri-apps/plugin-dialog';
+import useWatermarkOptionsStore from '@/stores/watermark-options.store.ts';
+import { WATERMARK_KIND, WATERMARK_POSITION } from '@/types.ts';
+
+function WatermarkOptions() {
+  const { t } = useTranslation();
+  const {
+    enabled,
+    kind,
+    imagePath,
+    text,
+    fontPath,
+    color,
+    position,
+    margin,
+    opacity,
+    scale,
+    tiled,
+    setEnabled,
+    setKind,
+    setImagePath,
+    setText,
+    setFontPath,
+    setColor,
+    setPosition,
+    setMargin,
+    setOpacity,
+    setScale,
+    setTiled,
+  } = useWatermarkOptionsStore();
+
+  const kinds = [
+    { key: WATERMARK_KIND.IMAGE, label: t('watermark_options.image') },
+    { key: WATERMARK_KIND.TEXT, label: t('watermark_options.text') },
+  ];
+  const positions = Object.values(WATERMARK_POSITION).map((p) => ({
+    key: p,
+    label: t(`watermark_options.positions.${p}`),
+  }));
+  const selectClassNames = {
+    label: 'text-md',
+    trigger: 'shadow-none',
+    popoverContent: 'bg-content2 border-2 border-content1',
+  };
+  const numberInputClassNames = {
+    inputWrapper: 'p-1 h-8 shadow-none',
+    input: 'text-right',
+  };
+
+  const pickFile = async (name: string, extensions: string[], onPick: (file: string) => void) => {
+    const file = await open({ directory: false, multiple: false, filters: [{ name, extensions }] });
+    if (file) {
+      onPick(file);
+    }
+  };
+
+  const fileInput = (label: string, value: string, onPress: () => void) => (
+    <Input
+      readOnly
+      classNames={{
+        inputWrapper: 'pr-0 shadow-none',
+      }}
+      endContent={
+        <Button disableRipple className="h-full rounded-l-none" size="sm" onPress={onPress}>
+          {t('select_dots')}
+        </Button>
+      }
+      label={label}
+      labelPlacement="outside"
+      placeholder={label}
+      size="sm"
+      value={value}
+      variant="faded"
+    />
+  );
+
+  const percentInput = (label: string, value: number, min: number, max: number, onChange: (v: number) => void) => (
+    <NumberInput
+      disableAnimation
+      aria-label={label}
+      className="min-w-32"
+      classNames={numberInputClassNames}
+      endContent={
+        <div className="pointer-events-none flex items-center">
+          <span className="text-default-400 text-small">%</span>
+        </div>
+      }
+      formatOptions={{
+        useGrouping: false,
+      }}
+      label={label}
+      labelPlacement="outside"
+      maxValue={max}
+      minValue={min}
+      size="sm"
+      value={value}
+      variant="faded"
+      onValueChange={onChange}
+    ></NumberInput>
+  );
+
+  return (
+    <div className="size-full overflow-auto">
+      <div className="flex flex-col gap-2 p-2 text-sm">
+        <div className="flex w-full items-center justify-between">
+          <span>{t('watermark_options.enabled')}</span>
+          <Switch isSelected={enabled} size="sm" onValueChange={setEnabled}></Switch>
+        </div>
+        {enabled && (
+          <>
+            <Select
+              disallowEmptySelection
+              classNames={selectClassNames}
+              label={t('watermark_options.kind')}
+              labelPlacement="outside"
+              selectedKeys={[kind]}
+              selectionMode="single"
+              size="sm"
+              variant="faded"
+              onSelectionChange={(value) => setKind((value.currentKey as WATERMARK_KIND) || WATERMARK_KIND.IMAGE)}
+            >
+              {kinds.map((k) => (
+                <SelectItem key={k.key}>{k.label}</SelectItem>
+              ))}
+            </Select>
+            {kind === WATERMARK_KIND.IMAGE &&
+              fileInput(t('watermark_options.image_path'), imagePath, () =>
+                pickFile(t('watermark_options.image_path'), ['png', 'webp'], setImagePath),
+              )}
+            {kind === WATERMARK_KIND.TEXT && (
+              <>
+                <Input
+                  classNames={{ inputWrapper: 'shadow-none' }}
+                  label={t('watermark_options.text')}
+                  labelPlacement="outside"
+                  placeholder="© Jane Doe"
+                  size="sm"
+                  value={text}
+                  variant="faded"
+                  onValueChange={setText}
+                />
+                {fileInput(t('watermark_options.font_path'), fontPath, () =>
+                  pickFile(t('watermark_options.font_path'), ['ttf', 'otf'], setFontPath),
+                )}
+                <Input
+                  classNames={{ inputWrapper: 'shadow-none' }}
+                  description={t('watermark_options.color_description')}
+                  label={t('watermark_options.color')}
+                  labelPlacement="outside"
+                  placeholder="#FFFFFF"
+                  size="sm"
+                  startContent={
+                    <div className="border-default-300 size-4 shrink-0 rounded border" style={{ background: color }} />
+                  }
+                  value={color}
+                  variant="faded"
+                  onValueChange={setColor}
+                />
+              </>
+            )}
+            <Select
+              disallowEmptySelection
+              classNames={selectClassNames}
+              isDisabled={tiled}
+              label={t('watermark_options.position')}
+              labelPlacement="outside"
+              selectedKeys={[position]}
+              selectionMode="single"
+              size="sm"
+              variant="faded"
+              onSelectionChange={(value) =>
+                setPosition((value.currentKey as WATERMARK_POSITION) || WATERMARK_POSITION.BOTTOM_RIGHT)
+              }
+            >
+              {positions.map((p) => (
+                <SelectItem key={p.key}>{p.label}</SelectItem>
+              ))}
+            </Select>
+            <div className="flex w-full items-center justify-between gap-2">
+              {percentInput(t('watermark_options.margin'), margin, 0, 50, setMargin)}
+              {percentInput(t('watermark_options.opacity'), opacity, 0, 100, setOpacity)}
+            </div>
+            <div className="flex flex-col">
+              {percentInput(t('watermark_options.scale'), scale, 1, 100, setScale)}
+              <span className="text-default-500 text-xs">{t('watermark_options.scale_description')}</span>
+            </div>
+            <div className="flex w-full items-center justify-between">
+              <span>{t('watermark_options.tiled')}</span>
+              <Switch isSelected={tiled} size="sm" onValueChange={setTiled}></Switch>
+            </div>
+          </>
+        )}
+      </div>
+    </div>
+  );
+}
+
+export default WatermarkOptions;
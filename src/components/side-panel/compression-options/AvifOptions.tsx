@@ -0,0 +1,84 @@
+import { NumberInput, Slider } from '@heroui/react';
+import { useTranslation } from 'react-i18next';
+import useCompressionOptionsStore from '@/stores/compression-options.store.ts';
+
+function AvifOptions() {
+  const { t } = useTranslation();
+
+  const { avifOptions, setAvifOptions } = useCompressionOptionsStore();
+
+  const handleChange = (type: 'quality' | 'speed', value: number | number[]) => {
+    if (Array.isArray(value)) {
+      value = value[0];
+    }
+
+    setAvifOptions({ [type]: value });
+  };
+
+  return (
+    <div className="flex flex-col gap-2">
+      <Slider
+        classNames={{
+          label: 'text-sm',
+        }}
+        label={t('quality')}
+        maxValue={100}
+        minValue={0}
+        renderValue={() => (
+          <NumberInput
+            aria-label={t('quality')}
+            className="max-w-20"
+            classNames={{
+              inputWrapper: 'p-1 h-8 shadow-none',
+              input: 'text-right',
+            }}
+            maxValue={100}
+            minValue={0}
+            size="sm"
+            value={avifOptions.quality}
+            variant="faded"
+            onValueChange={(value) => setAvifOptions({ quality: value })}
+          ></NumberInput>
+        )}
+        size="sm"
+        step={1}
+        value={avifOptions.quality}
+        onChange={(v) => handleChange('quality', v)}
+      />
+
+      <div className="flex flex-col">
+        <Slider
+          classNames={{
+            label: 'text-sm',
+          }}
+          label={t('compression_options.speed')}
+          maxValue={10}
+          minValue={1}
+          renderValue={() => (
+            <NumberInput
+              aria-label={t('compression_options.speed')}
+              className="max-w-20"
+              classNames={{
+                inputWrapper: 'p-1 h-8 shadow-none',
+                input: 'text-right',
+              }}
+              maxValue={10}
+              minValue={1}
+              size="sm"
+              value={avifOptions.speed}
+              variant="faded"
+              onValueChange={(value) => setAvifOptions({ speed: value })}
+            ></NumberInput>
+          )}
+          size="sm"
+          step={1}
+          value={avifOptions.speed}
+          onChange={(v) => handleChange('speed', v)}
+        />
+        <span className="text-default-500 text-sm">{t('compression_options.speed_help')}</span>
+      </div>
+    </div>
+  );
+}
+
+export default AvifOptions;
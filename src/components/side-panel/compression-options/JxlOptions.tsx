@@ -0,0 +1,95 @@
+import { NumberInput, Slider, Switch } from '@heroui/react';
+import { useTranslation } from 'react-i18next';
+import useCompressionOptionsStore from '@/stores/compression-options.store.ts';
+
+function JxlOptions() {
+  const { t } = useTranslation();
+
+  const { jxlOptions, setJxlOptions } = useCompressionOptionsStore();
+
+  const handleChange = (type: 'quality' | 'effort', value: number | number[]) => {
+    if (Array.isArray(value)) {
+      value = value[0];
+    }
+
+    setJxlOptions({ [type]: value });
+  };
+
+  return (
+    <div className="flex flex-col gap-2">
+      <Slider
+        classNames={{
+          label: 'text-sm',
+        }}
+        isDisabled={jxlOptions.lossless}
+        label={t('quality')}
+        maxValue={100}
+        minValue={0}
+        renderValue={() => (
+          <NumberInput
+            aria-label={t('quality')}
+            className="max-w-20"
+            classNames={{
+              inputWrapper: 'p-1 h-8 shadow-none',
+              input: 'text-right',
+            }}
+            maxValue={100}
+            minValue={0}
+            size="sm"
+            value={jxlOptions.quality}
+            variant="faded"
+            onValueChange={(value) => setJxlOptions({ quality: value })}
+          ></NumberInput>
+        )}
+        size="sm"
+        step={1}
+        value={jxlOptions.quality}
+        onChange={(v) => handleChange('quality', v)}
+      />
+
+      <div className="flex flex-col">
+        <Slider
+          classNames={{
+            label: 'text-sm',
+          }}
+          label={t('compression_options.effort')}
+          maxValue={9}
+          minValue={1}
+          renderValue={() => (
+            <NumberInput
+              aria-label={t('compression_options.effort')}
+              className="max-w-20"
+              classNames={{
+                inputWrapper: 'p-1 h-8 shadow-none',
+                input: 'text-right',
+              }}
+              maxValue={9}
+              minValue={1}
+              size="sm"
+              value={jxlOptions.effort}
+              variant="faded"
+              onValueChange={(value) => setJxlOptions({ effort: value })}
+            ></NumberInput>
+          )}
+          size="sm"
+          step={1}
+          value={jxlOptions.effort}
+          onChange={(v) => handleChange('effort', v)}
+        />
+        <span className="text-default-500 text-sm">{t('compression_options.effort_help')}</span>
+      </div>
+      <div className="flex w-full items-center justify-between">
+        <div className="flex flex-col">
+          <span>{t('compression_options.lossless')}</span>
+        </div>
+        <Switch
+          isSelected={jxlOptions.lossless}
+          size="sm"
+          onValueChange={(value) => setJxlOptions({ lossless: value })}
+        ></Switch>
+      </div>
+    </div>
+  );
+}
+
+export default JxlOptions;
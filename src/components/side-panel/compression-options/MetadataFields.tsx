@@ -0,0 +1,125 @@
+import { Button, Input, Select, SelectItem } from '@heroui/react';
+import { Plus, X } from 'lucide-react';
+import { useTranslation } from 'react-i18next';
+import useCompressionOptionsStore from '@/stores/compression-options.store.ts';
+import { METADATA_FIELD_KIND } from '@/types.ts';
+
+function MetadataFields() {
+  const { t } = useTranslation();
+
+  const {
+    metadataFields,
+    setMetadataFields,
+    addCustomMetadataField,
+    setCustomMetadataField,
+    removeCustomMetadataField,
+  } = useCompressionOptionsStore();
+
+  const kinds = [
+    { key: METADATA_FIELD_KIND.XMP, label: t('metadata_fields.xmp') },
+    { key: METADATA_FIELD_KIND.IPTC, label: t('metadata_fields.iptc') },
+  ];
+  const inputClassNames = { inputWrapper: 'shadow-none' };
+
+  return (
+    <div className="flex flex-col gap-2">
+      <span>{t('metadata_fields.title')}</span>
+      <Input
+        classNames={inputClassNames}
+        label={t('metadata_fields.artist')}
+        labelPlacement="outside"
+        placeholder="{exif:Artist}"
+        size="sm"
+        value={metadataFields.artist}
+        variant="faded"
+        onValueChange={(v) => setMetadataFields({ artist: v })}
+      />
+      <Input
+        classNames={inputClassNames}
+        label={t('metadata_fields.copyright')}
+        labelPlacement="outside"
+        placeholder="© {date:%Y}"
+        size="sm"
+        value={metadataFields.copyright}
+        variant="faded"
+        onValueChange={(v) => setMetadataFields({ copyright: v })}
+      />
+      <Input
+        classNames={inputClassNames}
+        description={t('metadata_fields.description_help')}
+        label={t('metadata_fields.description')}
+        labelPlacement="outside"
+        size="sm"
+        value={metadataFields.description}
+        variant="faded"
+        onValueChange={(v) => setMetadataFields({ description: v })}
+      />
+
+      {metadataFields.custom.map((field, index) => (
+        <div key={index} className="flex items-center gap-1">
+          <Select
+            disallowEmptySelection
+            aria-label={t('metadata_fields.kind')}
+            className="max-w-[80px]"
+            classNames={{
+              trigger: 'shadow-none',
+              popoverContent: 'bg-content2 border-2 border-content1',
+            }}
+            selectedKeys={[field.kind]}
+            selectionMode="single"
+            size="sm"
+            variant="faded"
+            onSelectionChange={(value) =>
+              setCustomMetadataField(index, {
+                kind: (value.currentKey as METADATA_FIELD_KIND) || METADATA_FIELD_KIND.XMP,
+              })
+            }
+          >
+            {kinds.map((k) => (
+              <SelectItem key={k.key}>{k.label}</SelectItem>
+            ))}
+          </Select>
+          <Input
+            aria-label={t('metadata_fields.name')}
+            classNames={inputClassNames}
+            placeholder={field.kind === METADATA_FIELD_KIND.XMP ? 'photoshop:Credit' : 'Credit'}
+            size="sm"
+            value={field.name}
+            variant="faded"
+            onValueChange={(v) => setCustomMetadataField(index, { name: v })}
+          />
+          <Input
+            aria-label={t('metadata_fields.value')}
+            classNames={inputClassNames}
+            placeholder={t('metadata_fields.value')}
+            size="sm"
+            value={field.value}
+            variant="faded"
+            onValueChange={(v) => setCustomMetadataField(index, { value: v })}
+          />
+          <Button
+            disableRipple
+            isIconOnly
+            aria-label={t('metadata_fields.remove')}
+            size="sm"
+            variant="light"
+            onPress={() => removeCustomMetadataField(index)}
+          >
+            <X className="size-4"></X>
+          </Button>
+        </div>
+      ))}
+      <Button
+        disableRipple
+        size="sm"
+        startContent={<Plus className="size-4"></Plus>}
+        variant="flat"
+        onPress={addCustomMetadataField}
+      >
+        {t('metadata_fields.add')}
+      </Button>
+    </div>
+  );
+}
+
+export default MetadataFields;
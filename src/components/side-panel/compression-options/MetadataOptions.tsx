@@ -0,0 +1,77 @@
+import { Checkbox, Select, SelectItem } from '@heroui/react';
+import { useTranslation } from 'react-i18next';
+import useCompressionOptionsStore, {
+  MetadataOptions as MetadataCategories,
+} from '@/stores/compression-options.store.ts';
+import { METADATA_PRESET } from '@/types.ts';
+
+type MetadataCategory = Exclude<keyof MetadataCategories, 'preset'>;
+
+function MetadataOptions() {
+  const { t } = useTranslation();
+
+  const { metadataOptions, setMetadataOptions } = useCompressionOptionsStore();
+
+  const presets = [
+    { key: METADATA_PRESET.ALL, label: t('metadata_presets.all') },
+    { key: METADATA_PRESET.PRIVACY, label: t('metadata_presets.privacy') },
+    { key: METADATA_PRESET.CUSTOM, label: t('metadata_presets.custom') },
+  ];
+  const categories: { key: MetadataCategory; label: string }[] = [
+    { key: 'exif', label: t('metadata_categories.exif') },
+    { key: 'gps', label: t('metadata_categories.gps') },
+    { key: 'deviceIds', label: t('metadata_categories.device_ids') },
+    { key: 'xmp', label: t('metadata_categories.xmp') },
+    { key: 'iptc', label: t('metadata_categories.iptc') },
+    { key: 'icc', label: t('metadata_categories.icc') },
+    { key: 'orientation', label: t('metadata_categories.orientation') },
+    { key: 'thumbnail', label: t('metadata_categories.thumbnail') },
+    { key: 'comments', label: t('metadata_categories.comments') },
+  ];
+
+  return (
+    <div className="flex flex-col gap-2">
+      <Select
+        disallowEmptySelection
+        aria-label={t('compression_options.metadata_preset')}
+        classNames={{
+          label: 'text-sm',
+          trigger: 'shadow-none',
+          popoverContent: 'bg-content2 border-2 border-content1',
+        }}
+        description={t(`metadata_presets.${metadataOptions.preset}_description`)}
+        label={t('compression_options.metadata_preset')}
+        labelPlacement="outside"
+        selectedKeys={[metadataOptions.preset]}
+        selectionMode="single"
+        size="sm"
+        variant="faded"
+        onSelectionChange={(value) =>
+          setMetadataOptions({ preset: (value.currentKey as METADATA_PRESET) || METADATA_PRESET.ALL })
+        }
+      >
+        {presets.map((p) => (
+          <SelectItem key={p.key}>{p.label}</SelectItem>
+        ))}
+      </Select>
+
+      {metadataOptions.preset === METADATA_PRESET.CUSTOM && (
+        <div className="flex flex-col gap-1">
+          {categories.map((c) => (
+            <Checkbox
+              key={c.key}
+              disableAnimation
+              isSelected={metadataOptions[c.key]}
+              size="sm"
+              onValueChange={(v) => setMetadataOptions({ [c.key]: v })}
+            >
+              {c.label}
+            </Checkbox>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+}
+
+export default MetadataOptions;
@@ -1,6 +1,13 @@
 import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
-import { CHROMA_SUBSAMPLING, COMPRESSION_MODE, TIFF_COMPRESSION_METHOD, TIFF_DEFLATE_LEVEL } from '@/types.ts';
+import {
+  CHROMA_SUBSAMPLING,
+  COMPRESSION_MODE,
+  METADATA_FIELD_KIND,
+  METADATA_PRESET,
+  TIFF_COMPRESSION_METHOD,
+  TIFF_DEFLATE_LEVEL,
+} from '@/types.ts';
 import { subscribeWithSelector } from 'zustand/middleware';
 import { load } from '@tauri-apps/plugin-store';
 import { path } from '@tauri-apps/api';
@@ -35,6 +42,48 @@ interface TiffOptions {
   deflateLevel: TIFF_DEFLATE_LEVEL;
 }
 
+interface AvifOptions {
+  quality: number;
+  speed: number;
+}
+
+interface JxlOptions {
+  quality: number;
+  effort: number;
+  lossless: boolean;
+}
+
+interface TargetQualityOptions {
+  ssim: number;
+  maxIterations: number;
+}
+
+export interface MetadataOptions {
+  preset: METADATA_PRESET;
+  exif: boolean;
+  gps: boolean;
+  deviceIds: boolean;
+  xmp: boolean;
+  iptc: boolean;
+  icc: boolean;
+  orientation: boolean;
+  thumbnail: boolean;
+  comments: boolean;
+}
+
+export interface CustomMetadataField {
+  kind: METADATA_FIELD_KIND;
+  name: string;
+  value: string;
+}
+
+export interface MetadataFields {
+  artist: string;
+  copyright: string;
+  description: string;
+  custom: CustomMetadataField[];
+}
+
 export interface CompressionOptions {
   jpeg: {
     quality: number;
@@ -59,10 +108,37 @@ export interface CompressionOptions {
     method: TIFF_COMPRESSION_METHOD;
     deflate_level: TIFF_DEFLATE_LEVEL;
   };
+  avif: {
+    quality: number;
+    speed: number;
+    lossless: boolean;
+  };
+  jxl: {
+    quality: number;
+    effort: number;
+    lossless: boolean;
+  };
   compression_mode: COMPRESSION_MODE;
   keep_metadata: boolean;
+  metadata: {
+    preset: METADATA_PRESET;
+    exif: boolean;
+    gps: boolean;
+    device_ids: boolean;
+    xmp: boolean;
+    iptc: boolean;
+    icc: boolean;
+    orientation: boolean;
+    thumbnail: boolean;
+    comments: boolean;
+  };
+  metadata_fields: MetadataFields;
   max_size_value: number;
   max_size_unit: number;
+  target_quality: {
+    ssim: number;
+    max_iterations: number;
+  };
 }
 
 export interface StoredCompressionOptions {
@@ -89,11 +165,26 @@ export interface StoredCompressionOptions {
     method: TIFF_COMPRESSION_METHOD;
     deflateLevel: TIFF_DEFLATE_LEVEL;
   };
+  avifOptions: {
+    quality: number;
+    speed: number;
+  };
+  jxlOptions: {
+    quality: number;
+    effort: number;
+    lossless: boolean;
+  };
   maxSize: number;
   maxSizeUnit: number;
   lossless: boolean;
   keepMetadata: boolean;
+  metadataOptions: MetadataOptions;
+  metadataFields: MetadataFields;
   compressionMode: COMPRESSION_MODE;
+  targetQualityOptions: {
+    ssim: number;
+    maxIterations: number;
+  };
 }
 
 interface CompressionOptionsStore {
@@ -102,20 +193,33 @@ interface CompressionOptionsStore {
   gifOptions: GifOptions;
   webpOptions: WebpOptions;
   tiffOptions: TiffOptions;
+  avifOptions: AvifOptions;
+  jxlOptions: JxlOptions;
   keepMetadata: boolean;
+  metadataOptions: MetadataOptions;
+  metadataFields: MetadataFields;
   maxSize: number;
   maxSizeUnit: number;
   compressionMode: COMPRESSION_MODE;
+  targetQualityOptions: TargetQualityOptions;
 
   setJpegOptions: (options: Partial<JpegOptions>) => void;
   setPngOptions: (options: Partial<PngOptions>) => void;
   setGifOptions: (options: Partial<GifOptions>) => void;
   setWebpOptions: (options: Partial<WebpOptions>) => void;
   setTiffOptions: (options: Partial<TiffOptions>) => void;
+  setAvifOptions: (options: Partial<AvifOptions>) => void;
+  setJxlOptions: (options: Partial<JxlOptions>) => void;
   setKeepMetadata: (keepMetadata: boolean) => void;
+  setMetadataOptions: (options: Partial<MetadataOptions>) => void;
+  setMetadataFields: (fields: Partial<MetadataFields>) => void;
+  addCustomMetadataField: () => void;
+  setCustomMetadataField: (index: number, field: Partial<CustomMetadataField>) => void;
+  removeCustomMetadataField: (index: number) => void;
   setMaxSize: (maxSize: number) => void;
   setMaxSizeUnit: (maxSizeUnit: number) => void;
   setCompressionMode: (compressionMode: COMPRESSION_MODE) => void;
+  setTargetQualityOptions: (options: Partial<TargetQualityOptions>) => void;
 
   getCompressionOptions: () => CompressionOptions;
 }
@@ -144,11 +248,42 @@ const defaultValues: StoredCompressionOptions = {
     method: TIFF_COMPRESSION_METHOD.DEFLATE,
     deflateLevel: TIFF_DEFLATE_LEVEL.BALANCED,
   },
+  avifOptions: {
+    quality: 70,
+    speed: 6,
+  },
+  jxlOptions: {
+    quality: 80,
+    effort: 7,
+    lossless: false,
+  },
   maxSize: 500,
   maxSizeUnit: 1024,
   lossless: false,
   keepMetadata: true,
+  metadataOptions: {
+    preset: METADATA_PRESET.ALL,
+    exif: true,
+    gps: true,
+    deviceIds: true,
+    xmp: true,
+    iptc: true,
+    icc: true,
+    orientation: true,
+    thumbnail: true,
+    comments: true,
+  },
+  metadataFields: {
+    artist: '',
+    copyright: '',
+    description: '',
+    custom: [],
+  },
   compressionMode: COMPRESSION_MODE.QUALITY,
+  targetQualityOptions: {
+    ssim: 0.98,
+    maxIterations: 7,
+  },
 };
 
 let configPath = 'settings.json';
@@ -171,6 +306,11 @@ const useCompressionOptionsStore = create<CompressionOptionsStore>()(
       gifOptions: { ...defaultValues.gifOptions, ...preferences.gifOptions },
       webpOptions: { ...defaultValues.webpOptions, ...preferences.webpOptions },
       tiffOptions: { ...defaultValues.tiffOptions, ...preferences.tiffOptions },
+      avifOptions: { ...defaultValues.avifOptions, ...preferences.avifOptions },
+      jxlOptions: { ...defaultValues.jxlOptions, ...preferences.jxlOptions },
+      metadataOptions: { ...defaultValues.metadataOptions, ...preferences.metadataOptions },
+      metadataFields: { ...defaultValues.metadataFields, ...preferences.metadataFields },
+      targetQualityOptions: { ...defaultValues.targetQualityOptions, ...preferences.targetQualityOptions },
       setJpegOptions: (options: Partial<JpegOptions>) =>
         set((state) => {
           Object.assign(state.jpegOptions, options);
@@ -191,10 +331,38 @@ const useCompressionOptionsStore = create<CompressionOptionsStore>()(
         set((state) => {
           Object.assign(state.tiffOptions, options);
         }),
+      setAvifOptions: (options: Partial<AvifOptions>) =>
+        set((state) => {
+          Object.assign(state.avifOptions, options);
+        }),
+      setJxlOptions: (options: Partial<JxlOptions>) =>
+        set((state) => {
+          Object.assign(state.jxlOptions, options);
+        }),
       setKeepMetadata: (keepMetadata: boolean) =>
         set((state) => {
           state.keepMetadata = keepMetadata;
         }),
+      setMetadataOptions: (options: Partial<MetadataOptions>) =>
+        set((state) => {
+          Object.assign(state.metadataOptions, options);
+        }),
+      setMetadataFields: (fields: Partial<MetadataFields>) =>
+        set((state) => {
+          Object.assign(state.metadataFields, fields);
+        }),
+      addCustomMetadataField: () =>
+        set((state) => {
+          state.metadataFields.custom.push({ kind: METADATA_FIELD_KIND.XMP, name: '', value: '' });
+        }),
+      setCustomMetadataField: (index: number, field: Partial<CustomMetadataField>) =>
+        set((state) => {
+          Object.assign(state.metadataFields.custom[index], field);
+        }),
+      removeCustomMetadataField: (index: number) =>
+        set((state) => {
+          state.metadataFields.custom.splice(index, 1);
+        }),
       setMaxSize: (maxSize: number) =>
         set((state) => {
           state.maxSize = maxSize;
@@ -208,6 +376,10 @@ const useCompressionOptionsStore = create<CompressionOptionsStore>()(
           state.compressionMode = compressionMode;
         });
       },
+      setTargetQualityOptions: (options: Partial<TargetQualityOptions>) =>
+        set((state) => {
+          Object.assign(state.targetQualityOptions, options);
+        }),
       getCompressionOptions: () => ({
         jpeg: {
           quality: get().jpegOptions.quality,
@@ -232,10 +404,42 @@ const useCompressionOptionsStore = create<CompressionOptionsStore>()(
           method: get().tiffOptions.method,
           deflate_level: get().tiffOptions.deflateLevel,
         },
+        avif: {
+          quality: get().avifOptions.quality,
+          speed: get().avifOptions.speed,
+          lossless: false,
+        },
+        jxl: {
+          quality: get().jxlOptions.quality,
+          effort: get().jxlOptions.effort,
+          lossless: get().jxlOptions.lossless,
+        },
         compression_mode: get().compressionMode,
         keep_metadata: get().keepMetadata,
+        metadata: {
+          preset: get().metadataOptions.preset,
+          exif: get().metadataOptions.exif,
+          gps: get().metadataOptions.gps,
+          device_ids: get().metadataOptions.deviceIds,
+          xmp: get().metadataOptions.xmp,
+          iptc: get().metadataOptions.iptc,
+          icc: get().metadataOptions.icc,
+          orientation: get().metadataOptions.orientation,
+          thumbnail: get().metadataOptions.thumbnail,
+          comments: get().metadataOptions.comments,
+        },
+        metadata_fields: {
+          artist: get().metadataFields.artist,
+          copyright: get().metadataFields.copyright,
+          description: get().metadataFields.description,
+          custom: get().metadataFields.custom.map((f) => ({ kind: f.kind, name: f.name, value: f.value })),
+        },
         max_size_value: get().maxSize,
         max_size_unit: get().maxSizeUnit,
+        target_quality: {
+          ssim: get().targetQualityOptions.ssim,
+          max_iterations: get().targetQualityOptions.maxIterations,
+        },
       }),
     })),
   ),
@@ -248,10 +452,15 @@ useCompressionOptionsStore.subscribe(
     gifOptions: state.gifOptions,
     webpOptions: state.webpOptions,
     tiffOptions: state.tiffOptions,
+    avifOptions: state.avifOptions,
+    jxlOptions: state.jxlOptions,
     keepMetadata: state.keepMetadata,
+    metadataOptions: state.metadataOptions,
+    metadataFields: state.metadataFields,
     maxSize: state.maxSize,
     maxSizeUnit: state.maxSizeUnit,
     compressionMode: state.compressionMode,
+    targetQualityOptions: state.targetQualityOptions,
   }),
   async (data) => {
     await settings.set('compression_options.compression', data);
@@ -1,10 +1,11 @@
 import { create } from 'zustand';
-import { CImage, FileListPayload } from '@/types.ts';
+import { CImage, CompressionPlan, FileListPayload, RestoredList, UndoJobResult } from '@/types.ts';
 import { createJSONStorage, persist, subscribeWithSelector } from 'zustand/middleware';
 import { immer } from 'zustand/middleware/immer';
 import useSettingsStore from '@/stores/settings.store.ts';
 import useCompressionOptionsStore from '@/stores/compression-options.store.ts';
 import useResizeOptionsStore from '@/stores/resize-options.store.ts';
+import useWatermarkOptionsStore from '@/stores/watermark-options.store.ts';
 import useOutputOptionsStore from '@/stores/output-options.store.ts';
 import { SortDescriptor } from '@heroui/react';
 import { execPostCompressionAction } from '@/services/post-compression-actions.ts';
@@ -25,6 +26,8 @@ interface FileListStore {
   isCompressionCancelling: boolean;
   compressionProgress: number;
   currentSorting: SortDescriptor;
+  lastJobId: string | null;
+  hasReport: boolean;
 
   totalPages: () => number;
 
@@ -43,14 +46,31 @@ interface FileListStore {
   setCompressionProgress: (progress: number) => void;
   updateFile: (id: string, updatedData: Partial<CImage>) => void;
   setCurrentSorting: (sorting: SortDescriptor) => void;
+  setLastJobId: (jobId: string | null) => void;
 
   invokeCompress: (ids?: string[]) => void;
+  planCompression: () => Promise<CompressionPlan>;
   invokePauseCompression: () => void;
   invokeResumeCompression: () => void;
   invokeCancelCompression: () => void;
   updateList: (payload: FileListPayload) => void;
   filterList: (query: string) => void;
   finishCompression: () => void;
+  restoreSession: () => Promise<RestoredList | null>;
+  openProject: (path: string) => Promise<RestoredList>;
+  saveProject: (path: string) => Promise<void>;
+  exportReport: (path: string) => Promise<void>;
+  applyRestoredList: (restored: RestoredList) => void;
+  undoLastJob: () => Promise<UndoJobResult | null>;
+}
+
+function getOptionsPayload() {
+  return {
+    compression_options: useCompressionOptionsStore.getState().getCompressionOptions(),
+    resize_options: useResizeOptionsStore.getState().getResizeOptions(),
+    output_options: useOutputOptionsStore.getState().getOutputOptions(),
+    watermark_options: useWatermarkOptionsStore.getState().getWatermarkOptions(),
+  };
 }
 
 const useFileListStore = create<FileListStore>()(
@@ -71,6 +91,8 @@ const useFileListStore = create<FileListStore>()(
           isCompressionCancelling: false,
           compressionProgress: 0,
           currentSorting: { column: 'filename', direction: 'ascending' },
+          lastJobId: null,
+          hasReport: false,
 
           totalPages: () => Math.ceil(get().totalFiles / 50),
 
@@ -95,6 +117,45 @@ const useFileListStore = create<FileListStore>()(
           setIsCompressionPaused: (isCompressionPaused: boolean) => set({ isCompressionPaused }),
           setCompressionProgress: (progress: number) => set({ compressionProgress: progress }),
           setCurrentSorting: (sorting: SortDescriptor) => set({ currentSorting: sorting }),
+          setLastJobId: (jobId: string | null) => set({ lastJobId: jobId }),
+          restoreSession: async () => {
+            const restored = await invokeBackend<RestoredList | null>('restore_session');
+            if (restored) {
+              get().applyRestoredList(restored);
+            }
+            return restored;
+          },
+          openProject: async (path: string) => {
+            const restored = await invokeBackend<RestoredList>('open_project', { path });
+            get().applyRestoredList(restored);
+            return restored;
+          },
+          saveProject: async (path: string) => {
+            await invokeBackend('save_project', { path });
+          },
+          exportReport: async (path: string) => {
+            await invokeBackend('export_report', { path });
+          },
+          applyRestoredList: (restored: RestoredList) => {
+            set({
+              currentSorting: { column: restored.sorting.column, direction: restored.sorting.order },
+              selectedItems: [],
+            });
+            get().updateList(restored.file_list);
+          },
+          undoLastJob: async () => {
+            const jobId = get().lastJobId;
+            if (!jobId || get().isCompressing) {
+              return null;
+            }
+            const result = await invokeBackend<UndoJobResult>('undo_job', { jobId });
+            // Conflicting entries stay in the journal and can be undone again
+            if (result.undo.conflicts.length === 0) {
+              set({ lastJobId: null });
+            }
+            get().updateList(result.file_list);
+            return result;
+          },
           filterList: async (query: string) => {
             set({ isListLoading: true });
             invokeBackend<FileListPayload>('filter_list', { query })
@@ -118,14 +179,16 @@ const useFileListStore = create<FileListStore>()(
             }
             set({ isCompressing: true });
             invokeBackend('compress', {
-              options: {
-                compression_options: useCompressionOptionsStore.getState().getCompressionOptions(),
-                resize_options: useResizeOptionsStore.getState().getResizeOptions(),
-                output_options: useOutputOptionsStore.getState().getOutputOptions(),
-              },
+              options: getOptionsPayload(),
               threads: useSettingsStore.getState().threadsCount,
               baseFolder: get().baseFolder,
+              maxFileSize: useSettingsStore.getState().maxFileSize * 1024 * 1024,
+              memoryBudget: useSettingsStore.getState().memoryBudget * 1024 * 1024,
+              qualityMetrics: useSettingsStore.getState().qualityMetrics,
+              keepBackups: useSettingsStore.getState().keepBackups,
+              useCache: useSettingsStore.getState().useCache,
             })
+              .then(() => set({ hasReport: true }))
               .catch((e) => {
                 console.error(e);
                 //void error(e.message);
@@ -135,6 +198,12 @@ const useFileListStore = create<FileListStore>()(
               })
               .finally(useFileListStore.getState().finishCompression);
           },
+          planCompression: () =>
+            invokeBackend<CompressionPlan>('plan_compression', {
+              options: getOptionsPayload(),
+              baseFolder: get().baseFolder,
+              maxFileSize: useSettingsStore.getState().maxFileSize * 1024 * 1024,
+            }),
           updateFile: (id: string, updatedData: Partial<CImage>) =>
             set((state) => {
               const index = state.fileList.findIndex((file) => file.id === id);
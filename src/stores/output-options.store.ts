@@ -1,6 +1,6 @@
 import { create } from 'zustand/index';
 import { load } from '@tauri-apps/plugin-store';
-import { FILE_DATE, MOVE_ORIGINAL_FILE, OUTPUT_FORMAT } from '@/types.ts';
+import { COLLISION_POLICY, FILE_DATE, MOVE_ORIGINAL_FILE, OUTPUT_FORMAT } from '@/types.ts';
 import { path } from '@tauri-apps/api';
 import { platform } from '@tauri-apps/plugin-os';
 import { invokeBackend } from '@/utils/invoker.tsx';
@@ -14,7 +14,10 @@ interface OutputOptionsStore {
   moveOriginalFileType: MOVE_ORIGINAL_FILE;
   keepFileDates: FILE_DATE[];
   outputFormat: OUTPUT_FORMAT;
+  decodeOnlyFormat: OUTPUT_FORMAT;
   suffix: string;
+  filenameTemplate: string;
+  collisionPolicy: COLLISION_POLICY;
 
   setOutputFolder: (outputFolder: string) => void;
   setSameFolderAsInput: (sameFolderAsInput: boolean) => void;
@@ -24,7 +27,10 @@ interface OutputOptionsStore {
   setMoveOriginalFileType: (moveOriginalFileType: MOVE_ORIGINAL_FILE) => void;
   setKeepFileDates: (keepFileDates: FILE_DATE[]) => void;
   setOutputFormat: (outputFormat: OUTPUT_FORMAT) => void;
+  setDecodeOnlyFormat: (decodeOnlyFormat: OUTPUT_FORMAT) => void;
   setSuffix: (suffix: string) => void;
+  setFilenameTemplate: (filenameTemplate: string) => void;
+  setCollisionPolicy: (collisionPolicy: COLLISION_POLICY) => void;
 
   getOutputOptions: () => OutputOptions;
 }
@@ -41,7 +47,10 @@ export interface OutputOptions {
   keep_last_modified_date: boolean;
   keep_last_access_date: boolean;
   output_format: OUTPUT_FORMAT;
+  decode_only_format: OUTPUT_FORMAT;
   suffix: string;
+  filename_template: string;
+  collision_policy: COLLISION_POLICY;
 }
 
 let configPath = 'settings.json';
@@ -62,7 +71,10 @@ const defaultOptions = {
   moveOriginalFileType: MOVE_ORIGINAL_FILE.TRASH,
   keepFileDates: [FILE_DATE.MODIFIED, FILE_DATE.CREATED, FILE_DATE.ACCESSED],
   outputFormat: OUTPUT_FORMAT.ORIGINAL,
+  decodeOnlyFormat: OUTPUT_FORMAT.JPEG,
   suffix: '',
+  filenameTemplate: '',
+  collisionPolicy: COLLISION_POLICY.OVERWRITE,
 };
 
 const useOutputOptionsStore = create<OutputOptionsStore>()((set, get) => ({
@@ -77,7 +89,10 @@ const useOutputOptionsStore = create<OutputOptionsStore>()((set, get) => ({
   setMoveOriginalFileType: (moveOriginalFileType: MOVE_ORIGINAL_FILE) => set({ moveOriginalFileType }),
   setKeepFileDates: (keepFileDates: FILE_DATE[]) => set({ keepFileDates }),
   setOutputFormat: (outputFormat: OUTPUT_FORMAT) => set({ outputFormat }),
+  setDecodeOnlyFormat: (decodeOnlyFormat: OUTPUT_FORMAT) => set({ decodeOnlyFormat }),
   setSuffix: (suffix: string) => set({ suffix }),
+  setFilenameTemplate: (filenameTemplate: string) => set({ filenameTemplate }),
+  setCollisionPolicy: (collisionPolicy: COLLISION_POLICY) => set({ collisionPolicy }),
 
   getOutputOptions: () => ({
     output_folder: get().outputFolder,
@@ -91,7 +106,10 @@ const useOutputOptionsStore = create<OutputOptionsStore>()((set, get) => ({
     keep_last_modified_date: get().keepFileDates.includes(FILE_DATE.MODIFIED),
     keep_last_access_date: get().keepFileDates.includes(FILE_DATE.ACCESSED),
     output_format: get().outputFormat,
+    decode_only_format: get().decodeOnlyFormat,
     suffix: get().suffix,
+    filename_template: get().filenameTemplate,
+    collision_policy: get().collisionPolicy,
   }),
 }));
 
@@ -105,7 +123,10 @@ useOutputOptionsStore.subscribe(async (state) => {
     moveOriginalFileType: state.moveOriginalFileType,
     keepFileDates: state.keepFileDates,
     outputFormat: state.outputFormat,
+    decodeOnlyFormat: state.decodeOnlyFormat,
     suffix: state.suffix,
+    filenameTemplate: state.filenameTemplate,
+    collisionPolicy: state.collisionPolicy,
   };
 
   await settings.set('compression_options.output', dataToSave);
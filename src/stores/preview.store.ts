@@ -3,11 +3,12 @@ import { create } from 'zustand/index';
 import useFileListStore from '@/stores/file-list.store.ts';
 import useCompressionOptionsStore from '@/stores/compression-options.store.ts';
 import useResizeOptionsStore from '@/stores/resize-options.store.ts';
+import useWatermarkOptionsStore from '@/stores/watermark-options.store.ts';
 import useOutputOptionsStore from '@/stores/output-options.store.ts';
 import { subscribeWithSelector } from 'zustand/middleware';
 import useUIStore from '@/stores/ui.store.ts';
 import useSettingsStore from '@/stores/settings.store.ts';
-import { invokeBackend } from '@/utils/invoker.tsx';
+import { describeError, invokeBackend } from '@/utils/invoker.tsx';
 
 interface PreviewStore {
   isLoading: boolean;
@@ -43,11 +44,12 @@ const usePreviewStore = create<PreviewStore>()(
           compression_options: useCompressionOptionsStore.getState().getCompressionOptions(),
           resize_options: useResizeOptionsStore.getState().getResizeOptions(),
           output_options: useOutputOptionsStore.getState().getOutputOptions(),
+          watermark_options: useWatermarkOptionsStore.getState().getWatermarkOptions(),
         },
         threads: useSettingsStore.getState().threadsCount,
       }).catch((e) => {
         for (const id of ids) {
-          useFileListStore.getState().updateFile(id, { status: IMAGE_STATUS.ERROR, info: e.toString() }); //TODO maybe we don't need to set all of them as errors
+          useFileListStore.getState().updateFile(id, { status: IMAGE_STATUS.ERROR, info: describeError(e) }); //TODO maybe we don't need to set all of them as errors
         }
       });
     },
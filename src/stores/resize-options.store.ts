@@ -1,6 +1,6 @@
 import { create } from 'zustand/index';
 import { load } from '@tauri-apps/plugin-store';
-import { RESIZE_MODE } from '@/types.ts';
+import { RESIZE_FIT, RESIZE_MODE } from '@/types.ts';
 import { path } from '@tauri-apps/api';
 import { platform } from '@tauri-apps/plugin-os';
 import { invokeBackend } from '@/utils/invoker.tsx';
@@ -14,6 +14,14 @@ interface ResizeOptionsStore {
   dimension: number;
   keepAspectRatio: boolean;
   doNotEnlarge: boolean;
+  autoOrient: boolean;
+  fit: RESIZE_FIT;
+  focalPointX: number;
+  focalPointY: number;
+  background: string;
+  aspectRatioWidth: number;
+  aspectRatioHeight: number;
+  maxMegapixels: number;
 
   setResizeMode: (resizeMode: RESIZE_MODE) => void;
   setWidth: (width: number) => void;
@@ -23,6 +31,14 @@ interface ResizeOptionsStore {
   setDimension: (dimension: number) => void;
   setKeepAspectRatio: (keepAspectRatio: boolean) => void;
   setDoNotEnlarge: (doNotEnlarge: boolean) => void;
+  setAutoOrient: (autoOrient: boolean) => void;
+  setFit: (fit: RESIZE_FIT) => void;
+  setFocalPointX: (focalPointX: number) => void;
+  setFocalPointY: (focalPointY: number) => void;
+  setBackground: (background: string) => void;
+  setAspectRatioWidth: (aspectRatioWidth: number) => void;
+  setAspectRatioHeight: (aspectRatioHeight: number) => void;
+  setMaxMegapixels: (maxMegapixels: number) => void;
 
   getResizeOptions: () => ResizeOptions;
 }
@@ -36,6 +52,14 @@ export interface ResizeOptions {
   height: number;
   long_edge: number;
   short_edge: number;
+  auto_orient: boolean;
+  fit: RESIZE_FIT;
+  focal_point_x: number;
+  focal_point_y: number;
+  background: string;
+  aspect_ratio_width: number;
+  aspect_ratio_height: number;
+  max_megapixels: number;
 }
 
 let configPath = 'settings.json';
@@ -56,6 +80,14 @@ const defaultOptions = {
   dimension: 500,
   keepAspectRatio: true,
   doNotEnlarge: true,
+  autoOrient: false,
+  fit: RESIZE_FIT.CONTAIN,
+  focalPointX: 50,
+  focalPointY: 50,
+  background: '#FFFFFF',
+  aspectRatioWidth: 1,
+  aspectRatioHeight: 1,
+  maxMegapixels: 12,
 };
 
 const useResizeOptionsStore = create<ResizeOptionsStore>()((set, get) => ({
@@ -70,6 +102,14 @@ const useResizeOptionsStore = create<ResizeOptionsStore>()((set, get) => ({
   setDimension: (dimension: number) => set({ dimension }),
   setKeepAspectRatio: (keepAspectRatio: boolean) => set({ keepAspectRatio }),
   setDoNotEnlarge: (doNotEnlarge: boolean) => set({ doNotEnlarge }),
+  setAutoOrient: (autoOrient: boolean) => set({ autoOrient }),
+  setFit: (fit: RESIZE_FIT) => set({ fit }),
+  setFocalPointX: (focalPointX: number) => set({ focalPointX }),
+  setFocalPointY: (focalPointY: number) => set({ focalPointY }),
+  setBackground: (background: string) => set({ background }),
+  setAspectRatioWidth: (aspectRatioWidth: number) => set({ aspectRatioWidth }),
+  setAspectRatioHeight: (aspectRatioHeight: number) => set({ aspectRatioHeight }),
+  setMaxMegapixels: (maxMegapixels: number) => set({ maxMegapixels }),
 
   getResizeOptions: () => ({
     resize_enabled: get().resizeMode !== RESIZE_MODE.NONE,
@@ -82,6 +122,14 @@ const useResizeOptionsStore = create<ResizeOptionsStore>()((set, get) => ({
     height_percentage: get().heightPercentage,
     long_edge: get().dimension,
     short_edge: get().dimension,
+    auto_orient: get().autoOrient,
+    fit: get().fit,
+    focal_point_x: get().focalPointX,
+    focal_point_y: get().focalPointY,
+    background: get().background,
+    aspect_ratio_width: get().aspectRatioWidth,
+    aspect_ratio_height: get().aspectRatioHeight,
+    max_megapixels: get().maxMegapixels,
   }),
 }));
 
@@ -95,6 +143,14 @@ useResizeOptionsStore.subscribe(async (state) => {
     dimension: state.dimension,
     keepAspectRatio: state.keepAspectRatio,
     doNotEnlarge: state.doNotEnlarge,
+    autoOrient: state.autoOrient,
+    fit: state.fit,
+    focalPointX: state.focalPointX,
+    focalPointY: state.focalPointY,
+    background: state.background,
+    aspectRatioWidth: state.aspectRatioWidth,
+    aspectRatioHeight: state.aspectRatioHeight,
+    maxMegapixels: state.maxMegapixels,
   };
 
   await settings.set('compression_options.resize', dataToSave);
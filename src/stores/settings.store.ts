@@ -21,6 +21,11 @@ interface SettingsOptionsStore {
   postCompressionAction: POST_COMPRESSION_ACTION;
   threadsCount: number;
   threadsPriority: number;
+  maxFileSize: number;
+  memoryBudget: number;
+  qualityMetrics: boolean;
+  keepBackups: boolean;
+  useCache: boolean;
   maxThreads: number;
   allowNotifications: boolean;
 
@@ -35,6 +40,11 @@ interface SettingsOptionsStore {
   setPostCompressionAction: (postCompressionAction: POST_COMPRESSION_ACTION) => void;
   setThreadsCount: (threadsCount: number) => void;
   setThreadsPriority: (threadsPriority: number) => void;
+  setMaxFileSize: (maxFileSize: number) => void;
+  setMemoryBudget: (memoryBudget: number) => void;
+  setQualityMetrics: (qualityMetrics: boolean) => void;
+  setKeepBackups: (keepBackups: boolean) => void;
+  setUseCache: (useCache: boolean) => void;
   setAllowNotifications: (allowNotifications: boolean) => void;
 }
 
@@ -60,6 +70,11 @@ const defaultOptions = {
   postCompressionAction: POST_COMPRESSION_ACTION.NONE,
   threadsCount: maxThreads / 2,
   threadsPriority: 4,
+  maxFileSize: 500, // MB
+  memoryBudget: 2048, // MB, 0 is unlimited
+  qualityMetrics: false,
+  keepBackups: true,
+  useCache: true,
   allowNotifications: false,
 };
 
@@ -79,6 +94,11 @@ const useSettingsStore = create<SettingsOptionsStore>()(
     setPostCompressionAction: (postCompressionAction: POST_COMPRESSION_ACTION) => set({ postCompressionAction }),
     setThreadsCount: (threadsCount: number) => set({ threadsCount }),
     setThreadsPriority: (threadsPriority: number) => set({ threadsPriority }),
+    setMaxFileSize: (maxFileSize: number) => set({ maxFileSize }),
+    setMemoryBudget: (memoryBudget: number) => set({ memoryBudget }),
+    setQualityMetrics: (qualityMetrics: boolean) => set({ qualityMetrics }),
+    setKeepBackups: (keepBackups: boolean) => set({ keepBackups }),
+    setUseCache: (useCache: boolean) => set({ useCache }),
     setAllowNotifications: async (allowNotifications: boolean) => {
       if (!allowNotifications) {
         set({ allowNotifications: false });
@@ -115,6 +135,11 @@ useSettingsStore.subscribe(async (state) => {
     postCompressionAction: state.postCompressionAction,
     threadsCount: state.threadsCount,
     threadsPriority: state.threadsPriority,
+    maxFileSize: state.maxFileSize,
+    memoryBudget: state.memoryBudget,
+    qualityMetrics: state.qualityMetrics,
+    keepBackups: state.keepBackups,
+    useCache: state.useCache,
     allowNotifications: state.allowNotifications,
   };
 
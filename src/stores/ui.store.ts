@@ -20,6 +20,8 @@ interface UIOptions {
   gifAccordionOpen: boolean;
   webpAccordionOpen: boolean;
   tiffAccordionOpen: boolean;
+  avifAccordionOpen: boolean;
+  jxlAccordionOpen: boolean;
   currentSelectedTab: SIDE_PANEL_TAB;
   settingsDialogOpen: boolean;
   showPreviewPanel: boolean;
@@ -29,6 +31,7 @@ interface UIOptions {
   promptExitDialogOpen: boolean;
   checkForUpdatesDialogOpen: boolean;
   advancedImportDialogOpen: boolean;
+  planDialogOpen: boolean;
   compressionProgressDialogMinimized: boolean;
 
   setSplitPanels: (options: Partial<SplitPanels>) => void;
@@ -37,6 +40,8 @@ interface UIOptions {
   setGifAccordionOpen: (open: boolean) => void;
   setWebpAccordionOpen: (open: boolean) => void;
   setTiffAccordionOpen: (open: boolean) => void;
+  setAvifAccordionOpen: (open: boolean) => void;
+  setJxlAccordionOpen: (open: boolean) => void;
   setCurrentSelectedTab: (tab: SIDE_PANEL_TAB) => void;
   setSettingsDialogOpen: (open: boolean) => void;
   setShowPreviewPanel: (show: boolean) => void;
@@ -46,6 +51,7 @@ interface UIOptions {
   setPromptExitDialogOpen: (open: boolean) => void;
   setCheckForUpdatesDialogOpen: (open: boolean) => void;
   setAdvancedImportDialogOpen: (open: boolean) => void;
+  setPlanDialogOpen: (open: boolean) => void;
   setCompressionProgressDialogMinimized: (minimized: boolean) => void;
 
   getAppMenuSelectedItems: () => string[];
@@ -68,6 +74,8 @@ const defaultOptions = {
   gifAccordionOpen: true,
   webpAccordionOpen: true,
   tiffAccordionOpen: true,
+  avifAccordionOpen: true,
+  jxlAccordionOpen: true,
   currentSelectedTab: SIDE_PANEL_TAB.COMPRESSION,
   settingsDialogOpen: false,
   showPreviewPanel: true,
@@ -77,6 +85,7 @@ const defaultOptions = {
   promptExitDialogOpen: false,
   checkForUpdatesDialogOpen: false,
   advancedImportDialogOpen: false,
+  planDialogOpen: false,
   compressionProgressDialogMinimized: false,
 };
 
@@ -115,6 +124,16 @@ const useUIStore = create<UIOptions>()(
           state.tiffAccordionOpen = open;
         });
       },
+      setAvifAccordionOpen: (open: boolean) => {
+        set((state) => {
+          state.avifAccordionOpen = open;
+        });
+      },
+      setJxlAccordionOpen: (open: boolean) => {
+        set((state) => {
+          state.jxlAccordionOpen = open;
+        });
+      },
       setCurrentSelectedTab: (tab: SIDE_PANEL_TAB) => {
         set((state) => {
           state.currentSelectedTab = tab;
@@ -165,6 +184,12 @@ const useUIStore = create<UIOptions>()(
         });
       },
 
+      setPlanDialogOpen: (open: boolean) => {
+        set((state) => {
+          state.planDialogOpen = open;
+        });
+      },
+
       setCompressionProgressDialogMinimized: (minimized: boolean) => {
         set((state) => {
           state.compressionProgressDialogMinimized = minimized;
@@ -185,6 +210,15 @@ const useUIStore = create<UIOptions>()(
           disabledItems.push('advancedImport');
           disabledItems.push('checkForUpdates');
         }
+        if (useFileListStore.getState().isCompressing || useFileListStore.getState().totalFiles === 0) {
+          disabledItems.push('planCompression');
+        }
+        if (useFileListStore.getState().isCompressing || !useFileListStore.getState().lastJobId) {
+          disabledItems.push('undoLastJob');
+        }
+        if (useFileListStore.getState().isCompressing || !useFileListStore.getState().hasReport) {
+          disabledItems.push('exportReport');
+        }
         return disabledItems;
       },
     })),
@@ -199,6 +233,8 @@ useUIStore.subscribe(async (state) => {
     gifAccordionOpen: state.gifAccordionOpen,
     webpAccordionOpen: state.webpAccordionOpen,
     tiffAccordionOpen: state.tiffAccordionOpen,
+    avifAccordionOpen: state.avifAccordionOpen,
+    jxlAccordionOpen: state.jxlAccordionOpen,
     currentSelectedTab: state.currentSelectedTab,
     showPreviewPanel: state.showPreviewPanel,
     autoPreview: state.autoPreview,
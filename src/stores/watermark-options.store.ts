@@ -0,0 +1,122 @@
+import { create } from 'zustand/index';
+import { load } from '@tauri-apps/plugin-store';
+import { WATERMARK_KIND, WATERMARK_POSITION } from '@/types.ts';
+import { path } from '@tauri-apps/api';
+import { platform } from '@tauri-apps/plugin-os';
+import { invokeBackend } from '@/utils/invoker.tsx';
+
+interface WatermarkOptionsStore {
+  enabled: boolean;
+  kind: WATERMARK_KIND;
+  imagePath: string;
+  text: string;
+  fontPath: string;
+  color: string;
+  position: WATERMARK_POSITION;
+  margin: number;
+  opacity: number;
+  scale: number;
+  tiled: boolean;
+
+  setEnabled: (enabled: boolean) => void;
+  setKind: (kind: WATERMARK_KIND) => void;
+  setImagePath: (imagePath: string) => void;
+  setText: (text: string) => void;
+  setFontPath: (fontPath: string) => void;
+  setColor: (color: string) => void;
+  setPosition: (position: WATERMARK_POSITION) => void;
+  setMargin: (margin: number) => void;
+  setOpacity: (opacity: number) => void;
+  setScale: (scale: number) => void;
+  setTiled: (tiled: boolean) => void;
+
+  getWatermarkOptions: () => WatermarkOptions;
+}
+
+export interface WatermarkOptions {
+  enabled: boolean;
+  kind: WATERMARK_KIND;
+  image_path: string;
+  text: string;
+  font_path: string;
+  color: string;
+  position: WATERMARK_POSITION;
+  margin: number;
+  opacity: number;
+  scale: number;
+  tiled: boolean;
+}
+
+let configPath = 'settings.json';
+if (platform() === 'windows') {
+  const exeDir = await invokeBackend<string>('get_executable_dir');
+  configPath = await path.join(exeDir, 'settings.json');
+}
+
+const settings = await load(configPath);
+const preferences = (await settings.get('compression_options.watermark')) || {};
+
+const defaultOptions = {
+  enabled: false,
+  kind: WATERMARK_KIND.IMAGE,
+  imagePath: '',
+  text: '',
+  fontPath: '',
+  color: '#FFFFFF',
+  position: WATERMARK_POSITION.BOTTOM_RIGHT,
+  margin: 3,
+  opacity: 50,
+  scale: 25,
+  tiled: false,
+};
+
+const useWatermarkOptionsStore = create<WatermarkOptionsStore>()((set, get) => ({
+  ...defaultOptions,
+  ...preferences,
+
+  setEnabled: (enabled: boolean) => set({ enabled }),
+  setKind: (kind: WATERMARK_KIND) => set({ kind }),
+  setImagePath: (imagePath: string) => set({ imagePath }),
+  setText: (text: string) => set({ text }),
+  setFontPath: (fontPath: string) => set({ fontPath }),
+  setColor: (color: string) => set({ color }),
+  setPosition: (position: WATERMARK_POSITION) => set({ position }),
+  setMargin: (margin: number) => set({ margin }),
+  setOpacity: (opacity: number) => set({ opacity }),
+  setScale: (scale: number) => set({ scale }),
+  setTiled: (tiled: boolean) => set({ tiled }),
+
+  getWatermarkOptions: () => ({
+    enabled: get().enabled,
+    kind: get().kind,
+    image_path: get().imagePath,
+    text: get().text,
+    font_path: get().fontPath,
+    color: get().color,
+    position: get().position,
+    margin: get().margin,
+    opacity: get().opacity,
+    scale: get().scale,
+    tiled: get().tiled,
+  }),
+}));
+
+useWatermarkOptionsStore.subscribe(async (state) => {
+  const dataToSave = {
+    enabled: state.enabled,
+    kind: state.kind,
+    imagePath: state.imagePath,
+    text: state.text,
+    fontPath: state.fontPath,
+    color: state.color,
+    position: state.position,
+    margin: state.margin,
+    opacity: state.opacity,
+    scale: state.scale,
+    tiled: state.tiled,
+  };
+
+  await settings.set('compression_options.watermark', dataToSave);
+});
+
+export default useWatermarkOptionsStore;
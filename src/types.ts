@@ -18,8 +18,34 @@ export type CImage = {
   compressed_file_path: string;
   info: string;
   status: IMAGE_STATUS;
+  applied_rule: string | null;
+  collision_policy: COLLISION_POLICY | null;
+  error_code: COMPRESSION_ERROR_CODE | null;
+  quality_metrics: QualityMetrics | null;
 };
 
+export type QualityMetrics = {
+  ssim: number;
+  psnr: number;
+};
+
+export enum COMPRESSION_ERROR_CODE {
+  TOO_LARGE = 'too_large',
+  PERMISSION = 'permission',
+  READ = 'read',
+  DECODE = 'decode',
+  ENCODE = 'encode',
+  OUTPUT_PATH = 'output_path',
+  OUTPUT_COLLISION = 'output_collision',
+  WRITE = 'write',
+  METADATA = 'metadata',
+  IMAGE_METADATA = 'image_metadata',
+  WATERMARK = 'watermark',
+  MOVE_ORIGINAL = 'move_original',
+  JOURNAL = 'journal',
+  INTERNAL = 'internal',
+}
+
 export type ImageLoaderRequest = {
   mimeType: string;
   imageUrl: string;
@@ -67,6 +93,7 @@ export enum SIDE_PANEL_TAB {
   COMPRESSION = 'compression',
   OUTPUT = 'output',
   RESIZE = 'resize',
+  WATERMARK = 'watermark',
 }
 
 export enum MOVE_ORIGINAL_FILE {
@@ -74,6 +101,13 @@ export enum MOVE_ORIGINAL_FILE {
   DELETE = 'delete',
 }
 
+export enum COLLISION_POLICY {
+  OVERWRITE = 'overwrite',
+  SKIP = 'skip',
+  RENAME = 'rename',
+  OVERWRITE_IF_OLDER = 'overwrite_if_older',
+}
+
 export enum FILE_DATE {
   CREATED = 'created',
   MODIFIED = 'modified',
@@ -86,6 +120,8 @@ export enum OUTPUT_FORMAT {
   PNG = 'png',
   TIFF = 'tiff',
   WEBP = 'webp',
+  AVIF = 'avif',
+  JXL = 'jxl',
 }
 
 export enum RESIZE_MODE {
@@ -96,11 +132,48 @@ export enum RESIZE_MODE {
   LONG_EDGE = 'long_edge',
   SHORT_EDGE = 'short_edge',
   PERCENTAGE = 'percentage',
+  ASPECT_RATIO = 'aspect_ratio',
+  MAX_MEGAPIXELS = 'max_megapixels',
+}
+
+export enum RESIZE_FIT {
+  CONTAIN = 'contain',
+  COVER = 'cover',
+  PAD = 'pad',
+}
+
+export enum WATERMARK_KIND {
+  IMAGE = 'image',
+  TEXT = 'text',
+}
+
+export enum WATERMARK_POSITION {
+  TOP_LEFT = 'top_left',
+  TOP = 'top',
+  TOP_RIGHT = 'top_right',
+  LEFT = 'left',
+  CENTER = 'center',
+  RIGHT = 'right',
+  BOTTOM_LEFT = 'bottom_left',
+  BOTTOM = 'bottom',
+  BOTTOM_RIGHT = 'bottom_right',
+}
+
+export enum METADATA_PRESET {
+  ALL = 'all',
+  PRIVACY = 'privacy',
+  CUSTOM = 'custom',
+}
+
+export enum METADATA_FIELD_KIND {
+  XMP = 'xmp',
+  IPTC = 'iptc',
 }
 
 export enum COMPRESSION_MODE {
   QUALITY,
   SIZE,
+  TARGET_QUALITY,
 }
 
 export enum THEME {
@@ -142,6 +215,10 @@ export type CompressionFinished = {
   total_success: number;
   total_skipped: number;
   total_errors: number;
+  total_cached: number;
+  errors_by_code: Partial<Record<COMPRESSION_ERROR_CODE, number>>;
+  job_id: string | null;
+  output_collisions: number;
   original_size: number;
   compressed_size: number;
   total_time: number;
@@ -160,4 +237,59 @@ export enum FILE_SIZE_UNIT {
   GIGABYTE = 1000000000,
 }
 
+export enum PLANNED_ACTION {
+  COMPRESS = 'compress',
+  SKIP = 'skip',
+  FAIL = 'fail',
+}
+
+export enum ORIGINAL_ACTION {
+  KEPT = 'kept',
+  REPLACED = 'replaced',
+  TRASHED = 'trashed',
+  DELETED = 'deleted',
+}
+
+export type ImagePlan = {
+  id: string;
+  input_path: string;
+  output_path: string;
+  action: PLANNED_ACTION;
+  reason: string;
+  error_code: COMPRESSION_ERROR_CODE | null;
+  applied_rule: string | null;
+  collision_policy: COLLISION_POLICY | null;
+  output_width: number;
+  output_height: number;
+  original: ORIGINAL_ACTION;
+};
+
+export type CompressionPlan = {
+  images: ImagePlan[];
+  total_compress: number;
+  total_skip: number;
+  total_fail: number;
+  output_collisions: number;
+  total_originals_affected: number;
+};
+
+export type OutputPathPreview = { id: string; path: string | null; error: string | null };
+
+// Sent instead of a message when the options do not pass validation
+export type FieldError = { field: string; message: string };
+
 export type FileListPayload = { files: CImage[]; base_folder: string; total_files: number };
+
+export type RestoredList = {
+  file_list: FileListPayload;
+  sorting: { column: string; order: 'ascending' | 'descending' };
+  revalidation: { total: number; missing: number; changed: number };
+};
+
+export type UndoJobResult = {
+  undo: {
+    restored: string[];
+    conflicts: { path: string; reason: string }[];
+  };
+  file_list: FileListPayload;
+};
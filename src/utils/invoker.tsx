@@ -1,6 +1,7 @@
 import { invoke, InvokeArgs } from '@tauri-apps/api/core';
 import { addToast } from '@heroui/react';
 import i18next from 'i18next';
+import { FieldError } from '@/types.ts';
 
 interface InvokeOptions {
   errorMessage?: string;
@@ -9,6 +10,18 @@ interface InvokeOptions {
   color?: 'danger' | 'warning' | 'success' | 'default' | 'foreground' | 'primary' | 'secondary';
 }
 
+function isFieldErrors(e: unknown): e is FieldError[] {
+  return Array.isArray(e) && e.every((f) => typeof f?.field === 'string' && typeof f?.message === 'string');
+}
+
+// Backend errors as text, invalid options are listed one field per line
+export function describeError(e: unknown): string {
+  if (isFieldErrors(e)) {
+    return e.map((f) => `${f.field} ${f.message}`).join('\n');
+  }
+  return String(e);
+}
+
 export async function invokeBackend<T>(cmd: string, args?: InvokeArgs, options: InvokeOptions = {}): Promise<T> {
   try {
     return await invoke<T>(cmd, args);
@@ -17,7 +30,17 @@ export async function invokeBackend<T>(cmd: string, args?: InvokeArgs, options:
 
     let description = (
       <>
-        <p>{e as string}</p>
+        {isFieldErrors(e) ? (
+          <ul>
+            {e.map((f) => (
+              <li key={f.field}>
+                <code>{f.field}</code> {f.message}
+              </li>
+            ))}
+          </ul>
+        ) : (
+          <p>{e as string}</p>
+        )}
       </>
     );
     if (errorMessage) {